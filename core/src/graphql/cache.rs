@@ -0,0 +1,605 @@
+use graph_graphql::graphql_parser::{query as q, Style};
+use lru_time_cache::LruCache;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use graph::prelude::*;
+use graph::tokio::executor::{DefaultExecutor, Executor};
+
+/// Identifies the result of a query against a deployment at a specific block.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct QueryCacheKey {
+    subgraph_id: SubgraphDeploymentId,
+    generation: usize,
+    block_number: u64,
+    block_hash: String,
+    query: String,
+    variables: String,
+}
+
+/// The parts of a successful query result that are cached. Besides the data,
+/// this includes the extensions, e.g. the query's complexity and entity count.
+#[derive(Clone)]
+struct CachedResult {
+    data: q::Value,
+    extensions: Option<BTreeMap<String, serde_json::Value>>,
+}
+
+impl From<CachedResult> for QueryResult {
+    fn from(cached: CachedResult) -> Self {
+        let mut result = QueryResult::new(Some(cached.data));
+        result.extensions = cached.extensions;
+        result
+    }
+}
+
+/// What the cache knows about a deployment.
+#[derive(Default)]
+struct DeploymentState {
+    /// Incremented every time the deployment changes; results computed for
+    /// an older generation are never stored.
+    generation: usize,
+
+    /// The deployment's block pointer, looked up once per generation.
+    block_ptr: Option<EthereumBlockPointer>,
+}
+
+/// Counters describing how effective the query cache is.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct QueryCacheStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+/// Generations and block pointers of all deployments the cache has seen.
+type DeploymentStates = Mutex<HashMap<SubgraphDeploymentId, DeploymentState>>;
+
+/// How often the hit and miss counters of the cache are logged.
+const STATS_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Bumps the generation of every deployment whose `SubgraphDeployment`
+/// entity changed in `event`.
+fn invalidate(logger: &Logger, deployments: &DeploymentStates, event: &StoreEvent) {
+    let mut deployments = deployments.lock().unwrap();
+    for change in event.changes.iter() {
+        if let Ok(subgraph_id) = SubgraphDeploymentId::new(change.entity_id.clone()) {
+            if let Some(state) = deployments.get_mut(&subgraph_id) {
+                trace!(logger, "Invalidate cached query results";
+                       "subgraph_id" => subgraph_id.to_string());
+                state.generation += 1;
+                state.block_ptr = None;
+            }
+        }
+    }
+}
+
+/// A bounded cache of query results.
+///
+/// Results are keyed by the normalized query text, the query variables and
+/// the block pointer of the deployment the query runs against. Results are
+/// stored under the block pointer of the store snapshot they were computed
+/// from, which may be newer than the one looked up for the key. Whenever the
+/// `SubgraphDeployment` entity of a deployment changes (i.e. the deployment
+/// advances or is reverted), all cached results for the deployment are
+/// invalidated. Only results without errors are cached.
+///
+/// Invalidation runs as a task on the Tokio runtime that is started when
+/// the cache is first used, and restarted with the next query if it ends;
+/// outside of a runtime, queries are not cached.
+pub(crate) struct QueryCache<S> {
+    logger: Logger,
+    store: Arc<S>,
+    results: Mutex<LruCache<QueryCacheKey, CachedResult>>,
+    deployments: Arc<DeploymentStates>,
+    invalidating: Arc<Mutex<bool>>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+    stats_logged_at: Mutex<Instant>,
+}
+
+impl<S> QueryCache<S>
+where
+    S: Store,
+{
+    pub fn new(logger: &Logger, store: Arc<S>, capacity: usize) -> Self {
+        QueryCache {
+            logger: logger.new(o!("component" => "QueryCache")),
+            store,
+            results: Mutex::new(LruCache::with_capacity(capacity)),
+            deployments: Arc::new(Mutex::new(HashMap::new())),
+            invalidating: Arc::new(Mutex::new(false)),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+            stats_logged_at: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Spawns the task that invalidates cached results whenever a
+    /// deployment changes, unless it is running already. Returns `false` if
+    /// there is no Tokio runtime to run the task on.
+    fn start_invalidation(&self) -> bool {
+        let mut invalidating = self.invalidating.lock().unwrap();
+        if *invalidating {
+            return true;
+        }
+        if DefaultExecutor::current().status().is_err() {
+            return false;
+        }
+
+        let events = self
+            .store
+            .subscribe(vec![SubgraphDeploymentEntity::subgraph_entity_pair()]);
+        tokio::spawn(self.invalidate_on(events));
+
+        *invalidating = true;
+        true
+    }
+
+    /// Invalidates cached results as `events` come in. Once the events end,
+    /// changes may go unnoticed, so all cached results are invalidated and
+    /// invalidation is restarted with the next query.
+    fn invalidate_on(
+        &self,
+        events: StoreEventStreamBox,
+    ) -> impl Future<Item = (), Error = ()> + Send {
+        let logger = self.logger.clone();
+        let deployments = self.deployments.clone();
+        let invalidating = self.invalidating.clone();
+
+        events
+            .for_each({
+                let logger = logger.clone();
+                let deployments = deployments.clone();
+                move |event| {
+                    invalidate(&logger, &deployments, &event);
+                    Ok(())
+                }
+            })
+            .then(move |_| {
+                warn!(
+                    logger,
+                    "Query cache invalidation stopped, restarting it with the next query"
+                );
+
+                let mut invalidating = invalidating.lock().unwrap();
+                for state in deployments.lock().unwrap().values_mut() {
+                    state.generation += 1;
+                    state.block_ptr = None;
+                }
+                *invalidating = false;
+                Ok(())
+            })
+    }
+
+    /// Returns the current generation and block pointer of the deployment,
+    /// looking up the block pointer in the store if necessary.
+    fn deployment_state(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+    ) -> Result<(usize, EthereumBlockPointer), Error> {
+        let generation = {
+            let mut deployments = self.deployments.lock().unwrap();
+            let state = deployments
+                .entry(subgraph_id.clone())
+                .or_insert_with(DeploymentState::default);
+            if let Some(block_ptr) = state.block_ptr {
+                return Ok((state.generation, block_ptr));
+            }
+            state.generation
+        };
+
+        // Look up the block pointer without holding the lock
+        let block_ptr = self.store.block_ptr(subgraph_id.clone())?;
+
+        let mut deployments = self.deployments.lock().unwrap();
+        let state = deployments
+            .entry(subgraph_id.clone())
+            .or_insert_with(DeploymentState::default);
+        if state.generation == generation {
+            state.block_ptr = Some(block_ptr);
+        }
+        Ok((generation, block_ptr))
+    }
+
+    fn is_current(&self, subgraph_id: &SubgraphDeploymentId, generation: usize) -> bool {
+        self.deployments
+            .lock()
+            .unwrap()
+            .get(subgraph_id)
+            .map_or(false, |state| state.generation == generation)
+    }
+
+    /// Returns the cached result for `query` if there is one, or runs
+    /// `execute` and caches its result otherwise. Besides the result,
+    /// `execute` returns the block pointer of the deployment in the snapshot
+    /// of the store that the query read from; the block pointer the result
+    /// belongs to is returned along with it.
+    pub fn get_or_execute<F>(
+        &self,
+        query: &Query,
+        execute: F,
+    ) -> (QueryResult, Option<EthereumBlockPointer>)
+    where
+        F: FnOnce() -> (QueryResult, Option<EthereumBlockPointer>),
+    {
+        let subgraph_id = &query.schema.id;
+
+        // Without invalidation, cached results could be stale
+        if !self.start_invalidation() {
+            return execute();
+        }
+
+        let (generation, block_ptr) = match self.deployment_state(subgraph_id) {
+            Ok(state) => state,
+            Err(e) => {
+                // Deployments without a block pointer (e.g. the subgraph of
+                // subgraphs) are not cached
+                trace!(self.logger, "Not caching query result";
+                       "subgraph_id" => subgraph_id.to_string(),
+                       "reason" => e.to_string());
+                return execute();
+            }
+        };
+
+        // Sort variables by name so that the key does not depend on the
+        // order in which the client listed them
+        let variables = query
+            .variables
+            .as_ref()
+            .map(|variables| variables.iter().collect::<BTreeMap<_, _>>())
+            .unwrap_or_default();
+
+        let key = QueryCacheKey {
+            subgraph_id: subgraph_id.clone(),
+            generation,
+            block_number: block_ptr.number,
+            block_hash: format!("{:x}", block_ptr.hash),
+            query: query.document.format(&Style::default().indent(0)),
+            variables: format!("{:?}", variables),
+        };
+
+        if let Some(cached) = self.results.lock().unwrap().get(&key).cloned() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            self.log_stats();
+            return (cached.into(), Some(block_ptr));
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        self.log_stats();
+
+        let (result, snapshot_block_ptr) = execute();
+
+        // Don't cache errors, and don't cache results of a generation that
+        // has been invalidated in the meantime. A block may have been
+        // committed between looking up the key and taking the snapshot, so
+        // the result is stored under the block the snapshot saw
+        if let (None, Some(data), Some(block_ptr)) =
+            (&result.errors, &result.data, snapshot_block_ptr)
+        {
+            if self.is_current(subgraph_id, generation) {
+                let cached = CachedResult {
+                    data: data.clone(),
+                    extensions: result.extensions.clone(),
+                };
+                let key = QueryCacheKey {
+                    block_number: block_ptr.number,
+                    block_hash: format!("{:x}", block_ptr.hash),
+                    ..key
+                };
+                self.results.lock().unwrap().insert(key, cached);
+            }
+        }
+
+        (result, snapshot_block_ptr)
+    }
+
+    pub fn stats(&self) -> QueryCacheStats {
+        QueryCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Logs the hit and miss counters once every `STATS_INTERVAL`.
+    fn log_stats(&self) {
+        {
+            let mut logged_at = self.stats_logged_at.lock().unwrap();
+            if logged_at.elapsed() < STATS_INTERVAL {
+                return;
+            }
+            *logged_at = Instant::now();
+        }
+
+        let stats = self.stats();
+        info!(self.logger, "Query cache statistics";
+        "hits" => stats.hits,
+        "misses" => stats.misses,
+        "hit_rate" => format!(
+            "{:.1}%",
+            100.0 * stats.hits as f64 / (stats.hits + stats.misses).max(1) as f64
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::web3::types::H256;
+    use graph_graphql::graphql_parser::parse_query;
+    use graph_mock::MockStore;
+    use std::cell::Cell;
+
+    fn subgraph_id() -> SubgraphDeploymentId {
+        SubgraphDeploymentId::new("cachetest").unwrap()
+    }
+
+    fn block_ptr(number: u64) -> EthereumBlockPointer {
+        EthereumBlockPointer {
+            hash: H256::from([number as u8; 32]),
+            number,
+        }
+    }
+
+    fn store() -> Arc<MockStore> {
+        let store = MockStore::new(vec![]);
+        let block_ptr = block_ptr(1);
+        let mut data = Entity::new();
+        data.set("latestEthereumBlockHash", block_ptr.hash_hex());
+        data.set("latestEthereumBlockNumber", block_ptr.number);
+        store
+            .apply_entity_operations(
+                vec![EntityOperation::Set {
+                    key: SubgraphDeploymentEntity::key(subgraph_id()),
+                    data,
+                }],
+                None,
+            )
+            .unwrap();
+        Arc::new(store)
+    }
+
+    fn query(text: &str, variables: Vec<(&str, q::Value)>) -> Query {
+        let schema = Schema::parse("type User @entity { id: ID! }", subgraph_id()).unwrap();
+        Query {
+            schema: Arc::new(schema),
+            document: parse_query(text).unwrap(),
+            variables: if variables.is_empty() {
+                None
+            } else {
+                Some(QueryVariables::new(
+                    variables
+                        .into_iter()
+                        .map(|(name, value)| (name.to_owned(), value))
+                        .collect(),
+                ))
+            },
+        }
+    }
+
+    fn data(value: &str) -> QueryResult {
+        QueryResult::new(Some(q::Value::String(value.to_owned())))
+    }
+
+    /// Runs `test` on a Tokio runtime, which the cache needs to invalidate
+    /// results.
+    fn run_test<F>(test: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime
+            .block_on(future::lazy(move || {
+                test();
+                Ok::<_, ()>(())
+            }))
+            .unwrap();
+    }
+
+    /// Runs `query` through the cache and returns the result and whether
+    /// the query had to be executed.
+    fn run(cache: &QueryCache<MockStore>, query: &Query, value: &str) -> (QueryResult, bool) {
+        let executed = Cell::new(false);
+        let (result, _) = cache.get_or_execute(query, || {
+            executed.set(true);
+            (data(value), Some(block_ptr(1)))
+        });
+        (result, executed.get())
+    }
+
+    #[test]
+    fn caches_results() {
+        run_test(|| {
+            let cache = QueryCache::new(&Logger::root(slog::Discard, o!()), store(), 10);
+            let query = query("{ users { id } }", vec![]);
+
+            let (result, executed) = run(&cache, &query, "first");
+            assert!(executed);
+            assert_eq!(result.data, data("first").data);
+
+            let (result, executed) = run(&cache, &query, "second");
+            assert!(!executed);
+            assert_eq!(result.data, data("first").data);
+
+            // Cached results belong to the block they were cached for
+            let (_, result_block_ptr) = cache.get_or_execute(&query, || unreachable!());
+            assert_eq!(result_block_ptr, Some(block_ptr(1)));
+
+            assert_eq!(cache.stats(), QueryCacheStats { hits: 2, misses: 1 });
+        })
+    }
+
+    #[test]
+    fn caches_extensions() {
+        run_test(|| {
+            let cache = QueryCache::new(&Logger::root(slog::Discard, o!()), store(), 10);
+            let query = query("{ users { id } }", vec![]);
+
+            for _ in 0..2 {
+                let (result, _) = cache.get_or_execute(&query, || {
+                    let mut result = data("first");
+                    result.add_extension("complexity", serde_json::json!(3));
+                    result.add_extension("entityCount", serde_json::json!(2));
+                    (result, Some(block_ptr(1)))
+                });
+                let extensions = result.extensions.expect("extensions are missing");
+                assert_eq!(extensions["complexity"], serde_json::json!(3));
+                assert_eq!(extensions["entityCount"], serde_json::json!(2));
+            }
+
+            assert_eq!(cache.stats(), QueryCacheStats { hits: 1, misses: 1 });
+        })
+    }
+
+    #[test]
+    fn store_events_invalidate_results() {
+        run_test(|| {
+            let cache = QueryCache::new(&Logger::root(slog::Discard, o!()), store(), 10);
+            let query = query("{ users { id } }", vec![]);
+
+            run(&cache, &query, "first");
+
+            // Changes to other entities don't matter
+            let other = EntityKey {
+                subgraph_id: subgraph_id(),
+                entity_type: "User".to_owned(),
+                entity_id: "1".to_owned(),
+            };
+            let event = StoreEvent::new(vec![EntityChange::from_key(
+                other,
+                EntityChangeOperation::Set,
+            )]);
+            invalidate(&cache.logger, &cache.deployments, &event);
+            let (_, executed) = run(&cache, &query, "second");
+            assert!(!executed);
+
+            let event = StoreEvent::new(vec![EntityChange::from_key(
+                SubgraphDeploymentEntity::key(subgraph_id()),
+                EntityChangeOperation::Set,
+            )]);
+            invalidate(&cache.logger, &cache.deployments, &event);
+            let (result, executed) = run(&cache, &query, "second");
+            assert!(executed);
+            assert_eq!(result.data, data("second").data);
+        })
+    }
+
+    #[test]
+    fn restarts_invalidation_when_it_stops() {
+        run_test(|| {
+            let cache = QueryCache::new(&Logger::root(slog::Discard, o!()), store(), 10);
+            let query = query("{ users { id } }", vec![]);
+
+            run(&cache, &query, "first");
+            assert!(*cache.invalidating.lock().unwrap());
+
+            // Store events that end right away, e.g. after losing the
+            // connection to the database
+            let events = StoreEventStream::new(Box::new(stream::empty()) as _);
+            cache.invalidate_on(events).wait().unwrap();
+            assert!(!*cache.invalidating.lock().unwrap());
+
+            // Results cached before may be stale now
+            let (result, executed) = run(&cache, &query, "second");
+            assert!(executed);
+            assert_eq!(result.data, data("second").data);
+            assert!(*cache.invalidating.lock().unwrap());
+        })
+    }
+
+    #[test]
+    fn does_not_cache_errors() {
+        run_test(|| {
+            let cache = QueryCache::new(&Logger::root(slog::Discard, o!()), store(), 10);
+            let query = query("{ users { id } }", vec![]);
+
+            for _ in 0..2 {
+                let executed = Cell::new(false);
+                let (result, _) = cache.get_or_execute(&query, || {
+                    executed.set(true);
+                    (
+                        QueryResult::from(QueryExecutionError::Timeout),
+                        Some(block_ptr(1)),
+                    )
+                });
+                assert!(executed.get());
+                assert!(result.errors.is_some());
+            }
+        })
+    }
+
+    #[test]
+    fn caches_results_under_the_block_of_the_snapshot() {
+        run_test(|| {
+            let cache = QueryCache::new(&Logger::root(slog::Discard, o!()), store(), 10);
+            let query = query("{ users { id } }", vec![]);
+
+            // A block was committed after looking up the key but before the
+            // query took its snapshot of the store
+            let (result, result_block_ptr) =
+                cache.get_or_execute(&query, || (data("first"), Some(block_ptr(2))));
+            assert_eq!(result.data, data("first").data);
+            assert_eq!(result_block_ptr, Some(block_ptr(2)));
+
+            let (result, executed) = run(&cache, &query, "second");
+            assert!(executed);
+            assert_eq!(result.data, data("second").data);
+        })
+    }
+
+    #[test]
+    fn normalizes_variable_order() {
+        run_test(|| {
+            let cache = QueryCache::new(&Logger::root(slog::Discard, o!()), store(), 10);
+            let text = "query($a: Int, $b: Int) { users(first: $a, skip: $b) { id } }";
+            let one = q::Value::Int(q::Number::from(1));
+            let two = q::Value::Int(q::Number::from(2));
+
+            let (_, executed) = run(
+                &cache,
+                &query(text, vec![("a", one.clone()), ("b", two.clone())]),
+                "first",
+            );
+            assert!(executed);
+
+            let (result, executed) = run(
+                &cache,
+                &query(text, vec![("b", two.clone()), ("a", one.clone())]),
+                "second",
+            );
+            assert!(!executed);
+            assert_eq!(result.data, data("first").data);
+
+            // Different values are different queries
+            let (_, executed) = run(&cache, &query(text, vec![("a", two), ("b", one)]), "third");
+            assert!(executed);
+        })
+    }
+
+    #[test]
+    fn evicts_results_at_capacity() {
+        run_test(|| {
+            let cache = QueryCache::new(&Logger::root(slog::Discard, o!()), store(), 1);
+            let users = query("{ users { id } }", vec![]);
+            let user = query("{ user(id: \"1\") { id } }", vec![]);
+
+            run(&cache, &users, "users");
+            run(&cache, &user, "user");
+
+            let (result, executed) = run(&cache, &users, "users again");
+            assert!(executed);
+            assert_eq!(result.data, data("users again").data);
+        })
+    }
+
+    #[test]
+    fn does_not_cache_outside_of_a_runtime() {
+        let cache = QueryCache::new(&Logger::root(slog::Discard, o!()), store(), 10);
+        let query = query("{ users { id } }", vec![]);
+
+        run(&cache, &query, "first");
+        let (_, executed) = run(&cache, &query, "second");
+        assert!(executed);
+    }
+}
@@ -1,3 +1,4 @@
+mod cache;
 mod runner;
 
 pub use self::runner::GraphQlRunner;
@@ -1,17 +1,26 @@
 use futures::future;
 use std::env;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::{Duration, Instant};
 
 use graph::prelude::{GraphQlRunner as GraphQlRunnerTrait, *};
+use graph_graphql::graphql_parser::Style;
 use graph_graphql::prelude::*;
+use graph_graphql::query::ast::is_introspection_only;
 
 use lazy_static::lazy_static;
 
+use super::cache::QueryCache;
+
 /// GraphQL runner implementation for The Graph.
 pub struct GraphQlRunner<S> {
     logger: Logger,
     store: Arc<S>,
+    subscriptions: SharedSubscriptions,
+    query_cache: Option<QueryCache<S>>,
+    /// Counts queries to decide which ones are sampled for detailed traces.
+    trace_samples: AtomicUsize,
 }
 
 lazy_static! {
@@ -30,6 +39,35 @@ lazy_static! {
         .map(|s| u8::from_str(&s)
             .unwrap_or_else(|_| panic!("failed to parse env var GRAPH_GRAPHQL_MAX_DEPTH")))
         .unwrap_or(u8::max_value());
+    static ref GRAPHQL_MAX_ENTITIES: Option<usize> = env::var("GRAPH_GRAPHQL_MAX_ENTITIES")
+        .ok()
+        .map(|s| usize::from_str(&s)
+            .unwrap_or_else(|_| panic!("failed to parse env var GRAPH_GRAPHQL_MAX_ENTITIES")));
+    static ref GRAPHQL_TRACE_THRESHOLD: Option<Duration> =
+        env::var("GRAPH_GRAPHQL_TRACE_THRESHOLD")
+            .ok()
+            .map(
+                |s| Duration::from_millis(u64::from_str(&s).unwrap_or_else(|_| panic!(
+                    "failed to parse env var GRAPH_GRAPHQL_TRACE_THRESHOLD"
+                )))
+            );
+    static ref GRAPHQL_TRACE_SAMPLE_INTERVAL: usize =
+        env::var("GRAPH_GRAPHQL_TRACE_SAMPLE_INTERVAL")
+            .ok()
+            .map(|s| usize::from_str(&s).unwrap_or_else(|_| panic!(
+                "failed to parse env var GRAPH_GRAPHQL_TRACE_SAMPLE_INTERVAL"
+            )))
+            .unwrap_or(0);
+    static ref GRAPHQL_TRACE_SQL: bool = env::var("GRAPH_GRAPHQL_TRACE_SQL")
+        .ok()
+        .map(|s| bool::from_str(&s)
+            .unwrap_or_else(|_| panic!("failed to parse env var GRAPH_GRAPHQL_TRACE_SQL")))
+        .unwrap_or(false);
+    static ref GRAPHQL_QUERY_CACHE_SIZE: usize = env::var("GRAPH_GRAPHQL_QUERY_CACHE_SIZE")
+        .ok()
+        .map(|s| usize::from_str(&s)
+            .unwrap_or_else(|_| panic!("failed to parse env var GRAPH_GRAPHQL_QUERY_CACHE_SIZE")))
+        .unwrap_or(1000);
 }
 
 impl<S> GraphQlRunner<S>
@@ -38,28 +76,142 @@ where
 {
     /// Creates a new query runner.
     pub fn new(logger: &Logger, store: Arc<S>) -> Self {
+        let logger = logger.new(o!("component" => "GraphQlRunner"));
+
+        let query_cache = match *GRAPHQL_QUERY_CACHE_SIZE {
+            0 => None,
+            capacity => Some(QueryCache::new(&logger, store.clone(), capacity)),
+        };
+
         GraphQlRunner {
-            logger: logger.new(o!("component" => "GraphQlRunner")),
+            subscriptions: SharedSubscriptions::new(&logger),
+            query_cache,
+            trace_samples: AtomicUsize::new(0),
+            logger,
             store,
         }
     }
-}
 
-impl<S> GraphQlRunnerTrait for GraphQlRunner<S>
-where
-    S: Store,
-{
-    fn run_query(&self, query: Query) -> QueryResultFuture {
-        let result = execute_query(
-            &query,
+    /// Whether a query the client did not ask to trace should be traced
+    /// anyway, so that it can be logged with its trace if it is slow.
+    fn sample_trace(&self) -> bool {
+        match *GRAPHQL_TRACE_SAMPLE_INTERVAL {
+            0 => false,
+            _ if GRAPHQL_TRACE_THRESHOLD.is_none() => false,
+            interval => self.trace_samples.fetch_add(1, Ordering::Relaxed) % interval == 0,
+        }
+    }
+
+    /// Creates a resolver whose reads all see the same state of the store, so
+    /// that query results do not mix data from different blocks. Queries
+    /// that only select introspection fields don't read from the store and
+    /// get by without a snapshot.
+    fn resolver(&self, query: &Query) -> Result<StoreResolver<S>, QueryExecutionError> {
+        let resolver = StoreResolver::new(&self.logger, self.store.clone());
+        if is_introspection_only(&query.document) {
+            Ok(resolver)
+        } else {
+            resolver.with_snapshot()
+        }
+    }
+
+    /// Executes `query` against a fresh snapshot of the store.
+    fn execute_query(
+        &self,
+        query: &Query,
+        max_complexity: Option<u64>,
+        traced: bool,
+    ) -> QueryResult {
+        match self.resolver(query) {
+            Ok(resolver) => self.execute_query_with(query, resolver, max_complexity, traced),
+            Err(e) => QueryResult::from(e),
+        }
+    }
+
+    /// Executes `query` and returns the block the deployment was at in the
+    /// snapshot the query read from, for the query cache and HTTP caches.
+    fn execute_query_at_block(&self, query: &Query) -> (QueryResult, Option<EthereumBlockPointer>) {
+        let resolver = match self.resolver(query) {
+            Ok(resolver) => resolver,
+            Err(e) => return (QueryResult::from(e), None),
+        };
+        let block_ptr = resolver.block_ptr(query.schema.id.clone()).ok();
+        let result = self.execute_query_with(query, resolver, *GRAPHQL_MAX_COMPLEXITY, false);
+        (result, block_ptr)
+    }
+
+    fn execute_query_with(
+        &self,
+        query: &Query,
+        mut resolver: StoreResolver<S>,
+        max_complexity: Option<u64>,
+        traced: bool,
+    ) -> QueryResult {
+        let start = Instant::now();
+
+        // Tracing every field is expensive, so slow queries are only logged
+        // with a trace if they were sampled
+        let trace = if traced || self.sample_trace() {
+            Some(Arc::new(Trace::new()))
+        } else {
+            None
+        };
+
+        if let Some(ref trace) = trace {
+            resolver = resolver.with_trace(trace.clone());
+        }
+
+        let mut result = execute_query(
+            query,
             QueryExecutionOptions {
                 logger: self.logger.clone(),
-                resolver: StoreResolver::new(&self.logger, self.store.clone()),
+                resolver,
                 deadline: GRAPHQL_QUERY_TIMEOUT.map(|t| Instant::now() + t),
-                max_complexity: *GRAPHQL_MAX_COMPLEXITY,
+                max_complexity,
                 max_depth: *GRAPHQL_MAX_DEPTH,
+                max_entities: *GRAPHQL_MAX_ENTITIES,
+                trace: trace.clone(),
             },
         );
+
+        let duration = start.elapsed();
+        if GRAPHQL_TRACE_THRESHOLD.map_or(false, |threshold| duration > threshold) {
+            warn!(
+                self.logger,
+                "Slow GraphQL query";
+                "subgraph_id" => query.schema.id.to_string(),
+                "query" => query.document.format(&Style::default().indent(0)).replace('\n', " "),
+                "query_time_ms" => duration.as_millis(),
+                "trace" => trace.as_ref().map(|trace| trace.to_json(true).to_string()),
+            );
+        }
+
+        // The SQL may reveal more about the database than clients should
+        // know, so it only goes into responses if that is enabled
+        match trace {
+            Some(ref trace) if traced => {
+                result.add_extension("tracing", trace.to_json(*GRAPHQL_TRACE_SQL))
+            }
+            _ => (),
+        }
+
+        result
+    }
+}
+
+impl<S> GraphQlRunnerTrait for GraphQlRunner<S>
+where
+    S: Store,
+{
+    fn run_query(&self, query: Query) -> QueryResultFuture {
+        Box::new(self.run_query_at_block(query).map(|(result, _)| result))
+    }
+
+    fn run_query_at_block(&self, query: Query) -> QueryResultAtBlockFuture {
+        let result = match self.query_cache {
+            Some(ref cache) => cache.get_or_execute(&query, || self.execute_query_at_block(&query)),
+            None => self.execute_query_at_block(&query),
+        };
         Box::new(future::ok(result))
     }
 
@@ -68,30 +220,35 @@ where
         query: Query,
         max_complexity: Option<u64>,
     ) -> QueryResultFuture {
-        let result = execute_query(
-            &query,
-            QueryExecutionOptions {
-                logger: self.logger.clone(),
-                resolver: StoreResolver::new(&self.logger, self.store.clone()),
-                deadline: GRAPHQL_QUERY_TIMEOUT.map(|t| Instant::now() + t),
-                max_complexity: max_complexity,
-                max_depth: *GRAPHQL_MAX_DEPTH,
-            },
-        );
+        // Queries with a custom complexity limit bypass the cache, since a
+        // cached result may have been computed with a more generous limit
+        let result = self.execute_query(&query, max_complexity, false);
+        Box::new(future::ok(result))
+    }
+
+    fn run_traced_query(&self, query: Query) -> QueryResultFuture {
+        // Traced queries bypass the cache so that the trace reflects
+        // actually executing the query
+        let result = self.execute_query(&query, *GRAPHQL_MAX_COMPLEXITY, true);
         Box::new(future::ok(result))
     }
 
     fn run_subscription(&self, subscription: Subscription) -> SubscriptionResultFuture {
-        let result = execute_subscription(
-            &subscription,
-            SubscriptionExecutionOptions {
-                logger: self.logger.clone(),
-                resolver: StoreResolver::new(&self.logger, self.store.clone()),
-                timeout: GRAPHQL_QUERY_TIMEOUT.clone(),
-                max_complexity: *GRAPHQL_MAX_COMPLEXITY,
-                max_depth: *GRAPHQL_MAX_DEPTH,
-            },
-        );
+        // Identical subscriptions are executed only once and share their
+        // results; each execution reads from its own snapshot of the store
+        let result = self.subscriptions.subscribe(&subscription, || {
+            execute_subscription(
+                &subscription,
+                SubscriptionExecutionOptions {
+                    logger: self.logger.clone(),
+                    resolver: StoreResolver::new(&self.logger, self.store.clone()),
+                    timeout: GRAPHQL_QUERY_TIMEOUT.clone(),
+                    max_complexity: *GRAPHQL_MAX_COMPLEXITY,
+                    max_depth: *GRAPHQL_MAX_DEPTH,
+                    max_entities: *GRAPHQL_MAX_ENTITIES,
+                },
+            )
+        });
 
         Box::new(future::result(result))
     }
@@ -5,6 +5,7 @@ extern crate graph_graphql;
 extern crate graph_mock;
 extern crate graph_runtime_wasm;
 extern crate lazy_static;
+extern crate reqwest;
 extern crate semver;
 extern crate serde;
 extern crate serde_json;
@@ -16,7 +17,9 @@ mod link_resolver;
 mod subgraph;
 
 pub use crate::graphql::GraphQlRunner;
-pub use crate::link_resolver::LinkResolver;
+pub use crate::link_resolver::{
+    ipfs_timeout, CompositeLinkResolver, FileLinkResolver, GatewayLinkResolver, LinkResolver,
+};
 pub use crate::subgraph::{
     DataSourceLoader, SubgraphAssignmentProvider, SubgraphInstanceManager, SubgraphRegistrar,
 };
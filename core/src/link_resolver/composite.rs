@@ -0,0 +1,175 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use graph::prelude::{LinkResolver as LinkResolverTrait, *};
+
+/// Resolves links by trying several link resolvers, e.g. a number of IPFS
+/// nodes, in order until one of them succeeds. Every attempt is limited by
+/// its own timeout so that an unresponsive node does not hold up the others.
+pub struct CompositeLinkResolver {
+    logger: Logger,
+    resolvers: Arc<Vec<Box<dyn LinkResolverTrait>>>,
+    timeout: Duration,
+}
+
+impl CompositeLinkResolver {
+    pub fn new(
+        logger: &Logger,
+        resolvers: Vec<Box<dyn LinkResolverTrait>>,
+        timeout: Duration,
+    ) -> Self {
+        assert!(
+            !resolvers.is_empty(),
+            "a composite link resolver needs at least one resolver"
+        );
+
+        Self {
+            logger: logger.new(o!("component" => "CompositeLinkResolver")),
+            resolvers: Arc::new(resolvers),
+            timeout,
+        }
+    }
+
+    /// Runs `attempt` against each resolver in turn and returns the first
+    /// successful result, or the error of the last resolver.
+    fn first_success<T, F>(
+        &self,
+        link: &Link,
+        attempt: F,
+    ) -> Box<Future<Item = T, Error = failure::Error> + Send>
+    where
+        T: Send + 'static,
+        F: Fn(
+                &dyn LinkResolverTrait,
+                &Link,
+            ) -> Box<Future<Item = T, Error = failure::Error> + Send>
+            + Send
+            + 'static,
+    {
+        let logger = self.logger.clone();
+        let resolvers = self.resolvers.clone();
+        let timeout = self.timeout;
+        let link = link.clone();
+
+        Box::new(future::loop_fn(0, move |i| {
+            let logger = logger.clone();
+            let link_for_log = link.link.clone();
+            let remaining = resolvers.len() - i - 1;
+
+            attempt(resolvers[i].as_ref(), &link).timeout(timeout).then(
+                move |result| match result {
+                    Ok(value) => Ok(future::Loop::Break(value)),
+                    Err(e) => {
+                        let e = e.into_inner().unwrap_or_else(|| {
+                            format_err!("link resolver timed out after {}s", timeout.as_secs())
+                        });
+                        if remaining == 0 {
+                            return Err(e);
+                        }
+                        warn!(
+                            logger,
+                            "Failed to resolve link, trying the next resolver";
+                            "link" => link_for_log,
+                            "error" => e.to_string(),
+                            "remaining_resolvers" => remaining,
+                        );
+                        Ok(future::Loop::Continue(i + 1))
+                    }
+                },
+            )
+        }))
+    }
+}
+
+impl LinkResolverTrait for CompositeLinkResolver {
+    fn cat(
+        &self,
+        logger: &Logger,
+        link: &Link,
+    ) -> Box<Future<Item = Vec<u8>, Error = failure::Error> + Send> {
+        let logger = logger.clone();
+        self.first_success(link, move |resolver, link| resolver.cat(&logger, link))
+    }
+
+    fn json_stream(
+        &self,
+        link: &Link,
+    ) -> Box<Future<Item = JsonValueStream, Error = failure::Error> + Send + 'static> {
+        self.first_success(link, |resolver, link| resolver.json_stream(link))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Serves fixed data, or fails, or never responds.
+    enum TestResolver {
+        Data(&'static [u8]),
+        Fail,
+        Hang,
+    }
+
+    impl LinkResolverTrait for TestResolver {
+        fn cat(
+            &self,
+            _: &Logger,
+            _: &Link,
+        ) -> Box<Future<Item = Vec<u8>, Error = failure::Error> + Send> {
+            match self {
+                TestResolver::Data(data) => Box::new(future::ok(data.to_vec())),
+                TestResolver::Fail => Box::new(future::err(format_err!("not found"))),
+                TestResolver::Hang => Box::new(future::empty()),
+            }
+        }
+
+        fn json_stream(
+            &self,
+            _: &Link,
+        ) -> Box<Future<Item = JsonValueStream, Error = failure::Error> + Send + 'static> {
+            unimplemented!()
+        }
+    }
+
+    fn cat(resolvers: Vec<TestResolver>) -> Result<Vec<u8>, failure::Error> {
+        let logger = Logger::root(slog::Discard, o!());
+        let resolver = CompositeLinkResolver::new(
+            &logger,
+            resolvers
+                .into_iter()
+                .map(|resolver| Box::new(resolver) as Box<dyn LinkResolverTrait>)
+                .collect(),
+            Duration::from_millis(100),
+        );
+
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(resolver.cat(&logger, &Link::from("QmTest".to_owned())))
+    }
+
+    #[test]
+    fn tries_resolvers_in_order() {
+        assert_eq!(
+            cat(vec![
+                TestResolver::Data(b"first"),
+                TestResolver::Data(b"second")
+            ])
+            .unwrap(),
+            b"first".to_vec()
+        );
+        assert_eq!(
+            cat(vec![
+                TestResolver::Fail,
+                TestResolver::Hang,
+                TestResolver::Data(b"third")
+            ])
+            .unwrap(),
+            b"third".to_vec()
+        );
+        assert_eq!(
+            cat(vec![TestResolver::Hang, TestResolver::Fail])
+                .unwrap_err()
+                .to_string(),
+            "not found"
+        );
+    }
+}
@@ -0,0 +1,190 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use graph::prelude::{LinkResolver as LinkResolverTrait, *};
+use graph::util::futures::blocking;
+
+use super::{
+    json_value_stream, read_u64_from_env, DEFAULT_MAX_IPFS_MAP_FILE_SIZE, MAX_IPFS_FILE_SIZE_VAR,
+    MAX_IPFS_MAP_FILE_SIZE_VAR,
+};
+
+/// Resolves links to files in a local directory, which makes it possible to
+/// run subgraphs without an IPFS node during development.
+///
+/// Links of the form `file:///absolute/path` refer to that file and links of
+/// the form `file://relative/path` to a file relative to the directory. Any
+/// other link, e.g. `/ipfs/Qm...` or `Qm...`, refers to the file named after
+/// the hash in the directory. Links to files outside of the directory are
+/// rejected, so that subgraphs cannot read arbitrary files on the host.
+pub struct FileLinkResolver {
+    dir: PathBuf,
+}
+
+impl FileLinkResolver {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Resolves `link` to a file inside of `dir`. Absolute paths replace
+    /// `dir` when joined onto it, so the check has to happen after resolving
+    /// `..` and symlinks.
+    fn path(dir: &Path, link: &Link) -> Result<PathBuf, failure::Error> {
+        let path = if link.link.starts_with("file://") {
+            dir.join(link.link.trim_start_matches("file://"))
+        } else {
+            dir.join(link.link.trim_start_matches("/ipfs/"))
+        };
+
+        let dir = dir
+            .canonicalize()
+            .map_err(|e| format_err!("failed to read directory {}: {}", dir.display(), e))?;
+        let path = path
+            .canonicalize()
+            .map_err(|e| format_err!("failed to read file {}: {}", path.display(), e))?;
+
+        if path.starts_with(&dir) {
+            Ok(path)
+        } else {
+            Err(format_err!(
+                "file {} is outside of directory {}",
+                path.display(),
+                dir.display()
+            ))
+        }
+    }
+
+    /// Reads the file behind `link`, unless it is larger than `max_file_bytes`.
+    /// The file system is accessed on a blocking thread of the runtime.
+    fn read(
+        &self,
+        link: &Link,
+        max_file_bytes: Option<u64>,
+    ) -> impl Future<Item = Vec<u8>, Error = failure::Error> + Send {
+        let dir = self.dir.clone();
+        let link = link.clone();
+        blocking(future::lazy(move || {
+            Self::path(&dir, &link).and_then(|path| Self::read_file(&path, max_file_bytes))
+        }))
+    }
+
+    fn read_file(path: &Path, max_file_bytes: Option<u64>) -> Result<Vec<u8>, failure::Error> {
+        let size = fs::metadata(path)
+            .map_err(|e| format_err!("failed to read file {}: {}", path.display(), e))?
+            .len();
+
+        match max_file_bytes {
+            Some(max_bytes) if size > max_bytes => Err(format_err!(
+                "File {} is too large. It can be at most {} bytes but is {} bytes",
+                path.display(),
+                max_bytes,
+                size
+            )),
+            _ => fs::read(path)
+                .map_err(|e| format_err!("failed to read file {}: {}", path.display(), e)),
+        }
+    }
+}
+
+impl LinkResolverTrait for FileLinkResolver {
+    fn cat(
+        &self,
+        logger: &Logger,
+        link: &Link,
+    ) -> Box<Future<Item = Vec<u8>, Error = failure::Error> + Send> {
+        trace!(logger, "Reading local file"; "link" => &link.link);
+
+        let max_file_size = read_u64_from_env(MAX_IPFS_FILE_SIZE_VAR);
+        Box::new(self.read(link, max_file_size))
+    }
+
+    fn json_stream(
+        &self,
+        link: &Link,
+    ) -> Box<Future<Item = JsonValueStream, Error = failure::Error> + Send + 'static> {
+        let max_file_size =
+            read_u64_from_env(MAX_IPFS_MAP_FILE_SIZE_VAR).unwrap_or(DEFAULT_MAX_IPFS_MAP_FILE_SIZE);
+        Box::new(
+            self.read(link, Some(max_file_size))
+                .map(|data| json_value_stream(stream::once(Ok(data)))),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::env;
+
+    /// Reads from the resolver on a runtime, which `blocking` requires.
+    fn run<T: Send + 'static>(
+        f: impl Future<Item = T, Error = failure::Error> + Send + 'static,
+    ) -> Result<T, failure::Error> {
+        tokio::runtime::Runtime::new().unwrap().block_on(f)
+    }
+
+    #[test]
+    fn resolves_links_in_directory() {
+        let dir = env::temp_dir().join("graph-file-link-resolver-test");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("QmManifest"), "manifest").unwrap();
+        fs::write(dir.join("values.json"), "\"one\"\n[\"two\"]\n").unwrap();
+
+        let resolver = FileLinkResolver::new(&dir);
+        let logger = Logger::root(slog::Discard, o!());
+
+        for link in vec!["QmManifest", "/ipfs/QmManifest", "file://QmManifest"] {
+            let data = run(resolver.cat(&logger, &Link::from(link.to_owned()))).unwrap();
+            assert_eq!(data, b"manifest".to_vec());
+        }
+
+        let absolute = format!("file://{}", dir.join("QmManifest").display());
+        assert!(run(resolver.cat(&logger, &Link::from(absolute))).is_ok());
+        assert!(run(resolver.cat(&logger, &Link::from("QmMissing".to_owned()))).is_err());
+
+        let values = run(resolver
+            .json_stream(&Link::from("file://values.json".to_owned()))
+            .and_then(|stream| stream.map(|sv| sv.value).collect()))
+        .unwrap();
+        assert_eq!(values, vec![json!("one"), json!(["two"])]);
+    }
+
+    #[test]
+    fn rejects_links_outside_of_directory() {
+        let parent = env::temp_dir().join("graph-file-link-resolver-outside-test");
+        let dir = parent.join("subgraphs");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(parent.join("secret"), "secret").unwrap();
+        fs::write(dir.join("QmManifest"), "manifest").unwrap();
+
+        let resolver = FileLinkResolver::new(&dir);
+        let logger = Logger::root(slog::Discard, o!());
+
+        let absolute = format!("file://{}", parent.join("secret").display());
+        for link in vec![
+            absolute.as_str(),
+            "file://../secret",
+            "/ipfs/../secret",
+            "../secret",
+            "file://../subgraphs/../secret",
+        ] {
+            let err = run(resolver.cat(&logger, &Link::from(link.to_owned()))).unwrap_err();
+            assert!(
+                err.to_string().contains("is outside of directory"),
+                "link {} was not rejected: {}",
+                link,
+                err
+            );
+            assert!(run(resolver.json_stream(&Link::from(link.to_owned()))).is_err());
+        }
+
+        // `..` is fine as long as the file stays inside of the directory
+        let data = run(resolver.cat(
+            &logger,
+            &Link::from("file://../subgraphs/QmManifest".to_owned()),
+        ))
+        .unwrap();
+        assert_eq!(data, b"manifest".to_vec());
+    }
+}
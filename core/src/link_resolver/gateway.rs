@@ -0,0 +1,228 @@
+use reqwest::r#async::{Client, Decoder};
+use reqwest::Url;
+
+use graph::prelude::{LinkResolver as LinkResolverTrait, *};
+
+use super::{
+    ipfs_timeout, json_value_stream, read_u64_from_env, DEFAULT_MAX_IPFS_MAP_FILE_SIZE,
+    MAX_IPFS_FILE_SIZE_VAR, MAX_IPFS_MAP_FILE_SIZE_VAR,
+};
+
+/// Resolves links by fetching them from a read-only HTTP IPFS gateway, e.g.
+/// `https://ipfs.io`, at `<gateway>/ipfs/<hash>`.
+pub struct GatewayLinkResolver {
+    client: Client,
+    url: Url,
+}
+
+impl GatewayLinkResolver {
+    pub fn new(mut url: Url) -> Self {
+        // `Url::join` replaces the last path segment of a URL that does not
+        // end in a slash, which would drop e.g. the `/gateway` of
+        // `https://example.com/gateway`
+        if !url.path().ends_with('/') {
+            let path = format!("{}/", url.path());
+            url.set_path(&path);
+        }
+
+        Self {
+            client: Client::new(),
+            url,
+        }
+    }
+
+    fn url(&self, hash: &str) -> Result<Url, reqwest::UrlError> {
+        self.url.join(&format!("ipfs/{}", hash))
+    }
+
+    /// Requests the file behind `link` and returns its body as a stream,
+    /// failing as soon as the file turns out to be larger than
+    /// `max_file_bytes`.
+    fn get(
+        &self,
+        link: &Link,
+        max_file_bytes: Option<u64>,
+    ) -> impl Future<Item = impl Stream<Item = Vec<u8>, Error = failure::Error>, Error = failure::Error>
+    {
+        // Discard the `/ipfs/` prefix (if present) to get the hash.
+        let hash = link.link.trim_start_matches("/ipfs/").to_owned();
+        let url = self.url(&hash);
+
+        let client = self.client.clone();
+        future::result(url.map_err(failure::Error::from))
+            .and_then(move |url| {
+                client
+                    .get(url)
+                    .send()
+                    .and_then(|response| response.error_for_status())
+                    .map_err(failure::Error::from)
+            })
+            .and_then(move |response| {
+                let too_large = move |size: u64| {
+                    format_err!(
+                        "IPFS file {} is too large. It can be at most {} bytes but is {} bytes",
+                        hash,
+                        max_file_bytes.unwrap_or(0),
+                        size
+                    )
+                };
+
+                // Gateways usually announce the file size, but do not rely
+                // on it when streaming the body
+                if let (Some(max_bytes), Some(size)) = (max_file_bytes, response.content_length()) {
+                    if size > max_bytes {
+                        return Err(too_large(size));
+                    }
+                }
+
+                let mut size = 0u64;
+                let body: Decoder = response.into_body();
+                Ok(body.map_err(failure::Error::from).and_then(move |chunk| {
+                    size += chunk.as_ref().len() as u64;
+                    match max_file_bytes {
+                        Some(max_bytes) if size > max_bytes => Err(too_large(size)),
+                        _ => Ok(chunk.as_ref().to_vec()),
+                    }
+                }))
+            })
+    }
+}
+
+impl LinkResolverTrait for GatewayLinkResolver {
+    fn cat(
+        &self,
+        logger: &Logger,
+        link: &Link,
+    ) -> Box<Future<Item = Vec<u8>, Error = failure::Error> + Send> {
+        trace!(logger, "Fetching file from IPFS gateway"; "link" => &link.link);
+
+        let max_file_size = read_u64_from_env(MAX_IPFS_FILE_SIZE_VAR);
+        Box::new(
+            self.get(link, max_file_size)
+                .and_then(|body| body.concat2())
+                .timeout(ipfs_timeout())
+                .map_err(|e| {
+                    e.into_inner().unwrap_or_else(|| {
+                        format_err!("IPFS gateway took too long to return the file")
+                    })
+                }),
+        )
+    }
+
+    fn json_stream(
+        &self,
+        link: &Link,
+    ) -> Box<Future<Item = JsonValueStream, Error = failure::Error> + Send + 'static> {
+        let max_file_size =
+            read_u64_from_env(MAX_IPFS_MAP_FILE_SIZE_VAR).unwrap_or(DEFAULT_MAX_IPFS_MAP_FILE_SIZE);
+        Box::new(self.get(link, Some(max_file_size)).map(json_value_stream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+
+    /// Answers a single HTTP request with `status` and `body` and sends the
+    /// requested path to the returned receiver.
+    fn serve_once(status: &'static str, body: &'static str) -> (Url, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!(
+            "http://{}/gateway",
+            listener.local_addr().unwrap()
+        ))
+        .unwrap();
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+            }
+            sender
+                .send(request_line.split(' ').nth(1).unwrap().to_owned())
+                .unwrap();
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            )
+            .unwrap();
+        });
+
+        (url, receiver)
+    }
+
+    #[test]
+    fn keeps_path_of_gateway_url() {
+        for base in vec![
+            "https://example.com/gateway",
+            "https://example.com/gateway/",
+        ] {
+            let resolver = GatewayLinkResolver::new(Url::parse(base).unwrap());
+            assert_eq!(
+                resolver.url("QmFile").unwrap().as_str(),
+                "https://example.com/gateway/ipfs/QmFile"
+            );
+        }
+
+        let resolver = GatewayLinkResolver::new(Url::parse("https://example.com").unwrap());
+        assert_eq!(
+            resolver.url("QmFile").unwrap().as_str(),
+            "https://example.com/ipfs/QmFile"
+        );
+    }
+
+    #[test]
+    fn fetches_files_from_gateway() {
+        let (url, paths) = serve_once("200 OK", "\"one\"\n[\"two\"]\n");
+        let resolver = GatewayLinkResolver::new(url);
+        let logger = Logger::root(slog::Discard, o!());
+
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let data = runtime
+            .block_on(resolver.cat(&logger, &Link::from("/ipfs/QmFile".to_owned())))
+            .unwrap();
+        assert_eq!(data, b"\"one\"\n[\"two\"]\n".to_vec());
+        assert_eq!(paths.recv().unwrap(), "/gateway/ipfs/QmFile");
+
+        let (url, _) = serve_once("200 OK", "\"one\"\n[\"two\"]\n");
+        let resolver = GatewayLinkResolver::new(url);
+        let values = runtime
+            .block_on(
+                resolver
+                    .json_stream(&Link::from("QmFile".to_owned()))
+                    .and_then(|stream| stream.map(|sv| sv.value).collect()),
+            )
+            .unwrap();
+        assert_eq!(
+            values,
+            vec![serde_json::json!("one"), serde_json::json!(["two"])]
+        );
+    }
+
+    #[test]
+    fn fails_on_error_status() {
+        let (url, _) = serve_once("404 Not Found", "not found");
+        let resolver = GatewayLinkResolver::new(url);
+        let logger = Logger::root(slog::Discard, o!());
+
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        assert!(runtime
+            .block_on(resolver.cat(&logger, &Link::from("QmMissing".to_owned())))
+            .is_err());
+    }
+}
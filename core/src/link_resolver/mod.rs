@@ -11,6 +11,14 @@ use std::time::Duration;
 use graph::prelude::{LinkResolver as LinkResolverTrait, *};
 use graph::serde_json::Value;
 
+mod composite;
+mod file;
+mod gateway;
+
+pub use self::composite::CompositeLinkResolver;
+pub use self::file::FileLinkResolver;
+pub use self::gateway::GatewayLinkResolver;
+
 // Environment variable for limiting the `ipfs.map` file size limit.
 const MAX_IPFS_MAP_FILE_SIZE_VAR: &'static str = "GRAPH_MAX_IPFS_MAP_FILE_SIZE";
 
@@ -31,8 +39,8 @@ lazy_static! {
 
 }
 
-// The timeout for IPFS requests in seconds
-fn ipfs_timeout() -> Duration {
+/// The timeout for IPFS requests, set in seconds with `GRAPH_IPFS_TIMEOUT`.
+pub fn ipfs_timeout() -> Duration {
     let timeout = env::var("GRAPH_IPFS_TIMEOUT").ok().map(|s| {
         u64::from_str(&s).unwrap_or_else(|_| panic!("failed to parse env var GRAPH_IPFS_TIMEOUT"))
     });
@@ -83,6 +91,70 @@ where
     }
 }
 
+/// Deserializes the lines of the byte `stream` into a stream of JSON values,
+/// one value per line.
+fn json_value_stream<S, B>(stream: S) -> JsonValueStream
+where
+    S: Stream<Item = B, Error = failure::Error> + Send + 'static,
+    B: AsRef<[u8]>,
+{
+    let mut stream = stream.fuse();
+    let mut buf = BytesMut::with_capacity(1024);
+    // Count the number of lines we've already successfully deserialized.
+    // We need that to adjust the line number in error messages from serde_json
+    // to translate from line numbers in the snippet we are deserializing
+    // to the line number in the overall file
+    let mut count = 0;
+
+    Box::new(poll_fn(
+        move || -> Poll<Option<JsonStreamValue>, failure::Error> {
+            loop {
+                if let Some(offset) = buf.iter().position(|b| *b == b'\n') {
+                    let line_bytes = buf.split_to(offset + 1);
+                    count += 1;
+                    if line_bytes.len() > 1 {
+                        let line = std::str::from_utf8(&line_bytes)?;
+                        let res = match serde_json::from_str::<Value>(line) {
+                            Ok(v) => Ok(Async::Ready(Some(JsonStreamValue {
+                                value: v,
+                                line: count,
+                            }))),
+                            Err(e) => {
+                                // Adjust the line number in the serde error. This
+                                // is fun because we can only get at the full error
+                                // message, and not the error message without line number
+                                let msg = e.to_string();
+                                let msg = msg.split(" at line ").next().unwrap();
+                                Err(format_err!(
+                                    "{} at line {} column {}: '{}'",
+                                    msg,
+                                    e.line() + count - 1,
+                                    e.column(),
+                                    line
+                                ))
+                            }
+                        };
+                        return res;
+                    }
+                } else {
+                    // We only get here if there is no complete line in buf, and
+                    // it is therefore ok to immediately pass an Async::NotReady
+                    // from stream through.
+                    // If we get a None from poll, but still have something in buf,
+                    // that means the input was not terminated with a newline. We
+                    // add that so that the last line gets picked up in the next
+                    // run through the loop.
+                    match try_ready!(stream.poll()) {
+                        Some(b) => buf.extend_from_slice(b.as_ref()),
+                        None if buf.len() > 0 => buf.extend_from_slice(&[b'\n']),
+                        None => return Ok(Async::Ready(None)),
+                    }
+                }
+            }
+        },
+    ))
+}
+
 pub struct LinkResolver {
     client: ipfs_api::IpfsClient,
     cache: Arc<Mutex<LruCache<String, Vec<u8>>>>,
@@ -151,61 +223,11 @@ impl LinkResolverTrait for LinkResolver {
     ) -> Box<Future<Item = JsonValueStream, Error = failure::Error> + Send + 'static> {
         // Discard the `/ipfs/` prefix (if present) to get the hash.
         let path = link.link.trim_start_matches("/ipfs/").to_owned();
-        let mut stream = self.client.cat(&path).fuse();
-        let mut buf = BytesMut::with_capacity(1024);
-        // Count the number of lines we've already successfully deserialized.
-        // We need that to adjust the line number in error messages from serde_json
-        // to translate from line numbers in the snippet we are deserializing
-        // to the line number in the overall file
-        let mut count = 0;
-
-        let stream: JsonValueStream = Box::new(poll_fn(
-            move || -> Poll<Option<JsonStreamValue>, failure::Error> {
-                loop {
-                    if let Some(offset) = buf.iter().position(|b| *b == b'\n') {
-                        let line_bytes = buf.split_to(offset + 1);
-                        count += 1;
-                        if line_bytes.len() > 1 {
-                            let line = std::str::from_utf8(&line_bytes)?;
-                            let res = match serde_json::from_str::<Value>(line) {
-                                Ok(v) => Ok(Async::Ready(Some(JsonStreamValue {
-                                    value: v,
-                                    line: count,
-                                }))),
-                                Err(e) => {
-                                    // Adjust the line number in the serde error. This
-                                    // is fun because we can only get at the full error
-                                    // message, and not the error message without line number
-                                    let msg = e.to_string();
-                                    let msg = msg.split(" at line ").next().unwrap();
-                                    Err(format_err!(
-                                        "{} at line {} column {}: '{}'",
-                                        msg,
-                                        e.line() + count - 1,
-                                        e.column(),
-                                        line
-                                    ))
-                                }
-                            };
-                            return res;
-                        }
-                    } else {
-                        // We only get here if there is no complete line in buf, and
-                        // it is therefore ok to immediately pass an Async::NotReady
-                        // from stream through.
-                        // If we get a None from poll, but still have something in buf,
-                        // that means the input was not terminated with a newline. We
-                        // add that so that the last line gets picked up in the next
-                        // run through the loop.
-                        match try_ready!(stream.poll()) {
-                            Some(b) => buf.extend_from_slice(&b),
-                            None if buf.len() > 0 => buf.extend_from_slice(&[b'\n']),
-                            None => return Ok(Async::Ready(None)),
-                        }
-                    }
-                }
-            },
-        ));
+        let stream = json_value_stream(
+            self.client
+                .cat(&path)
+                .map_err(|e| failure::err_msg(e.to_string())),
+        );
 
         let max_file_size =
             read_u64_from_env(MAX_IPFS_MAP_FILE_SIZE_VAR).unwrap_or(DEFAULT_MAX_IPFS_MAP_FILE_SIZE);
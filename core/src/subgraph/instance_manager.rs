@@ -2,7 +2,9 @@ use futures::future::{loop_fn, Loop};
 use futures::sync::mpsc::{channel, Receiver, Sender};
 use std::collections::HashMap;
 use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::RwLock;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
 use graph::data::subgraph::schema::{
@@ -23,6 +25,7 @@ struct IndexingInputs<B, S, T> {
     pub host_builder: T,
     pub templates: Vec<(String, DataSourceTemplate)>,
     pub include_calls_in_blocks: bool,
+    pub shutdown_handle: CancelHandle,
 }
 
 struct IndexingState<T>
@@ -54,6 +57,8 @@ where
 pub struct SubgraphInstanceManager {
     logger: Logger,
     input: Sender<SubgraphAssignmentProviderEvent>,
+    shutdown_guard: SharedCancelGuard,
+    running: Arc<AtomicUsize>,
 }
 
 impl SubgraphInstanceManager {
@@ -63,6 +68,7 @@ impl SubgraphInstanceManager {
         stores: HashMap<String, Arc<S>>,
         host_builder: T,
         block_stream_builder: B,
+        network_capabilities: HashMap<String, Vec<NodeCapabilities>>,
     ) -> Self
     where
         S: Store + ChainStore,
@@ -75,6 +81,9 @@ impl SubgraphInstanceManager {
         // Create channel for receiving subgraph provider events.
         let (subgraph_sender, subgraph_receiver) = channel(100);
 
+        let shutdown_guard = SharedCancelGuard::new();
+        let running = Arc::new(AtomicUsize::new(0));
+
         // Handle incoming events from the subgraph provider.
         Self::handle_subgraph_events(
             logger_factory,
@@ -82,14 +91,37 @@ impl SubgraphInstanceManager {
             stores,
             host_builder,
             block_stream_builder,
+            network_capabilities,
+            shutdown_guard.handle(),
+            running.clone(),
         );
 
         SubgraphInstanceManager {
             logger,
             input: subgraph_sender,
+            shutdown_guard,
+            running,
         }
     }
 
+    /// Stops indexing all subgraphs. Subgraphs finish the block they are
+    /// currently processing, so that no block is interrupted halfway. The
+    /// returned future resolves once all subgraphs have stopped.
+    pub fn shutdown(&self) -> impl Future<Item = (), Error = ()> {
+        info!(
+            self.logger,
+            "Stopping subgraphs";
+            "running" => self.running.load(Ordering::SeqCst),
+        );
+        self.shutdown_guard.cancel();
+
+        let running = self.running.clone();
+        tokio::timer::Interval::new(Instant::now(), Duration::from_millis(100))
+            .map_err(|_| ())
+            .take_while(move |_| Ok(running.load(Ordering::SeqCst) > 0))
+            .for_each(|_| Ok(()))
+    }
+
     /// Handle incoming events from subgraph providers.
     fn handle_subgraph_events<B, S, T>(
         logger_factory: LoggerFactory,
@@ -97,6 +129,9 @@ impl SubgraphInstanceManager {
         stores: HashMap<String, Arc<S>>,
         host_builder: T,
         block_stream_builder: B,
+        network_capabilities: HashMap<String, Vec<NodeCapabilities>>,
+        shutdown_handle: CancelHandle,
+        running: Arc<AtomicUsize>,
     ) where
         S: Store + ChainStore,
         T: RuntimeHostBuilder,
@@ -125,6 +160,7 @@ impl SubgraphInstanceManager {
                                 instances.clone(),
                                 host_builder.clone(),
                                 block_stream_builder.clone(),
+                                network_capabilities.get(&n).map_or(&[][..], Vec::as_slice),
                                 stores
                                     .get(&n)
                                     .expect(&format!(
@@ -133,6 +169,8 @@ impl SubgraphInstanceManager {
                                     ))
                                     .clone(),
                                 manifest,
+                                shutdown_handle.clone(),
+                                running.clone(),
                             )
                             .map_err(|err| {
                                 error!(
@@ -169,14 +207,32 @@ impl SubgraphInstanceManager {
         instances: SharedInstanceKeepAliveMap,
         host_builder: T,
         stream_builder: B,
+        network_capabilities: &[NodeCapabilities],
         store: Arc<S>,
         manifest: SubgraphManifest,
+        shutdown_handle: CancelHandle,
+        running: Arc<AtomicUsize>,
     ) -> Result<(), Error>
     where
         T: RuntimeHostBuilder,
         B: BlockStreamBuilder + 'static,
         S: Store + ChainStore,
     {
+        // Providers may have changed since the subgraph was deployed; if
+        // none of them can index the subgraph anymore, fail it right away
+        let required = manifest.required_ethereum_capabilities();
+        if !network_capabilities
+            .iter()
+            .any(|capabilities| capabilities.supports(&required))
+        {
+            let err = SubgraphRegistrarError::NodeCapabilitiesNotSupported(
+                manifest.network_name()?,
+                required,
+            );
+            record_subgraph_failure(&logger, store.as_ref(), &manifest.id);
+            return Err(err.into());
+        }
+
         // Clear the 'failed' state of the subgraph. We were told explicitly
         // to start, which implies we assume the subgraph has not failed (yet)
         // If we can't even clear the 'failed' flag, don't try to start
@@ -235,6 +291,7 @@ impl SubgraphInstanceManager {
                 stream_builder,
                 host_builder,
                 include_calls_in_blocks,
+                shutdown_handle,
             },
             state: IndexingState {
                 logger,
@@ -254,9 +311,13 @@ impl SubgraphInstanceManager {
         // forward; this is easier than updating the existing block stream.
         //
         // This task has many calls to the store, so mark it as `blocking`.
-        tokio::spawn(graph::util::futures::blocking(loop_fn(ctx, |ctx| {
-            run_subgraph(ctx)
-        })));
+        running.fetch_add(1, Ordering::SeqCst);
+        tokio::spawn(graph::util::futures::blocking(
+            loop_fn(ctx, |ctx| run_subgraph(ctx)).then(move |result| {
+                running.fetch_sub(1, Ordering::SeqCst);
+                result
+            }),
+        ));
 
         Ok(())
     }
@@ -300,20 +361,33 @@ where
 
     let block_stream_canceler = CancelGuard::new();
     let block_stream_cancel_handle = block_stream_canceler.handle();
-    let block_stream = ctx
-        .inputs
-        .stream_builder
-        .build(
-            logger.clone(),
-            ctx.inputs.deployment_id.clone(),
-            ctx.inputs.network_name.clone(),
-            ctx.state.log_filter.clone(),
-            ctx.state.call_filter.clone(),
-            ctx.state.block_filter.clone(),
-            ctx.inputs.include_calls_in_blocks,
-        )
+    let block_stream = match ctx.inputs.stream_builder.build(
+        logger.clone(),
+        ctx.inputs.deployment_id.clone(),
+        ctx.inputs.network_name.clone(),
+        ctx.state.log_filter.clone(),
+        ctx.state.call_filter.clone(),
+        ctx.state.block_filter.clone(),
+        ctx.inputs.include_calls_in_blocks,
+    ) {
+        Ok(block_stream) => block_stream,
+        Err(e) => {
+            error!(
+                logger,
+                "Failed to create block stream: {}", e;
+                "id" => id_for_err.to_string(),
+                "code" => LogCode::SubgraphSyncingFailure
+            );
+            record_subgraph_failure(&logger, store_for_err.as_ref(), &id_for_err);
+            return future::Either::A(future::err(()));
+        }
+    };
+    let block_stream = block_stream
         .from_err()
-        .cancelable(&block_stream_canceler, || CancelableError::Cancel);
+        .cancelable(&block_stream_canceler, || CancelableError::Cancel)
+        // Shutting down only cancels the stream between blocks, so the
+        // block that is being processed is still written to the store
+        .cancelable(&ctx.inputs.shutdown_handle, || CancelableError::Cancel);
 
     // Keep the stream's cancel guard around to be able to shut it down
     // when the subgraph deployment is unassigned
@@ -332,84 +406,92 @@ where
         NeedsRestart(IndexingContext<B, S, T>),
     }
 
-    block_stream
-        // Log and drop the errors from the block_stream
-        // The block stream will continue attempting to produce blocks
-        .then(move |result| match result {
-            Ok(block) => Ok(Some(block)),
-            Err(e) => {
-                debug!(
-                    logger_for_block_stream_errors,
-                    "Block stream produced a non-fatal error";
-                    "error" => format!("{}", e),
-                );
-                Ok(None)
-            }
-        })
-        .filter_map(|block_opt| block_opt)
-        // Process blocks from the stream as long as no restart is needed
-        .fold(ctx, move |ctx, block| {
-            process_block(
-                logger.clone(),
-                ctx,
-                block_stream_cancel_handle.clone(),
-                block,
-            )
-            .map_err(|e| StreamEnd::Error(e))
-            .and_then(|(ctx, needs_restart)| match needs_restart {
-                false => Ok(ctx),
-                true => Err(StreamEnd::NeedsRestart(ctx)),
+    future::Either::B(
+        block_stream
+            // Log and drop the errors from the block_stream
+            // The block stream will continue attempting to produce blocks
+            .then(move |result| match result {
+                Ok(block) => Ok(Some(block)),
+                // Canceling only errors the stream once, so stop processing
+                // blocks right away
+                Err(CancelableError::Cancel) => Err(StreamEnd::Error(CancelableError::Cancel)),
+                Err(e) => {
+                    debug!(
+                        logger_for_block_stream_errors,
+                        "Block stream produced a non-fatal error";
+                        "error" => format!("{}", e),
+                    );
+                    Ok(None)
+                }
             })
-        })
-        .then(move |res| match res {
-            Ok(_) => unreachable!("block stream finished without error"),
-            Err(StreamEnd::NeedsRestart(mut ctx)) => {
-                // Increase the restart counter
-                ctx.state.restarts += 1;
-
-                // Cancel the stream for real
-                ctx.state
-                    .instances
-                    .write()
-                    .unwrap()
-                    .remove(&ctx.inputs.deployment_id);
-
-                // And restart the subgraph
-                Ok(Loop::Continue(ctx))
-            }
-
-            Err(StreamEnd::Error(CancelableError::Cancel)) => {
-                debug!(
-                    logger_for_err,
-                    "Subgraph block stream shut down cleanly";
-                    "id" => id_for_err.to_string(),
-                );
-                Err(())
-            }
+            .filter_map(|block_opt| block_opt)
+            // Process blocks from the stream as long as no restart is needed
+            .fold(ctx, move |ctx, block| {
+                process_block(
+                    logger.clone(),
+                    ctx,
+                    block_stream_cancel_handle.clone(),
+                    block,
+                )
+                .map_err(|e| StreamEnd::Error(e))
+                .and_then(|(ctx, needs_restart)| match needs_restart {
+                    false => Ok(ctx),
+                    true => Err(StreamEnd::NeedsRestart(ctx)),
+                })
+            })
+            .then(move |res| match res {
+                Ok(_) => unreachable!("block stream finished without error"),
+                Err(StreamEnd::NeedsRestart(mut ctx)) => {
+                    // Increase the restart counter
+                    ctx.state.restarts += 1;
+
+                    // Cancel the stream for real
+                    ctx.state
+                        .instances
+                        .write()
+                        .unwrap()
+                        .remove(&ctx.inputs.deployment_id);
+
+                    // And restart the subgraph
+                    Ok(Loop::Continue(ctx))
+                }
 
-            // Handle unexpected stream errors by marking the subgraph as failed.
-            Err(StreamEnd::Error(CancelableError::Error(e))) => {
-                error!(
-                    logger_for_err,
-                    "Subgraph instance failed to run: {}", e;
-                    "id" => id_for_err.to_string(),
-                    "code" => LogCode::SubgraphSyncingFailure
-                );
+                Err(StreamEnd::Error(CancelableError::Cancel)) => {
+                    debug!(
+                        logger_for_err,
+                        "Subgraph block stream shut down cleanly";
+                        "id" => id_for_err.to_string(),
+                    );
+                    Err(())
+                }
 
-                // Set subgraph status to Failed
-                let status_ops =
-                    SubgraphDeploymentEntity::update_failed_operations(&id_for_err, true);
-                if let Err(e) = store_for_err.apply_entity_operations(status_ops, None) {
+                // Handle unexpected stream errors by marking the subgraph as failed.
+                Err(StreamEnd::Error(CancelableError::Error(e))) => {
                     error!(
                         logger_for_err,
-                        "Failed to set subgraph status to Failed: {}", e;
+                        "Subgraph instance failed to run: {}", e;
                         "id" => id_for_err.to_string(),
-                        "code" => LogCode::SubgraphSyncingFailureNotRecorded
+                        "code" => LogCode::SubgraphSyncingFailure
                     );
+
+                    record_subgraph_failure(&logger_for_err, store_for_err.as_ref(), &id_for_err);
+                    Err(())
                 }
-                Err(())
-            }
-        })
+            }),
+    )
+}
+
+/// Sets the status of a subgraph deployment to failed.
+fn record_subgraph_failure<S: Store>(logger: &Logger, store: &S, id: &SubgraphDeploymentId) {
+    let status_ops = SubgraphDeploymentEntity::update_failed_operations(id, true);
+    if let Err(e) = store.apply_entity_operations(status_ops, None) {
+        error!(
+            logger,
+            "Failed to set subgraph status to Failed: {}", e;
+            "id" => id.to_string(),
+            "code" => LogCode::SubgraphSyncingFailureNotRecorded
+        );
+    }
 }
 
 /// Processes a block and returns the updated context and a boolean flag indicating
@@ -16,6 +16,7 @@ pub struct SubgraphRegistrar<L, P, S, CS> {
     provider: Arc<P>,
     store: Arc<S>,
     chain_stores: HashMap<String, Arc<CS>>,
+    network_capabilities: HashMap<String, Vec<NodeCapabilities>>,
     node_id: NodeId,
     version_switching_mode: SubgraphVersionSwitchingMode,
     assignment_event_stream_cancel_guard: CancelGuard, // cancels on drop
@@ -34,6 +35,7 @@ where
         provider: Arc<P>,
         store: Arc<S>,
         chain_stores: HashMap<String, Arc<CS>>,
+        network_capabilities: HashMap<String, Vec<NodeCapabilities>>,
         node_id: NodeId,
         version_switching_mode: SubgraphVersionSwitchingMode,
     ) -> Self {
@@ -47,6 +49,7 @@ where
             provider,
             store,
             chain_stores,
+            network_capabilities,
             node_id,
             version_switching_mode,
             assignment_event_stream_cancel_guard: CancelGuard::new(),
@@ -270,6 +273,7 @@ where
     ) -> Box<Future<Item = (), Error = SubgraphRegistrarError> + Send + 'static> {
         let store = self.store.clone();
         let chain_stores = self.chain_stores.clone();
+        let network_capabilities = self.network_capabilities.clone();
         let version_switching_mode = self.version_switching_mode;
 
         let logger = self.logger_factory.subgraph_logger(&hash);
@@ -280,9 +284,27 @@ where
                 .and_then(validation::validate_manifest)
                 .and_then(move |manifest| {
                     let network_name = manifest.network_name()?;
-                    let chain_store = chain_stores
-                        .get(&network_name)
-                        .ok_or(SubgraphRegistrarError::NetworkNotSupported(network_name))?;
+                    let chain_store = chain_stores.get(&network_name).ok_or(
+                        SubgraphRegistrarError::NetworkNotSupported(network_name.clone()),
+                    )?;
+
+                    // Refuse subgraphs that none of our Ethereum providers can index
+                    let required = manifest.required_ethereum_capabilities();
+                    let supported =
+                        network_capabilities
+                            .get(&network_name)
+                            .map_or(false, |available| {
+                                available
+                                    .iter()
+                                    .any(|capabilities| capabilities.supports(&required))
+                            });
+                    if !supported {
+                        return Err(SubgraphRegistrarError::NodeCapabilitiesNotSupported(
+                            network_name,
+                            required,
+                        ));
+                    }
+
                     create_subgraph_version(
                         &logger,
                         store,
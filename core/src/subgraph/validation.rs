@@ -1,4 +1,5 @@
 use graph::prelude::*;
+use semver::Version;
 
 pub fn validate_manifest(
     manifest: SubgraphManifest,
@@ -18,7 +19,7 @@ pub fn validate_manifest(
             .mapping
             .block_handlers
             .as_ref()
-            .map_or(false, |handlers| handlers.is_empty());
+            .map_or(false, |handlers| !handlers.is_empty());
 
         no_source_address && (has_call_handlers || has_block_handlers)
     });
@@ -57,9 +58,141 @@ pub fn validate_manifest(
         errors.push(SubgraphManifestValidationError::DataSourceBlockHandlerLimitExceeded)
     }
 
+    // Validate that handlers only request transaction receipts if the
+    // mapping API version supports passing them to the handler
+    let has_unsupported_receipts = manifest.data_sources.iter().any(|data_source| {
+        let wants_receipts = data_source
+            .mapping
+            .event_handlers
+            .as_ref()
+            .map_or(false, |handlers| {
+                handlers.iter().any(|handler| handler.receipt)
+            })
+            || data_source
+                .mapping
+                .call_handlers
+                .as_ref()
+                .map_or(false, |handlers| {
+                    handlers.iter().any(|handler| handler.receipt)
+                });
+
+        wants_receipts
+            && Version::parse(&data_source.mapping.api_version)
+                .map_or(false, |version| version < Version::new(0, 0, 4))
+    });
+
+    if has_unsupported_receipts {
+        errors.push(SubgraphManifestValidationError::ReceiptsRequireApiVersion)
+    }
+
     if errors.is_empty() {
         return Ok(manifest);
     }
 
     return Err(SubgraphRegistrarError::ManifestValidationError(errors));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    /// Serves the files of a single-data-source manifest from memory.
+    struct TestResolver(HashMap<&'static str, Vec<u8>>);
+
+    impl LinkResolver for TestResolver {
+        fn cat(
+            &self,
+            _: &Logger,
+            link: &Link,
+        ) -> Box<Future<Item = Vec<u8>, Error = failure::Error> + Send> {
+            Box::new(future::result(
+                self.0
+                    .get(link.link.as_str())
+                    .cloned()
+                    .ok_or_else(|| format_err!("no such file: {}", link.link)),
+            ))
+        }
+
+        fn json_stream(
+            &self,
+            _: &Link,
+        ) -> Box<Future<Item = JsonValueStream, Error = failure::Error> + Send + 'static> {
+            unimplemented!()
+        }
+    }
+
+    fn manifest(api_version: &str, receipt: bool) -> SubgraphManifest {
+        let yaml = format!(
+            "
+specVersion: 0.0.1
+schema:
+  file:
+    /: schema.graphql
+dataSources:
+- kind: ethereum/contract
+  network: mainnet
+  name: ExampleDataSource
+  source:
+    address: \"22843e74c59580b3eaf6c233fa67d8b7c561a835\"
+    abi: ExampleContract
+  mapping:
+    kind: ethereum/events
+    apiVersion: {}
+    language: wasm/assemblyscript
+    entities: []
+    abis:
+    - name: ExampleContract
+      file:
+        /: ExampleContract.json
+    eventHandlers:
+    - event: ExampleEvent(string)
+      handler: handleExampleEvent
+      receipt: {}
+    file:
+      /: mapping.wasm
+",
+            api_version, receipt
+        );
+
+        let mut files = HashMap::new();
+        files.insert("/ipfs/validationtest", yaml.into_bytes());
+        files.insert("schema.graphql", b"type Thing @entity { id: ID! }".to_vec());
+        files.insert("ExampleContract.json", b"[]".to_vec());
+        files.insert("mapping.wasm", b"\0asm\x01\0\0\0".to_vec());
+
+        SubgraphManifest::resolve(
+            Link::from("/ipfs/validationtest".to_owned()),
+            Arc::new(TestResolver(files)),
+            Logger::root(slog::Discard, o!()),
+        )
+        .wait()
+        .expect("failed to resolve manifest")
+    }
+
+    fn requires_api_version(result: Result<SubgraphManifest, SubgraphRegistrarError>) -> bool {
+        match result {
+            Err(SubgraphRegistrarError::ManifestValidationError(errors)) => {
+                errors.iter().any(|error| match error {
+                    SubgraphManifestValidationError::ReceiptsRequireApiVersion => true,
+                    _ => false,
+                })
+            }
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn rejects_receipts_before_api_version_0_0_4() {
+        assert!(requires_api_version(validate_manifest(manifest(
+            "0.0.3", true
+        ))));
+    }
+
+    #[test]
+    fn accepts_receipts_from_api_version_0_0_4() {
+        assert!(validate_manifest(manifest("0.0.4", true)).is_ok());
+        assert!(validate_manifest(manifest("0.0.3", false)).is_ok());
+    }
+}
@@ -53,6 +53,8 @@ fn insert_and_query(
         deadline: None,
         max_complexity: None,
         max_depth: 100,
+        max_entities: None,
+        trace: None,
     };
     let document = graphql_parser::parse_query(query).unwrap();
     let query = Query {
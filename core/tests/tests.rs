@@ -13,17 +13,19 @@ use std::collections::HashMap;
 use std::collections::HashSet;
 use std::fs::read_to_string;
 use std::io::Cursor;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
 use std::time::Duration;
 use std::time::Instant;
 
 use graph::components::ethereum::*;
+use graph::components::link_resolver::LinkResolver as LinkResolverTrait;
 use graph::prelude::*;
 use graph::web3::types::*;
 use graph_core::{LinkResolver, SubgraphInstanceManager};
 use graph_mock::{FakeStore, MockBlockStreamBuilder, MockStore};
 
-use crate::tokio::timer::Delay;
+use crate::tokio::timer::{Delay, Timeout};
 
 /// Adds subgraph located in `test/subgraphs/`, replacing "link to" placeholders
 /// in the subgraph manifest with links to files just added into a local IPFS
@@ -171,12 +173,15 @@ fn multiple_data_sources_per_subgraph() {
             stores.insert("mainnet".to_string(), Arc::new(FakeStore));
             let host_builder = MockRuntimeHostBuilder::new();
             let block_stream_builder = MockBlockStreamBuilder::new();
+            let mut capabilities = HashMap::new();
+            capabilities.insert("mainnet".to_string(), vec![NodeCapabilities::full()]);
 
             let manager = SubgraphInstanceManager::new(
                 &logger_factory,
                 stores,
                 host_builder.clone(),
                 block_stream_builder.clone(),
+                capabilities,
             );
 
             // Load a subgraph with two data sources
@@ -266,6 +271,9 @@ fn subgraph_provider_events() {
                 Arc::new(provider),
                 store.clone(),
                 stores,
+                vec![("mainnet".to_string(), vec![NodeCapabilities::full()])]
+                    .into_iter()
+                    .collect(),
                 node_id.clone(),
                 SubgraphVersionSwitchingMode::Instant,
             );
@@ -391,3 +399,268 @@ fn subgraph_provider_events() {
         }))
         .unwrap();
 }
+
+/// Starts the `two-datasources` subgraph on an instance manager whose
+/// block streams never produce a block. Returns the manager, a flag that is
+/// set once the block stream was created and the store of the subgraph.
+fn start_pending_subgraph(
+    runtime: &mut tokio::runtime::Runtime,
+    network_capabilities: Vec<NodeCapabilities>,
+) -> (
+    SubgraphInstanceManager,
+    Arc<AtomicBool>,
+    Arc<MockStore>,
+    SubgraphDeploymentId,
+) {
+    #[derive(Debug)]
+    struct MockRuntimeHost {}
+
+    impl RuntimeHost for MockRuntimeHost {
+        fn matches_log(&self, _: &Log) -> bool {
+            false
+        }
+
+        fn matches_call(&self, _call: &EthereumCall) -> bool {
+            false
+        }
+
+        fn matches_block(&self, _call: EthereumBlockTriggerType) -> bool {
+            false
+        }
+
+        fn process_log(
+            &self,
+            _: Logger,
+            _: Arc<EthereumBlock>,
+            _: Arc<Transaction>,
+            _: Arc<Log>,
+            _: BlockState,
+        ) -> Box<Future<Item = BlockState, Error = Error> + Send> {
+            unimplemented!();
+        }
+
+        fn process_call(
+            &self,
+            _logger: Logger,
+            _block: Arc<EthereumBlock>,
+            _transaction: Arc<Transaction>,
+            _call: Arc<EthereumCall>,
+            _state: BlockState,
+        ) -> Box<Future<Item = BlockState, Error = Error> + Send> {
+            unimplemented!();
+        }
+
+        fn process_block(
+            &self,
+            _logger: Logger,
+            _block: Arc<EthereumBlock>,
+            _trigger_type: EthereumBlockTriggerType,
+            _state: BlockState,
+        ) -> Box<Future<Item = BlockState, Error = Error> + Send> {
+            unimplemented!();
+        }
+    }
+
+    #[derive(Clone)]
+    struct MockRuntimeHostBuilder {}
+
+    impl RuntimeHostBuilder for MockRuntimeHostBuilder {
+        type Host = MockRuntimeHost;
+
+        fn build(
+            &self,
+            _: &Logger,
+            _: String,
+            _: SubgraphDeploymentId,
+            _: DataSource,
+        ) -> Result<Self::Host, Error> {
+            Ok(MockRuntimeHost {})
+        }
+    }
+
+    /// A block stream that never produces a block, like a subgraph that
+    /// is waiting for the next block.
+    struct PendingBlockStream {}
+
+    impl Stream for PendingBlockStream {
+        type Item = EthereumBlockWithTriggers;
+        type Error = Error;
+
+        fn poll(&mut self) -> Poll<Option<EthereumBlockWithTriggers>, Error> {
+            Ok(Async::NotReady)
+        }
+    }
+
+    impl BlockStream for PendingBlockStream {
+        fn parse_triggers(
+            _: Option<EthereumLogFilter>,
+            _: Option<EthereumCallFilter>,
+            _: Option<EthereumBlockFilter>,
+            _: bool,
+            _: EthereumBlockWithCalls,
+        ) -> Result<EthereumBlockWithTriggers, Error> {
+            unimplemented!()
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct PendingBlockStreamBuilder {
+        started: Arc<AtomicBool>,
+    }
+
+    impl BlockStreamBuilder for PendingBlockStreamBuilder {
+        type Stream = PendingBlockStream;
+
+        fn build(
+            &self,
+            _: Logger,
+            _: SubgraphDeploymentId,
+            _: String,
+            _: Option<EthereumLogFilter>,
+            _: Option<EthereumCallFilter>,
+            _: Option<EthereumBlockFilter>,
+            _: bool,
+        ) -> Result<Self::Stream, Error> {
+            self.started.store(true, Ordering::SeqCst);
+            Ok(PendingBlockStream {})
+        }
+    }
+
+    /// Serves the files of a subgraph in `tests/subgraphs/` under the
+    /// `link to <file>` placeholders of its manifest.
+    struct TestLinkResolver {
+        files: HashMap<String, Vec<u8>>,
+    }
+
+    impl LinkResolverTrait for TestLinkResolver {
+        fn cat(
+            &self,
+            _: &Logger,
+            link: &Link,
+        ) -> Box<Future<Item = Vec<u8>, Error = Error> + Send> {
+            Box::new(future::result(
+                self.files
+                    .get(&link.link)
+                    .cloned()
+                    .ok_or_else(|| format_err!("unknown link: {}", link.link)),
+            ))
+        }
+
+        fn json_stream(
+            &self,
+            _: &Link,
+        ) -> Box<Future<Item = JsonValueStream, Error = Error> + Send + 'static> {
+            unimplemented!()
+        }
+    }
+
+    let dir = "tests/subgraphs/two-datasources";
+    let mut files: HashMap<String, Vec<u8>> = WalkDir::new(dir)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| {
+            (
+                format!("link to {}", entry.file_name().to_str().unwrap()),
+                std::fs::read(entry.path()).unwrap(),
+            )
+        })
+        .collect();
+    let manifest = files.remove("link to two-datasources.yaml").unwrap();
+    files.insert("/ipfs/twodatasources".to_owned(), manifest);
+    let resolver = Arc::new(TestLinkResolver { files });
+
+    let logger = Logger::root(slog::Discard, o!());
+    let logger_factory = LoggerFactory::new(logger.clone(), None);
+
+    let subgraph = runtime
+        .block_on(SubgraphManifest::resolve(
+            Link::from("/ipfs/twodatasources".to_owned()),
+            resolver,
+            logger.clone(),
+        ))
+        .unwrap();
+
+    // The subgraph deployment has to exist for the subgraph to start
+    let store = Arc::new(MockStore::new(vec![]));
+    store
+        .apply_entity_operations(
+            vec![EntityOperation::Set {
+                key: SubgraphDeploymentEntity::key(subgraph.id.clone()),
+                data: Entity::new(),
+            }],
+            None,
+        )
+        .unwrap();
+    let id = subgraph.id.clone();
+    let mut stores = HashMap::new();
+    stores.insert("mainnet".to_string(), store.clone());
+    let mut capabilities = HashMap::new();
+    capabilities.insert("mainnet".to_string(), network_capabilities);
+
+    let block_stream_builder = PendingBlockStreamBuilder::default();
+    let started = block_stream_builder.started.clone();
+    let manager = runtime
+        .block_on(future::lazy(move || {
+            let manager = SubgraphInstanceManager::new(
+                &logger_factory,
+                stores,
+                MockRuntimeHostBuilder {},
+                block_stream_builder,
+                capabilities,
+            );
+            manager
+                .event_sink()
+                .send(SubgraphAssignmentProviderEvent::SubgraphStart(subgraph))
+                .map(move |_| manager)
+        }))
+        .unwrap();
+
+    (manager, started, store, id)
+}
+
+#[test]
+fn subgraph_instances_stop_on_shutdown() {
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    let (manager, started, _, _) =
+        start_pending_subgraph(&mut runtime, vec![NodeCapabilities::default()]);
+
+    // Wait for the subgraph to start indexing
+    let start_time = Instant::now();
+    while !started.load(Ordering::SeqCst) {
+        if start_time.elapsed() > Duration::from_secs(30) {
+            panic!("Test failed, subgraph did not start");
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    // Shutting down stops the subgraph while it waits for the next block
+    runtime
+        .block_on(future::lazy(move || {
+            Timeout::new(manager.shutdown(), Duration::from_secs(30))
+        }))
+        .expect("Test failed, subgraph did not stop on shutdown");
+}
+
+#[test]
+fn subgraphs_fail_to_start_without_capable_providers() {
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    let (_manager, started, store, id) = start_pending_subgraph(&mut runtime, vec![]);
+
+    // Wait for the subgraph to be marked as failed
+    let start_time = Instant::now();
+    loop {
+        let deployment = store
+            .get(SubgraphDeploymentEntity::key(id.clone()))
+            .unwrap()
+            .unwrap();
+        if deployment.get("failed") == Some(&Value::Bool(true)) {
+            break;
+        }
+        if start_time.elapsed() > Duration::from_secs(30) {
+            panic!("Test failed, subgraph was not marked as failed");
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert!(!started.load(Ordering::SeqCst));
+}
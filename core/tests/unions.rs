@@ -0,0 +1,190 @@
+// Tests for graphql unions.
+
+use graph::prelude::*;
+use graph_graphql::prelude::{execute_query, QueryExecutionOptions, StoreResolver};
+use test_store::*;
+
+// `entities` is `(entity, type)`.
+fn insert_and_query(
+    subgraph_id: &str,
+    schema: &str,
+    entities: Vec<(Entity, &str)>,
+    query: &str,
+) -> Result<QueryResult, StoreError> {
+    let subgraph_id = SubgraphDeploymentId::new(subgraph_id).unwrap();
+    let schema = Schema::parse(schema, subgraph_id.clone()).unwrap();
+
+    let manifest = SubgraphManifest {
+        id: subgraph_id.clone(),
+        location: String::new(),
+        spec_version: "1".to_owned(),
+        description: None,
+        repository: None,
+        schema: schema.clone(),
+        data_sources: vec![],
+    };
+
+    let logger = Logger::root(slog::Discard, o!());
+
+    let ops = SubgraphDeploymentEntity::new(&manifest, false, false, Default::default(), 1)
+        .create_operations_replace(&subgraph_id);
+    STORE
+        .create_subgraph_deployment(&logger, &subgraph_id, ops)
+        .unwrap();
+
+    let insert_ops = entities
+        .into_iter()
+        .map(|(data, entity_type)| EntityOperation::Set {
+            key: EntityKey {
+                subgraph_id: subgraph_id.clone(),
+                entity_type: entity_type.to_owned(),
+                entity_id: data["id"].clone().as_string().unwrap(),
+            },
+            data,
+        });
+
+    STORE.apply_entity_operations(insert_ops.collect(), None)?;
+
+    let resolver = StoreResolver::new(&logger, STORE.clone());
+
+    let options = QueryExecutionOptions {
+        logger,
+        resolver,
+        deadline: None,
+        max_complexity: None,
+        max_depth: 100,
+        max_entities: None,
+        trace: None,
+    };
+    let document = graphql_parser::parse_query(query).unwrap();
+    let query = Query {
+        schema: STORE.subgraph_schema(&subgraph_id).unwrap(),
+        document,
+        variables: None,
+    };
+    Ok(execute_query(&query, options))
+}
+
+#[test]
+fn union_zero_entities() {
+    let subgraph_id = "unionZeroEntities";
+    let schema = "type Cat @entity { id: ID!, name: String }
+                  type Dog @entity { id: ID!, barks: Boolean }
+                  union Pet = Cat | Dog";
+
+    let query = "query { pets(first: 100) { __typename } }";
+
+    let res = insert_and_query(subgraph_id, schema, vec![], query).unwrap();
+
+    assert!(res.errors.is_none());
+    assert_eq!(
+        format!("{:?}", res.data.unwrap()),
+        "Object({\"pets\": List([])})"
+    )
+}
+
+#[test]
+fn union_inline_fragment() {
+    let subgraph_id = "unionInlineFragment";
+    let schema = "type Cat @entity { id: ID!, name: String }
+                  type Dog @entity { id: ID!, barks: Boolean }
+                  union Pet = Cat | Dog";
+
+    let cat = (
+        Entity::from(vec![("id", Value::from("1")), ("name", Value::from("Tom"))]),
+        "Cat",
+    );
+    let dog = (
+        Entity::from(vec![("id", Value::from("2")), ("barks", Value::from(true))]),
+        "Dog",
+    );
+
+    let query =
+        "query { pets(orderBy: id) { __typename ... on Cat { name } ... on Dog { barks } } }";
+    let res = insert_and_query(subgraph_id, schema, vec![cat, dog], query).unwrap();
+    assert!(res.errors.is_none());
+    assert_eq!(
+        res.data.unwrap().to_string(),
+        "{pets: [{__typename: \"Cat\", name: \"Tom\"}, {__typename: \"Dog\", barks: true}]}"
+    );
+
+    // Query a single member of the union by ID.
+    let query = "query { pet(id: \"2\") { ... on Cat { name } ... on Dog { barks } } }";
+    let res = insert_and_query(subgraph_id, schema, vec![], query).unwrap();
+    assert!(res.errors.is_none());
+    assert_eq!(res.data.unwrap().to_string(), "{pet: {barks: true}}");
+}
+
+#[test]
+fn reference_union() {
+    let subgraph_id = "ReferenceUnion";
+    let schema = "type Cat @entity { id: ID!, name: String }
+                  type Dog @entity { id: ID!, barks: Boolean }
+                  union Pet = Cat | Dog
+                  type Owner @entity { id: ID!, pet: Pet, pets: [Pet!]! }";
+
+    let cat = (
+        Entity::from(vec![("id", Value::from("1")), ("name", Value::from("Tom"))]),
+        "Cat",
+    );
+    let dog = (
+        Entity::from(vec![
+            ("id", Value::from("2")),
+            ("barks", Value::from(false)),
+        ]),
+        "Dog",
+    );
+    let owner = (
+        Entity::from(vec![
+            ("id", Value::from("1")),
+            ("pet", Value::from("2")),
+            (
+                "pets",
+                Value::List(vec![Value::from("1"), Value::from("2")]),
+            ),
+        ]),
+        "Owner",
+    );
+
+    let query = "query {
+        owners(first: 100) {
+            pet { ... on Dog { barks } }
+            pets(orderBy: id) { __typename id }
+        }
+    }";
+
+    let res = insert_and_query(subgraph_id, schema, vec![cat, dog, owner], query).unwrap();
+
+    assert!(res.errors.is_none());
+    assert_eq!(
+        res.data.unwrap().to_string(),
+        "{owners: [{pet: {barks: false}, \
+         pets: [{__typename: \"Cat\", id: \"1\"}, {__typename: \"Dog\", id: \"2\"}]}]}"
+    );
+}
+
+#[test]
+fn conflicting_union_members_id() {
+    let subgraph_id = "ConflictingUnionMembersId";
+    let schema = "type Cat @entity { id: ID!, name: String }
+                  type Dog @entity { id: ID!, barks: Boolean }
+                  union Pet = Cat | Dog";
+
+    let cat = (
+        Entity::from(vec![("id", Value::from("1")), ("name", Value::from("Tom"))]),
+        "Cat",
+    );
+    let dog = (
+        Entity::from(vec![("id", Value::from("1")), ("barks", Value::from(true))]),
+        "Dog",
+    );
+
+    let query = "query { pets(first: 100) { __typename } }";
+
+    let res = insert_and_query(subgraph_id, schema, vec![cat, dog], query);
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "tried to set entity of type `Dog` with ID \"1\" but an entity of type `Cat`, \
+         which is a member of a union together with `Dog`, exists with the same ID"
+    );
+}
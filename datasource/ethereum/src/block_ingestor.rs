@@ -1,9 +1,32 @@
+use futures::future::{loop_fn, Loop};
+use lazy_static::lazy_static;
+use std::cmp;
+use std::env;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 use std::time::Instant;
 
 use graph::prelude::*;
+use graph::tokio::timer::{Delay, Interval, Timeout};
 use graph::web3::types::*;
 
+/// The longest time to wait before resubscribing to new blocks after
+/// subscribing failed repeatedly.
+const MAX_RESUBSCRIBE_DELAY: Duration = Duration::from_secs(60);
+
+lazy_static! {
+    /// How long to wait for a new head from a subscription before assuming
+    /// the subscription is stuck and resubscribing.
+    static ref NEW_HEADS_TIMEOUT: Duration = env::var("ETHEREUM_NEW_HEADS_TIMEOUT")
+        .ok()
+        .map(|s| u64::from_str(&s).unwrap_or_else(|_| {
+            panic!("failed to parse env var ETHEREUM_NEW_HEADS_TIMEOUT")
+        }))
+        .map(Duration::from_secs)
+        .unwrap_or(Duration::from_secs(120));
+}
+
 pub struct BlockIngestor<S, E>
 where
     S: ChainStore,
@@ -50,43 +73,66 @@ where
     }
 
     pub fn into_polling_stream(self) -> impl Future<Item = (), Error = ()> {
-        // Currently, there is no way to stop block ingestion, so just leak self
-        let static_self: &'static _ = Box::leak(Box::new(self));
+        Arc::new(self).poll_at_interval()
+    }
 
-        // Create stream that emits at polling interval
-        tokio::timer::Interval::new(Instant::now(), static_self.polling_interval)
-            .map_err(move |e| {
-                error!(static_self.logger, "timer::Interval failed: {:?}", e);
-            })
-            .for_each(move |_| {
-                // Attempt to poll
-                static_self.do_poll().then(move |result| {
-                    if let Err(err) = result {
-                        // Some polls will fail due to transient issues
-                        match err {
-                            EthereumAdapterError::BlockUnavailable(_) => {
-                                trace!(
-                                    static_self.logger,
-                                    "Trying again after block polling failed: {}",
-                                    err
-                                );
-                            }
-                            EthereumAdapterError::Unknown(inner_err) => {
-                                warn!(
-                                    static_self.logger,
-                                    "Trying again after block polling failed: {}", inner_err
-                                );
-                            }
-                        }
+    /// Ingests the latest block whenever a stream returned by `new_heads`
+    /// yields, i.e. whenever the Ethereum node has a new head block. If the
+    /// subscription fails, ends or yields nothing for
+    /// `ETHEREUM_NEW_HEADS_TIMEOUT` seconds, subscribes again by calling
+    /// `new_heads`; while that keeps failing, polls in between attempts.
+    pub fn into_subscription_stream<F, H>(self, new_heads: F) -> impl Future<Item = (), Error = ()>
+    where
+        F: Fn() -> H + Send + 'static,
+        H: Stream<Item = (), Error = Error> + Send + 'static,
+    {
+        let ingestor = Arc::new(self);
+        let logger = ingestor.logger.clone();
+        let polling_interval = ingestor.polling_interval;
+
+        follow_new_heads(
+            logger,
+            new_heads,
+            *NEW_HEADS_TIMEOUT,
+            polling_interval,
+            move || ingestor.clone().poll_once(),
+        )
+    }
+
+    fn poll_at_interval(self: Arc<Self>) -> impl Future<Item = (), Error = ()> {
+        let logger = self.logger.clone();
+        let polling_interval = self.polling_interval;
+        poll_at_interval(logger, polling_interval, move || self.clone().poll_once())
+    }
+
+    /// Ingests the latest block. Never fails, so that ingestion continues
+    /// after transient errors.
+    fn poll_once(self: Arc<Self>) -> impl Future<Item = (), Error = ()> {
+        let logger = self.logger.clone();
+
+        // Attempt to poll
+        self.do_poll().then(move |result| {
+            if let Err(err) = result {
+                // Some polls will fail due to transient issues
+                match err {
+                    EthereumAdapterError::BlockUnavailable(_) => {
+                        trace!(logger, "Trying again after block polling failed: {}", err);
+                    }
+                    EthereumAdapterError::Unknown(inner_err) => {
+                        warn!(
+                            logger,
+                            "Trying again after block polling failed: {}", inner_err
+                        );
                     }
+                }
+            }
 
-                    // Continue polling even if polling failed
-                    future::ok(())
-                })
-            })
+            // Continue polling even if polling failed
+            future::ok(())
+        })
     }
 
-    fn do_poll<'a>(&'a self) -> impl Future<Item = (), Error = EthereumAdapterError> + 'a {
+    fn do_poll(self: Arc<Self>) -> impl Future<Item = (), Error = EthereumAdapterError> {
         trace!(self.logger, "BlockIngestor::do_poll");
         let network_name = self.network_name.clone();
 
@@ -138,6 +184,7 @@ where
                             return Box::new(future::ok(()));
                         }
 
+                        let ingestor = self.clone();
                         Box::new(
                             self.eth_adapter.load_full_block(&self.logger, latest_block)
                             .and_then(move |latest_block: EthereumBlock| {
@@ -145,7 +192,7 @@ where
                                 // Might be a no-op if latest block is one that we have seen.
                                 // ingest_blocks will return a (potentially incomplete) list of blocks that are
                                 // missing.
-                                self.ingest_blocks(stream::once(Ok(latest_block)))
+                                ingestor.ingest_blocks(stream::once(Ok(latest_block)))
                             }).and_then(move |missing_block_hashes| {
                                 // Repeatedly fetch missing parent blocks, and ingest them.
                                 // ingest_blocks will continue to tell us about more missing parent
@@ -188,18 +235,22 @@ where
     /// Put some blocks into the block store (if they are not there already), and try to update the
     /// head block pointer. If missing blocks prevent such an update, return a Vec with at least
     /// one of the missing blocks' hashes.
-    fn ingest_blocks<
-        'a,
-        B: Stream<Item = EthereumBlock, Error = EthereumAdapterError> + Send + 'a,
-    >(
-        &'a self,
+    fn ingest_blocks<B>(
+        &self,
         blocks: B,
-    ) -> impl Future<Item = Vec<H256>, Error = EthereumAdapterError> + Send + 'a {
+    ) -> impl Future<Item = Vec<H256>, Error = EthereumAdapterError> + Send
+    where
+        B: Stream<Item = EthereumBlock, Error = EthereumAdapterError> + Send + 'static,
+    {
+        let chain_store = self.chain_store.clone();
+        let ancestor_count = self.ancestor_count;
+        let logger = self.logger.clone();
+
         self.chain_store.upsert_blocks(blocks).and_then(move |()| {
-            self.chain_store
-                .attempt_chain_head_update(self.ancestor_count)
+            chain_store
+                .attempt_chain_head_update(ancestor_count)
                 .map_err(|e| {
-                    error!(self.logger, "failed to update chain head");
+                    error!(logger, "failed to update chain head");
                     EthereumAdapterError::Unknown(e)
                 })
         })
@@ -207,10 +258,10 @@ where
 
     /// Requests the specified blocks via web3, returning them in a stream (potentially out of
     /// order).
-    fn get_blocks<'a>(
-        &'a self,
+    fn get_blocks(
+        &self,
         block_hashes: &[H256],
-    ) -> Box<Stream<Item = EthereumBlock, Error = EthereumAdapterError> + Send + 'a> {
+    ) -> Box<Stream<Item = EthereumBlock, Error = EthereumAdapterError> + Send> {
         let logger = self.logger.clone();
         let eth_adapter = self.eth_adapter.clone();
 
@@ -230,3 +281,205 @@ where
         Box::new(stream::futures_unordered(block_futures))
     }
 }
+
+/// Calls `poll` every `polling_interval`.
+fn poll_at_interval<P, R>(
+    logger: Logger,
+    polling_interval: Duration,
+    poll: P,
+) -> impl Future<Item = (), Error = ()>
+where
+    P: Fn() -> R,
+    R: Future<Item = (), Error = ()>,
+{
+    // Create stream that emits at polling interval
+    Interval::new(Instant::now(), polling_interval)
+        .map_err(move |e| {
+            error!(logger, "timer::Interval failed: {:?}", e);
+        })
+        .for_each(move |_| poll())
+}
+
+/// Subscribes with `new_heads` and calls `ingest` right away and whenever
+/// the subscription yields. Subscribes again whenever the subscription
+/// fails, ends or yields nothing for `timeout`. Subscriptions that fail
+/// before yielding a new head are retried with exponential backoff, and
+/// `ingest` is called every `polling_interval` while waiting to retry.
+fn follow_new_heads<F, H, I, R>(
+    logger: Logger,
+    new_heads: F,
+    timeout: Duration,
+    polling_interval: Duration,
+    ingest: I,
+) -> impl Future<Item = (), Error = ()>
+where
+    F: Fn() -> H + Send + 'static,
+    H: Stream<Item = (), Error = Error> + Send + 'static,
+    I: Fn() -> R + Send + Sync + 'static,
+    R: Future<Item = (), Error = ()> + Send + 'static,
+{
+    let ingest = Arc::new(ingest);
+
+    // `failures` counts the subscriptions in a row that ended before
+    // yielding a new head
+    loop_fn(0u32, move |failures| {
+        let logger = logger.clone();
+        let logger_for_errors = logger.clone();
+        let ingest_for_heads = ingest.clone();
+        let ingest_for_polls = ingest.clone();
+        let received_head = Arc::new(AtomicBool::new(false));
+        let received_head_for_heads = received_head.clone();
+
+        info!(logger, "Subscribing to new blocks"; "attempt" => failures + 1);
+
+        // Catch up right away instead of waiting for the next block
+        let heads = stream::once(Ok(false)).chain(new_heads().map(|()| true));
+        Timeout::new(heads, timeout)
+            .map_err(move |e| {
+                if e.is_elapsed() {
+                    warn!(
+                        logger_for_errors,
+                        "No new block received for {}s",
+                        timeout.as_secs()
+                    );
+                } else {
+                    warn!(
+                        logger_for_errors,
+                        "Subscribing to new blocks failed: {}",
+                        e.into_inner()
+                            .map_or_else(|| "timer error".to_owned(), |e| e.to_string())
+                    );
+                }
+            })
+            .for_each(move |is_head| {
+                if is_head {
+                    received_head_for_heads.store(true, Ordering::SeqCst);
+                }
+                ingest_for_heads()
+            })
+            .then(move |_| -> Box<Future<Item = _, Error = _> + Send> {
+                // A subscription that worked for a while is renewed right
+                // away; one that didn't is retried after a delay
+                if received_head.load(Ordering::SeqCst) {
+                    warn!(logger, "No longer subscribed to new blocks, resubscribing");
+                    return Box::new(future::ok(Loop::Continue(0)));
+                }
+
+                let failures = failures + 1;
+                let delay = cmp::min(
+                    Duration::from_secs(1 << cmp::min(failures - 1, 6)),
+                    MAX_RESUBSCRIBE_DELAY,
+                );
+                warn!(
+                    logger,
+                    "No longer subscribed to new blocks, polling until resubscribing";
+                    "resubscribe_in_secs" => delay.as_secs(),
+                );
+
+                let polls =
+                    poll_at_interval(logger.clone(), polling_interval, move || ingest_for_polls());
+                let retry = Delay::new(Instant::now() + delay).map_err(move |e| {
+                    error!(logger, "timer::Delay failed: {:?}", e);
+                });
+                Box::new(
+                    retry
+                        .select(polls)
+                        .then(move |_| Ok(Loop::Continue(failures))),
+                )
+            })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::sync::mpsc;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Mutex;
+
+    type NewHeads = Box<Stream<Item = (), Error = Error> + Send>;
+
+    /// Runs `follow_new_heads` in the background, subscribing with the
+    /// streams in `subscriptions` in order. Returns the number of
+    /// subscriptions made and the number of times a block was ingested.
+    fn follow(
+        runtime: &mut tokio::runtime::Runtime,
+        subscriptions: Vec<NewHeads>,
+    ) -> (Arc<AtomicUsize>, Arc<AtomicUsize>) {
+        let subscriptions = Arc::new(Mutex::new(subscriptions.into_iter()));
+        let subscribed = Arc::new(AtomicUsize::new(0));
+        let ingested = Arc::new(AtomicUsize::new(0));
+
+        let subscribed_for_heads = subscribed.clone();
+        let ingested_for_heads = ingested.clone();
+        runtime.spawn(follow_new_heads(
+            Logger::root(slog::Discard, o!()),
+            move || {
+                subscribed_for_heads.fetch_add(1, Ordering::SeqCst);
+                // Once all subscriptions are used up, subscribing fails
+                subscriptions.lock().unwrap().next().unwrap_or_else(|| {
+                    Box::new(stream::once(Err(format_err!("subscription failed")))) as NewHeads
+                })
+            },
+            Duration::from_millis(500),
+            Duration::from_millis(50),
+            move || {
+                ingested_for_heads.fetch_add(1, Ordering::SeqCst);
+                future::ok(())
+            },
+        ));
+
+        (subscribed, ingested)
+    }
+
+    fn wait_until<F: Fn() -> bool>(condition: F) {
+        let start_time = Instant::now();
+        while !condition() {
+            if start_time.elapsed() > Duration::from_secs(10) {
+                panic!("Test failed, condition not met in time");
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn failed_subscriptions_are_retried_while_polling() {
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let (subscribed, ingested) = follow(&mut runtime, vec![]);
+
+        // The first retry is a second after the first attempt; the block
+        // is polled for in the meantime
+        wait_until(|| subscribed.load(Ordering::SeqCst) >= 2);
+        assert!(ingested.load(Ordering::SeqCst) > 2);
+    }
+
+    #[test]
+    fn stalled_subscriptions_are_renewed() {
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let (sender, receiver) = mpsc::unbounded();
+        let (subscribed, ingested) = follow(
+            &mut runtime,
+            vec![
+                // Yields one head, then stalls until it times out
+                Box::new(stream::once(Ok(())).chain(stream::poll_fn(
+                    || -> Poll<Option<()>, Error> { Ok(Async::NotReady) },
+                ))) as NewHeads,
+                Box::new(receiver.map_err(|()| format_err!("subscription failed"))) as NewHeads,
+            ],
+        );
+
+        // Both subscriptions catch up when they start, and the first one
+        // also ingests its head. Resubscribing right after the stall works,
+        // so there is no polling
+        wait_until(|| {
+            subscribed.load(Ordering::SeqCst) == 2 && ingested.load(Ordering::SeqCst) == 3
+        });
+        std::thread::sleep(Duration::from_millis(150));
+        assert_eq!(ingested.load(Ordering::SeqCst), 3);
+
+        // New heads are ingested right away
+        sender.unbounded_send(()).unwrap();
+        wait_until(|| ingested.load(Ordering::SeqCst) == 4);
+        assert_eq!(subscribed.load(Ordering::SeqCst), 2);
+    }
+}
@@ -20,7 +20,9 @@ use graph::web3::types::*;
 const FAST_SCAN_SPEEDUP: u64 = 10;
 
 lazy_static! {
-    /// Number of blocks to request in each chunk.
+    /// Number of blocks to request in each chunk. This is also the initial
+    /// number of blocks to request logs for at once; that number is adjusted
+    /// to the responses of the Ethereum node.
     static ref ETHEREUM_BLOCK_RANGE_SIZE: u64 = ::std::env::var("ETHEREUM_BLOCK_RANGE_SIZE")
         .unwrap_or("10000".into())
         .parse::<u64>()
@@ -30,6 +32,23 @@ lazy_static! {
         .unwrap_or("4000000".into())
         .parse::<u64>()
         .expect("invalid fast scan end block number");
+
+    /// Initial number of blocks to request traces for at once; adjusted to
+    /// the responses of the Ethereum node like the range for logs.
+    static ref TRACE_STREAM_STEP_SIZE: u64 = ::std::env::var("ETHEREUM_TRACE_STREAM_STEP_SIZE")
+        .unwrap_or("200".into())
+        .parse::<u64>()
+        .expect("invalid trace stream step size");
+
+    /// Number of blocks to request in each chunk if the subgraph has data
+    /// sources without a contract address. These match events of every
+    /// contract on the network, so scanning needs to proceed in much smaller
+    /// steps to keep the number of logs and blocks to load manageable.
+    static ref ETHEREUM_WILDCARD_BLOCK_RANGE_SIZE: u64 =
+        ::std::env::var("ETHEREUM_WILDCARD_BLOCK_RANGE_SIZE")
+            .unwrap_or("100".into())
+            .parse::<u64>()
+            .expect("invalid Ethereum wildcard block range size");
 }
 
 enum BlockStreamState {
@@ -127,6 +146,7 @@ struct BlockStreamContext<S, C, E> {
     call_filter: Option<EthereumCallFilter>,
     block_filter: Option<EthereumBlockFilter>,
     include_calls_in_blocks: bool,
+    scan_ranges: ScanRanges,
     logger: Logger,
 }
 
@@ -143,6 +163,7 @@ impl<S, C, E> Clone for BlockStreamContext<S, C, E> {
             call_filter: self.call_filter.clone(),
             block_filter: self.block_filter.clone(),
             include_calls_in_blocks: self.include_calls_in_blocks,
+            scan_ranges: self.scan_ranges.clone(),
             logger: self.logger.clone(),
         }
     }
@@ -171,6 +192,7 @@ where
         call_filter: Option<EthereumCallFilter>,
         block_filter: Option<EthereumBlockFilter>,
         include_calls_in_blocks: bool,
+        scan_ranges: ScanRanges,
         reorg_threshold: u64,
         logger: Logger,
     ) -> Self {
@@ -190,6 +212,7 @@ where
                 call_filter,
                 block_filter,
                 include_calls_in_blocks,
+                scan_ranges,
             },
         }
     }
@@ -392,6 +415,10 @@ where
                             // If there is a block trigger on every block, go
                             // one block at a time.
                             from
+                        } else if log_filter.as_ref().map_or(false, |l| l.has_wildcard_events()) {
+                            // Events of any contract are too numerous to scan
+                            // large ranges at once, even at the start of the chain
+                            cmp::min(from + *ETHEREUM_WILDCARD_BLOCK_RANGE_SIZE - 1, to_limit)
                         } else {
                             // Otherwise use ETHEREUM_BLOCK_RANGE_SIZE to ensure the subgraph
                             //  block ptr is updated frequently.
@@ -403,6 +430,9 @@ where
                             cmp::min(from + speedup * *ETHEREUM_BLOCK_RANGE_SIZE - 1, to_limit)
                         };
 
+                        // Range scans always go to the Ethereum node: the chain
+                        // store only has the blocks that some subgraph needed,
+                        // so it can't tell that a range has no triggers
                         debug!(ctx.logger, "Scanning blocks [{}, {}]", from, to);
                         Box::new(
                             ctx.eth_adapter
@@ -413,6 +443,7 @@ where
                                     log_filter.clone(),
                                     call_filter.clone(),
                                     block_filter.clone(),
+                                    ctx.scan_ranges.clone(),
                                 )
                                 .and_then(move |descendant_ptrs| -> Box<Future<Item = _, Error = _> + Send> {
                                     if descendant_ptrs.is_empty() {
@@ -511,7 +542,6 @@ where
             let offset = head_ptr.number - subgraph_ptr.number - 1;
             let head_ancestor_opt = ctx.chain_store.ancestor_block(head_ptr, offset).unwrap();
             let include_calls_in_blocks = self.include_calls_in_blocks();
-            let logger = self.logger.clone();
             match head_ancestor_opt {
                 None => {
                     // Block is missing in the block store.
@@ -538,16 +568,12 @@ where
                                         calls: None,
                                     }));
                                 }
-                                let block_with_calls = ctx
-                                    .eth_adapter
-                                    .calls_in_block(
-                                        &logger,
-                                        block.block.number.unwrap().as_u64(),
-                                        block.block.hash.unwrap(),
-                                    )
-                                    .map(move |calls| EthereumBlockWithCalls {
-                                        ethereum_block: block,
-                                        calls: Some(calls),
+                                let block_with_calls =
+                                    ctx.calls_in_block(&block).map(move |calls| {
+                                        EthereumBlockWithCalls {
+                                            ethereum_block: block,
+                                            calls: Some(calls),
+                                        }
                                     });
                                 Box::new(block_with_calls)
                             },
@@ -887,8 +913,7 @@ where
         include_calls_in_block: bool,
     ) -> impl Future<Item = EthereumBlockWithCalls, Error = Error> + Send {
         let ctx = self.clone();
-        let eth = self.eth_adapter.clone();
-        let logger = self.logger.clone();
+        let calls_ctx = self.clone();
 
         // Search for the block in the store first then use the ethereum adapter as a backup
         let block = future::result(ctx.chain_store.block(block_hash))
@@ -936,21 +961,62 @@ where
                         calls: None,
                     }));
                 }
-                let block = eth
-                    .calls_in_block(
-                        &logger,
-                        block.block.number.unwrap().as_u64(),
-                        block.block.hash.unwrap(),
-                    )
-                    .map(move |calls| EthereumBlockWithCalls {
-                        ethereum_block: block,
-                        calls: Some(calls),
-                    });
+                let block =
+                    calls_ctx
+                        .calls_in_block(&block)
+                        .map(move |calls| EthereumBlockWithCalls {
+                            ethereum_block: block,
+                            calls: Some(calls),
+                        });
                 Box::new(block)
             });
         Box::new(block)
     }
 
+    /// Loads the calls made in a block. Like blocks, calls are served from
+    /// the chain store if another subgraph has needed them before, and are
+    /// cached there after fetching them from the Ethereum node. This only
+    /// covers blocks with triggers; scanning block ranges for triggers with
+    /// `blocks_with_triggers` is not cached.
+    fn calls_in_block(
+        &self,
+        block: &EthereumBlock,
+    ) -> Box<Future<Item = Vec<EthereumCall>, Error = Error> + Send> {
+        let block_number = block.block.number.unwrap().as_u64();
+        let block_hash = block.block.hash.unwrap();
+
+        match self.chain_store.block_calls(block_hash) {
+            Ok(Some(calls)) => return Box::new(future::ok(calls)),
+            Ok(None) => (),
+            Err(e) => debug!(
+                self.logger,
+                "Failed to load calls from the chain store";
+                "block_hash" => format!("{:x}", block_hash),
+                "error" => e.to_string(),
+            ),
+        }
+
+        let chain_store = self.chain_store.clone();
+        let logger = self.logger.clone();
+        Box::new(
+            self.eth_adapter
+                .calls_in_block(&self.logger, block_number, block_hash)
+                .map(move |calls| {
+                    // Failing to cache the calls is not fatal; they will be
+                    // fetched from the Ethereum node again when needed
+                    if let Err(e) = chain_store.upsert_block_calls(block_hash, &calls) {
+                        warn!(
+                            logger,
+                            "Failed to cache calls in the chain store";
+                            "block_hash" => format!("{:x}", block_hash),
+                            "error" => e.to_string(),
+                        );
+                    }
+                    calls
+                }),
+        )
+    }
+
     pub fn parse_triggers(
         log_filter_opt: Option<EthereumLogFilter>,
         call_filter_opt: Option<EthereumCallFilter>,
@@ -1235,9 +1301,12 @@ where
 pub struct BlockStreamBuilder<S, C, E> {
     subgraph_store: Arc<S>,
     chain_stores: HashMap<String, Arc<C>>,
-    eth_adapters: HashMap<String, Arc<E>>,
+    eth_networks: EthereumNetworks<E>,
     node_id: NodeId,
     reorg_threshold: u64,
+    // shared by all block streams of a deployment, so that what was learned
+    // about suitable range sizes survives restarting the block stream
+    scan_ranges: Arc<Mutex<HashMap<SubgraphDeploymentId, ScanRanges>>>,
 }
 
 impl<S, C, E> Clone for BlockStreamBuilder<S, C, E> {
@@ -1245,9 +1314,10 @@ impl<S, C, E> Clone for BlockStreamBuilder<S, C, E> {
         BlockStreamBuilder {
             subgraph_store: self.subgraph_store.clone(),
             chain_stores: self.chain_stores.clone(),
-            eth_adapters: self.eth_adapters.clone(),
+            eth_networks: self.eth_networks.clone(),
             node_id: self.node_id.clone(),
             reorg_threshold: self.reorg_threshold,
+            scan_ranges: self.scan_ranges.clone(),
         }
     }
 }
@@ -1261,16 +1331,17 @@ where
     pub fn new(
         subgraph_store: Arc<S>,
         chain_stores: HashMap<String, Arc<C>>,
-        eth_adapters: HashMap<String, Arc<E>>,
+        eth_networks: EthereumNetworks<E>,
         node_id: NodeId,
         reorg_threshold: u64,
     ) -> Self {
         BlockStreamBuilder {
             subgraph_store,
             chain_stores,
-            eth_adapters,
+            eth_networks,
             node_id,
             reorg_threshold,
+            scan_ranges: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 }
@@ -1292,7 +1363,7 @@ where
         call_filter: Option<EthereumCallFilter>,
         block_filter: Option<EthereumBlockFilter>,
         include_calls_in_blocks: bool,
-    ) -> Self::Stream {
+    ) -> Result<Self::Stream, Error> {
         let logger = logger.new(o!(
             "component" => "BlockStream",
         ));
@@ -1305,17 +1376,42 @@ where
                 &network_name
             ))
             .clone();
+
+        // Scanning blocks for calls requires traces; subgraphs that need
+        // capabilities none of our providers have are refused when they are
+        // deployed and fail when they are started
+        let required_capabilities = NodeCapabilities {
+            archive: false,
+            traces: call_filter.is_some()
+                || include_calls_in_blocks
+                || block_filter
+                    .as_ref()
+                    .map_or(false, |filter| !filter.contract_addresses.is_empty()),
+        };
         let eth_adapter = self
-            .eth_adapters
-            .get(&network_name)
-            .expect(&format!(
-                "no eth adapter that supports network: {}",
-                &network_name
-            ))
+            .eth_networks
+            .adapter_with_capabilities(&network_name, &required_capabilities)?
+            .clone();
+
+        let scan_ranges = self
+            .scan_ranges
+            .lock()
+            .unwrap()
+            .entry(deployment_id.clone())
+            .or_insert_with(|| ScanRanges {
+                logs: AdaptiveRangeSize::new(
+                    *ETHEREUM_BLOCK_RANGE_SIZE,
+                    FAST_SCAN_SPEEDUP * *ETHEREUM_BLOCK_RANGE_SIZE,
+                ),
+                traces: AdaptiveRangeSize::new(
+                    *TRACE_STREAM_STEP_SIZE,
+                    FAST_SCAN_SPEEDUP * *TRACE_STREAM_STEP_SIZE,
+                ),
+            })
             .clone();
 
         // Create the actual subgraph-specific block stream
-        BlockStream::new(
+        Ok(BlockStream::new(
             self.subgraph_store.clone(),
             chain_store,
             eth_adapter,
@@ -1325,9 +1421,10 @@ where
             call_filter,
             block_filter,
             include_calls_in_blocks,
+            scan_ranges,
             self.reorg_threshold,
             logger,
-        )
+        ))
     }
 }
 
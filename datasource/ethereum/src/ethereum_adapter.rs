@@ -4,9 +4,11 @@ use graph::ethabi::Token;
 use lazy_static::lazy_static;
 use std::collections::HashSet;
 use std::sync::Arc;
+use std::time::Duration;
 
 use graph::components::ethereum::{EthereumAdapter as EthereumAdapterTrait, *};
 use graph::prelude::*;
+use graph::tokio::timer::timeout;
 use graph::web3;
 use graph::web3::api::Web3;
 use graph::web3::transports::batch::Batch;
@@ -16,14 +18,16 @@ use graph::web3::types::{Filter, *};
 pub struct EthereumAdapter<T: web3::Transport> {
     web3: Arc<Web3<T>>,
     start_block: u64,
+
+    /// How often to retry failed requests; `None` retries forever.
+    retry_limit: Option<usize>,
 }
 
-lazy_static! {
-    static ref TRACE_STREAM_STEP_SIZE: u64 = ::std::env::var("ETHEREUM_TRACE_STREAM_STEP_SIZE")
-        .unwrap_or("200".into())
-        .parse::<u64>()
-        .expect("invalid trace stream step size");
+/// How long to wait for a response to a request for a block range before
+/// retrying it with a smaller range.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
 
+lazy_static! {
     /// Maximum number of chunks to request in parallel when streaming logs.
     static ref LOG_STREAM_PARALLEL_CHUNKS: u64 = ::std::env::var("ETHEREUM_PARALLEL_BLOCK_RANGES")
         .unwrap_or("100".into())
@@ -40,9 +44,17 @@ where
         EthereumAdapter {
             web3: Arc::new(Web3::new(transport)),
             start_block,
+            retry_limit: None,
         }
     }
 
+    /// Gives up on requests after `limit` failed attempts instead of
+    /// retrying forever, so that requests can be sent to another provider.
+    pub fn with_retry_limit(mut self, limit: usize) -> Self {
+        self.retry_limit = Some(limit);
+        self
+    }
+
     fn traces(
         &self,
         logger: &Logger,
@@ -53,9 +65,15 @@ where
         let eth = self.clone();
         let logger = logger.to_owned();
 
+        // Requests that time out are not retried; the caller retries them
+        // with a smaller block range instead
         retry("trace_filter RPC call", &logger)
-            .no_limit()
-            .timeout_secs(60)
+            .when(|res: &Result<_, timeout::Error<Error>>| match res {
+                Ok(_) => false,
+                Err(e) => !e.is_elapsed(),
+            })
+            .limit_opt(self.retry_limit)
+            .no_timeout()
             .run(move || {
                 let trace_filter: TraceFilter = match addresses.len() {
                     0 => TraceFilterBuilder::default()
@@ -109,15 +127,16 @@ where
                         }
                         result
                     })
+                    .timeout(REQUEST_TIMEOUT)
             })
             .map_err(move |e| {
                 e.into_inner().unwrap_or_else(move || {
-                    format_err!(
-                        "Ethereum node took too long to respond to trace_filter \
-                         (from block {}, to block {})",
+                    BlockRangeTooLarge {
                         from,
-                        to
-                    )
+                        to,
+                        reason: "Ethereum node took too long to respond to trace_filter".to_owned(),
+                    }
+                    .into()
                 })
             })
     }
@@ -129,29 +148,42 @@ where
         to: u64,
         addresses: Vec<H160>,
         event_signatures: Vec<H256>,
-        too_many_logs_fingerprint: &'static str,
-    ) -> impl Future<Item = Vec<Log>, Error = graph::tokio_timer::timeout::Error<web3::error::Error>>
-    {
+        indexed_topics: IndexedTopics,
+        too_many_logs_fingerprints: &'static [&'static str],
+    ) -> impl Future<Item = Vec<Log>, Error = timeout::Error<web3::error::Error>> {
         let eth_adapter = self.clone();
 
+        // Requests that time out or return too many logs are not retried;
+        // the caller retries them with a smaller block range instead
         retry("eth_getLogs RPC call", &logger)
-            .when(move |res: &Result<_, web3::error::Error>| match res {
-                Ok(_) => false,
-                Err(e) => !e.to_string().contains(too_many_logs_fingerprint),
-            })
-            .no_limit()
-            .timeout_secs(60)
+            .when(
+                move |res: &Result<_, timeout::Error<web3::error::Error>>| match res {
+                    Ok(_) => false,
+                    Err(e) => !is_range_too_large_error(e, too_many_logs_fingerprints),
+                },
+            )
+            .limit_opt(self.retry_limit)
+            .no_timeout()
             .run(move || {
                 // Create a log filter
                 let log_filter: Filter = FilterBuilder::default()
                     .from_block(from.into())
                     .to_block(to.into())
                     .address(addresses.clone())
-                    .topics(Some(event_signatures.clone()), None, None, None)
+                    .topics(
+                        Some(event_signatures.clone()),
+                        indexed_topics[0].clone(),
+                        indexed_topics[1].clone(),
+                        indexed_topics[2].clone(),
+                    )
                     .build();
 
                 // Request logs from client
-                eth_adapter.web3.eth().logs(log_filter)
+                eth_adapter
+                    .web3
+                    .eth()
+                    .logs(log_filter)
+                    .timeout(REQUEST_TIMEOUT)
             })
     }
 
@@ -161,6 +193,7 @@ where
         from: u64,
         to: u64,
         addresses: Vec<H160>,
+        trace_range: AdaptiveRangeSize,
     ) -> impl Stream<Item = Vec<Trace>, Error = Error> + Send {
         if from > to {
             panic!(
@@ -175,17 +208,36 @@ where
             if start > to {
                 return None;
             }
-            let end = (start + *TRACE_STREAM_STEP_SIZE - 1).min(to);
+            let end = (start + trace_range.get() - 1).min(to);
             let new_start = end + 1;
             if start == end {
                 debug!(logger, "Requesting traces for block {}", start);
             } else {
                 debug!(logger, "Requesting traces for blocks [{}, {}]", start, end);
             }
-            Some(
-                eth.traces(&logger, start, end, addresses.clone())
-                    .map(move |traces| (traces, new_start)),
-            )
+            let logger = logger.clone();
+            let trace_range = trace_range.clone();
+            Some(eth.traces(&logger, start, end, addresses.clone()).then(
+                move |result| match result {
+                    Ok(traces) => {
+                        // Measure the response before any calls are filtered
+                        // out of it, since that is what the node has to send
+                        trace_range.grow_if_sparse(traces.len());
+                        Ok((traces, new_start))
+                    }
+                    Err(e) => {
+                        // Try the same blocks again with a smaller range
+                        if e.downcast_ref::<BlockRangeTooLarge>().is_some() && trace_range.shrink()
+                        {
+                            debug!(logger, "Reducing block range size to scan for calls";
+                                               "new_size" => trace_range.get());
+                            Ok((vec![], start))
+                        } else {
+                            Err(e)
+                        }
+                    }
+                },
+            ))
         })
     }
 
@@ -195,10 +247,18 @@ where
         from: u64,
         to: u64,
         log_filter: EthereumLogFilter,
+        log_range: AdaptiveRangeSize,
     ) -> impl Future<Item = Vec<Log>, Error = Error> {
-        // Code returned by Infura if a request returns too many logs.
-        // web3 doesn't seem to offer a better way of checking the error code.
-        const TOO_MANY_LOGS_FINGERPRINT: &str = "ServerError(-32005)";
+        // Errors returned by providers if a request returns too many logs:
+        // Infura's error code, and the messages of Alchemy and of nodes that
+        // limit the size of responses. web3 doesn't seem to offer a better
+        // way of checking the error code.
+        const TOO_MANY_LOGS_FINGERPRINTS: &[&str] = &[
+            "ServerError(-32005)",
+            "query returned more than",
+            "response size exceeded",
+            "response size should not greater than",
+        ];
 
         if from > to {
             panic!(
@@ -207,61 +267,59 @@ where
             );
         }
 
-        // Collect all event sigs
         let eth = self.clone();
-        let logger = logger.to_owned();
-
-        let event_sigs = log_filter
-            .contract_address_and_event_sig_pairs
-            .iter()
-            .map(|(_addr, sig)| *sig)
-            .collect::<HashSet<H256>>()
-            .into_iter()
-            .collect::<Vec<H256>>();
 
-        // Collect all contract addresses; if we have a data source without a contract
-        // address, we can't add addresses to the filter because it would only match
-        // the contracts for which we _have_ addresses; therefore if we have a data source
-        // without a contract address, we perform a broader logs scan and filter out
-        // irrelevant events ourselves.
+        // Events of specific contracts and events of any contract are
+        // requested separately: adding the contract addresses to the request
+        // lets the node use its indexes, while a data source without a contract
+        // address needs a scan by event signature only. Requesting everything
+        // by event signature would match the events of every contract on the
+        // network for all data sources.
         //
         // Our own filtering is performed later when the events are passed to
         // subgraphs and runtime hosts for processing:
         // - At the top level in `BlockStreamContext::do_step`
         // - At the subgraph level in `SubgraphInstance::matches_log`
         // - At the data source level in `RuntimeHost::matches_log`
-        let addresses = if log_filter
-            .contract_address_and_event_sig_pairs
-            .iter()
-            .any(|(addr, _)| addr.is_none())
-        {
-            vec![]
-        } else {
-            log_filter
-                .contract_address_and_event_sig_pairs
-                .iter()
-                .map(|(addr, _sig)| match addr {
-                    None => unreachable!(
-                        "shouldn't include addresses in Ethereum logs filter \
-                         if there are data sources without a contract address"
-                    ),
-                    Some(addr) => *addr,
-                })
-                .collect::<HashSet<H160>>()
-                .into_iter()
-                .collect::<Vec<H160>>()
-        };
+        let (wildcard_events, contract_events) = log_filter.partition_wildcard_events();
+        let requests = vec![contract_events, wildcard_events]
+            .into_iter()
+            .filter(|filter| !filter.is_empty())
+            .map(|filter| {
+                let addresses = filter
+                    .contract_address_and_event_sig_pairs
+                    .iter()
+                    .filter_map(|(addr, _sig, _indexed_topics)| *addr)
+                    .collect::<HashSet<H160>>()
+                    .into_iter()
+                    .collect::<Vec<H160>>();
+                let event_sigs = filter
+                    .contract_address_and_event_sig_pairs
+                    .iter()
+                    .map(|(_addr, sig, _indexed_topics)| *sig)
+                    .collect::<HashSet<H256>>()
+                    .into_iter()
+                    .collect::<Vec<H256>>();
+
+                // Only ask for the indexed topics that all events are
+                // restricted to; the rest is filtered out by
+                // `log_filter.matches` below
+                (addresses, event_sigs, filter.indexed_topics())
+            })
+            .collect::<Vec<_>>();
 
         let logger = logger.to_owned();
 
-        stream::unfold((from, to - from), move |(start, step)| {
+        stream::unfold(from, move |start| {
             if start > to {
                 return None;
             }
 
-            // This will iterate more than once iff `step` has been reduced,
-            // making as many parallel requests of size `step` as necessary,
-            // respecting `LOG_STREAM_PARALLEL_CHUNKS`.
+            // The range size for a request is `step + 1`. This will iterate
+            // more than once iff the range is smaller than the blocks left to
+            // scan, making as many parallel requests of size `step` as
+            // necessary, respecting `LOG_STREAM_PARALLEL_CHUNKS`.
+            let step = log_range.get() - 1;
             let mut chunk_futures = vec![];
             let mut low = start;
             for _ in 0..*LOG_STREAM_PARALLEL_CHUNKS {
@@ -271,49 +329,77 @@ where
                 let log_filter = log_filter.clone();
                 let high = (low + step).min(to);
                 debug!(logger, "Requesting logs for blocks [{}, {}]", low, high);
-                chunk_futures.push(
-                    eth.logs_with_sigs(
-                        &logger,
-                        low,
-                        high,
-                        addresses.clone(),
-                        event_sigs.clone(),
-                        TOO_MANY_LOGS_FINGERPRINT,
-                    )
-                    .map(move |logs| {
-                        logs.into_iter()
-                            .filter(|log| log_filter.matches(log))
-                            .collect::<Vec<Log>>()
-                    }),
-                );
+                let request_futures = requests
+                    .iter()
+                    .map(|(addresses, event_sigs, indexed_topics)| {
+                        eth.logs_with_sigs(
+                            &logger,
+                            low,
+                            high,
+                            addresses.clone(),
+                            event_sigs.clone(),
+                            indexed_topics.clone(),
+                            TOO_MANY_LOGS_FINGERPRINTS,
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                chunk_futures.push(future::join_all(request_futures).map(move |logs| {
+                    // The range size depends on how many logs the node
+                    // returns per request, not on how many we keep
+                    let largest_response = logs.iter().map(Vec::len).max().unwrap_or(0);
+
+                    // A log may be returned by both requests, and the logs of
+                    // the two requests need to be put back in block order
+                    let mut logs = logs
+                        .into_iter()
+                        .flatten()
+                        .filter(|log| log_filter.matches(log))
+                        .collect::<Vec<Log>>();
+                    sort_and_dedup_logs(&mut logs);
+                    (largest_response, logs)
+                }));
                 low = high + 1;
             }
             let logger = logger.clone();
+            let log_range = log_range.clone();
             Some(
                 stream::futures_ordered(chunk_futures)
                     .collect()
-                    .map(|chunks| chunks.into_iter().flatten().collect::<Vec<Log>>())
                     .then(move |res| match res {
                         Err(e) => {
                             let string_err = e.to_string();
 
-                            // If the step is already 0, we're hitting the log
-                            // limit even for a single block. We hope this never
-                            // happens, but if it does, make sure to error.
-                            if string_err.contains(TOO_MANY_LOGS_FINGERPRINT) && step > 0 {
-                                // The range size for a request is `step + 1`.
-                                // So it's ok if the step goes down to 0, in
-                                // that case we'll request one block at a time.
-                                let new_step = step / 10;
+                            // If the range is already a single block, we're
+                            // hitting the log limit even for a single block. We
+                            // hope this never happens, but if it does, make sure
+                            // to error.
+                            if is_range_too_large_error(&e, TOO_MANY_LOGS_FINGERPRINTS)
+                                && log_range.shrink()
+                            {
                                 debug!(logger, "Reducing block range size to scan for events";
-                                               "new_size" => new_step + 1);
-                                Ok((vec![], (start, new_step)))
+                                               "new_size" => log_range.get());
+                                Ok((vec![], start))
                             } else {
                                 warn!(logger, "Unexpected RPC error"; "error" => &string_err);
                                 Err(err_msg(string_err))
                             }
                         }
-                        Ok(logs) => Ok((logs, (low, step))),
+                        Ok(chunks) => {
+                            log_range.grow_if_sparse(
+                                chunks
+                                    .iter()
+                                    .map(|(largest_response, _)| *largest_response)
+                                    .max()
+                                    .unwrap_or(0),
+                            );
+                            Ok((
+                                chunks
+                                    .into_iter()
+                                    .flat_map(|(_, logs)| logs)
+                                    .collect::<Vec<Log>>(),
+                                low,
+                            ))
+                        }
                     }),
             )
         })
@@ -326,9 +412,10 @@ where
         contract_address: Address,
         call_data: Bytes,
         block_number_opt: Option<BlockNumber>,
-    ) -> impl Future<Item = Bytes, Error = Error> + Send {
+    ) -> impl Future<Item = Bytes, Error = EthereumContractCallError> + Send {
         let web3 = self.web3.clone();
         let logger = logger.clone();
+        let retry_limit = self.retry_limit;
 
         // Outer retry used only for 0-byte responses,
         // where we can't guarantee the problem is temporary.
@@ -352,7 +439,15 @@ where
                 let call_data = call_data.clone();
 
                 retry("eth_call RPC call", &logger)
-                    .no_limit()
+                    .when(
+                        |result: &Result<Bytes, EthereumContractCallError>| match result {
+                            Ok(_) => false,
+                            // Calls that failed to execute fail again
+                            Err(EthereumContractCallError::CallError(_)) => false,
+                            Err(_) => true,
+                        },
+                    )
+                    .limit_opt(retry_limit)
                     .timeout_secs(60)
                     .run(move || {
                         let req = CallRequest {
@@ -366,11 +461,12 @@ where
                         web3.eth()
                             .call(req, block_number_opt)
                             .from_err::<EthereumContractCallError>()
-                            .from_err()
                     })
                     .map_err(|e| {
                         e.into_inner().unwrap_or_else(|| {
-                            format_err!("Ethereum node took too long to perform function call")
+                            EthereumContractCallError::Error(format_err!(
+                                "Ethereum node took too long to perform function call"
+                            ))
                         })
                     })
             })
@@ -392,7 +488,7 @@ where
 
         let web3 = self.web3.clone();
         let net_version_future = retry("net_version RPC call", &logger)
-            .no_limit()
+            .limit_opt(self.retry_limit)
             .timeout_secs(20)
             .run(move || {
                 web3.net()
@@ -403,7 +499,7 @@ where
 
         let web3 = self.web3.clone();
         let gen_block_hash_future = retry("eth_getBlockByNumber(0, false) RPC call", &logger)
-            .no_limit()
+            .limit_opt(self.retry_limit)
             .timeout_secs(30)
             .run(move || {
                 web3.eth()
@@ -450,7 +546,7 @@ where
 
         Box::new(
             retry("eth_getBlockByNumber(latest) RPC call", logger)
-                .no_limit()
+                .limit_opt(self.retry_limit)
                 .timeout_secs(60)
                 .run(move || {
                     web3.eth()
@@ -481,7 +577,7 @@ where
 
         Box::new(
             retry("eth_getBlockByHash RPC call", &logger)
-                .no_limit()
+                .limit_opt(self.retry_limit)
                 .timeout_secs(60)
                 .run(move || {
                     web3.eth()
@@ -628,7 +724,7 @@ where
 
         Box::new(
             retry("eth_getBlockByHash RPC call", &logger)
-                .no_limit()
+                .limit_opt(self.retry_limit)
                 .timeout_secs(60)
                 .run(move || {
                     web3.eth()
@@ -657,7 +753,7 @@ where
 
         Box::new(
             retry("eth_getBlockByNumber RPC call", &logger)
-                .no_limit()
+                .limit_opt(self.retry_limit)
                 .timeout_secs(60)
                 .run(move || {
                     web3.eth()
@@ -703,7 +799,13 @@ where
         let eth = self.clone();
         let addresses = Vec::new();
         let calls = eth
-            .trace_stream(&logger, block_number, block_number, addresses)
+            .trace_stream(
+                &logger,
+                block_number,
+                block_number,
+                addresses,
+                AdaptiveRangeSize::new(1, 1),
+            )
             .collect()
             .map(|trace_chunks| match trace_chunks.len() {
                 0 => vec![],
@@ -751,6 +853,7 @@ where
         log_filter_opt: Option<EthereumLogFilter>,
         call_filter_opt: Option<EthereumCallFilter>,
         block_filter_opt: Option<EthereumBlockFilter>,
+        scan_ranges: ScanRanges,
     ) -> Box<Future<Item = Vec<EthereumBlockPointer>, Error = Error> + Send> {
         // If no filters are provided, return an empty vector of blocks.
         if log_filter_opt.is_none() && call_filter_opt.is_none() && block_filter_opt.is_none() {
@@ -775,6 +878,7 @@ where
                     from,
                     to,
                     log_filter_opt.unwrap(),
+                    scan_ranges.logs.clone(),
                 )));
             }
             if call_filter_opt.is_some() {
@@ -783,6 +887,7 @@ where
                     from,
                     to,
                     call_filter_opt.unwrap(),
+                    scan_ranges.traces.clone(),
                 )));
             }
             if block_filter_opt.is_some() {
@@ -799,6 +904,7 @@ where
                             from,
                             to,
                             call_filter,
+                            scan_ranges.traces.clone(),
                         )));
                     }
                 }
@@ -867,30 +973,32 @@ where
         from: u64,
         to: u64,
         log_filter: EthereumLogFilter,
+        log_range: AdaptiveRangeSize,
     ) -> Box<Future<Item = Vec<EthereumBlockPointer>, Error = Error> + Send> {
         let eth = self.clone();
         Box::new(
             // Get a stream of all relevant logs in range
-            eth.log_stream(&logger, from, to, log_filter).map(|logs| {
-                let mut block_ptrs = vec![];
-                for log in logs.iter() {
-                    let hash = log
-                        .block_hash
-                        .expect("log from Eth node is missing block hash");
-                    let number = log
-                        .block_number
-                        .expect("log from Eth node is missing block number")
-                        .as_u64();
-                    let block_ptr = EthereumBlockPointer::from((hash, number));
-                    if !block_ptrs.contains(&block_ptr) {
-                        if let Some(prev) = block_ptrs.last() {
-                            assert!(prev.number < number);
+            eth.log_stream(&logger, from, to, log_filter, log_range)
+                .map(|logs| {
+                    let mut block_ptrs = vec![];
+                    for log in logs.iter() {
+                        let hash = log
+                            .block_hash
+                            .expect("log from Eth node is missing block hash");
+                        let number = log
+                            .block_number
+                            .expect("log from Eth node is missing block number")
+                            .as_u64();
+                        let block_ptr = EthereumBlockPointer::from((hash, number));
+                        if !block_ptrs.contains(&block_ptr) {
+                            if let Some(prev) = block_ptrs.last() {
+                                assert!(prev.number < number);
+                            }
+                            block_ptrs.push(block_ptr);
                         }
-                        block_ptrs.push(block_ptr);
                     }
-                }
-                block_ptrs
-            }),
+                    block_ptrs
+                }),
         )
     }
 
@@ -900,6 +1008,7 @@ where
         from: u64,
         to: u64,
         call_filter: EthereumCallFilter,
+        trace_range: AdaptiveRangeSize,
     ) -> Box<Future<Item = Vec<EthereumBlockPointer>, Error = Error> + Send> {
         let eth = self.clone();
 
@@ -911,7 +1020,7 @@ where
             .into_iter()
             .collect::<Vec<H160>>();
         Box::new(
-            eth.trace_stream(&logger, from, to, addresses)
+            eth.trace_stream(&logger, from, to, addresses, trace_range)
                 .collect()
                 .map(move |trace_chunks| {
                     match trace_chunks.len() {
@@ -979,7 +1088,6 @@ where
                 Bytes(call_data),
                 Some(call.block_ptr.number.into()),
             )
-            .map_err(EthereumContractCallError::from)
             .and_then(move |output| {
                 // Decode the return values according to the ABI
                 call.function
@@ -989,3 +1097,83 @@ where
         )
     }
 }
+
+/// A request for a block range failed because the range is too large: the
+/// Ethereum node took too long to respond or refused to return that many
+/// results. The request should be retried with a smaller range.
+#[derive(Debug, Fail)]
+#[fail(display = "Block range [{}, {}] is too large: {}", from, to, reason)]
+struct BlockRangeTooLarge {
+    from: u64,
+    to: u64,
+    reason: String,
+}
+
+/// Checks whether an `eth_getLogs` error means that the request timed out or
+/// matched too many logs and should be retried with a smaller block range.
+fn is_range_too_large_error(
+    error: &timeout::Error<web3::error::Error>,
+    fingerprints: &[&str],
+) -> bool {
+    let error_string = error.to_string();
+    error.is_elapsed()
+        || fingerprints
+            .iter()
+            .any(|fingerprint| error_string.contains(fingerprint))
+}
+
+/// Sorts logs into block order and removes logs that appear more than once.
+fn sort_and_dedup_logs(logs: &mut Vec<Log>) {
+    logs.sort_by_key(|log| (log.block_number, log.log_index));
+
+    // Logs that lack any of these fields can't be told apart from other
+    // logs, so they are all kept
+    let mut seen = HashSet::new();
+    logs.retain(
+        |log| match (log.block_hash, log.transaction_hash, log.log_index) {
+            (Some(block_hash), Some(transaction_hash), Some(log_index)) => {
+                seen.insert((block_hash, transaction_hash, log_index))
+            }
+            _ => true,
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::serde_json;
+
+    fn log(block: u64, transaction: u64, log_index: Option<u64>) -> Log {
+        serde_json::from_value(serde_json::json!({
+            "address": H160::from([1; 20]),
+            "topics": [],
+            "data": "0x",
+            "blockHash": H256::from([block as u8; 32]),
+            "blockNumber": format!("{:#x}", block),
+            "transactionHash": H256::from([transaction as u8; 32]),
+            "logIndex": log_index.map(|index| format!("{:#x}", index)),
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn duplicate_logs_are_removed() {
+        let mut logs = vec![log(2, 3, Some(0)), log(1, 1, Some(0)), log(2, 3, Some(0))];
+        sort_and_dedup_logs(&mut logs);
+        assert_eq!(logs, vec![log(1, 1, Some(0)), log(2, 3, Some(0))]);
+    }
+
+    #[test]
+    fn logs_that_differ_in_any_field_are_kept() {
+        // Same block and log index, but different transactions
+        let mut logs = vec![log(1, 1, Some(0)), log(1, 2, Some(0))];
+        sort_and_dedup_logs(&mut logs);
+        assert_eq!(logs.len(), 2);
+
+        // Logs without a log index can't be told apart
+        let mut logs = vec![log(1, 1, None), log(1, 1, None)];
+        sort_and_dedup_logs(&mut logs);
+        assert_eq!(logs.len(), 2);
+    }
+}
@@ -0,0 +1,515 @@
+use futures::future::{self, Loop};
+use futures::prelude::*;
+use graph::ethabi::Token;
+use lazy_static::lazy_static;
+use std::cmp::Ordering as CmpOrdering;
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use graph::components::ethereum::{EthereumAdapter as EthereumAdapterTrait, *};
+use graph::prelude::*;
+use graph::web3::types::*;
+
+lazy_static! {
+    /// Fraction of failed requests (as a moving average) above which a
+    /// provider is considered unhealthy.
+    static ref MAX_ERROR_RATE: f64 = env::var("ETHEREUM_PROVIDER_MAX_ERROR_RATE")
+        .ok()
+        .map(|s| f64::from_str(&s).unwrap_or_else(|_| {
+            panic!("failed to parse env var ETHEREUM_PROVIDER_MAX_ERROR_RATE")
+        }))
+        .unwrap_or(0.5);
+
+    /// How long an unhealthy provider is avoided before it is tried again.
+    static ref UNHEALTHY_COOLDOWN: Duration = env::var("ETHEREUM_PROVIDER_COOLDOWN")
+        .ok()
+        .map(|s| u64::from_str(&s).unwrap_or_else(|_| {
+            panic!("failed to parse env var ETHEREUM_PROVIDER_COOLDOWN")
+        }))
+        .map(Duration::from_secs)
+        .unwrap_or(Duration::from_secs(30));
+}
+
+/// Weight of the most recent request in the moving averages of error rate
+/// and latency.
+const SMOOTHING_FACTOR: f64 = 0.2;
+
+/// Errors returned by Ethereum adapters, classified by whether another
+/// provider might succeed where one provider failed.
+trait ProviderError: fmt::Display {
+    fn is_provider_failure(&self) -> bool;
+}
+
+impl ProviderError for Error {
+    fn is_provider_failure(&self) -> bool {
+        true
+    }
+}
+
+impl ProviderError for EthereumAdapterError {
+    fn is_provider_failure(&self) -> bool {
+        // A provider that doesn't know a block may simply be lagging behind
+        true
+    }
+}
+
+impl ProviderError for EthereumContractCallError {
+    fn is_provider_failure(&self) -> bool {
+        match self {
+            EthereumContractCallError::Error(_) => true,
+
+            // Reverts and decoding errors are the same no matter which
+            // provider we ask
+            EthereumContractCallError::CallError(_)
+            | EthereumContractCallError::ABIError(_)
+            | EthereumContractCallError::TypeError(..) => false,
+        }
+    }
+}
+
+#[derive(Default)]
+struct ProviderHealth {
+    /// Moving average of the fraction of failed requests.
+    error_rate: f64,
+
+    /// Moving average of the latency of successful requests, in ms.
+    latency_ms: f64,
+
+    /// Set when the provider became unhealthy; it won't receive requests
+    /// (unless all other providers fail) until this time.
+    unhealthy_until: Option<Instant>,
+}
+
+/// A single Ethereum node, along with what we know about its health.
+struct Provider<A> {
+    name: String,
+    capabilities: NodeCapabilities,
+    adapter: Arc<A>,
+    health: Mutex<ProviderHealth>,
+    in_flight: AtomicUsize,
+}
+
+impl<A> Provider<A> {
+    fn is_healthy(&self) -> bool {
+        let mut health = self.health.lock().unwrap();
+        match health.unhealthy_until {
+            Some(until) if until > Instant::now() => false,
+            Some(_) => {
+                // The cooldown is over, give the provider another chance
+                health.unhealthy_until = None;
+                health.error_rate = 0.0;
+                true
+            }
+            None => true,
+        }
+    }
+
+    /// The expected cost of sending a request to this provider. Prefers
+    /// fast providers while spreading requests across providers with
+    /// similar latencies.
+    fn load(&self) -> f64 {
+        let latency_ms = self.health.lock().unwrap().latency_ms;
+        let in_flight = self.in_flight.load(Ordering::SeqCst);
+        latency_ms.max(1.0) * (in_flight + 1) as f64
+    }
+
+    fn record_success(&self, latency: Duration) {
+        let mut health = self.health.lock().unwrap();
+        let latency_ms = latency.as_millis() as f64;
+        health.error_rate *= 1.0 - SMOOTHING_FACTOR;
+        health.latency_ms = if health.latency_ms == 0.0 {
+            latency_ms
+        } else {
+            health.latency_ms * (1.0 - SMOOTHING_FACTOR) + latency_ms * SMOOTHING_FACTOR
+        };
+    }
+
+    fn record_failure(&self, logger: &Logger) {
+        let mut health = self.health.lock().unwrap();
+        health.error_rate = health.error_rate * (1.0 - SMOOTHING_FACTOR) + SMOOTHING_FACTOR;
+
+        if health.error_rate > *MAX_ERROR_RATE && health.unhealthy_until.is_none() {
+            warn!(
+                logger,
+                "Ethereum provider is unhealthy, avoiding it";
+                "error_rate" => format!("{:.2}", health.error_rate),
+                "cooldown_secs" => UNHEALTHY_COOLDOWN.as_secs(),
+            );
+            health.unhealthy_until = Some(Instant::now() + *UNHEALTHY_COOLDOWN);
+        }
+    }
+}
+
+/// An `EthereumAdapter` that spreads requests across several providers for
+/// the same network.
+///
+/// Requests go to the healthy provider with the lowest expected load. If a
+/// provider fails a request, the request is retried with the next provider,
+/// so that a single flaky node does not stall indexing. Providers whose
+/// error rate exceeds `ETHEREUM_PROVIDER_MAX_ERROR_RATE` are avoided for
+/// `ETHEREUM_PROVIDER_COOLDOWN` seconds.
+pub struct FailoverEthereumAdapter<A> {
+    providers: Vec<Arc<Provider<A>>>,
+}
+
+impl<A> FailoverEthereumAdapter<A>
+where
+    A: EthereumAdapterTrait,
+{
+    /// Creates an adapter for the given `(name, capabilities, adapter)`
+    /// triples. Panics if no providers are passed in.
+    pub fn new(network_name: &str, providers: Vec<(String, NodeCapabilities, Arc<A>)>) -> Self {
+        assert!(
+            !providers.is_empty(),
+            "at least one Ethereum provider is required for network {}",
+            network_name
+        );
+
+        FailoverEthereumAdapter {
+            providers: providers
+                .into_iter()
+                .map(|(name, capabilities, adapter)| {
+                    Arc::new(Provider {
+                        name,
+                        capabilities,
+                        adapter,
+                        health: Mutex::new(ProviderHealth::default()),
+                        in_flight: AtomicUsize::new(0),
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    /// The distinct capability sets of the providers of this adapter.
+    pub fn capabilities(&self) -> Vec<NodeCapabilities> {
+        let mut capabilities: Vec<_> = self
+            .providers
+            .iter()
+            .map(|provider| provider.capabilities)
+            .collect();
+        capabilities.sort_by_key(|c| (c.archive, c.traces));
+        capabilities.dedup();
+        capabilities
+    }
+
+    /// Returns an adapter that only uses the providers that support the
+    /// `required` capabilities, or `None` if there are no such providers.
+    /// The providers' health is shared with this adapter.
+    pub fn with_capabilities(&self, required: &NodeCapabilities) -> Option<Self> {
+        let providers: Vec<_> = self
+            .providers
+            .iter()
+            .filter(|provider| provider.capabilities.supports(required))
+            .cloned()
+            .collect();
+
+        if providers.is_empty() {
+            None
+        } else {
+            Some(FailoverEthereumAdapter { providers })
+        }
+    }
+
+    /// Asks all providers for their network identifiers and makes sure they
+    /// agree. Providers that cannot be reached are skipped.
+    pub fn check_net_identifiers(
+        &self,
+        logger: &Logger,
+    ) -> Box<Future<Item = EthereumNetworkIdentifier, Error = Error> + Send> {
+        let logger = logger.clone();
+        let identifiers = self.providers.iter().map(|provider| {
+            let name = provider.name.clone();
+            let logger = logger.new(o!("provider" => name.clone()));
+            provider
+                .adapter
+                .net_identifiers(&logger)
+                .then(move |result| -> Result<_, Error> { Ok((name, result)) })
+        });
+
+        Box::new(future::join_all(identifiers).and_then(
+            move |results: Vec<(_, Result<_, Error>)>| {
+                let mut network: Option<(String, EthereumNetworkIdentifier)> = None;
+
+                for (name, result) in results {
+                    let identifier = match result {
+                        Ok(identifier) => identifier,
+                        Err(e) => {
+                            warn!(
+                                logger,
+                                "Failed to connect to Ethereum provider";
+                                "provider" => &name,
+                                "error" => e.to_string(),
+                            );
+                            continue;
+                        }
+                    };
+
+                    if let Some((ref first_name, ref first)) = network {
+                        if first.net_version != identifier.net_version
+                            || first.genesis_block_hash != identifier.genesis_block_hash
+                        {
+                            return Err(format_err!(
+                                "Ethereum providers `{}` and `{}` are connected to different \
+                                 networks (net_version {} vs. {}, genesis block {:x} vs. {:x})",
+                                first_name,
+                                name,
+                                first.net_version,
+                                identifier.net_version,
+                                first.genesis_block_hash,
+                                identifier.genesis_block_hash,
+                            ));
+                        }
+                    } else {
+                        network = Some((name, identifier));
+                    }
+                }
+
+                network
+                    .map(|(_, identifier)| identifier)
+                    .ok_or_else(|| format_err!("failed to connect to any Ethereum provider"))
+            },
+        ))
+    }
+
+    /// Returns all providers, in the order in which they should be tried.
+    fn providers_in_order(&self) -> Vec<Arc<Provider<A>>> {
+        let (mut healthy, mut unhealthy): (Vec<_>, Vec<_>) = self
+            .providers
+            .iter()
+            .cloned()
+            .partition(|provider| provider.is_healthy());
+
+        healthy.sort_by(|a, b| {
+            a.load()
+                .partial_cmp(&b.load())
+                .unwrap_or(CmpOrdering::Equal)
+        });
+
+        // Unhealthy providers are only used as a last resort, starting with
+        // the one that will recover first
+        unhealthy.sort_by_key(|provider| provider.health.lock().unwrap().unhealthy_until);
+
+        healthy.extend(unhealthy);
+        healthy
+    }
+
+    /// Sends a request to the providers in turn until one of them succeeds.
+    fn call<I, E, F>(
+        &self,
+        logger: &Logger,
+        method: &'static str,
+        request: F,
+    ) -> Box<Future<Item = I, Error = E> + Send>
+    where
+        I: Send + 'static,
+        E: ProviderError + Send + 'static,
+        F: Fn(&A, &Logger) -> Box<Future<Item = I, Error = E> + Send> + Send + Sync + 'static,
+    {
+        let logger = logger.clone();
+        let request = Arc::new(request);
+
+        Box::new(future::loop_fn(
+            self.providers_in_order().into_iter(),
+            move |mut providers| {
+                // There is at least one provider, and we stop once all have
+                // been tried
+                let provider = providers.next().unwrap();
+                let logger = logger.new(o!("provider" => provider.name.clone()));
+
+                let start = Instant::now();
+                provider.in_flight.fetch_add(1, Ordering::SeqCst);
+
+                (*request)(&provider.adapter, &logger).then(move |result| {
+                    provider.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+                    match result {
+                        Ok(item) => {
+                            provider.record_success(start.elapsed());
+                            Ok(Loop::Break(item))
+                        }
+                        Err(e) => {
+                            if !e.is_provider_failure() {
+                                return Err(e);
+                            }
+
+                            provider.record_failure(&logger);
+
+                            if providers.len() == 0 {
+                                return Err(e);
+                            }
+
+                            warn!(
+                                logger,
+                                "Ethereum provider failed, trying the next one";
+                                "method" => method,
+                                "error" => e.to_string(),
+                            );
+                            Ok(Loop::Continue(providers))
+                        }
+                    }
+                })
+            },
+        ))
+    }
+}
+
+impl<A> EthereumAdapterTrait for FailoverEthereumAdapter<A>
+where
+    A: EthereumAdapterTrait,
+{
+    fn net_identifiers(
+        &self,
+        logger: &Logger,
+    ) -> Box<Future<Item = EthereumNetworkIdentifier, Error = Error> + Send> {
+        self.call(logger, "net_identifiers", |adapter, logger| {
+            adapter.net_identifiers(logger)
+        })
+    }
+
+    fn latest_block(
+        &self,
+        logger: &Logger,
+    ) -> Box<Future<Item = Block<Transaction>, Error = EthereumAdapterError> + Send> {
+        self.call(logger, "latest_block", |adapter, logger| {
+            adapter.latest_block(logger)
+        })
+    }
+
+    fn block_by_hash(
+        &self,
+        logger: &Logger,
+        block_hash: H256,
+    ) -> Box<Future<Item = Option<Block<Transaction>>, Error = Error> + Send> {
+        self.call(logger, "block_by_hash", move |adapter, logger| {
+            adapter.block_by_hash(logger, block_hash)
+        })
+    }
+
+    fn load_full_block(
+        &self,
+        logger: &Logger,
+        block: Block<Transaction>,
+    ) -> Box<Future<Item = EthereumBlock, Error = EthereumAdapterError> + Send> {
+        self.call(logger, "load_full_block", move |adapter, logger| {
+            adapter.load_full_block(logger, block.clone())
+        })
+    }
+
+    fn block_parent_hash(
+        &self,
+        logger: &Logger,
+        block_hash: H256,
+    ) -> Box<Future<Item = Option<H256>, Error = Error> + Send> {
+        self.call(logger, "block_parent_hash", move |adapter, logger| {
+            adapter.block_parent_hash(logger, block_hash)
+        })
+    }
+
+    fn block_hash_by_block_number(
+        &self,
+        logger: &Logger,
+        block_number: u64,
+    ) -> Box<Future<Item = Option<H256>, Error = Error> + Send> {
+        self.call(
+            logger,
+            "block_hash_by_block_number",
+            move |adapter, logger| adapter.block_hash_by_block_number(logger, block_number),
+        )
+    }
+
+    fn is_on_main_chain(
+        &self,
+        logger: &Logger,
+        block_ptr: EthereumBlockPointer,
+    ) -> Box<Future<Item = bool, Error = Error> + Send> {
+        self.call(logger, "is_on_main_chain", move |adapter, logger| {
+            adapter.is_on_main_chain(logger, block_ptr)
+        })
+    }
+
+    fn calls_in_block(
+        &self,
+        logger: &Logger,
+        block_number: u64,
+        block_hash: H256,
+    ) -> Box<Future<Item = Vec<EthereumCall>, Error = Error> + Send> {
+        self.call(logger, "calls_in_block", move |adapter, logger| {
+            adapter.calls_in_block(logger, block_number, block_hash)
+        })
+    }
+
+    fn blocks_with_triggers(
+        &self,
+        logger: &Logger,
+        from: u64,
+        to: u64,
+        log_filter: Option<EthereumLogFilter>,
+        call_filter: Option<EthereumCallFilter>,
+        block_filter: Option<EthereumBlockFilter>,
+        scan_ranges: ScanRanges,
+    ) -> Box<Future<Item = Vec<EthereumBlockPointer>, Error = Error> + Send> {
+        self.call(logger, "blocks_with_triggers", move |adapter, logger| {
+            adapter.blocks_with_triggers(
+                logger,
+                from,
+                to,
+                log_filter.clone(),
+                call_filter.clone(),
+                block_filter.clone(),
+                scan_ranges.clone(),
+            )
+        })
+    }
+
+    fn blocks_with_logs(
+        &self,
+        logger: &Logger,
+        from: u64,
+        to: u64,
+        log_filter: EthereumLogFilter,
+        log_range: AdaptiveRangeSize,
+    ) -> Box<Future<Item = Vec<EthereumBlockPointer>, Error = Error> + Send> {
+        self.call(logger, "blocks_with_logs", move |adapter, logger| {
+            adapter.blocks_with_logs(logger, from, to, log_filter.clone(), log_range.clone())
+        })
+    }
+
+    fn blocks_with_calls(
+        &self,
+        logger: &Logger,
+        from: u64,
+        to: u64,
+        call_filter: EthereumCallFilter,
+        trace_range: AdaptiveRangeSize,
+    ) -> Box<Future<Item = Vec<EthereumBlockPointer>, Error = Error> + Send> {
+        self.call(logger, "blocks_with_calls", move |adapter, logger| {
+            adapter.blocks_with_calls(logger, from, to, call_filter.clone(), trace_range.clone())
+        })
+    }
+
+    fn blocks(
+        &self,
+        logger: &Logger,
+        from: u64,
+        to: u64,
+    ) -> Box<Future<Item = Vec<EthereumBlockPointer>, Error = Error> + Send> {
+        self.call(logger, "blocks", move |adapter, logger| {
+            adapter.blocks(logger, from, to)
+        })
+    }
+
+    fn contract_call(
+        &self,
+        logger: &Logger,
+        call: EthereumContractCall,
+    ) -> Box<Future<Item = Vec<Token>, Error = EthereumContractCallError> + Send> {
+        self.call(logger, "contract_call", move |adapter, logger| {
+            adapter.contract_call(logger, call.clone())
+        })
+    }
+}
@@ -8,9 +8,11 @@ extern crate lazy_static;
 mod block_ingestor;
 mod block_stream;
 mod ethereum_adapter;
+mod failover;
 mod transport;
 
 pub use self::block_ingestor::BlockIngestor;
 pub use self::block_stream::{BlockStream, BlockStreamBuilder};
 pub use self::ethereum_adapter::EthereumAdapter;
+pub use self::failover::FailoverEthereumAdapter;
 pub use self::transport::{EventLoopHandle, Transport};
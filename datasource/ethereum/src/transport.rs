@@ -1,4 +1,5 @@
 use futures::prelude::*;
+use graph::prelude::{format_err, Error};
 use graph::serde_json::Value;
 use jsonrpc_core::types::Call;
 use std::env;
@@ -47,6 +48,44 @@ impl Transport {
     }
 }
 
+impl Transport {
+    /// Whether the transport can push notifications to us, which is the
+    /// case for WebSocket and IPC connections.
+    pub fn supports_subscriptions(&self) -> bool {
+        match self {
+            Transport::RPC(_) => false,
+            Transport::IPC(_) | Transport::WS(_) => true,
+        }
+    }
+
+    /// Subscribes to new chain heads with `eth_subscribe("newHeads")`. The
+    /// stream yields whenever the Ethereum node sees a new head block.
+    /// Returns `None` if the transport does not support subscriptions.
+    pub fn new_heads(&self) -> Option<Box<Stream<Item = (), Error = Error> + Send>> {
+        match self {
+            Transport::RPC(_) => None,
+            Transport::IPC(ipc) => Some(subscribe_new_heads(ipc.clone())),
+            Transport::WS(ws) => Some(subscribe_new_heads(ws.clone())),
+        }
+    }
+}
+
+fn subscribe_new_heads<T>(transport: T) -> Box<Stream<Item = (), Error = Error> + Send>
+where
+    T: web3::DuplexTransport + Send + 'static,
+    T::Out: Send,
+    T::NotificationStream: Send,
+{
+    Box::new(
+        web3::Web3::new(transport)
+            .eth_subscribe()
+            .subscribe_new_heads()
+            .flatten_stream()
+            .map(|_| ())
+            .map_err(|e| format_err!("newHeads subscription failed: {}", e)),
+    )
+}
+
 impl web3::Transport for Transport {
     type Out = Box<Future<Item = Value, Error = web3::error::Error> + Send>;
 
@@ -14,7 +14,7 @@ use graph::web3::error::Error;
 use graph::web3::helpers::*;
 use graph::web3::types::*;
 use graph::web3::{BatchTransport, RequestId, Transport};
-use graph_datasource_ethereum::EthereumAdapter;
+use graph_datasource_ethereum::{EthereumAdapter, FailoverEthereumAdapter};
 
 pub type Result<T> = Box<Future<Item = T, Error = Error> + Send + 'static>;
 
@@ -48,7 +48,7 @@ fn mock_block() -> Block<U256> {
 pub struct TestTransport {
     asserted: usize,
     requests: Arc<Mutex<Vec<(String, Vec<jsonrpc_core::Value>)>>>,
-    response: Arc<Mutex<VecDeque<jsonrpc_core::Value>>>,
+    response: Arc<Mutex<VecDeque<::std::result::Result<jsonrpc_core::Value, jsonrpc_core::Error>>>>,
 }
 
 impl Transport for TestTransport {
@@ -66,7 +66,8 @@ impl Transport for TestTransport {
 
     fn send(&self, _: RequestId, _: jsonrpc_core::Call) -> Result<jsonrpc_core::Value> {
         match self.response.lock().unwrap().pop_front() {
-            Some(response) => Box::new(finished(response)),
+            Some(Ok(response)) => Box::new(finished(response)),
+            Some(Err(e)) => Box::new(failed(Error::Rpc(e))),
             None => Box::new(failed(Error::Unreachable.into())),
         }
     }
@@ -92,11 +93,15 @@ impl BatchTransport for TestTransport {
 
 impl TestTransport {
     pub fn set_response(&mut self, value: jsonrpc_core::Value) {
-        *self.response.lock().unwrap() = vec![value].into();
+        *self.response.lock().unwrap() = vec![Ok(value)].into();
     }
 
     pub fn add_response(&mut self, value: jsonrpc_core::Value) {
-        self.response.lock().unwrap().push_back(value);
+        self.response.lock().unwrap().push_back(Ok(value));
+    }
+
+    pub fn add_error(&mut self, error: jsonrpc_core::Error) {
+        self.response.lock().unwrap().push_back(Err(error));
     }
 
     pub fn assert_request(&mut self, method: &str, params: &[String]) {
@@ -129,21 +134,7 @@ impl TestTransport {
     }
 }
 
-#[test]
-#[ignore]
-fn contract_call() {
-    let mut transport = TestTransport::default();
-
-    transport.add_response(serde_json::to_value(mock_block()).unwrap());
-    transport.add_response(jsonrpc_core::Value::String(format!(
-        "{:?}",
-        H256::from(hex!(
-            "bd34884280958002c51d3f7b5f853e6febeba33de0f40d15b0363006533c924f"
-        )),
-    )));
-
-    let logger = Logger::root(slog::Discard, o!());
-    let adapter = EthereumAdapter::new(transport, 0u64);
+fn balance_of_call() -> EthereumContractCall {
     let balance_of = Function {
         name: "balanceOf".to_owned(),
         inputs: vec![Param {
@@ -159,13 +150,143 @@ fn contract_call() {
     let function = Function::from(balance_of);
     let gnt_addr = Address::from_str("eF7FfF64389B814A946f3E92105513705CA6B990").unwrap();
     let holder_addr = Address::from_str("00d04c4b12C4686305bb4F4fC93487CdFBa62580").unwrap();
-    let call = EthereumContractCall {
+    EthereumContractCall {
         address: gnt_addr,
         block_ptr: EthereumBlockPointer::from((H256::zero(), 0 as i64)),
         function: function,
         args: vec![Token::Address(holder_addr)],
-    };
-    let call_result = adapter.contract_call(&logger, call).wait().unwrap();
+    }
+}
+
+#[test]
+#[ignore]
+fn contract_call() {
+    let mut transport = TestTransport::default();
+
+    transport.add_response(serde_json::to_value(mock_block()).unwrap());
+    transport.add_response(jsonrpc_core::Value::String(format!(
+        "{:?}",
+        H256::from(hex!(
+            "bd34884280958002c51d3f7b5f853e6febeba33de0f40d15b0363006533c924f"
+        )),
+    )));
+
+    let logger = Logger::root(slog::Discard, o!());
+    let adapter = EthereumAdapter::new(transport, 0u64);
+    let call_result = adapter
+        .contract_call(&logger, balance_of_call())
+        .wait()
+        .unwrap();
 
     assert_eq!(call_result[0], Token::Uint(U256::from(100000)));
 }
+
+#[test]
+fn failover_to_next_provider() {
+    // The first provider doesn't respond at all
+    let failing_transport = TestTransport::default();
+    let mut working_transport = TestTransport::default();
+    working_transport.add_response(serde_json::to_value(mock_block()).unwrap());
+
+    let logger = Logger::root(slog::Discard, o!());
+    let adapter = FailoverEthereumAdapter::new(
+        "mainnet",
+        vec![
+            (
+                "failing".to_owned(),
+                NodeCapabilities::full(),
+                Arc::new(EthereumAdapter::new(failing_transport.clone(), 0u64).with_retry_limit(1)),
+            ),
+            (
+                "working".to_owned(),
+                NodeCapabilities::full(),
+                Arc::new(EthereumAdapter::new(working_transport.clone(), 0u64).with_retry_limit(1)),
+            ),
+        ],
+    );
+
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    let block_hash = runtime
+        .block_on(adapter.block_hash_by_block_number(&logger, 1))
+        .expect("Should fail over to the working provider");
+
+    assert_eq!(block_hash, Some(H256::default()));
+    assert!(!failing_transport.requests.lock().unwrap().is_empty());
+    assert_eq!(working_transport.requests.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn reverted_calls_do_not_fail_over() {
+    let mut reverting_transport = TestTransport::default();
+    reverting_transport.add_error(jsonrpc_core::Error {
+        code: jsonrpc_core::ErrorCode::ServerError(-32015),
+        message: "VM execution error.".to_owned(),
+        data: Some(jsonrpc_core::Value::String("Reverted 0x".to_owned())),
+    });
+    let working_transport = TestTransport::default();
+
+    let logger = Logger::root(slog::Discard, o!());
+    let adapter = FailoverEthereumAdapter::new(
+        "mainnet",
+        vec![
+            (
+                "reverting".to_owned(),
+                NodeCapabilities::full(),
+                Arc::new(
+                    EthereumAdapter::new(reverting_transport.clone(), 0u64).with_retry_limit(1),
+                ),
+            ),
+            (
+                "working".to_owned(),
+                NodeCapabilities::full(),
+                Arc::new(EthereumAdapter::new(working_transport.clone(), 0u64).with_retry_limit(1)),
+            ),
+        ],
+    );
+
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    match runtime.block_on(adapter.contract_call(&logger, balance_of_call())) {
+        Err(EthereumContractCallError::CallError(_)) => (),
+        result => panic!("expected the call to revert, got {:?}", result),
+    }
+
+    // The revert is neither retried nor sent to the next provider
+    assert_eq!(reverting_transport.requests.lock().unwrap().len(), 1);
+    assert!(working_transport.requests.lock().unwrap().is_empty());
+}
+
+#[test]
+fn log_ranges_grow_based_on_response_size() {
+    let contract = H160::from([1; 20]);
+    let other_contract = H160::from([2; 20]);
+    let event_sig = H256::from([3; 32]);
+
+    // The node returns a dense response, but none of the logs are for
+    // the contract we are interested in
+    let mut transport = TestTransport::default();
+    transport.add_response(serde_json::Value::Array(
+        (0..1000)
+            .map(|_| {
+                serde_json::json!({
+                    "address": other_contract,
+                    "topics": [event_sig],
+                    "data": "0x",
+                })
+            })
+            .collect(),
+    ));
+
+    let logger = Logger::root(slog::Discard, o!());
+    let adapter = EthereumAdapter::new(transport, 0u64).with_retry_limit(1);
+    let log_filter: EthereumLogFilter =
+        std::iter::once((Some(contract), event_sig, [None, None, None])).collect();
+    let log_range = AdaptiveRangeSize::new(10, 100);
+
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    let blocks = runtime
+        .block_on(adapter.blocks_with_logs(&logger, 0, 9, log_filter, log_range.clone()))
+        .unwrap();
+
+    assert!(blocks.is_empty());
+    assert_eq!(log_range.get(), 10);
+}
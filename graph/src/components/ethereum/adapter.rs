@@ -4,6 +4,7 @@ use futures::Future;
 use slog::Logger;
 use std::collections::{HashMap, HashSet};
 use std::iter::FromIterator;
+use std::sync::{Arc, Mutex};
 use tiny_keccak::keccak256;
 use web3::error::Error as Web3Error;
 use web3::types::*;
@@ -11,6 +12,7 @@ use web3::types::*;
 use super::types::*;
 use crate::prelude::BlockHandlerFilter;
 use crate::prelude::DataSource;
+use crate::prelude::{indexed_topics_match, IndexedTopics};
 use crate::util::extend::Extend;
 
 /// A collection of attributes that (kind of) uniquely identify an Ethereum blockchain.
@@ -47,6 +49,8 @@ pub struct EthereumContractCall {
 
 #[derive(Fail, Debug)]
 pub enum EthereumContractCallError {
+    /// The Ethereum node executed the call and the call failed, e.g. because
+    /// the contract reverted.
     #[fail(display = "call error: {}", _0)]
     CallError(Error),
     #[fail(display = "ABI error: {}", _0)]
@@ -60,10 +64,34 @@ pub enum EthereumContractCallError {
 
 impl From<Web3Error> for EthereumContractCallError {
     fn from(e: Web3Error) -> Self {
-        EthereumContractCallError::CallError(failure::err_msg(e.to_string()))
+        match e {
+            Web3Error::Rpc(ref rpc_error)
+                if is_execution_error(rpc_error.code.code(), &rpc_error.message) =>
+            {
+                EthereumContractCallError::CallError(failure::err_msg(e.to_string()))
+            }
+            _ => EthereumContractCallError::Error(failure::err_msg(e.to_string())),
+        }
     }
 }
 
+/// Whether an RPC error returned for an `eth_call` means that executing the
+/// call failed, as opposed to the Ethereum node failing to handle the request.
+/// Parity reports failed executions with a dedicated error code, Geth with a
+/// generic server error that describes the failure.
+fn is_execution_error(code: i64, message: &str) -> bool {
+    const PARITY_VM_EXECUTION_ERROR: i64 = -32015;
+    const GETH_EXECUTION_REVERTED: i64 = 3;
+
+    let message = message.to_lowercase();
+    code == PARITY_VM_EXECUTION_ERROR
+        || code == GETH_EXECUTION_REVERTED
+        || message.contains("revert")
+        || message.contains("invalid opcode")
+        || message.contains("out of gas")
+        || message.contains("vm exception")
+}
+
 impl From<ABIError> for EthereumContractCallError {
     fn from(e: ABIError) -> Self {
         EthereumContractCallError::ABIError(SyncFailure::new(e))
@@ -76,6 +104,63 @@ impl From<Error> for EthereumContractCallError {
     }
 }
 
+/// Requests whose densest block range returned fewer results than this are
+/// considered sparse, and the range size is increased for the next requests.
+const SPARSE_RESULTS: usize = 1000;
+
+/// The size of the block ranges that logs or traces are requested for,
+/// adjusted to how the Ethereum node responds. The size shrinks when the
+/// node refuses a range because it contains too many results or takes too
+/// long to respond, and grows again, up to a maximum, while responses are
+/// sparse. Clones share the same size.
+#[derive(Clone, Debug)]
+pub struct AdaptiveRangeSize {
+    size: Arc<Mutex<u64>>,
+    max: u64,
+}
+
+impl AdaptiveRangeSize {
+    pub fn new(initial: u64, max: u64) -> Self {
+        AdaptiveRangeSize {
+            size: Arc::new(Mutex::new(initial.max(1).min(max.max(1)))),
+            max: max.max(1),
+        }
+    }
+
+    /// The number of blocks to request at once.
+    pub fn get(&self) -> u64 {
+        *self.size.lock().unwrap()
+    }
+
+    /// Reduces the range size after a range was too large. Returns `false`
+    /// if the range is already a single block and can't be reduced.
+    pub fn shrink(&self) -> bool {
+        let mut size = self.size.lock().unwrap();
+        if *size == 1 {
+            return false;
+        }
+        *size = (*size / 10).max(1);
+        true
+    }
+
+    /// Increases the range size if the densest range in the last requests
+    /// returned only a few results.
+    pub fn grow_if_sparse(&self, results: usize) {
+        if results < SPARSE_RESULTS {
+            let mut size = self.size.lock().unwrap();
+            *size = (*size * 2).min(self.max);
+        }
+    }
+}
+
+/// The range sizes used to scan a subgraph deployment's block ranges for
+/// logs and for traces.
+#[derive(Clone, Debug)]
+pub struct ScanRanges {
+    pub logs: AdaptiveRangeSize,
+    pub traces: AdaptiveRangeSize,
+}
+
 #[derive(Fail, Debug)]
 pub enum EthereumAdapterError {
     /// The Ethereum node does not know about this block for some reason, probably because it
@@ -99,7 +184,10 @@ impl From<Error> for EthereumAdapterError {
 
 #[derive(Clone, Debug)]
 pub struct EthereumLogFilter {
-    pub contract_address_and_event_sig_pairs: HashSet<(Option<Address>, H256)>,
+    /// The events of interest: the contract address (`None` for any
+    /// contract), the event signature and the values the indexed topics of
+    /// the event may take.
+    pub contract_address_and_event_sig_pairs: HashSet<(Option<Address>, H256, IndexedTopics)>,
 }
 
 impl EthereumLogFilter {
@@ -116,20 +204,80 @@ impl EthereumLogFilter {
         // First topic should be event sig
         match log.topics.first() {
             None => false,
-            Some(sig) => self
+            Some(sig) => {
+                self.contract_address_and_event_sig_pairs
+                    .iter()
+                    .any(|(addr, s, indexed_topics)| {
+                        // The `Log` matches the filter either if the filter contains
+                        // a (contract address, event signature) pair that matches the
+                        // `Log`, or if the filter contains a pair with no contract
+                        // address but an event signature that matches the event.
+                        // In both cases, the indexed topics of the `Log` must have
+                        // one of the values the filter allows for them.
+                        addr.map_or(true, |addr| addr == log.address)
+                            && s == sig
+                            && indexed_topics_match(indexed_topics, &log.topics)
+                    })
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contract_address_and_event_sig_pairs.is_empty()
+    }
+
+    /// Whether this filter contains events of any contract, i.e. events of
+    /// data sources without a contract address.
+    pub fn has_wildcard_events(&self) -> bool {
+        self.contract_address_and_event_sig_pairs
+            .iter()
+            .any(|(addr, _, _)| addr.is_none())
+    }
+
+    /// Splits this filter into the events of any contract and the events of
+    /// specific contracts, in that order.
+    pub fn partition_wildcard_events(&self) -> (EthereumLogFilter, EthereumLogFilter) {
+        let (wildcard, contract) = self
+            .contract_address_and_event_sig_pairs
+            .iter()
+            .cloned()
+            .partition(|(addr, _, _)| addr.is_none());
+        (
+            EthereumLogFilter {
+                contract_address_and_event_sig_pairs: wildcard,
+            },
+            EthereumLogFilter {
+                contract_address_and_event_sig_pairs: contract,
+            },
+        )
+    }
+
+    /// The values `topic1` to `topic3` may take for any event in this filter,
+    /// suitable for narrowing down an `eth_getLogs` request. A topic is only
+    /// constrained if every event in the filter constrains it.
+    pub fn indexed_topics(&self) -> IndexedTopics {
+        let mut indexed_topics = [None, None, None];
+        if self.contract_address_and_event_sig_pairs.is_empty() {
+            return indexed_topics;
+        }
+        for (i, topic) in indexed_topics.iter_mut().enumerate() {
+            let allowed = self
                 .contract_address_and_event_sig_pairs
                 .iter()
-                .any(|pair| match pair {
-                    // The `Log` matches the filter either if the filter contains
-                    // a (contract address, event signature) pair that matches the
-                    // `Log`...
-                    (Some(addr), s) => addr == &log.address && s == sig,
-
-                    // ...or if the filter contains a pair with no contract address
-                    // but an event signature that matches the event
-                    (None, s) => s == sig,
-                }),
+                .map(|(_, _, indexed_topics)| indexed_topics[i].as_ref())
+                .collect::<Option<Vec<_>>>();
+
+            *topic = allowed.map(|allowed| {
+                allowed
+                    .into_iter()
+                    .flatten()
+                    .cloned()
+                    .collect::<HashSet<H256>>()
+                    .into_iter()
+                    .collect::<Vec<H256>>()
+            });
         }
+        indexed_topics
     }
 
     pub fn from_data_sources_opt<'a, I>(iter: I) -> Option<Self>
@@ -145,7 +293,7 @@ impl EthereumLogFilter {
                         .iter()
                         .map(move |event_handler| {
                             let event_sig = event_handler.topic0();
-                            (contract_addr, event_sig)
+                            (contract_addr, event_sig, event_handler.indexed_topics())
                         })
                         .collect::<EthereumLogFilter>(),
                 )
@@ -160,15 +308,15 @@ impl Extend<EthereumLogFilter> for EthereumLogFilter {
     /// Extends this log filter with another one.
     fn extend(mut self, other: EthereumLogFilter) -> Self {
         self.contract_address_and_event_sig_pairs
-            .extend(other.contract_address_and_event_sig_pairs.iter());
+            .extend(other.contract_address_and_event_sig_pairs.into_iter());
         self
     }
 }
 
-impl FromIterator<(Option<Address>, H256)> for EthereumLogFilter {
+impl FromIterator<(Option<Address>, H256, IndexedTopics)> for EthereumLogFilter {
     fn from_iter<I>(iter: I) -> Self
     where
-        I: IntoIterator<Item = (Option<Address>, H256)>,
+        I: IntoIterator<Item = (Option<Address>, H256, IndexedTopics)>,
     {
         EthereumLogFilter {
             contract_address_and_event_sig_pairs: iter.into_iter().collect(),
@@ -421,6 +569,7 @@ pub trait EthereumAdapter: Send + Sync + 'static {
         log_filter: Option<EthereumLogFilter>,
         call_filter: Option<EthereumCallFilter>,
         block_filter: Option<EthereumBlockFilter>,
+        scan_ranges: ScanRanges,
     ) -> Box<Future<Item = Vec<EthereumBlockPointer>, Error = Error> + Send>;
 
     /// Find the first few blocks in the specified range containing at least one transaction with
@@ -441,6 +590,7 @@ pub trait EthereumAdapter: Send + Sync + 'static {
         from: u64,
         to: u64,
         log_filter: EthereumLogFilter,
+        log_range: AdaptiveRangeSize,
     ) -> Box<Future<Item = Vec<EthereumBlockPointer>, Error = Error> + Send>;
 
     fn blocks_with_calls(
@@ -449,6 +599,7 @@ pub trait EthereumAdapter: Send + Sync + 'static {
         from: u64,
         to: u64,
         call_filter: EthereumCallFilter,
+        trace_range: AdaptiveRangeSize,
     ) -> Box<Future<Item = Vec<EthereumBlockPointer>, Error = Error> + Send>;
 
     fn blocks(
@@ -465,3 +616,89 @@ pub trait EthereumAdapter: Send + Sync + 'static {
         call: EthereumContractCall,
     ) -> Box<Future<Item = Vec<Token>, Error = EthereumContractCallError> + Send>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(address: Address, topics: Vec<H256>) -> Log {
+        Log {
+            address,
+            topics,
+            data: vec![].into(),
+            block_hash: None,
+            block_number: None,
+            transaction_hash: None,
+            transaction_index: None,
+            log_index: None,
+            transaction_log_index: None,
+            log_type: None,
+            removed: None,
+        }
+    }
+
+    #[test]
+    fn log_filter_matches_indexed_topics() {
+        let contract = Address::from([1; 20]);
+        let transfer = H256::from([2; 32]);
+        let receiver = H256::from([3; 32]);
+        let other = H256::from([4; 32]);
+
+        let filter = vec![(Some(contract), transfer, [None, Some(vec![receiver]), None])]
+            .into_iter()
+            .collect::<EthereumLogFilter>();
+
+        assert!(filter.matches(&log(contract, vec![transfer, other, receiver])));
+        assert!(!filter.matches(&log(contract, vec![transfer, receiver, other])));
+        assert!(!filter.matches(&log(contract, vec![transfer, other])));
+        assert_eq!(filter.indexed_topics(), [None, Some(vec![receiver]), None]);
+
+        // An event that does not constrain `topic2` lifts the constraint for
+        // `eth_getLogs` requests, but not for matching
+        let filter = filter.extend(
+            vec![(Some(contract), H256::from([5; 32]), [None, None, None])]
+                .into_iter()
+                .collect(),
+        );
+        assert_eq!(filter.indexed_topics(), [None, None, None]);
+        assert!(!filter.matches(&log(contract, vec![transfer, receiver, other])));
+    }
+
+    #[test]
+    fn log_filter_partitions_wildcard_events() {
+        let transfer = H256::from([2; 32]);
+        let filter = vec![
+            (Some(Address::from([1; 20])), transfer, [None, None, None]),
+            (None, transfer, [None, None, None]),
+        ]
+        .into_iter()
+        .collect::<EthereumLogFilter>();
+        assert!(filter.has_wildcard_events());
+
+        let (wildcard, contract) = filter.partition_wildcard_events();
+        assert_eq!(wildcard.contract_address_and_event_sig_pairs.len(), 1);
+        assert!(wildcard.has_wildcard_events());
+        assert_eq!(contract.contract_address_and_event_sig_pairs.len(), 1);
+        assert!(!contract.has_wildcard_events());
+    }
+
+    #[test]
+    fn adaptive_range_size() {
+        let range = AdaptiveRangeSize::new(1000, 4000);
+
+        range.grow_if_sparse(SPARSE_RESULTS);
+        assert_eq!(range.get(), 1000);
+        range.grow_if_sparse(0);
+        range.grow_if_sparse(0);
+        range.grow_if_sparse(0);
+        assert_eq!(range.get(), 4000);
+
+        assert!(range.clone().shrink());
+        assert_eq!(range.get(), 400);
+        assert!(range.shrink());
+        assert!(range.shrink());
+        assert!(range.shrink());
+        assert_eq!(range.get(), 1);
+        assert!(!range.shrink());
+    }
+}
@@ -1,18 +1,20 @@
 mod adapter;
 mod listener;
+mod network;
 mod stream;
 mod types;
 
 pub use self::adapter::{
-    EthereumAdapter, EthereumAdapterError, EthereumBlockFilter, EthereumCallFilter,
-    EthereumContractCall, EthereumContractCallError, EthereumContractState,
+    AdaptiveRangeSize, EthereumAdapter, EthereumAdapterError, EthereumBlockFilter,
+    EthereumCallFilter, EthereumContractCall, EthereumContractCallError, EthereumContractState,
     EthereumContractStateError, EthereumContractStateRequest, EthereumLogFilter,
-    EthereumNetworkIdentifier,
+    EthereumNetworkIdentifier, ScanRanges,
 };
 pub use self::listener::{ChainHeadUpdate, ChainHeadUpdateListener, ChainHeadUpdateStream};
+pub use self::network::{EthereumNetworks, NodeCapabilities};
 pub use self::stream::{BlockStream, BlockStreamBuilder};
 pub use self::types::{
     EthereumBlock, EthereumBlockData, EthereumBlockPointer, EthereumBlockTriggerType,
     EthereumBlockWithCalls, EthereumBlockWithTriggers, EthereumCall, EthereumCallData,
-    EthereumEventData, EthereumTransactionData, EthereumTrigger,
+    EthereumEventData, EthereumTransactionData, EthereumTransactionReceiptData, EthereumTrigger,
 };
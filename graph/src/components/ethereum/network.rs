@@ -0,0 +1,225 @@
+use failure::Error;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Features of an Ethereum node that subgraphs may depend on.
+///
+/// An archive node keeps the state of every block, which is needed to run
+/// `eth_call` against historical blocks. A node with traces supports the
+/// `trace_filter` API, which is needed for call handlers and for block
+/// handlers with a `call` filter.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct NodeCapabilities {
+    pub archive: bool,
+    pub traces: bool,
+}
+
+impl NodeCapabilities {
+    /// Capabilities of a node that supports everything.
+    pub fn full() -> Self {
+        NodeCapabilities {
+            archive: true,
+            traces: true,
+        }
+    }
+
+    /// Whether a node with these capabilities can serve a subgraph that
+    /// needs the `required` capabilities.
+    pub fn supports(&self, required: &NodeCapabilities) -> bool {
+        (self.archive || !required.archive) && (self.traces || !required.traces)
+    }
+
+    /// Combines the capabilities required by two parts of a subgraph.
+    pub fn union(self, other: NodeCapabilities) -> Self {
+        NodeCapabilities {
+            archive: self.archive || other.archive,
+            traces: self.traces || other.traces,
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.archive as usize + self.traces as usize
+    }
+}
+
+impl FromStr for NodeCapabilities {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let mut capabilities = NodeCapabilities::default();
+        for capability in s.split(',').map(str::trim) {
+            match capability {
+                "archive" => capabilities.archive = true,
+                "traces" => capabilities.traces = true,
+                "" | "none" => (),
+                _ => return Err(format_err!("unknown node capability `{}`", capability)),
+            }
+        }
+        Ok(capabilities)
+    }
+}
+
+impl fmt::Display for NodeCapabilities {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.archive, self.traces) {
+            (true, true) => write!(f, "archive, traces"),
+            (true, false) => write!(f, "archive"),
+            (false, true) => write!(f, "traces"),
+            (false, false) => write!(f, "none"),
+        }
+    }
+}
+
+/// The Ethereum adapters available for each network, along with the
+/// capabilities of the nodes behind them.
+#[derive(Debug)]
+pub struct EthereumNetworks<E> {
+    networks: HashMap<String, Vec<(NodeCapabilities, Arc<E>)>>,
+}
+
+impl<E> Clone for EthereumNetworks<E> {
+    fn clone(&self) -> Self {
+        EthereumNetworks {
+            networks: self.networks.clone(),
+        }
+    }
+}
+
+impl<E> Default for EthereumNetworks<E> {
+    fn default() -> Self {
+        EthereumNetworks {
+            networks: HashMap::new(),
+        }
+    }
+}
+
+impl<E> EthereumNetworks<E> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, network: String, capabilities: NodeCapabilities, adapter: Arc<E>) {
+        self.networks
+            .entry(network)
+            .or_insert_with(Vec::new)
+            .push((capabilities, adapter));
+    }
+
+    /// The capabilities of the providers available for each network.
+    pub fn capabilities(&self) -> HashMap<String, Vec<NodeCapabilities>> {
+        self.networks
+            .iter()
+            .map(|(network, adapters)| {
+                (
+                    network.clone(),
+                    adapters
+                        .iter()
+                        .map(|(capabilities, _)| *capabilities)
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+
+    /// Returns the adapter for `network` with the fewest capabilities that
+    /// still satisfy `required`, so that scarce archive and tracing nodes
+    /// are only used by subgraphs that need them.
+    pub fn adapter_with_capabilities(
+        &self,
+        network: &str,
+        required: &NodeCapabilities,
+    ) -> Result<&Arc<E>, Error> {
+        let adapters = self
+            .networks
+            .get(network)
+            .ok_or_else(|| format_err!("network `{}` is not supported by this node", network))?;
+
+        adapters
+            .iter()
+            .filter(|(capabilities, _)| capabilities.supports(required))
+            .min_by_key(|(capabilities, _)| capabilities.count())
+            .map(|(_, adapter)| adapter)
+            .ok_or_else(|| {
+                let available = adapters
+                    .iter()
+                    .map(|(capabilities, _)| format!("[{}]", capabilities))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format_err!(
+                    "no Ethereum provider for network `{}` supports the required \
+                     capabilities [{}]; available providers have {}",
+                    network,
+                    required,
+                    available
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_capabilities() {
+        assert_eq!(
+            NodeCapabilities::from_str("archive,traces").unwrap(),
+            NodeCapabilities::full()
+        );
+        assert_eq!(
+            NodeCapabilities::from_str("traces").unwrap(),
+            NodeCapabilities {
+                archive: false,
+                traces: true
+            }
+        );
+        assert_eq!(
+            NodeCapabilities::from_str("none").unwrap(),
+            NodeCapabilities::default()
+        );
+        assert!(NodeCapabilities::from_str("127.0.0.1").is_err());
+    }
+
+    #[test]
+    fn selects_the_least_capable_matching_adapter() {
+        let mut networks = EthereumNetworks::new();
+        networks.insert("mainnet".to_owned(), NodeCapabilities::full(), Arc::new(1));
+        networks.insert(
+            "mainnet".to_owned(),
+            NodeCapabilities::default(),
+            Arc::new(2),
+        );
+
+        let archive = NodeCapabilities {
+            archive: true,
+            traces: false,
+        };
+        assert_eq!(
+            **networks
+                .adapter_with_capabilities("mainnet", &NodeCapabilities::default())
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            **networks
+                .adapter_with_capabilities("mainnet", &archive)
+                .unwrap(),
+            1
+        );
+        assert!(networks
+            .adapter_with_capabilities("ropsten", &archive)
+            .is_err());
+
+        let mut pruned = EthereumNetworks::new();
+        pruned.insert(
+            "mainnet".to_owned(),
+            NodeCapabilities::default(),
+            Arc::new(3),
+        );
+        assert!(pruned
+            .adapter_with_capabilities("mainnet", &archive)
+            .is_err());
+    }
+}
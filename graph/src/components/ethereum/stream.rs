@@ -25,5 +25,5 @@ pub trait BlockStreamBuilder: Clone + Send + Sync {
         call_filter: Option<EthereumCallFilter>,
         block_filter: Option<EthereumBlockFilter>,
         include_calls_in_blocks: bool,
-    ) -> Self::Stream;
+    ) -> Result<Self::Stream, Error>;
 }
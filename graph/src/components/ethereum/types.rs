@@ -29,6 +29,12 @@ impl EthereumBlock {
             .cloned()
     }
 
+    pub fn receipt_for_transaction(&self, hash: &H256) -> Option<&TransactionReceipt> {
+        self.transaction_receipts
+            .iter()
+            .find(|receipt| &receipt.transaction_hash == hash)
+    }
+
     pub fn transaction_for_call(&self, call: &EthereumCall) -> Option<Transaction> {
         call.transaction_hash
             .and_then(|hash| self.block.transactions.iter().find(|tx| tx.hash == hash))
@@ -218,6 +224,28 @@ impl<'a> From<&'a Transaction> for EthereumTransactionData {
     }
 }
 
+/// Ethereum transaction receipt data, passed to handlers that request it.
+#[derive(Clone, Debug)]
+pub struct EthereumTransactionReceiptData {
+    pub status: Option<U64>,
+    pub gas_used: Option<U256>,
+    pub cumulative_gas_used: U256,
+    pub contract_address: Option<H160>,
+    pub logs: Vec<Log>,
+}
+
+impl<'a> From<&'a TransactionReceipt> for EthereumTransactionReceiptData {
+    fn from(receipt: &'a TransactionReceipt) -> EthereumTransactionReceiptData {
+        EthereumTransactionReceiptData {
+            status: receipt.status,
+            gas_used: receipt.gas_used,
+            cumulative_gas_used: receipt.cumulative_gas_used,
+            contract_address: receipt.contract_address,
+            logs: receipt.logs.clone(),
+        }
+    }
+}
+
 /// An Ethereum event logged from a specific contract address and block.
 #[derive(Debug)]
 pub struct EthereumEventData {
@@ -227,6 +255,7 @@ pub struct EthereumEventData {
     pub log_type: Option<String>,
     pub block: EthereumBlockData,
     pub transaction: EthereumTransactionData,
+    pub receipt: Option<EthereumTransactionReceiptData>,
     pub params: Vec<LogParam>,
 }
 
@@ -239,6 +268,7 @@ impl Clone for EthereumEventData {
             log_type: self.log_type.clone(),
             block: self.block.clone(),
             transaction: self.transaction.clone(),
+            receipt: self.receipt.clone(),
             params: self
                 .params
                 .iter()
@@ -258,6 +288,7 @@ pub struct EthereumCallData {
     pub to: Address,
     pub block: EthereumBlockData,
     pub transaction: EthereumTransactionData,
+    pub receipt: Option<EthereumTransactionReceiptData>,
     pub inputs: Vec<LogParam>,
     pub outputs: Vec<LogParam>,
 }
@@ -269,6 +300,7 @@ impl Clone for EthereumCallData {
             from: self.from,
             block: self.block.clone(),
             transaction: self.transaction.clone(),
+            receipt: self.receipt.clone(),
             inputs: self
                 .inputs
                 .iter()
@@ -1,11 +1,17 @@
 use futures::prelude::*;
 
+use crate::components::ethereum::EthereumBlockPointer;
 use crate::data::query::{Query, QueryError, QueryResult};
 use crate::data::subscription::{Subscription, SubscriptionError, SubscriptionResult};
 
 /// Future for query results.
 pub type QueryResultFuture = Box<Future<Item = QueryResult, Error = QueryError> + Send>;
 
+/// Future for query results, together with the block the deployment was at
+/// in the state of the store the result was computed from, if known.
+pub type QueryResultAtBlockFuture =
+    Box<Future<Item = (QueryResult, Option<EthereumBlockPointer>), Error = QueryError> + Send>;
+
 /// Future for subscription results.
 pub type SubscriptionResultFuture =
     Box<Future<Item = SubscriptionResult, Error = SubscriptionError> + Send>;
@@ -15,6 +21,12 @@ pub trait GraphQlRunner: Send + Sync + 'static {
     /// Runs a GraphQL query and returns its result.
     fn run_query(&self, query: Query) -> QueryResultFuture;
 
+    /// Runs a GraphQL query like `run_query` and also returns the block the
+    /// deployment was at when the result was computed. Results of the same
+    /// query at the same block are identical, which allows HTTP caches to
+    /// revalidate them.
+    fn run_query_at_block(&self, query: Query) -> QueryResultAtBlockFuture;
+
     /// Runs a GraphqL query up to the given complexity. Overrides the global complexity limit.
     fn run_query_with_complexity(
         &self,
@@ -22,6 +34,10 @@ pub trait GraphQlRunner: Send + Sync + 'static {
         max_complexity: Option<u64>,
     ) -> QueryResultFuture;
 
+    /// Runs a GraphQL query and reports where the time executing it went in
+    /// the `tracing` entry of the result's `extensions`.
+    fn run_traced_query(&self, query: Query) -> QueryResultFuture;
+
     /// Runs a GraphQL subscription and returns a stream of results.
     fn run_subscription(&self, subscription: Subscription) -> SubscriptionResultFuture;
 }
@@ -17,7 +17,7 @@ pub type JsonValueStream =
     Box<Stream<Item = JsonStreamValue, Error = failure::Error> + Send + 'static>;
 
 /// Resolves links to subgraph manifests and resources referenced by them.
-pub trait LinkResolver: Send + Sync + 'static + Sized {
+pub trait LinkResolver: Send + Sync + 'static {
     /// Fetches the link contents as bytes.
     fn cat(
         &self,
@@ -34,3 +34,22 @@ pub trait LinkResolver: Send + Sync + 'static + Sized {
         link: &Link,
     ) -> Box<Future<Item = JsonValueStream, Error = failure::Error> + Send + 'static>;
 }
+
+/// Allows choosing the link resolver at runtime, e.g. based on command line
+/// arguments.
+impl LinkResolver for Box<dyn LinkResolver> {
+    fn cat(
+        &self,
+        logger: &Logger,
+        link: &Link,
+    ) -> Box<Future<Item = Vec<u8>, Error = failure::Error> + Send> {
+        (**self).cat(logger, link)
+    }
+
+    fn json_stream(
+        &self,
+        link: &Link,
+    ) -> Box<Future<Item = JsonValueStream, Error = failure::Error> + Send + 'static> {
+        (**self).json_stream(link)
+    }
+}
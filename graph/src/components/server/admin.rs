@@ -1,19 +1,261 @@
+use std::collections::HashMap;
+use std::fmt;
 use std::io;
+use std::net::SocketAddr;
+use std::str::FromStr;
 use std::sync::Arc;
 
 use crate::prelude::Logger;
 use crate::prelude::NodeId;
 
+/// The permission granted to an admin access token.
+///
+/// Permissions are ordered: a token with `Admin` permission may call every
+/// method that a `Deploy` token may call.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AdminPermission {
+    /// May create subgraphs and deploy new subgraph versions.
+    Deploy,
+    /// May call every admin method, including removing and reassigning subgraphs.
+    Admin,
+}
+
+impl FromStr for AdminPermission {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "deploy" => Ok(AdminPermission::Deploy),
+            "admin" => Ok(AdminPermission::Admin),
+            _ => Err(format!("invalid admin permission `{}`", s)),
+        }
+    }
+}
+
+impl fmt::Display for AdminPermission {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AdminPermission::Deploy => write!(f, "deploy"),
+            AdminPermission::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+/// Access control configuration for the JSON-RPC admin server.
+///
+/// Clients authenticate by passing one of the configured tokens in an
+/// `Authorization: Bearer <token>` header. If no tokens are configured,
+/// access control is disabled and every request is allowed.
+#[derive(Clone, Default)]
+pub struct AdminAccessControl {
+    tokens: HashMap<String, AdminPermission>,
+}
+
+impl AdminAccessControl {
+    /// Creates an access control configuration that allows every request.
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// Adds an access token with the given permission.
+    pub fn add_token(&mut self, token: String, permission: AdminPermission) {
+        self.tokens.insert(token, permission);
+    }
+
+    /// Parses a token specification of the form `[PERMISSION:]TOKEN`, where
+    /// `PERMISSION` is `deploy` or `admin` and defaults to `admin`.
+    pub fn add_token_spec(&mut self, spec: &str) -> Result<(), String> {
+        let (permission, token) = match spec.find(':') {
+            Some(i) => match spec[..i].parse::<AdminPermission>() {
+                Ok(permission) => (permission, &spec[i + 1..]),
+                Err(_) => (AdminPermission::Admin, spec),
+            },
+            None => (AdminPermission::Admin, spec),
+        };
+
+        if token.is_empty() {
+            return Err("admin access token cannot be an empty string".to_owned());
+        }
+
+        self.add_token(token.to_owned(), permission);
+        Ok(())
+    }
+
+    /// Returns `true` if requests must present an access token.
+    pub fn is_enabled(&self) -> bool {
+        !self.tokens.is_empty()
+    }
+
+    /// Checks whether a request with the given `Authorization` header value
+    /// may perform an operation that requires `required` permission.
+    pub fn authorize(
+        &self,
+        authorization: Option<&str>,
+        required: AdminPermission,
+    ) -> Result<(), AdminAuthError> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+
+        let presented = authorization
+            .and_then(|value| {
+                let mut parts = value.trim().splitn(2, ' ');
+                match (parts.next(), parts.next()) {
+                    (Some(scheme), Some(token)) if scheme.eq_ignore_ascii_case("bearer") => {
+                        Some(token.trim())
+                    }
+                    _ => None,
+                }
+            })
+            .ok_or(AdminAuthError::MissingToken)?;
+
+        // Compare against every configured token so that the time taken does
+        // not reveal which tokens exist.
+        let permission = self
+            .tokens
+            .iter()
+            .fold(None, |found, (token, permission)| {
+                if constant_time_eq(token.as_bytes(), presented.as_bytes()) {
+                    Some(*permission)
+                } else {
+                    found
+                }
+            })
+            .ok_or(AdminAuthError::InvalidToken)?;
+
+        if permission >= required {
+            Ok(())
+        } else {
+            Err(AdminAuthError::InsufficientPermission(required))
+        }
+    }
+}
+
+impl fmt::Debug for AdminAccessControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Never print the tokens themselves
+        f.debug_struct("AdminAccessControl")
+            .field("tokens", &self.tokens.len())
+            .finish()
+    }
+}
+
+/// Reasons why a request to the admin server was rejected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AdminAuthError {
+    MissingToken,
+    InvalidToken,
+    InsufficientPermission(AdminPermission),
+}
+
+impl fmt::Display for AdminAuthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AdminAuthError::MissingToken => {
+                write!(f, "missing `Authorization: Bearer <token>` header")
+            }
+            AdminAuthError::InvalidToken => write!(f, "invalid access token"),
+            AdminAuthError::InsufficientPermission(required) => write!(
+                f,
+                "access token lacks the `{}` permission required by this method",
+                required
+            ),
+        }
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter()
+        .zip(b.iter())
+        .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+        == 0
+}
+
 /// Common trait for JSON-RPC admin server implementations.
 pub trait JsonRpcServer<P> {
     type Server;
 
     fn serve(
-        port: u16,
+        addr: SocketAddr,
         http_port: u16,
         ws_port: u16,
         provider: Arc<P>,
         node_id: NodeId,
+        access_control: AdminAccessControl,
         logger: Logger,
     ) -> Result<Self::Server, io::Error>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn access_control() -> AdminAccessControl {
+        let mut access_control = AdminAccessControl::disabled();
+        access_control.add_token_spec("deploy:deployer").unwrap();
+        access_control.add_token_spec("admin:root").unwrap();
+        access_control.add_token_spec("plain").unwrap();
+        access_control
+    }
+
+    #[test]
+    fn disabled_access_control_allows_everything() {
+        let access_control = AdminAccessControl::disabled();
+        assert_eq!(
+            access_control.authorize(None, AdminPermission::Admin),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn token_permissions_are_enforced() {
+        let access_control = access_control();
+
+        assert_eq!(
+            access_control.authorize(Some("Bearer deployer"), AdminPermission::Deploy),
+            Ok(())
+        );
+        assert_eq!(
+            access_control.authorize(Some("Bearer deployer"), AdminPermission::Admin),
+            Err(AdminAuthError::InsufficientPermission(
+                AdminPermission::Admin
+            ))
+        );
+        assert_eq!(
+            access_control.authorize(Some("bearer root"), AdminPermission::Admin),
+            Ok(())
+        );
+        assert_eq!(
+            access_control.authorize(Some("Bearer plain"), AdminPermission::Admin),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn missing_or_unknown_tokens_are_rejected() {
+        let access_control = access_control();
+
+        assert_eq!(
+            access_control.authorize(None, AdminPermission::Deploy),
+            Err(AdminAuthError::MissingToken)
+        );
+        assert_eq!(
+            access_control.authorize(Some("Basic deployer"), AdminPermission::Deploy),
+            Err(AdminAuthError::MissingToken)
+        );
+        assert_eq!(
+            access_control.authorize(Some("Bearer nope"), AdminPermission::Deploy),
+            Err(AdminAuthError::InvalidToken)
+        );
+    }
+
+    #[test]
+    fn empty_tokens_are_invalid() {
+        let mut access_control = AdminAccessControl::disabled();
+        assert!(access_control.add_token_spec("deploy:").is_err());
+        assert!(access_control.add_token_spec("").is_err());
+    }
+}
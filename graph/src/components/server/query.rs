@@ -2,6 +2,7 @@ use crate::data::query::QueryError;
 use futures::prelude::*;
 use futures::sync::oneshot::Canceled;
 use serde::ser::*;
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::fmt;
 
@@ -12,6 +13,10 @@ pub enum GraphQLServerError {
     ClientError(String),
     QueryError(QueryError),
     InternalError(String),
+    /// The client sent the hash of an automatic persisted query that is not
+    /// known to the server; the client is expected to retry with the full
+    /// query text.
+    PersistedQueryNotFound,
 }
 
 impl From<Canceled> for GraphQLServerError {
@@ -53,6 +58,7 @@ impl fmt::Display for GraphQLServerError {
             GraphQLServerError::InternalError(ref s) => {
                 write!(f, "GraphQL server error (internal error): {}", s)
             }
+            GraphQLServerError::PersistedQueryNotFound => write!(f, "PersistedQueryNotFound"),
         }
     }
 }
@@ -68,6 +74,7 @@ impl Error for GraphQLServerError {
             GraphQLServerError::ClientError(_) => None,
             GraphQLServerError::QueryError(ref e) => Some(e),
             GraphQLServerError::InternalError(_) => None,
+            GraphQLServerError::PersistedQueryNotFound => None,
         }
     }
 }
@@ -79,6 +86,16 @@ impl Serialize for GraphQLServerError {
     {
         if let GraphQLServerError::QueryError(ref e) = *self {
             serializer.serialize_some(e)
+        } else if let GraphQLServerError::PersistedQueryNotFound = *self {
+            // Clients implementing automatic persisted queries look for this
+            // exact message and code
+            let mut extensions = BTreeMap::new();
+            extensions.insert("code", "PERSISTED_QUERY_NOT_FOUND");
+
+            let mut map = serializer.serialize_map(Some(2))?;
+            map.serialize_entry("message", "PersistedQueryNotFound")?;
+            map.serialize_entry("extensions", &extensions)?;
+            map.end()
         } else {
             let mut map = serializer.serialize_map(Some(1))?;
             let msg = format!("{}", self);
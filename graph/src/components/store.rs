@@ -57,6 +57,25 @@ pub enum EntityFilter {
     NotStartsWith(Attribute, Value),
     EndsWith(Attribute, Value),
     NotEndsWith(Attribute, Value),
+    ContainsNoCase(Attribute, Value),
+    NotContainsNoCase(Attribute, Value),
+    StartsWithNoCase(Attribute, Value),
+    NotStartsWithNoCase(Attribute, Value),
+    EndsWithNoCase(Attribute, Value),
+    NotEndsWithNoCase(Attribute, Value),
+    /// The attribute matches a SQL `LIKE` pattern.
+    Like(Attribute, Value),
+    LikeNoCase(Attribute, Value),
+    /// The attribute matches a POSIX regular expression.
+    Regex(Attribute, Value),
+    RegexNoCase(Attribute, Value),
+    /// The list attribute contains at least one of the values.
+    ContainsAny(Attribute, Vec<Value>),
+    /// The list attribute contains all of the values.
+    ContainsAll(Attribute, Vec<Value>),
+    /// The list attribute is empty (`true`) or not empty (`false`). Null
+    /// lists match neither.
+    IsEmpty(Attribute, bool),
 }
 
 // Define some convenience methods
@@ -605,6 +624,12 @@ pub enum StoreError {
         _0, _1, _2, _0
     )]
     ConflictingId(String, String, String), // (entity, id, conflicting_entity)
+    #[fail(
+        display = "tried to set entity of type `{}` with ID \"{}\" but an entity of type `{}`, \
+                   which is a member of a union together with `{}`, exists with the same ID",
+        _0, _1, _2, _0
+    )]
+    ConflictingUnionId(String, String, String), // (entity, id, conflicting_entity)
 }
 
 impl From<TransactionAbortError> for StoreError {
@@ -631,6 +656,71 @@ impl From<serde_json::Error> for StoreError {
     }
 }
 
+/// Reads entities on behalf of a single GraphQL query, see
+/// `Store::query_store`.
+pub trait QueryStore: Send + Sync {
+    /// Get a pointer to the most recently processed block in the subgraph.
+    fn block_ptr(&self, subgraph_id: SubgraphDeploymentId) -> Result<EthereumBlockPointer, Error>;
+
+    /// Looks up an entity using the given store key.
+    fn get(&self, key: EntityKey) -> Result<Option<Entity>, QueryExecutionError>;
+
+    /// Like `get`, but also returns the SQL that was run to look up the
+    /// entity, if any.
+    fn get_with_sql(
+        &self,
+        key: EntityKey,
+    ) -> Result<(Option<Entity>, Option<String>), QueryExecutionError> {
+        self.get(key).map(|entity| (entity, None))
+    }
+
+    /// Queries the store for entities that match the store query, and
+    /// returns them together with the SQL that was run to find them, if any.
+    fn find_with_sql(
+        &self,
+        query: EntityQuery,
+    ) -> Result<(Vec<Entity>, Option<String>), QueryExecutionError>;
+
+    /// Queries the store for entities that match the store query.
+    fn find(&self, query: EntityQuery) -> Result<Vec<Entity>, QueryExecutionError> {
+        self.find_with_sql(query).map(|(entities, _)| entities)
+    }
+
+    /// Queries the store for a single entity matching the store query.
+    fn find_one(&self, query: EntityQuery) -> Result<Option<Entity>, QueryExecutionError>;
+}
+
+/// A `QueryStore` that passes all reads on to a `Store`.
+struct DirectQueryStore<S>(Arc<S>);
+
+impl<S: Store> QueryStore for DirectQueryStore<S> {
+    fn block_ptr(&self, subgraph_id: SubgraphDeploymentId) -> Result<EthereumBlockPointer, Error> {
+        self.0.block_ptr(subgraph_id)
+    }
+
+    fn get(&self, key: EntityKey) -> Result<Option<Entity>, QueryExecutionError> {
+        self.0.get(key)
+    }
+
+    fn get_with_sql(
+        &self,
+        key: EntityKey,
+    ) -> Result<(Option<Entity>, Option<String>), QueryExecutionError> {
+        self.0.get_with_sql(key)
+    }
+
+    fn find_with_sql(
+        &self,
+        query: EntityQuery,
+    ) -> Result<(Vec<Entity>, Option<String>), QueryExecutionError> {
+        self.0.find_with_sql(query)
+    }
+
+    fn find_one(&self, query: EntityQuery) -> Result<Option<Entity>, QueryExecutionError> {
+        self.0.find_one(query)
+    }
+}
+
 #[derive(Fail, PartialEq, Eq, Debug)]
 pub enum TransactionAbortError {
     #[fail(
@@ -654,12 +744,45 @@ pub trait Store: Send + Sync + 'static {
     /// Looks up an entity using the given store key.
     fn get(&self, key: EntityKey) -> Result<Option<Entity>, QueryExecutionError>;
 
+    /// Like `get`, but also returns the SQL the store ran to look up the
+    /// entity. Stores that are not backed by a SQL database return no SQL.
+    fn get_with_sql(
+        &self,
+        key: EntityKey,
+    ) -> Result<(Option<Entity>, Option<String>), QueryExecutionError> {
+        self.get(key).map(|entity| (entity, None))
+    }
+
     /// Queries the store for entities that match the store query.
     fn find(&self, query: EntityQuery) -> Result<Vec<Entity>, QueryExecutionError>;
 
+    /// Like `find`, but also returns the SQL the store ran to find the
+    /// entities, so that queries can be traced. Stores that are not backed
+    /// by a SQL database return no SQL.
+    fn find_with_sql(
+        &self,
+        query: EntityQuery,
+    ) -> Result<(Vec<Entity>, Option<String>), QueryExecutionError> {
+        self.find(query).map(|entities| (entities, None))
+    }
+
     /// Queries the store for a single entity matching the store query.
     fn find_one(&self, query: EntityQuery) -> Result<Option<Entity>, QueryExecutionError>;
 
+    /// Returns a handle for the reads of a single GraphQL query. All reads
+    /// through the handle see the same state of the store, even if changes
+    /// are committed while the query runs.
+    ///
+    /// The default implementation reads directly from the store and gives
+    /// no such guarantee; it is only suitable for stores that do not change
+    /// while they are being queried.
+    fn query_store(self: Arc<Self>) -> Result<Arc<QueryStore>, QueryExecutionError>
+    where
+        Self: Sized,
+    {
+        Ok(Arc::new(DirectQueryStore(self)))
+    }
+
     /// Find the reverse of keccak256 for `hash` through looking it up in the
     /// rainbow table.
     fn find_ens_name(&self, _hash: &str) -> Result<Option<String>, QueryExecutionError>;
@@ -1086,6 +1209,15 @@ pub trait ChainStore: Send + Sync + 'static {
     /// Get Some(block) if it is present in the chain store, or None.
     fn block(&self, block_hash: H256) -> Result<Option<EthereumBlock>, Error>;
 
+    /// Store the calls made in a block, as found in its traces. The block
+    /// must already be in the store. Only blocks that had triggers for a
+    /// subgraph are cached this way; the results of range scans are not.
+    fn upsert_block_calls(&self, block_hash: H256, calls: &[EthereumCall]) -> Result<(), Error>;
+
+    /// Get Some(calls) if the calls of the block are present in the chain
+    /// store, or None.
+    fn block_calls(&self, block_hash: H256) -> Result<Option<Vec<EthereumCall>>, Error>;
+
     /// Get the `offset`th ancestor of `block_hash`, where offset=0 means the block matching
     /// `block_hash` and offset=1 means its parent. Returns None if unable to complete due to
     /// missing blocks in the chain store.
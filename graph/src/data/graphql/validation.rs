@@ -25,11 +25,25 @@ pub enum SchemaValidationError {
         _0, _1, _2
     )]
     CannotImplement(String, String, Strings), // (type, interface, missing_fields)
+
+    #[fail(
+        display = "Union `{}` contains `{}`, which is not an entity type",
+        _0, _1
+    )]
+    InvalidUnionMember(String, String), // (union, member)
+
+    #[fail(
+        display = "The `@cost` directive on `{}.{}` needs a non-negative \
+                   integer `value` argument",
+        _0, _1
+    )]
+    InvalidCostDirective(String, String), // (type, field)
 }
 
 /// Validates whether a GraphQL schema is compatible with The Graph.
 pub(crate) fn validate_schema(schema: &Document) -> Result<(), SchemaValidationError> {
-    validate_schema_types(schema)
+    validate_schema_types(schema)?;
+    validate_cost_directives(schema)
 }
 
 /// Validates whether all object types in the schema are declared with an @entity directive.
@@ -51,6 +65,34 @@ fn validate_schema_types(schema: &Document) -> Result<(), SchemaValidationError>
     }
 }
 
+/// Validates that all `@cost` directives on fields of object and interface
+/// types have a `value` argument that is a non-negative integer.
+fn validate_cost_directives(schema: &Document) -> Result<(), SchemaValidationError> {
+    for (type_name, fields) in schema.definitions.iter().filter_map(|d| match d {
+        Definition::TypeDefinition(TypeDefinition::Object(t)) => Some((&t.name, &t.fields)),
+        Definition::TypeDefinition(TypeDefinition::Interface(t)) => Some((&t.name, &t.fields)),
+        _ => None,
+    }) {
+        for field in fields {
+            for directive in field.directives.iter().filter(|d| d.name == "cost") {
+                let valid = match directive.arguments.as_slice() {
+                    [(name, Value::Int(n))] => {
+                        name == "value" && n.as_i64().map_or(false, |n| n >= 0)
+                    }
+                    _ => false,
+                };
+                if !valid {
+                    return Err(SchemaValidationError::InvalidCostDirective(
+                        type_name.clone(),
+                        field.name.clone(),
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Validate `interfaceethat `object` implements `interface`.
 pub(crate) fn validate_interface_implementation(
     object: &ObjectType,
@@ -79,6 +121,27 @@ pub(crate) fn validate_interface_implementation(
     }
 }
 
+/// Validates that all members of `union` are entity types.
+pub(crate) fn validate_union_members(
+    schema: &Document,
+    union: &UnionType,
+) -> Result<(), SchemaValidationError> {
+    let object_types = get_object_type_definitions(schema);
+    for member in &union.types {
+        let is_entity = object_types.iter().any(|object_type| {
+            &object_type.name == member
+                && get_object_type_directive(object_type, String::from("entity")).is_some()
+        });
+        if !is_entity {
+            return Err(SchemaValidationError::InvalidUnionMember(
+                union.name.clone(),
+                member.clone(),
+            ));
+        }
+    }
+    Ok(())
+}
+
 /// Returns all object type definitions in the schema.
 pub fn get_object_type_definitions(schema: &Document) -> Vec<&ObjectType> {
     schema
@@ -19,6 +19,18 @@ impl TryFromValue for String {
     }
 }
 
+impl TryFromValue for bool {
+    fn try_from_value(value: &Value) -> Result<Self, Error> {
+        match value {
+            Value::Boolean(b) => Ok(*b),
+            _ => Err(format_err!(
+                "Cannot parse value into a boolean: {:?}",
+                value
+            )),
+        }
+    }
+}
+
 impl TryFromValue for H160 {
     fn try_from_value(value: &Value) -> Result<Self, Error> {
         match value {
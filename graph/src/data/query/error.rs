@@ -41,6 +41,7 @@ pub enum QueryExecutionError {
     EntityFieldError(String, String),
     ListTypesError(String, Vec<String>),
     ListFilterError(String),
+    FilterPatternTooLong(String, usize), // (filter, max_length)
     ValueParseError(String, String),
     AttributeTypeError(String, String),
     EntityParseError(String),
@@ -51,8 +52,10 @@ pub enum QueryExecutionError {
     Unimplemented(String),
     EnumCoercionError(Pos, String, q::Value, String, Vec<String>),
     ScalarCoercionError(Pos, String, q::Value, String),
-    TooComplex(u64, u64), // (complexity, max_complexity)
-    TooDeep(u8),          // max_depth
+    TooComplex(u64, u64),   // (complexity, max_complexity)
+    TooDeep(u8),            // max_depth
+    TooManyEntities(usize), // max_entities
+    ValidationError(Pos, String),
 }
 
 impl Error for QueryExecutionError {
@@ -155,6 +158,9 @@ impl fmt::Display for QueryExecutionError {
             ListFilterError(s) => {
                 write!(f, "Non-list value passed to `{}` filter", s)
             }
+            FilterPatternTooLong(s, max_length) => {
+                write!(f, "Pattern passed to `{}` filter is longer than {} characters", s, max_length)
+            }
             ValueParseError(t, e) => {
                 write!(f, "Failed to decode `{}` value: `{}`", t, e)
             }
@@ -191,7 +197,12 @@ impl fmt::Display for QueryExecutionError {
                            of the query, querying fewer relationships or using `first` to \
                            return smaller collections", complexity, max_complexity)
             }
-            TooDeep(max_depth) => write!(f, "query has a depth that exceeds the limit of `{}`", max_depth)
+            TooDeep(max_depth) => write!(f, "query has a depth that exceeds the limit of `{}`", max_depth),
+            TooManyEntities(max_entities) => {
+                write!(f, "query returns more than the limit of `{}` entities. Use `first` to \
+                           return smaller collections or query fewer relationships", max_entities)
+            }
+            ValidationError(_, message) => write!(f, "{}", message),
         }
     }
 }
@@ -232,6 +243,22 @@ pub enum QueryError {
     EncodingError(FromUtf8Error),
     ParseError(q::ParseError),
     ExecutionError(QueryExecutionError),
+    /// An error in the part of a query whose result is placed at the given
+    /// path of the response, e.g. a namespace of a federated query.
+    Nested(Vec<String>, Box<QueryError>),
+}
+
+impl QueryError {
+    /// Prepends `key` to the path of the error.
+    pub fn nest(self, key: impl Into<String>) -> Self {
+        match self {
+            QueryError::Nested(mut path, e) => {
+                path.insert(0, key.into());
+                QueryError::Nested(path, e)
+            }
+            e => QueryError::Nested(vec![key.into()], Box::new(e)),
+        }
+    }
 }
 
 impl From<FromUtf8Error> for QueryError {
@@ -261,6 +288,7 @@ impl Error for QueryError {
         match *self {
             QueryError::EncodingError(ref e) => Some(e),
             QueryError::ExecutionError(ref e) => Some(e),
+            QueryError::Nested(_, ref e) => Some(e.as_ref()),
             _ => None,
         }
     }
@@ -272,6 +300,7 @@ impl fmt::Display for QueryError {
             QueryError::EncodingError(ref e) => write!(f, "{}", e),
             QueryError::ExecutionError(ref e) => write!(f, "{}", e),
             QueryError::ParseError(ref e) => write!(f, "{}", e),
+            QueryError::Nested(_, ref e) => write!(f, "{}", e),
         }
     }
 }
@@ -285,7 +314,12 @@ impl Serialize for QueryError {
 
         let mut map = serializer.serialize_map(Some(1))?;
 
-        let msg = match self {
+        let (path, error) = match self {
+            QueryError::Nested(path, error) => (Some(path), error.as_ref()),
+            error => (None, error),
+        };
+
+        let msg = match error {
             // Serialize parse errors with their location (line, column) to make it easier
             // for users to find where the errors are; this is likely to change as the
             // graphql_parser team makes improvements to their error reporting
@@ -331,7 +365,9 @@ impl Serialize for QueryError {
             | QueryError::ExecutionError(MissingVariableError(pos, _))
             | QueryError::ExecutionError(AmbiguousDerivedFromResult(pos, _, _, _))
             | QueryError::ExecutionError(EnumCoercionError(pos, _, _, _, _))
-            | QueryError::ExecutionError(ScalarCoercionError(pos, _, _, _)) => {
+            | QueryError::ExecutionError(ScalarCoercionError(pos, _, _, _))
+            | QueryError::ExecutionError(UnknownField(pos, _, _))
+            | QueryError::ExecutionError(ValidationError(pos, _)) => {
                 let mut location = HashMap::new();
                 location.insert("line", pos.line);
                 location.insert("column", pos.column);
@@ -342,6 +378,9 @@ impl Serialize for QueryError {
         };
 
         map.serialize_entry("message", msg.as_str())?;
+        if let Some(path) = path {
+            map.serialize_entry("path", path)?;
+        }
         map.end()
     }
 }
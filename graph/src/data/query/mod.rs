@@ -1,7 +1,9 @@
 mod error;
 mod query;
 mod result;
+mod trace;
 
 pub use self::error::{QueryError, QueryExecutionError};
 pub use self::query::{Query, QueryVariables};
 pub use self::result::QueryResult;
+pub use self::trace::{PathSegment, ResolverTrace, Trace};
@@ -1,5 +1,6 @@
 use graphql_parser::query as q;
 use serde::ser::*;
+use std::collections::BTreeMap;
 
 use super::error::{QueryError, QueryExecutionError};
 use crate::data::graphql::SerializableValue;
@@ -21,11 +22,26 @@ pub struct QueryResult {
     pub data: Option<q::Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub errors: Option<Vec<QueryError>>,
+    /// Additional information about the execution of the query, e.g. its
+    /// complexity, keyed by name.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<BTreeMap<String, serde_json::Value>>,
 }
 
 impl QueryResult {
     pub fn new(data: Option<q::Value>) -> Self {
-        QueryResult { data, errors: None }
+        QueryResult {
+            data,
+            errors: None,
+            extensions: None,
+        }
+    }
+
+    /// Adds an entry to the `extensions` of the result.
+    pub fn add_extension(&mut self, name: impl Into<String>, value: serde_json::Value) {
+        self.extensions
+            .get_or_insert_with(BTreeMap::new)
+            .insert(name.into(), value);
     }
 }
 
@@ -42,6 +58,7 @@ impl From<Vec<QueryExecutionError>> for QueryResult {
         QueryResult {
             data: None,
             errors: Some(e.into_iter().map(QueryError::from).collect()),
+            extensions: None,
         }
     }
 }
@@ -0,0 +1,219 @@
+use chrono::prelude::{DateTime, SecondsFormat, Utc};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A segment of the path of a field in the response: either the response
+/// key of a field or the index of an item in a list.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// The time it took to resolve a single field.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolverTrace {
+    pub path: Vec<PathSegment>,
+    pub parent_type: String,
+    pub field_name: String,
+    pub return_type: String,
+    /// Nanoseconds since the start of the query.
+    pub start_offset: u64,
+    /// Nanoseconds it took to resolve the field.
+    pub duration: u64,
+    /// The SQL the store ran to resolve the field.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sql: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct TraceData {
+    /// Offset and duration of validating the query, in nanoseconds.
+    validation: Option<(u64, u64)>,
+    resolvers: Vec<ResolverTrace>,
+    /// SQL run since the last field was recorded.
+    pending_sql: Vec<String>,
+    duration: Option<Duration>,
+}
+
+/// Records where the time executing a query goes, so that it can be
+/// reported in the Apollo tracing format, see
+/// https://github.com/apollographql/apollo-tracing.
+#[derive(Debug)]
+pub struct Trace {
+    start_time: DateTime<Utc>,
+    start: Instant,
+    data: Mutex<TraceData>,
+}
+
+fn nanos(duration: Duration) -> u64 {
+    duration.as_secs() * 1_000_000_000 + u64::from(duration.subsec_nanos())
+}
+
+impl Trace {
+    pub fn new() -> Self {
+        Trace {
+            start_time: Utc::now(),
+            start: Instant::now(),
+            data: Mutex::new(TraceData::default()),
+        }
+    }
+
+    fn offset(&self, instant: Instant) -> u64 {
+        nanos(instant.duration_since(self.start))
+    }
+
+    /// Records that validating the query began at `start` and has just
+    /// finished.
+    pub fn validation(&self, start: Instant) {
+        let offset = self.offset(start);
+        self.data.lock().unwrap().validation = Some((offset, nanos(start.elapsed())));
+    }
+
+    /// Records SQL that the store ran for the field that is currently
+    /// being resolved.
+    pub fn sql(&self, sql: String) {
+        self.data.lock().unwrap().pending_sql.push(sql);
+    }
+
+    /// Records that resolving a field began at `start` and has just
+    /// finished. All SQL recorded since the previous field is attributed to
+    /// this field.
+    pub fn resolver(
+        &self,
+        path: Vec<PathSegment>,
+        parent_type: &str,
+        field_name: &str,
+        return_type: &str,
+        start: Instant,
+    ) {
+        let start_offset = self.offset(start);
+        let duration = nanos(start.elapsed());
+        let mut data = self.data.lock().unwrap();
+        let sql = data.pending_sql.drain(..).collect();
+        data.resolvers.push(ResolverTrace {
+            path,
+            parent_type: parent_type.to_owned(),
+            field_name: field_name.to_owned(),
+            return_type: return_type.to_owned(),
+            start_offset,
+            duration,
+            sql,
+        });
+    }
+
+    /// Marks the end of executing the query.
+    pub fn finish(&self) {
+        self.data.lock().unwrap().duration = Some(self.start.elapsed());
+    }
+
+    /// How long executing the query took, or has taken so far if it is
+    /// not finished.
+    pub fn duration(&self) -> Duration {
+        self.data
+            .lock()
+            .unwrap()
+            .duration
+            .unwrap_or_else(|| self.start.elapsed())
+    }
+
+    /// Returns the trace in the Apollo tracing format, i.e. the value of the
+    /// `tracing` entry in the `extensions` of a response. The SQL the store
+    /// ran is only included if `include_sql` is set.
+    pub fn to_json(&self, include_sql: bool) -> serde_json::Value {
+        let duration = self.duration();
+        let data = self.data.lock().unwrap();
+        let end_time = self.start_time
+            + chrono::Duration::from_std(duration).unwrap_or_else(|_| chrono::Duration::zero());
+
+        let resolvers = if include_sql {
+            json!(data.resolvers)
+        } else {
+            json!(data
+                .resolvers
+                .iter()
+                .map(|resolver| ResolverTrace {
+                    sql: vec![],
+                    ..resolver.clone()
+                })
+                .collect::<Vec<_>>())
+        };
+
+        let mut tracing = json!({
+            "version": 1,
+            "startTime": self.start_time.to_rfc3339_opts(SecondsFormat::Millis, true),
+            "endTime": end_time.to_rfc3339_opts(SecondsFormat::Millis, true),
+            "duration": nanos(duration),
+            "execution": {
+                "resolvers": resolvers,
+            },
+        });
+        if let Some((start_offset, duration)) = data.validation {
+            tracing["validation"] = json!({
+                "startOffset": start_offset,
+                "duration": duration,
+            });
+        }
+        tracing
+    }
+}
+
+impl Default for Trace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apollo_tracing_format() {
+        let trace = Trace::new();
+        trace.validation(Instant::now());
+        trace.sql("select 1".to_owned());
+        trace.resolver(
+            vec![PathSegment::Key("musicians".to_owned())],
+            "Query",
+            "musicians",
+            "[Musician!]!",
+            Instant::now(),
+        );
+        trace.resolver(
+            vec![
+                PathSegment::Key("musicians".to_owned()),
+                PathSegment::Index(0),
+                PathSegment::Key("name".to_owned()),
+            ],
+            "Musician",
+            "name",
+            "String!",
+            Instant::now(),
+        );
+        trace.finish();
+
+        let json = trace.to_json(true);
+        assert_eq!(json["version"], json!(1));
+        assert!(json["startTime"].is_string());
+        assert!(json["endTime"].is_string());
+        assert!(json["validation"]["duration"].is_u64());
+
+        let resolvers = json["execution"]["resolvers"].as_array().unwrap();
+        assert_eq!(resolvers.len(), 2);
+        assert_eq!(resolvers[0]["path"], json!(["musicians"]));
+        assert_eq!(resolvers[0]["parentType"], json!("Query"));
+        assert_eq!(resolvers[0]["sql"], json!(["select 1"]));
+        assert_eq!(resolvers[1]["path"], json!(["musicians", 0, "name"]));
+        assert_eq!(resolvers[1]["returnType"], json!("String!"));
+        assert!(resolvers[1].get("sql").is_none());
+
+        // SQL is left out unless asked for
+        let json = trace.to_json(false);
+        let resolvers = json["execution"]["resolvers"].as_array().unwrap();
+        assert_eq!(resolvers.len(), 2);
+        assert!(resolvers[0].get("sql").is_none());
+    }
+}
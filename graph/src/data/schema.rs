@@ -1,13 +1,13 @@
 use crate::data::graphql::validation::{
     get_object_type_definitions, validate_interface_implementation, validate_schema,
-    SchemaValidationError,
+    validate_union_members, SchemaValidationError,
 };
 use crate::data::subgraph::SubgraphDeploymentId;
 use failure::Error;
 use graphql_parser;
 use graphql_parser::{
     query::Name,
-    schema::{self, InterfaceType, ObjectType, TypeDefinition},
+    schema::{self, InterfaceType, ObjectType, TypeDefinition, UnionType},
     Pos,
 };
 use std::collections::BTreeMap;
@@ -22,7 +22,12 @@ pub struct Schema {
     // Maps type name to implemented interfaces.
     interfaces_for_type: BTreeMap<Name, Vec<InterfaceType>>,
 
-    // Maps an interface name to the list of entities that implement it.
+    // Maps type name to the unions it is a member of.
+    unions_for_type: BTreeMap<Name, Vec<UnionType>>,
+
+    // Maps an interface name to the list of entities that implement it, and
+    // a union name to the list of its member entities. Both are queried the
+    // same way, across all of these entity types.
     types_for_interface: BTreeMap<Name, Vec<ObjectType>>,
 }
 
@@ -35,6 +40,7 @@ impl Schema {
             id,
             document,
             interfaces_for_type: BTreeMap::new(),
+            unions_for_type: BTreeMap::new(),
             types_for_interface: BTreeMap::new(),
         }
     }
@@ -84,10 +90,34 @@ impl Schema {
             }
         }
 
+        let mut unions_for_type = BTreeMap::<_, Vec<_>>::new();
+        for union_type in document.definitions.iter().filter_map(|d| match d {
+            schema::Definition::TypeDefinition(TypeDefinition::Union(t)) => Some(t),
+            _ => None,
+        }) {
+            validate_union_members(&document, union_type)?;
+
+            for member in &union_type.types {
+                unions_for_type
+                    .entry(member.clone())
+                    .or_default()
+                    .push(union_type.clone());
+            }
+            types_for_interface.insert(
+                union_type.name.clone(),
+                get_object_type_definitions(&document)
+                    .into_iter()
+                    .filter(|object_type| union_type.types.contains(&object_type.name))
+                    .cloned()
+                    .collect(),
+            );
+        }
+
         let mut schema = Schema {
             id: id.clone(),
             document,
             interfaces_for_type,
+            unions_for_type,
             types_for_interface,
         };
         schema.add_subgraph_id_directives(id);
@@ -95,7 +125,8 @@ impl Schema {
         Ok(schema)
     }
 
-    /// Returned map has one an entry for each interface in the schema.
+    /// Returned map has one an entry for each interface and each union in
+    /// the schema.
     pub fn types_for_interface(&self) -> &BTreeMap<Name, Vec<ObjectType>> {
         &self.types_for_interface
     }
@@ -105,6 +136,11 @@ impl Schema {
         self.interfaces_for_type.get(type_name)
     }
 
+    /// Returns `None` if the type is not a member of any union.
+    pub fn unions_for_type(&self, type_name: &Name) -> Option<&Vec<UnionType>> {
+        self.unions_for_type.get(type_name)
+    }
+
     // Adds a @subgraphId(id: ...) directive to object/interface/enum types in the schema.
     pub fn add_subgraph_id_directives(&mut self, id: SubgraphDeploymentId) {
         for definition in self.document.definitions.iter_mut() {
@@ -176,3 +212,57 @@ fn invalid_interface_implementation() {
          required fields: x: Int, y: Int"
     );
 }
+
+#[test]
+fn invalid_union_member() {
+    let schema = "
+        type Foo @entity { id: ID! }
+        union FooOrBaz = Foo | Baz
+    ";
+    let res = Schema::parse(schema, SubgraphDeploymentId::new("dummy").unwrap());
+    assert_eq!(
+        res.unwrap_err()
+            .downcast::<SchemaValidationError>()
+            .unwrap(),
+        SchemaValidationError::InvalidUnionMember("FooOrBaz".to_owned(), "Baz".to_owned())
+    );
+}
+
+#[test]
+fn union_members() {
+    let schema = "
+        type Foo @entity { id: ID! }
+        type Bar @entity { id: ID! }
+        union FooOrBar = Foo | Bar
+    ";
+    let schema = Schema::parse(schema, SubgraphDeploymentId::new("dummy").unwrap()).unwrap();
+    assert_eq!(
+        schema.types_for_interface()["FooOrBar"]
+            .iter()
+            .map(|object_type| object_type.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["Foo", "Bar"]
+    );
+    assert_eq!(schema.unions_for_type(&"Foo".to_owned()).unwrap().len(), 1);
+}
+
+#[test]
+fn invalid_cost_directive() {
+    let id = SubgraphDeploymentId::new("dummy").unwrap();
+    let valid = "type Foo @entity { id: ID!, bars: [Foo!]! @cost(value: 10) }";
+    assert!(Schema::parse(valid, id.clone()).is_ok());
+
+    for invalid in vec![
+        "type Foo @entity { id: ID!, bar: Int @cost(value: -1) }",
+        "type Foo @entity { id: ID!, bar: Int @cost(value: \"10\") }",
+        "type Foo @entity { id: ID!, bar: Int @cost(cost: 10) }",
+    ] {
+        assert_eq!(
+            Schema::parse(invalid, id.clone())
+                .unwrap_err()
+                .downcast::<SchemaValidationError>()
+                .unwrap(),
+            SchemaValidationError::InvalidCostDirective("Foo".to_owned(), "bar".to_owned())
+        );
+    }
+}
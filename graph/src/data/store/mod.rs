@@ -97,6 +97,8 @@ pub const ID: &str = "ID";
 pub const BYTES_SCALAR: &str = "Bytes";
 pub const BIG_INT_SCALAR: &str = "BigInt";
 pub const BIG_DECIMAL_SCALAR: &str = "BigDecimal";
+pub const INT8_SCALAR: &str = "Int8";
+pub const TIMESTAMP_SCALAR: &str = "Timestamp";
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum ValueType {
@@ -106,8 +108,10 @@ pub enum ValueType {
     BigDecimal,
     ID,
     Int,
+    Int8,
     String,
     List,
+    Timestamp,
 }
 
 impl FromStr for ValueType {
@@ -121,8 +125,10 @@ impl FromStr for ValueType {
             "BigDecimal" => Ok(ValueType::BigDecimal),
             "ID" => Ok(ValueType::ID),
             "Int" => Ok(ValueType::Int),
+            "Int8" => Ok(ValueType::Int8),
             "String" => Ok(ValueType::String),
             "List" => Ok(ValueType::List),
+            "Timestamp" => Ok(ValueType::Timestamp),
             s => Err(format_err!("Type not available in this context: {}", s)),
         }
     }
@@ -140,6 +146,15 @@ pub enum Value {
     Null,
     Bytes(scalar::Bytes),
     BigInt(scalar::BigInt),
+    Int8(i64),
+    /// Microseconds since the Unix epoch.
+    Timestamp(i64),
+}
+
+/// Parses the string representation of an `Int8` or `Timestamp` value.
+fn parse_i64(type_name: &str, s: &str) -> Result<i64, QueryExecutionError> {
+    i64::from_str(s)
+        .map_err(|e| QueryExecutionError::ValueParseError(type_name.to_owned(), e.to_string()))
 }
 
 impl Value {
@@ -174,9 +189,21 @@ impl Value {
                     BYTES_SCALAR => Value::Bytes(scalar::Bytes::from_str(s)?),
                     BIG_INT_SCALAR => Value::BigInt(scalar::BigInt::from_str(s)?),
                     BIG_DECIMAL_SCALAR => Value::BigDecimal(scalar::BigDecimal::from_str(s)?),
+                    INT8_SCALAR => Value::Int8(parse_i64(INT8_SCALAR, s)?),
+                    TIMESTAMP_SCALAR => Value::Timestamp(parse_i64(TIMESTAMP_SCALAR, s)?),
                     _ => Value::String(s.clone()),
                 }
             }
+            (query::Value::Int(i), NamedType(n)) if n == INT8_SCALAR || n == TIMESTAMP_SCALAR => {
+                let i = i
+                    .as_i64()
+                    .ok_or_else(|| QueryExecutionError::NamedTypeError(n.to_owned()))?;
+                if n == INT8_SCALAR {
+                    Value::Int8(i)
+                } else {
+                    Value::Timestamp(i)
+                }
+            }
             (query::Value::Int(i), _) => Value::Int(
                 i.to_owned()
                     .as_i64()
@@ -210,6 +237,22 @@ impl Value {
         }
     }
 
+    pub fn as_int8(self) -> Option<i64> {
+        if let Value::Int8(i) = self {
+            Some(i)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_timestamp(self) -> Option<i64> {
+        if let Value::Timestamp(t) = self {
+            Some(t)
+        } else {
+            None
+        }
+    }
+
     pub fn as_big_decimal(self) -> Option<scalar::BigDecimal> {
         if let Value::BigDecimal(d) = self {
             Some(d)
@@ -268,6 +311,8 @@ impl fmt::Display for Value {
                     .collect(),
                 Value::Bytes(ref bytes) => bytes.to_string(),
                 Value::BigInt(ref number) => number.to_string(),
+                Value::Int8(i) => i.to_string(),
+                Value::Timestamp(t) => t.to_string(),
             }
         )
     }
@@ -286,6 +331,11 @@ impl From<Value> for query::Value {
             }
             Value::Bytes(bytes) => query::Value::String(bytes.to_string()),
             Value::BigInt(number) => query::Value::String(number.to_string()),
+            // 64-bit numbers do not fit into GraphQL's `Int` and lose
+            // precision in JavaScript clients, which is why they are
+            // returned as strings, just like `BigInt`
+            Value::Int8(i) => query::Value::String(i.to_string()),
+            Value::Timestamp(t) => query::Value::String(t.to_string()),
         }
     }
 }
@@ -464,3 +514,25 @@ fn value_bigint() {
     );
     assert_eq!(query::Value::from(from_query), graphql_value);
 }
+
+#[test]
+fn value_int8_and_timestamp() {
+    for (type_name, value) in vec![
+        (INT8_SCALAR, Value::Int8(i64::min_value())),
+        (TIMESTAMP_SCALAR, Value::Timestamp(1_564_000_000_000_000)),
+    ] {
+        let ty = query::Type::NamedType(type_name.to_owned());
+        let graphql_value = query::Value::from(value.clone());
+        let from_query = Value::from_query_value(&graphql_value, &ty).unwrap();
+        assert_eq!(from_query, value);
+
+        // Int literals are accepted as well
+        let graphql_value = query::Value::Int(query::Number::from(42));
+        let from_query = Value::from_query_value(&graphql_value, &ty).unwrap();
+        assert_eq!(from_query.to_string(), "42");
+    }
+
+    let ty = query::Type::NamedType(INT8_SCALAR.to_owned());
+    let graphql_value = query::Value::String("9223372036854775808".to_owned());
+    assert!(Value::from_query_value(&graphql_value, &ty).is_err());
+}
@@ -15,6 +15,7 @@ use std::sync::Arc;
 use tokio::prelude::*;
 use web3::types::{Address, H256};
 
+use crate::components::ethereum::NodeCapabilities;
 use crate::components::link_resolver::LinkResolver;
 use crate::components::store::StoreError;
 use crate::data::query::QueryExecutionError;
@@ -234,6 +235,11 @@ pub enum SubgraphRegistrarError {
     NameNotFound(String),
     #[fail(display = "Ethereum network not supported by registrar: {}", _0)]
     NetworkNotSupported(String),
+    #[fail(
+        display = "no Ethereum provider for network `{}` supports the capabilities required by the subgraph: {}",
+        _0, _1
+    )]
+    NodeCapabilitiesNotSupported(String, NodeCapabilities),
     #[fail(display = "deployment not found: {}", _0)]
     DeploymentNotFound(String),
     #[fail(display = "deployment assignment unchanged: {}", _0)]
@@ -318,7 +324,9 @@ pub enum SubgraphAssignmentProviderEvent {
 
 #[derive(Fail, Debug)]
 pub enum SubgraphManifestValidationError {
-    #[fail(display = "subgraph source address is required")]
+    #[fail(
+        display = "subgraph source address is required for data sources with call or block handlers"
+    )]
     SourceAddressRequired,
     #[fail(display = "subgraph cannot index data from different Ethereum networks")]
     MultipleEthereumNetworks,
@@ -326,6 +334,8 @@ pub enum SubgraphManifestValidationError {
     EthereumNetworkRequired,
     #[fail(display = "subgraph data source has too many similar block handlers")]
     DataSourceBlockHandlerLimitExceeded,
+    #[fail(display = "transaction receipts require mapping API version 0.0.4 or later")]
+    ReceiptsRequireApiVersion,
 }
 
 #[derive(Fail, Debug)]
@@ -481,6 +491,9 @@ impl From<EthereumBlockHandlerEntity> for MappingBlockHandler {
 pub struct MappingCallHandler {
     pub function: String,
     pub handler: String,
+    /// Whether the receipt of the transaction is passed to the handler.
+    #[serde(default)]
+    pub receipt: bool,
 }
 
 impl From<EthereumCallHandlerEntity> for MappingCallHandler {
@@ -488,6 +501,7 @@ impl From<EthereumCallHandlerEntity> for MappingCallHandler {
         Self {
             function: entity.function,
             handler: entity.handler,
+            receipt: entity.receipt,
         }
     }
 }
@@ -496,7 +510,13 @@ impl From<EthereumCallHandlerEntity> for MappingCallHandler {
 pub struct MappingEventHandler {
     pub event: String,
     pub topic0: Option<H256>,
+    pub topic1: Option<Vec<H256>>,
+    pub topic2: Option<Vec<H256>>,
+    pub topic3: Option<Vec<H256>>,
     pub handler: String,
+    /// Whether the receipt of the transaction is passed to the handler.
+    #[serde(default)]
+    pub receipt: bool,
 }
 
 impl MappingEventHandler {
@@ -504,6 +524,41 @@ impl MappingEventHandler {
         self.topic0
             .unwrap_or_else(|| string_to_h256(&self.event.replace("indexed ", "")))
     }
+
+    /// The values `topic1` to `topic3` of a log may take for the log to be
+    /// passed to this handler; `None` allows any value.
+    pub fn indexed_topics(&self) -> IndexedTopics {
+        [
+            self.topic1.clone(),
+            self.topic2.clone(),
+            self.topic3.clone(),
+        ]
+    }
+
+    /// Checks whether the `topics` of a log satisfy the constraints this
+    /// handler declares on `topic1` to `topic3`. The signature (`topic0`) is
+    /// not checked.
+    pub fn matches_indexed_topics(&self, topics: &[H256]) -> bool {
+        indexed_topics_match(&self.indexed_topics(), topics)
+    }
+}
+
+/// Constraints on the indexed parameters `topic1` to `topic3` of an event.
+/// Each entry lists the values the topic may take; `None` allows any value.
+pub type IndexedTopics = [Option<Vec<H256>>; 3];
+
+/// Checks whether the `topics` of a log, starting with `topic0`, satisfy
+/// `indexed_topics`. A log that lacks a constrained topic never matches.
+pub fn indexed_topics_match(indexed_topics: &IndexedTopics, topics: &[H256]) -> bool {
+    indexed_topics
+        .iter()
+        .enumerate()
+        .all(|(i, allowed)| match allowed {
+            None => true,
+            Some(allowed) => topics
+                .get(i + 1)
+                .map_or(false, |topic| allowed.contains(topic)),
+        })
 }
 
 impl From<EthereumContractEventHandlerEntity> for MappingEventHandler {
@@ -511,7 +566,11 @@ impl From<EthereumContractEventHandlerEntity> for MappingEventHandler {
         Self {
             event: entity.event,
             topic0: entity.topic0,
+            topic1: entity.topic1,
+            topic2: entity.topic2,
+            topic3: entity.topic3,
             handler: entity.handler,
+            receipt: entity.receipt,
         }
     }
 }
@@ -590,6 +649,42 @@ impl UnresolvedMapping {
     }
 }
 
+impl Mapping {
+    /// Whether the mapping calls contracts, which requires the state of
+    /// historical blocks and therefore an archive node.
+    pub fn requires_archive(&self) -> bool {
+        self.runtime.import_section().map_or(false, |imports| {
+            imports
+                .entries()
+                .iter()
+                .any(|entry| entry.field() == "ethereum.call")
+        })
+    }
+
+    /// Whether the mapping needs Ethereum traces, which is the case for call
+    /// handlers and block handlers with a `call` filter.
+    pub fn requires_traces(&self) -> bool {
+        let has_call_handler = self
+            .call_handlers
+            .as_ref()
+            .map_or(false, |handlers| !handlers.is_empty());
+        let has_call_filter = self.block_handlers.as_ref().map_or(false, |handlers| {
+            handlers.iter().any(|handler| match handler.filter {
+                Some(BlockHandlerFilter::Call) => true,
+                _ => false,
+            })
+        });
+        has_call_handler || has_call_filter
+    }
+
+    pub fn required_capabilities(&self) -> NodeCapabilities {
+        NodeCapabilities {
+            archive: self.requires_archive(),
+            traces: self.requires_traces(),
+        }
+    }
+}
+
 impl From<EthereumContractMappingEntity> for UnresolvedMapping {
     fn from(entity: EthereumContractMappingEntity) -> Self {
         Self {
@@ -664,6 +759,15 @@ impl UnresolvedDataSource {
 }
 
 impl DataSource {
+    /// The Ethereum node capabilities needed by this data source and the
+    /// data sources that may be created from its templates.
+    pub fn required_capabilities(&self) -> NodeCapabilities {
+        self.templates.iter().flatten().fold(
+            self.mapping.required_capabilities(),
+            |capabilities, template| capabilities.union(template.mapping.required_capabilities()),
+        )
+    }
+
     pub fn try_from_template(
         template: &DataSourceTemplate,
         params: &Vec<String>,
@@ -862,6 +966,15 @@ impl SubgraphManifest {
             })
     }
 
+    /// The Ethereum node capabilities needed to index this subgraph.
+    pub fn required_ethereum_capabilities(&self) -> NodeCapabilities {
+        self.data_sources
+            .iter()
+            .fold(NodeCapabilities::default(), |capabilities, data_source| {
+                capabilities.union(data_source.required_capabilities())
+            })
+    }
+
     pub fn network_name(&self) -> Result<String, SubgraphManifestValidationError> {
         let mut ethereum_networks: Vec<Option<String>> = self
             .data_sources
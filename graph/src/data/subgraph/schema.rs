@@ -920,6 +920,7 @@ impl TryFromValue for EthereumBlockHandlerFilterEntity {
 pub struct EthereumCallHandlerEntity {
     pub function: String,
     pub handler: String,
+    pub receipt: bool,
 }
 
 impl TypedEntity for EthereumCallHandlerEntity {
@@ -933,6 +934,7 @@ impl EthereumCallHandlerEntity {
         entity.set("id", id);
         entity.set("function", self.function);
         entity.set("handler", self.handler);
+        entity.set("receipt", self.receipt);
         vec![set_entity_operation(Self::TYPENAME, id, entity)]
     }
 }
@@ -942,6 +944,7 @@ impl From<super::MappingCallHandler> for EthereumCallHandlerEntity {
         Self {
             function: call_handler.function,
             handler: call_handler.handler,
+            receipt: call_handler.receipt,
         }
     }
 }
@@ -959,6 +962,7 @@ impl TryFromValue for EthereumCallHandlerEntity {
         Ok(Self {
             function: map.get_required("function")?,
             handler: map.get_required("handler")?,
+            receipt: map.get_optional("receipt")?.unwrap_or(false),
         })
     }
 }
@@ -967,7 +971,11 @@ impl TryFromValue for EthereumCallHandlerEntity {
 pub struct EthereumContractEventHandlerEntity {
     pub event: String,
     pub topic0: Option<H256>,
+    pub topic1: Option<Vec<H256>>,
+    pub topic2: Option<Vec<H256>>,
+    pub topic3: Option<Vec<H256>>,
     pub handler: String,
+    pub receipt: bool,
 }
 
 impl TypedEntity for EthereumContractEventHandlerEntity {
@@ -981,7 +989,24 @@ impl EthereumContractEventHandlerEntity {
         entity.set("id", id);
         entity.set("event", self.event);
         entity.set("topic0", self.topic0.map_or(Value::Null, Value::from));
+        for (name, topics) in vec![
+            ("topic1", self.topic1),
+            ("topic2", self.topic2),
+            ("topic3", self.topic3),
+        ] {
+            entity.set(
+                name,
+                topics.map_or(Value::Null, |topics| {
+                    topics
+                        .into_iter()
+                        .map(Value::from)
+                        .collect::<Vec<Value>>()
+                        .into()
+                }),
+            );
+        }
         entity.set("handler", self.handler);
+        entity.set("receipt", self.receipt);
         vec![set_entity_operation(Self::TYPENAME, id, entity)]
     }
 }
@@ -991,7 +1016,11 @@ impl From<super::MappingEventHandler> for EthereumContractEventHandlerEntity {
         Self {
             event: event_handler.event,
             topic0: event_handler.topic0,
+            topic1: event_handler.topic1,
+            topic2: event_handler.topic2,
+            topic3: event_handler.topic3,
             handler: event_handler.handler,
+            receipt: event_handler.receipt,
         }
     }
 }
@@ -1009,7 +1038,11 @@ impl TryFromValue for EthereumContractEventHandlerEntity {
         Ok(Self {
             event: map.get_required("event")?,
             topic0: map.get_optional("topic0")?,
+            topic1: map.get_optional("topic1")?,
+            topic2: map.get_optional("topic2")?,
+            topic3: map.get_optional("topic3")?,
             handler: map.get_required("handler")?,
+            receipt: map.get_optional("receipt")?.unwrap_or(false),
         })
     }
 }
@@ -1223,8 +1256,11 @@ fn is_entity(type_name: &str, definitions: &[Definition]) -> bool {
                 // an entity type
                 Interface(interface_type) => interface_type.name == type_name,
 
-                // Everything else (unions, scalars, enums) are not
-                // considered entity types
+                // Unions may only contain entity types
+                Union(union_type) => union_type.name == type_name,
+
+                // Everything else (scalars, enums) are not considered
+                // entity types
                 _ => false,
             }
         } else {
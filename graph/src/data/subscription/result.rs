@@ -1,9 +1,11 @@
 use futures::prelude::*;
+use std::sync::Arc;
 
 use crate::prelude::QueryResult;
 
-/// A stream of query results for a subscription.
-pub type QueryResultStream = Box<Stream<Item = QueryResult, Error = ()> + Send>;
+/// A stream of query results for a subscription. Results are shared between
+/// all subscribers of identical subscriptions, hence the `Arc`.
+pub type QueryResultStream = Box<Stream<Item = Arc<QueryResult>, Error = ()> + Send>;
 
 /// The result of running a subscription, if successful.
 pub type SubscriptionResult = QueryResultStream;
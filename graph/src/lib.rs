@@ -67,25 +67,30 @@ pub mod prelude {
     pub use tokio::prelude::*;
 
     pub use crate::components::ethereum::{
-        BlockStream, BlockStreamBuilder, ChainHeadUpdate, ChainHeadUpdateListener,
-        ChainHeadUpdateStream, EthereumAdapter, EthereumAdapterError, EthereumBlock,
-        EthereumBlockData, EthereumBlockFilter, EthereumBlockPointer, EthereumBlockTriggerType,
-        EthereumBlockWithCalls, EthereumBlockWithTriggers, EthereumCall, EthereumCallData,
-        EthereumCallFilter, EthereumContractCall, EthereumContractCallError, EthereumEventData,
-        EthereumLogFilter, EthereumNetworkIdentifier, EthereumTransactionData, EthereumTrigger,
+        AdaptiveRangeSize, BlockStream, BlockStreamBuilder, ChainHeadUpdate,
+        ChainHeadUpdateListener, ChainHeadUpdateStream, EthereumAdapter, EthereumAdapterError,
+        EthereumBlock, EthereumBlockData, EthereumBlockFilter, EthereumBlockPointer,
+        EthereumBlockTriggerType, EthereumBlockWithCalls, EthereumBlockWithTriggers, EthereumCall,
+        EthereumCallData, EthereumCallFilter, EthereumContractCall, EthereumContractCallError,
+        EthereumEventData, EthereumLogFilter, EthereumNetworkIdentifier, EthereumNetworks,
+        EthereumTransactionData, EthereumTransactionReceiptData, EthereumTrigger, NodeCapabilities,
+        ScanRanges,
     };
     pub use crate::components::graphql::{
-        GraphQlRunner, QueryResultFuture, SubscriptionResultFuture,
+        GraphQlRunner, QueryResultAtBlockFuture, QueryResultFuture, SubscriptionResultFuture,
     };
     pub use crate::components::link_resolver::{JsonStreamValue, JsonValueStream, LinkResolver};
-    pub use crate::components::server::admin::JsonRpcServer;
+    pub use crate::components::server::admin::{
+        AdminAccessControl, AdminAuthError, AdminPermission, JsonRpcServer,
+    };
     pub use crate::components::server::query::GraphQLServer;
     pub use crate::components::server::subscription::SubscriptionServer;
     pub use crate::components::store::{
         AttributeIndexDefinition, ChainStore, EntityChange, EntityChangeOperation, EntityFilter,
         EntityKey, EntityOperation, EntityOrder, EntityQuery, EntityRange, EventSource,
-        HistoryEvent, Store, StoreError, StoreEvent, StoreEventStream, StoreEventStreamBox,
-        SubgraphDeploymentStore, TransactionAbortError, SUBSCRIPTION_THROTTLE_INTERVAL,
+        HistoryEvent, QueryStore, Store, StoreError, StoreEvent, StoreEventStream,
+        StoreEventStreamBox, SubgraphDeploymentStore, TransactionAbortError,
+        SUBSCRIPTION_THROTTLE_INTERVAL,
     };
     pub use crate::components::subgraph::{
         BlockState, DataSourceLoader, DataSourceTemplateInfo, RuntimeHost, RuntimeHostBuilder,
@@ -96,7 +101,7 @@ pub mod prelude {
 
     pub use crate::data::graphql::{SerializableValue, TryFromValue, ValueMap};
     pub use crate::data::query::{
-        Query, QueryError, QueryExecutionError, QueryResult, QueryVariables,
+        PathSegment, Query, QueryError, QueryExecutionError, QueryResult, QueryVariables, Trace,
     };
     pub use crate::data::schema::Schema;
     pub use crate::data::store::scalar::{BigDecimal, BigInt, BigIntSign};
@@ -106,11 +111,12 @@ pub mod prelude {
     };
     pub use crate::data::subgraph::schema::{SubgraphDeploymentEntity, TypedEntity};
     pub use crate::data::subgraph::{
-        BlockHandlerFilter, CreateSubgraphResult, DataSource, DataSourceTemplate, Link, MappingABI,
-        MappingBlockHandler, MappingCallHandler, MappingEventHandler,
-        SubgraphAssignmentProviderError, SubgraphAssignmentProviderEvent, SubgraphDeploymentId,
-        SubgraphManifest, SubgraphManifestResolveError, SubgraphManifestValidationError,
-        SubgraphName, SubgraphRegistrarError,
+        indexed_topics_match, BlockHandlerFilter, CreateSubgraphResult, DataSource,
+        DataSourceTemplate, IndexedTopics, Link, MappingABI, MappingBlockHandler,
+        MappingCallHandler, MappingEventHandler, SubgraphAssignmentProviderError,
+        SubgraphAssignmentProviderEvent, SubgraphDeploymentId, SubgraphManifest,
+        SubgraphManifestResolveError, SubgraphManifestValidationError, SubgraphName,
+        SubgraphRegistrarError,
     };
     pub use crate::data::subscription::{
         QueryResultStream, Subscription, SubscriptionError, SubscriptionResult,
@@ -120,7 +126,9 @@ pub mod prelude {
         StreamExtension,
     };
     pub use crate::log::codes::LogCode;
-    pub use crate::log::elastic::{elastic_logger, ElasticDrainConfig, ElasticLoggingConfig};
+    pub use crate::log::elastic::{
+        elastic_logger, ElasticDrainConfig, ElasticFlushHandle, ElasticLoggingConfig,
+    };
     pub use crate::log::factory::{
         ComponentLoggerConfig, ElasticComponentLoggerConfig, LoggerFactory,
     };
@@ -2,7 +2,7 @@ use std::collections::HashMap;
 use std::fmt;
 use std::fmt::Write;
 use std::result::Result;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, Weak};
 use std::time::Duration;
 
 use chrono::prelude::{SecondsFormat, Utc};
@@ -179,11 +179,23 @@ impl ElasticDrain {
         drain
     }
 
+    /// Returns a handle for flushing the logs of this drain without waiting
+    /// for the next flush interval.
+    pub fn flush_handle(&self) -> ElasticFlushHandle {
+        let handle = ElasticFlushHandle::default();
+        handle.drains.lock().unwrap().push(PendingLogs {
+            logs: Arc::downgrade(&self.logs),
+            config: self.config.clone(),
+            error_logger: self.error_logger.clone(),
+        });
+        handle
+    }
+
     fn periodically_flush_logs(&self) {
-        let flush_logger = self.error_logger.clone();
         let interval_error_logger = self.error_logger.clone();
         let logs = self.logs.clone();
         let config = self.config.clone();
+        let error_logger = self.error_logger.clone();
 
         tokio::spawn(
             Interval::new_interval(self.config.flush_interval)
@@ -194,95 +206,162 @@ impl ElasticDrain {
                     );
                 })
                 .for_each(move |_| {
-                    let logs_to_send = {
-                        let mut logs = logs.lock().unwrap();
-                        let logs_to_send = (*logs).clone();
-                        // Clear the logs, so the next batch can be recorded
-                        logs.clear();
-                        logs_to_send
-                    };
-
-                    // Do nothing if there are no logs to flush
-                    if logs_to_send.is_empty() {
-                        return Box::new(future::ok(())) as Box<Future<Item = _, Error = _> + Send>;
+                    // Once the drain is dropped, only this task holds on to
+                    // its logs; send the remaining ones and stop
+                    let drain_dropped = Arc::strong_count(&logs) == 1;
+                    send_logs(&logs, &config, &error_logger).then(move |_| {
+                        if drain_dropped {
+                            Err(())
+                        } else {
+                            Ok(())
+                        }
+                    })
+                }),
+        );
+    }
+}
+
+/// Sends all logs collected in `logs` to Elasticsearch.
+fn send_logs(
+    logs: &Mutex<Vec<ElasticLog>>,
+    config: &ElasticDrainConfig,
+    flush_logger: &Logger,
+) -> Box<Future<Item = (), Error = ()> + Send> {
+    let logs_to_send = {
+        let mut logs = logs.lock().unwrap();
+        let logs_to_send = (*logs).clone();
+        // Clear the logs, so the next batch can be recorded
+        logs.clear();
+        logs_to_send
+    };
+
+    // Do nothing if there are no logs to flush
+    if logs_to_send.is_empty() {
+        return Box::new(future::ok(()));
+    }
+
+    trace!(
+        flush_logger,
+        "Flushing {} logs to Elasticsearch",
+        logs_to_send.len()
+    );
+
+    // The Elasticsearch batch API takes requests with the following format:
+    // ```ignore
+    // action_and_meta_data\n
+    // optional_source\n
+    // action_and_meta_data\n
+    // optional_source\n
+    // ```
+    // For more details, see:
+    // https://www.elastic.co/guide/en/elasticsearch/reference/current/docs-bulk.html
+    //
+    // We're assembly the request body in the same way below:
+    let batch_body = logs_to_send.iter().fold(String::from(""), |mut out, log| {
+        // Try to serialize the log itself to a JSON string
+        match serde_json::to_string(log) {
+            Ok(log_line) => {
+                // Serialize the action line to a string
+                let action_line = json!({
+                    "index": {
+                        "_index": config.index,
+                        "_type": config.document_type,
+                        "_id": log.id,
                     }
+                })
+                .to_string();
+
+                // Combine the two lines with newlines, make sure there is
+                // a newline at the end as well
+                out.push_str(format!("{}\n{}\n", action_line, log_line).as_str());
+            }
+            Err(e) => {
+                error!(
+                    flush_logger,
+                    "Failed to serialize Elasticsearch log to JSON: {}", e
+                );
+            }
+        };
 
-                    trace!(
-                        flush_logger,
-                        "Flushing {} logs to Elasticsearch",
-                        logs_to_send.len()
-                    );
+        out
+    });
+
+    // Build the batch API URL
+    let mut batch_url =
+        reqwest::Url::parse(config.general.endpoint.as_str()).expect("invalid Elasticsearch URL");
+    batch_url.set_path("_bulk");
+
+    // Send batch of logs to Elasticsearch
+    let client = Client::new();
+    let logger_for_err = flush_logger.clone();
+    Box::new(
+        client
+            .post(batch_url)
+            .header("Content-Type", "application/json")
+            .basic_auth(
+                config.general.username.clone().unwrap_or("".into()),
+                config.general.password.clone(),
+            )
+            .body(batch_body)
+            .send()
+            .and_then(|response| response.error_for_status())
+            .map(|_| ())
+            .map_err(move |e| {
+                // Log if there was a problem sending the logs
+                error!(
+                    logger_for_err,
+                    "Failed to send logs to Elasticsearch: {}", e
+                );
+            }),
+    )
+}
 
-                    // The Elasticsearch batch API takes requests with the following format:
-                    // ```ignore
-                    // action_and_meta_data\n
-                    // optional_source\n
-                    // action_and_meta_data\n
-                    // optional_source\n
-                    // ```
-                    // For more details, see:
-                    // https://www.elastic.co/guide/en/elasticsearch/reference/current/docs-bulk.html
-                    //
-                    // We're assembly the request body in the same way below:
-                    let batch_body = logs_to_send.iter().fold(String::from(""), |mut out, log| {
-                        // Try to serialize the log itself to a JSON string
-                        match serde_json::to_string(log) {
-                            Ok(log_line) => {
-                                // Serialize the action line to a string
-                                let action_line = json!({
-                                    "index": {
-                                        "_index": config.index,
-                                        "_type": config.document_type,
-                                        "_id": log.id,
-                                    }
-                                })
-                                .to_string();
-
-                                // Combine the two lines with newlines, make sure there is
-                                // a newline at the end as well
-                                out.push_str(format!("{}\n{}\n", action_line, log_line).as_str());
-                            }
-                            Err(e) => {
-                                error!(
-                                    flush_logger,
-                                    "Failed to serialize Elasticsearch log to JSON: {}", e
-                                );
-                            }
-                        };
-
-                        out
-                    });
-
-                    // Build the batch API URL
-                    let mut batch_url = reqwest::Url::parse(config.general.endpoint.as_str())
-                        .expect("invalid Elasticsearch URL");
-                    batch_url.set_path("_bulk");
-
-                    // Send batch of logs to Elasticsearch
-                    let client = Client::new();
-                    let logger_for_err = flush_logger.clone();
-                    Box::new(
-                        client
-                            .post(batch_url)
-                            .header("Content-Type", "application/json")
-                            .basic_auth(
-                                config.general.username.clone().unwrap_or("".into()),
-                                config.general.password.clone(),
-                            )
-                            .body(batch_body)
-                            .send()
-                            .and_then(|response| response.error_for_status())
-                            .map(|_| ())
-                            .map_err(move |e| {
-                                // Log if there was a problem sending the logs
-                                error!(
-                                    logger_for_err,
-                                    "Failed to send logs to Elasticsearch: {}", e
-                                );
-                            }),
-                    )
-                }),
-        );
+/// The logs of an Elasticsearch drain that have not been sent yet.
+#[derive(Clone)]
+struct PendingLogs {
+    logs: Weak<Mutex<Vec<ElasticLog>>>,
+    config: ElasticDrainConfig,
+    error_logger: Logger,
+}
+
+/// A handle for sending the logs of one or more Elasticsearch drains that
+/// have not been sent yet, instead of waiting for the next flush interval.
+/// Used when shutting down. Clones of a handle share the drains they flush.
+#[derive(Clone, Default)]
+pub struct ElasticFlushHandle {
+    drains: Arc<Mutex<Vec<PendingLogs>>>,
+}
+
+impl ElasticFlushHandle {
+    /// Makes this handle also flush the drains of `other`.
+    pub fn include(&self, other: &ElasticFlushHandle) {
+        let other_drains = other.drains.lock().unwrap().clone();
+        let mut drains = self.drains.lock().unwrap();
+
+        // Forget about drains that are gone
+        drains.retain(|drain| drain.logs.upgrade().is_some());
+        drains.extend(other_drains);
+    }
+
+    /// Sends the logs of all drains of this handle that have not been sent
+    /// yet.
+    pub fn flush(&self) -> impl Future<Item = (), Error = ()> {
+        let mut drains = self.drains.lock().unwrap();
+
+        // Forget about drains that are gone
+        drains.retain(|drain| drain.logs.upgrade().is_some());
+
+        future::join_all(
+            drains
+                .iter()
+                .filter_map(|drain| {
+                    drain.logs.upgrade().map(|logs| {
+                        send_logs(&logs, &drain.config, &drain.error_logger).then(|_| Ok(()))
+                    })
+                })
+                .collect::<Vec<_>>(),
+        )
+        .map(|_: Vec<()>| ())
     }
 }
 
@@ -359,15 +438,115 @@ impl Drain for ElasticDrain {
     }
 }
 
-/// Creates a new asynchronous Elasticsearch logger.
+/// Creates a new asynchronous Elasticsearch logger and a handle for
+/// flushing its logs.
 ///
 /// Uses `error_logger` to print any Elasticsearch logging errors,
 /// so they don't go unnoticed.
-pub fn elastic_logger(config: ElasticDrainConfig, error_logger: Logger) -> Logger {
-    let elastic_drain = ElasticDrain::new(config, error_logger).fuse();
-    let async_drain = slog_async::Async::new(elastic_drain)
+pub fn elastic_logger(
+    config: ElasticDrainConfig,
+    error_logger: Logger,
+) -> (Logger, ElasticFlushHandle) {
+    let elastic_drain = ElasticDrain::new(config, error_logger);
+    let flush_handle = elastic_drain.flush_handle();
+    let async_drain = slog_async::Async::new(elastic_drain.fuse())
         .chan_size(1000)
         .build()
         .fuse();
-    Logger::root(async_drain, o!())
+    (Logger::root(async_drain, o!()), flush_handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Instant;
+    use tokio::runtime::Runtime;
+
+    fn config(id: &str, flush_interval: Duration) -> ElasticDrainConfig {
+        ElasticDrainConfig {
+            general: ElasticLoggingConfig {
+                // Nothing listens here, so sending logs fails right away
+                endpoint: "http://127.0.0.1:1".to_owned(),
+                username: None,
+                password: None,
+            },
+            index: "test-logs".to_owned(),
+            document_type: "log".to_owned(),
+            custom_id_key: "testId".to_owned(),
+            custom_id_value: id.to_owned(),
+            flush_interval,
+        }
+    }
+
+    fn error_logger() -> Logger {
+        Logger::root(Discard, o!())
+    }
+
+    #[test]
+    fn flush_handles_send_pending_logs() {
+        let mut runtime = Runtime::new().unwrap();
+
+        // Use an interval long enough that only the flush handle sends the logs
+        let (logger, logs, flush_handle) = runtime
+            .block_on(future::lazy(|| {
+                let drain =
+                    ElasticDrain::new(config("flushed", Duration::from_secs(3600)), error_logger());
+                let logs = drain.logs.clone();
+                let flush_handle = ElasticFlushHandle::default();
+                flush_handle.include(&drain.flush_handle());
+                Ok::<_, ()>((Logger::root(drain.fuse(), o!()), logs, flush_handle))
+            }))
+            .unwrap();
+
+        info!(logger, "Shutting down");
+        assert_eq!(logs.lock().unwrap().len(), 1);
+
+        runtime.block_on(flush_handle.flush()).unwrap();
+        assert!(logs.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn dropped_drains_are_forgotten() {
+        let mut runtime = Runtime::new().unwrap();
+        let flush_handle = ElasticFlushHandle::default();
+
+        let handle = flush_handle.clone();
+        let logs = runtime
+            .block_on(future::lazy(move || {
+                let drain =
+                    ElasticDrain::new(config("dropped", Duration::from_millis(10)), error_logger());
+                handle.include(&drain.flush_handle());
+                Ok::<_, ()>(Arc::downgrade(&drain.logs))
+            }))
+            .unwrap();
+
+        // The flush task stops and releases the logs once it notices that
+        // the drain is gone
+        let start = Instant::now();
+        while logs.upgrade().is_some() {
+            assert!(
+                start.elapsed() < Duration::from_secs(10),
+                "flush task did not stop after the drain was dropped"
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        // Including another drain removes the dropped one from the handle
+        let handle = flush_handle.clone();
+        let _drain = runtime
+            .block_on(future::lazy(move || {
+                let drain =
+                    ElasticDrain::new(config("other", Duration::from_secs(3600)), error_logger());
+                handle.include(&drain.flush_handle());
+                Ok::<_, ()>(drain)
+            }))
+            .unwrap();
+        assert!(!flush_handle
+            .drains
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|drain| drain.config.custom_id_value == "dropped"));
+    }
 }
@@ -20,6 +20,7 @@ pub struct ComponentLoggerConfig {
 pub struct LoggerFactory {
     parent: Logger,
     elastic_config: Option<ElasticLoggingConfig>,
+    elastic_flush_handle: ElasticFlushHandle,
 }
 
 impl LoggerFactory {
@@ -28,6 +29,7 @@ impl LoggerFactory {
         Self {
             parent: logger,
             elastic_config,
+            elastic_flush_handle: ElasticFlushHandle::default(),
         }
     }
 
@@ -36,9 +38,23 @@ impl LoggerFactory {
         Self {
             parent,
             elastic_config: self.elastic_config.clone(),
+            elastic_flush_handle: self.elastic_flush_handle.clone(),
         }
     }
 
+    /// Returns a handle for flushing the Elasticsearch logs of all loggers
+    /// created by this factory and the factories derived from it.
+    pub fn elastic_flush_handle(&self) -> ElasticFlushHandle {
+        self.elastic_flush_handle.clone()
+    }
+
+    /// Creates an Elasticsearch logger that this factory can flush.
+    fn elastic_logger(&self, config: ElasticDrainConfig, error_logger: Logger) -> Logger {
+        let (logger, flush_handle) = elastic_logger(config, error_logger);
+        self.elastic_flush_handle.include(&flush_handle);
+        logger
+    }
+
     /// Creates a component-specific logger with optional Elasticsearch support.
     pub fn component_logger(
         &self,
@@ -57,7 +73,7 @@ impl LoggerFactory {
                     .map(|elastic_config| {
                         split_logger(
                             term_logger.clone(),
-                            elastic_logger(
+                            self.elastic_logger(
                                 ElasticDrainConfig {
                                     general: elastic_config,
                                     index: config.index,
@@ -86,7 +102,7 @@ impl LoggerFactory {
             .map(|elastic_config| {
                 split_logger(
                     term_logger.clone(),
-                    elastic_logger(
+                    self.elastic_logger(
                         ElasticDrainConfig {
                             general: elastic_config,
                             index: String::from("subgraph-logs"),
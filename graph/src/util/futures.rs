@@ -109,6 +109,15 @@ where
         self
     }
 
+    /// Set a limit on how many retry attempts to make if `limit` is `Some`,
+    /// or allow unlimited retry attempts otherwise.
+    pub fn limit_opt(self, limit: Option<usize>) -> Self {
+        match limit {
+            Some(limit) => self.limit(limit),
+            None => self.no_limit(),
+        }
+    }
+
     /// Set how long (in seconds) to wait for an attempt to complete before giving up on that
     /// attempt.
     pub fn timeout_secs(self, timeout_secs: u64) -> RetryConfigWithTimeout<I, E> {
@@ -4,6 +4,7 @@ use indexmap::IndexMap;
 use std::cmp;
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Instant;
 
 use graph::prelude::*;
@@ -11,6 +12,7 @@ use graph::prelude::*;
 use crate::introspection::INTROSPECTION_DOCUMENT;
 use crate::prelude::*;
 use crate::query::ast as qast;
+use crate::schema::api::{BLOCK_FIELD_TYPE, META_FIELD_TYPE};
 use crate::schema::ast as sast;
 use crate::values::coercion;
 
@@ -40,6 +42,20 @@ where
 
     /// Time at which the query times out.
     pub deadline: Option<Instant>,
+
+    /// Maximum number of entities the query may return.
+    pub max_entities: Option<usize>,
+
+    /// Number of entities resolved so far, shared by all contexts derived
+    /// from the same query.
+    pub entity_count: Arc<AtomicUsize>,
+
+    /// Records how long resolving each field takes, if the query is traced.
+    pub trace: Option<Arc<Trace>>,
+
+    /// The path of the current field in the response. Only maintained if
+    /// the query is traced.
+    pub path: Vec<PathSegment>,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -57,6 +73,17 @@ where
     pub fn for_field(&self, field: &'a q::Field) -> Self {
         let mut ctx = self.clone();
         ctx.fields.push(field);
+        if ctx.trace.is_some() {
+            ctx.path
+                .push(PathSegment::Key(qast::get_response_key(field).to_owned()));
+        }
+        ctx
+    }
+
+    /// Creates a derived context for an item of a list value.
+    fn for_index(&self, index: usize) -> Self {
+        let mut ctx = self.clone();
+        ctx.path.push(PathSegment::Index(index));
         ctx
     }
 
@@ -73,13 +100,38 @@ where
             fields: vec![],
             variable_values: self.variable_values.clone(),
             deadline: self.deadline,
+            // Introspection results are not entities and don't count
+            // towards the limit
+            max_entities: None,
+            entity_count: Arc::new(AtomicUsize::new(0)),
+            trace: self.trace.clone(),
+            path: self.path.clone(),
+        }
+    }
+
+    /// Adds the entities in a resolved object or list of objects to the
+    /// number of entities returned by the query, and fails if that exceeds
+    /// the limit.
+    fn count_entities(&self, value: q::Value) -> Result<q::Value, QueryExecutionError> {
+        let count = match &value {
+            q::Value::List(values) => values.len(),
+            q::Value::Null => 0,
+            _ => 1,
+        };
+        let total = self.entity_count.fetch_add(count, Ordering::SeqCst) + count;
+        match self.max_entities {
+            Some(max_entities) if total > max_entities => {
+                Err(QueryExecutionError::TooManyEntities(max_entities))
+            }
+            _ => Ok(value),
         }
     }
 
     /// See https://developer.github.com/v4/guides/resource-limitations/.
     ///
-    /// If the query is invalid, returns `Ok(0)` so that execution proceeds and
-    /// gives a proper error.
+    /// Queries are validated before their complexity is computed. Should the
+    /// query nevertheless be invalid, returns `Ok(0)` so that execution
+    /// proceeds and gives a proper error.
     pub(crate) fn root_query_complexity(
         &self,
         root_type: &s::TypeDefinition,
@@ -151,11 +203,6 @@ where
                 let schema = &self.schema.document;
                 match selection {
                     q::Selection::Field(field) => {
-                        // Empty selection sets are the base case.
-                        if field.selection_set.items.is_empty() {
-                            return Ok(total_complexity);
-                        }
-
                         // Get field type to determine if this is a collection query.
                         let s_field = match ty {
                             s::TypeDefinition::Object(t) => get_field(t, &field.name),
@@ -163,13 +210,25 @@ where
 
                             // `Scalar` and `Enum` cannot have selection sets.
                             // `InputObject` can't appear in a selection.
-                            // `Union` is not yet supported.
+                            // `Union` has no fields with selection sets, its
+                            // members are selected through fragments.
                             s::TypeDefinition::Scalar(_)
                             | s::TypeDefinition::Enum(_)
                             | s::TypeDefinition::InputObject(_)
                             | s::TypeDefinition::Union(_) => None,
+                        };
+
+                        // Fields can be made more expensive with a `@cost`
+                        // directive, which adds to the complexity every time
+                        // the field is queried.
+                        let cost = s_field.as_ref().and_then(sast::get_field_cost).unwrap_or(0);
+
+                        // Empty selection sets are the base case.
+                        if field.selection_set.items.is_empty() {
+                            return total_complexity.checked_add(cost).ok_or(Overflow);
                         }
-                        .ok_or(Invalid)?;
+
+                        let s_field = s_field.ok_or(Invalid)?;
 
                         let field_complexity = self.query_complexity(
                             &get_type_definition_from_type(schema, &s_field.field_type)?,
@@ -180,7 +239,10 @@ where
 
                         // Non-collection queries pass through.
                         if !sast::is_list_or_non_null_list_field(&s_field) {
-                            return Ok(total_complexity + field_complexity);
+                            return total_complexity
+                                .checked_add(field_complexity)
+                                .and_then(|complexity| complexity.checked_add(cost))
+                                .ok_or(Overflow);
                         }
 
                         // For collection queries, check the `first` argument.
@@ -191,9 +253,9 @@ where
                             })
                             .unwrap_or(100);
                         max_entities
-                            .checked_add(
-                                max_entities.checked_mul(field_complexity).ok_or(Overflow)?,
-                            )
+                            .checked_mul(field_complexity)
+                            .and_then(|complexity| complexity.checked_add(max_entities))
+                            .and_then(|complexity| complexity.checked_add(cost))
                             .ok_or(Overflow)
                     }
                     q::Selection::FragmentSpread(fragment) => {
@@ -508,7 +570,8 @@ where
 {
     coerce_argument_values(ctx, object_type, field)
         .and_then(|argument_values| {
-            resolve_field_value(
+            let start = Instant::now();
+            let value = resolve_field_value(
                 ctx,
                 object_type,
                 object_value,
@@ -516,7 +579,17 @@ where
                 field_definition,
                 &field_definition.field_type,
                 &argument_values,
-            )
+            );
+            if let Some(ref trace) = ctx.trace {
+                trace.resolver(
+                    ctx.path.clone(),
+                    &object_type.name,
+                    &field.name,
+                    &field_definition.field_type.to_string(),
+                    start,
+                );
+            }
+            value
         })
         .and_then(|value| complete_value(ctx, field, &field_definition.field_type, fields, value))
 }
@@ -587,14 +660,25 @@ where
     match named_type {
         // Let the resolver decide how the field (with the given object type)
         // is resolved into an entity based on the (potential) parent object
-        s::TypeDefinition::Object(t) => ctx.resolver.resolve_object(
-            object_value,
-            field,
-            field_definition,
-            t.into(),
-            argument_values,
-            ctx.schema.types_for_interface(),
-        ),
+        s::TypeDefinition::Object(t) => ctx
+            .resolver
+            .resolve_object(
+                object_value,
+                field,
+                field_definition,
+                t.into(),
+                argument_values,
+                ctx.schema.types_for_interface(),
+            )
+            .and_then(|value| {
+                // `_meta` and its `block` are not entities and don't count
+                // towards the limit
+                if t.name == META_FIELD_TYPE || t.name == BLOCK_FIELD_TYPE {
+                    Ok(value)
+                } else {
+                    ctx.count_entities(value)
+                }
+            }),
 
         // Let the resolver decide how values in the resolved object value
         // map to values of GraphQL enums
@@ -616,16 +700,29 @@ where
             _ => Ok(q::Value::Null),
         },
 
-        s::TypeDefinition::Interface(i) => ctx.resolver.resolve_object(
-            object_value,
-            field,
-            field_definition,
-            i.into(),
-            argument_values,
-            ctx.schema.types_for_interface(),
-        ),
+        s::TypeDefinition::Interface(i) => ctx
+            .resolver
+            .resolve_object(
+                object_value,
+                field,
+                field_definition,
+                i.into(),
+                argument_values,
+                ctx.schema.types_for_interface(),
+            )
+            .and_then(|value| ctx.count_entities(value)),
 
-        s::TypeDefinition::Union(_) => Err(QueryExecutionError::Unimplemented("unions".to_owned())),
+        s::TypeDefinition::Union(u) => ctx
+            .resolver
+            .resolve_object(
+                object_value,
+                field,
+                field_definition,
+                u.into(),
+                argument_values,
+                ctx.schema.types_for_interface(),
+            )
+            .and_then(|value| ctx.count_entities(value)),
 
         s::TypeDefinition::InputObject(_) => unreachable!("input objects are never resolved"),
     }
@@ -673,6 +770,7 @@ where
                         argument_values,
                         ctx.schema.types_for_interface(),
                     )
+                    .and_then(|value| ctx.count_entities(value))
                     .map_err(|e| vec![e]),
 
                 // Let the resolver decide how values in the resolved object value
@@ -705,11 +803,21 @@ where
                         argument_values,
                         ctx.schema.types_for_interface(),
                     )
+                    .and_then(|value| ctx.count_entities(value))
                     .map_err(|e| vec![e]),
 
-                s::TypeDefinition::Union(_) => Err(vec![QueryExecutionError::Unimplemented(
-                    "unions".to_owned(),
-                )]),
+                s::TypeDefinition::Union(t) => ctx
+                    .resolver
+                    .resolve_objects(
+                        object_value,
+                        &field.name,
+                        field_definition,
+                        t.into(),
+                        argument_values,
+                        ctx.schema.types_for_interface(),
+                    )
+                    .and_then(|value| ctx.count_entities(value))
+                    .map_err(|e| vec![e]),
 
                 s::TypeDefinition::InputObject(_) => {
                     unreachable!("input objects are never resolved")
@@ -760,7 +868,14 @@ where
                 q::Value::List(values) => {
                     let mut errors = Vec::new();
                     let mut out = Vec::with_capacity(values.len());
-                    for value in values.into_iter() {
+                    for (i, value) in values.into_iter().enumerate() {
+                        let item_ctx;
+                        let ctx = if ctx.trace.is_some() {
+                            item_ctx = ctx.for_index(i);
+                            &item_ctx
+                        } else {
+                            ctx
+                        };
                         match complete_value(ctx, field, inner_type, fields.clone(), value) {
                             Ok(value) => out.push(value),
                             Err(errs) => errors.extend(errs),
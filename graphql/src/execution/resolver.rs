@@ -1,4 +1,5 @@
-use graphql_parser::{query as q, schema as s};
+use graphql_parser::{query as q, schema as s, Pos};
+use lazy_static::lazy_static;
 use std::collections::{BTreeMap, HashMap};
 
 use crate::prelude::*;
@@ -9,6 +10,21 @@ use graph::prelude::{QueryExecutionError, StoreEventStreamBox};
 pub enum ObjectOrInterface<'a> {
     Object(&'a s::ObjectType),
     Interface(&'a s::InterfaceType),
+    Union(&'a s::UnionType),
+}
+
+lazy_static! {
+    /// Unions have no fields of their own, but all of their members are
+    /// entities and therefore share an `id` field that can be used for
+    /// filtering and ordering.
+    static ref UNION_FIELDS: Vec<s::Field> = vec![s::Field {
+        position: Pos::default(),
+        description: None,
+        name: "id".to_owned(),
+        arguments: vec![],
+        field_type: s::Type::NonNullType(Box::new(s::Type::NamedType("ID".to_owned()))),
+        directives: vec![],
+    }];
 }
 
 impl<'a> From<&'a s::ObjectType> for ObjectOrInterface<'a> {
@@ -23,11 +39,18 @@ impl<'a> From<&'a s::InterfaceType> for ObjectOrInterface<'a> {
     }
 }
 
+impl<'a> From<&'a s::UnionType> for ObjectOrInterface<'a> {
+    fn from(union: &'a s::UnionType) -> Self {
+        ObjectOrInterface::Union(union)
+    }
+}
+
 impl<'a> ObjectOrInterface<'a> {
     pub fn name(self) -> &'a str {
         match self {
             ObjectOrInterface::Object(object) => &object.name,
             ObjectOrInterface::Interface(interface) => &interface.name,
+            ObjectOrInterface::Union(union) => &union.name,
         }
     }
 
@@ -35,6 +58,7 @@ impl<'a> ObjectOrInterface<'a> {
         match self {
             ObjectOrInterface::Object(object) => &object.directives,
             ObjectOrInterface::Interface(interface) => &interface.directives,
+            ObjectOrInterface::Union(union) => &union.directives,
         }
     }
 
@@ -42,6 +66,7 @@ impl<'a> ObjectOrInterface<'a> {
         match self {
             ObjectOrInterface::Object(object) => &object.fields,
             ObjectOrInterface::Interface(interface) => &interface.fields,
+            ObjectOrInterface::Union(_) => &UNION_FIELDS,
         }
     }
 }
@@ -135,6 +160,13 @@ pub trait Resolver: Clone + Send + Sync {
         }
     }
 
+    /// Returns the resolver to use for a single execution of a query, e.g.
+    /// for one event of a subscription. Resolvers that read from a store use
+    /// this to make all reads of the execution see the same state.
+    fn for_execution(&self) -> Result<Self, QueryExecutionError> {
+        Ok(self.clone())
+    }
+
     // Resolves a change stream for a given field.
     fn resolve_field_stream<'a, 'b>(
         &self,
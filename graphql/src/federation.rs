@@ -0,0 +1,893 @@
+use graphql_parser::{query as q, schema as s};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use graph::prelude::*;
+use graph::serde_json;
+
+use crate::query::ast as qast;
+use crate::query::validate_query;
+
+/// The ID of the federated schema. It does not belong to a deployment, which
+/// also keeps queries against it out of the query cache.
+const FEDERATED_SCHEMA_ID: &str = "federated";
+
+#[derive(Fail, Debug)]
+pub enum FederationError {
+    #[fail(display = "Namespace `{}` is not a valid GraphQL name", _0)]
+    InvalidNamespace(String),
+    #[fail(display = "Namespace `{}` is used more than once", _0)]
+    DuplicateNamespace(String),
+}
+
+/// Part of a query against a federated schema that can be executed on its
+/// own.
+#[derive(Clone, Debug)]
+pub struct FederatedQuery {
+    /// The namespace the query runs against, or `None` for the introspection
+    /// fields on the root of the federated schema.
+    pub namespace: Option<String>,
+    /// The key under which the result of the query is placed in the
+    /// combined result, or `None` if the fields of the result are placed on
+    /// the root of the combined result.
+    pub response_key: Option<String>,
+    /// The query, against the schema of the namespace's deployment or the
+    /// federated schema.
+    pub query: Query,
+}
+
+/// Serves several deployments through one schema. The root query type of
+/// the federated schema has one field per deployment, named after the
+/// namespace of the deployment, whose type is the root query type of the
+/// deployment. All types except scalars are prefixed with `<namespace>_` so
+/// that types of different deployments do not clash.
+#[derive(Clone, Debug)]
+pub struct Federation {
+    /// The federated schema.
+    pub schema: Arc<Schema>,
+
+    /// The API schemas of the deployments, keyed by namespace.
+    schemas: BTreeMap<String, Arc<Schema>>,
+
+    /// The names of the types that are prefixed in the federated schema,
+    /// keyed by namespace.
+    prefixed_types: BTreeMap<String, HashSet<String>>,
+}
+
+impl Federation {
+    /// Creates a federated schema from the API schemas of several
+    /// deployments, each exposed under its namespace.
+    pub fn new(deployments: Vec<(String, Arc<Schema>)>) -> Result<Self, FederationError> {
+        let mut schemas = BTreeMap::new();
+        let mut prefixed_types = BTreeMap::new();
+        let mut scalars = BTreeMap::new();
+        let mut definitions = vec![];
+        let mut root_fields = vec![];
+
+        Self::check_namespaces(deployments.iter().map(|(namespace, _)| namespace.as_str()))?;
+
+        for (namespace, schema) in deployments {
+            let prefixed = schema
+                .document
+                .definitions
+                .iter()
+                .filter_map(|definition| match definition {
+                    s::Definition::TypeDefinition(s::TypeDefinition::Scalar(_)) => None,
+                    s::Definition::TypeDefinition(type_definition) => {
+                        Some(type_name(type_definition).to_owned())
+                    }
+                    _ => None,
+                })
+                .collect::<HashSet<_>>();
+            let renamer = Renamer {
+                namespace: &namespace,
+                prefixed: &prefixed,
+            };
+
+            for definition in schema.document.definitions.iter() {
+                match definition {
+                    s::Definition::TypeDefinition(s::TypeDefinition::Scalar(scalar)) => {
+                        scalars
+                            .entry(scalar.name.clone())
+                            .or_insert_with(|| scalar.clone());
+                    }
+
+                    // Subscriptions are not supported on the federated schema
+                    s::Definition::TypeDefinition(s::TypeDefinition::Object(object_type))
+                        if object_type.name == "Subscription" => {}
+
+                    s::Definition::TypeDefinition(type_definition) => {
+                        definitions.push(s::Definition::TypeDefinition(
+                            renamer.type_definition(type_definition),
+                        ));
+                    }
+
+                    // Directive definitions and schema definitions only
+                    // matter to the deployments' own schemas
+                    _ => {}
+                }
+            }
+
+            root_fields.push(s::Field {
+                position: Default::default(),
+                description: Some(format!("Queries deployment `{}`", schema.id)),
+                name: namespace.clone(),
+                arguments: vec![],
+                // Nullable, so that a failing deployment does not fail the
+                // entire query
+                field_type: s::Type::NamedType(renamer.name("Query")),
+                directives: vec![],
+            });
+
+            prefixed_types.insert(namespace.clone(), prefixed);
+            schemas.insert(namespace, schema);
+        }
+
+        definitions.extend(
+            scalars.into_iter().map(|(_, scalar)| {
+                s::Definition::TypeDefinition(s::TypeDefinition::Scalar(scalar))
+            }),
+        );
+        definitions.push(s::Definition::TypeDefinition(s::TypeDefinition::Object(
+            s::ObjectType {
+                position: Default::default(),
+                description: None,
+                name: "Query".to_owned(),
+                implements_interfaces: vec![],
+                directives: vec![],
+                fields: root_fields,
+            },
+        )));
+
+        let id = SubgraphDeploymentId::new(FEDERATED_SCHEMA_ID).unwrap();
+        Ok(Federation {
+            schema: Arc::new(Schema::new(id, s::Document { definitions })),
+            schemas,
+            prefixed_types,
+        })
+    }
+
+    /// Splits a query against the federated schema into one query per
+    /// top-level field, each against the schema of the deployment the field
+    /// belongs to. Top-level introspection fields are combined into one query
+    /// against the federated schema.
+    pub fn split_query(
+        &self,
+        query: &Query,
+    ) -> Result<Vec<FederatedQuery>, Vec<QueryExecutionError>> {
+        validate_query(&self.schema.document, &query.document)?;
+
+        let operation = qast::get_operation(&query.document, None).map_err(|e| vec![e])?;
+        let (name, selection_set) = match operation {
+            q::OperationDefinition::Query(query) => (query.name.clone(), &query.selection_set),
+            q::OperationDefinition::SelectionSet(selection_set) => (None, selection_set),
+            _ => {
+                return Err(vec![QueryExecutionError::NotSupported(
+                    "Only queries are supported".to_owned(),
+                )]);
+            }
+        };
+
+        let variables = query
+            .variables
+            .as_ref()
+            .map(|variables| (**variables).clone())
+            .unwrap_or_default();
+
+        // Group the selected fields by the query they belong to, keeping the
+        // order in which they are first selected
+        let mut introspection = vec![];
+        let mut namespaced: Vec<(String, String, Vec<q::Selection>)> = vec![];
+        for selection in selection_set.items.iter() {
+            if !qast::include_selection(selection, &variables)
+                || qast::skip_selection(selection, &variables)
+            {
+                continue;
+            }
+
+            let field = match selection {
+                q::Selection::Field(field) => field,
+                _ => {
+                    return Err(vec![QueryExecutionError::NotSupported(
+                        "Fragments on the root query type of a federated schema".to_owned(),
+                    )]);
+                }
+            };
+
+            if field.name.starts_with("__") {
+                introspection.push(selection.clone());
+                continue;
+            }
+
+            let response_key = qast::get_response_key(field);
+            match namespaced
+                .iter_mut()
+                .find(|(key, _, _)| *key == *response_key)
+            {
+                Some((_, _, items)) => items.extend(field.selection_set.items.iter().cloned()),
+                None => namespaced.push((
+                    response_key.clone(),
+                    field.name.clone(),
+                    field.selection_set.items.clone(),
+                )),
+            }
+        }
+
+        let mut queries = vec![];
+        if !introspection.is_empty() {
+            queries.push(FederatedQuery {
+                namespace: None,
+                response_key: None,
+                query: self.sub_query(query, name.clone(), introspection, None),
+            });
+        }
+        for (response_key, namespace, items) in namespaced {
+            let renamer = Renamer {
+                namespace: &namespace,
+                prefixed: &self.prefixed_types[&namespace],
+            };
+            queries.push(FederatedQuery {
+                query: self.sub_query(query, name.clone(), items, Some(&renamer)),
+                namespace: Some(namespace),
+                response_key: Some(response_key),
+            });
+        }
+        Ok(queries)
+    }
+
+    /// Builds a query from `items` that only contains the fragments and
+    /// variable definitions of `query` it uses. If a `renamer` is given, the
+    /// query is against the schema of its namespace and the type names in
+    /// it are stripped of the namespace prefix.
+    fn sub_query(
+        &self,
+        query: &Query,
+        name: Option<q::Name>,
+        items: Vec<q::Selection>,
+        renamer: Option<&Renamer>,
+    ) -> Query {
+        let fragments = query
+            .document
+            .definitions
+            .iter()
+            .filter_map(|definition| match definition {
+                q::Definition::Fragment(fragment) => Some((&fragment.name, fragment)),
+                _ => None,
+            })
+            .collect::<HashMap<_, _>>();
+        let variable_definitions = match qast::get_operation(&query.document, None) {
+            Ok(q::OperationDefinition::Query(query)) => query.variable_definitions.clone(),
+            _ => vec![],
+        };
+
+        let selection_set = q::SelectionSet {
+            span: Default::default(),
+            items,
+        };
+
+        // Find the fragments the selection uses, directly or through
+        // other fragments
+        let mut used_fragments = vec![];
+        let mut pending = vec![];
+        collect_spreads(&selection_set, &mut pending);
+        while let Some(fragment_name) = pending.pop() {
+            if used_fragments.contains(&fragment_name) {
+                continue;
+            }
+            if let Some(fragment) = fragments.get(&fragment_name) {
+                collect_spreads(&fragment.selection_set, &mut pending);
+                used_fragments.push(fragment_name);
+            }
+        }
+        let used_fragments = query
+            .document
+            .definitions
+            .iter()
+            .filter_map(|definition| match definition {
+                q::Definition::Fragment(fragment) if used_fragments.contains(&fragment.name) => {
+                    Some(fragment)
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut used_variables = HashSet::new();
+        collect_variables(&selection_set, &mut used_variables);
+        for fragment in used_fragments.iter() {
+            collect_variables_in_directives(&fragment.directives, &mut used_variables);
+            collect_variables(&fragment.selection_set, &mut used_variables);
+        }
+
+        let mut definitions = vec![q::Definition::Operation(q::OperationDefinition::Query(
+            q::Query {
+                position: Default::default(),
+                name,
+                variable_definitions: variable_definitions
+                    .into_iter()
+                    .filter(|definition| used_variables.contains(&definition.name))
+                    .map(|mut definition| {
+                        if let Some(renamer) = renamer {
+                            definition.var_type = renamer.unprefix_type(&definition.var_type);
+                        }
+                        definition
+                    })
+                    .collect(),
+                directives: vec![],
+                selection_set,
+            },
+        ))];
+        definitions.extend(used_fragments.into_iter().map(|fragment| {
+            let mut fragment = fragment.clone();
+            if let Some(renamer) = renamer {
+                let q::TypeCondition::On(ref mut type_name) = fragment.type_condition;
+                *type_name = renamer.unprefix(type_name);
+            }
+            q::Definition::Fragment(fragment)
+        }));
+
+        let mut document = q::Document { definitions };
+        let schema = match renamer {
+            Some(renamer) => {
+                unprefix_inline_fragments(&mut document, renamer);
+                self.schemas[renamer.namespace].clone()
+            }
+            None => self.schema.clone(),
+        };
+
+        Query {
+            schema,
+            document,
+            variables: query.variables.clone(),
+        }
+    }
+
+    /// Checks that `namespaces` are valid GraphQL names and distinct, so that
+    /// they can be used to federate deployments.
+    pub fn check_namespaces<'a>(
+        namespaces: impl IntoIterator<Item = &'a str>,
+    ) -> Result<(), FederationError> {
+        let mut seen = HashSet::new();
+        for namespace in namespaces {
+            if !is_valid_namespace(namespace) {
+                return Err(FederationError::InvalidNamespace(namespace.to_owned()));
+            }
+            if !seen.insert(namespace) {
+                return Err(FederationError::DuplicateNamespace(namespace.to_owned()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Combines the results of the queries a query against the federated
+    /// schema was split into. Names of types in `__typename` fields are
+    /// prefixed to match the federated schema, and errors and extensions are
+    /// reported under the response key of the query they belong to.
+    pub fn merge_results(&self, results: Vec<(FederatedQuery, QueryResult)>) -> QueryResult {
+        let mut data = BTreeMap::new();
+        let mut errors = vec![];
+        let mut extensions = BTreeMap::new();
+        let mut complete = true;
+
+        for (query, result) in results {
+            if let Some(query_errors) = result.errors {
+                match query.response_key {
+                    Some(ref response_key) => errors.extend(
+                        query_errors
+                            .into_iter()
+                            .map(|error| error.nest(response_key.as_str())),
+                    ),
+                    None => errors.extend(query_errors),
+                }
+            }
+
+            let value = match (&query.namespace, result.data) {
+                (Some(namespace), Some(mut value)) => {
+                    let renamer = Renamer {
+                        namespace,
+                        prefixed: &self.prefixed_types[namespace],
+                    };
+                    renamer.prefix_typenames(&mut value);
+                    Some(value)
+                }
+                (_, value) => value,
+            };
+
+            match (query.response_key, value) {
+                (Some(response_key), value) => {
+                    if let Some(query_extensions) = result.extensions {
+                        extensions.insert(
+                            response_key.clone(),
+                            serde_json::to_value(query_extensions).unwrap(),
+                        );
+                    }
+                    data.insert(response_key, value.unwrap_or(q::Value::Null));
+                }
+                (None, Some(q::Value::Object(fields))) => data.extend(fields),
+                (None, _) => complete = false,
+            }
+        }
+
+        let mut result = QueryResult::new(if complete {
+            Some(q::Value::Object(data))
+        } else {
+            None
+        });
+        if !errors.is_empty() {
+            result.errors = Some(errors);
+        }
+        if !extensions.is_empty() {
+            result.extensions = Some(extensions);
+        }
+        result
+    }
+}
+
+/// Renames the types of one deployment between its own schema and the
+/// federated schema.
+struct Renamer<'a> {
+    namespace: &'a str,
+    prefixed: &'a HashSet<String>,
+}
+
+impl<'a> Renamer<'a> {
+    fn name(&self, name: &str) -> String {
+        if self.prefixed.contains(name) {
+            format!("{}_{}", self.namespace, name)
+        } else {
+            name.to_owned()
+        }
+    }
+
+    fn unprefix(&self, name: &str) -> String {
+        let prefix = format!("{}_", self.namespace);
+        if name.starts_with(&prefix) && self.prefixed.contains(&name[prefix.len()..]) {
+            name[prefix.len()..].to_owned()
+        } else {
+            name.to_owned()
+        }
+    }
+
+    fn field_type(&self, t: &s::Type) -> s::Type {
+        match t {
+            s::Type::NamedType(name) => s::Type::NamedType(self.name(name)),
+            s::Type::ListType(t) => s::Type::ListType(Box::new(self.field_type(t))),
+            s::Type::NonNullType(t) => s::Type::NonNullType(Box::new(self.field_type(t))),
+        }
+    }
+
+    fn unprefix_type(&self, t: &q::Type) -> q::Type {
+        match t {
+            q::Type::NamedType(name) => q::Type::NamedType(self.unprefix(name)),
+            q::Type::ListType(t) => q::Type::ListType(Box::new(self.unprefix_type(t))),
+            q::Type::NonNullType(t) => q::Type::NonNullType(Box::new(self.unprefix_type(t))),
+        }
+    }
+
+    fn input_value(&self, input_value: &s::InputValue) -> s::InputValue {
+        s::InputValue {
+            value_type: self.field_type(&input_value.value_type),
+            ..input_value.clone()
+        }
+    }
+
+    fn field(&self, field: &s::Field) -> s::Field {
+        s::Field {
+            arguments: field
+                .arguments
+                .iter()
+                .map(|argument| self.input_value(argument))
+                .collect(),
+            field_type: self.field_type(&field.field_type),
+            ..field.clone()
+        }
+    }
+
+    fn type_definition(&self, type_definition: &s::TypeDefinition) -> s::TypeDefinition {
+        match type_definition {
+            s::TypeDefinition::Object(t) => s::TypeDefinition::Object(s::ObjectType {
+                name: self.name(&t.name),
+                implements_interfaces: t
+                    .implements_interfaces
+                    .iter()
+                    .map(|name| self.name(name))
+                    .collect(),
+                fields: t.fields.iter().map(|field| self.field(field)).collect(),
+                ..t.clone()
+            }),
+            s::TypeDefinition::Interface(t) => s::TypeDefinition::Interface(s::InterfaceType {
+                name: self.name(&t.name),
+                fields: t.fields.iter().map(|field| self.field(field)).collect(),
+                ..t.clone()
+            }),
+            s::TypeDefinition::Union(t) => s::TypeDefinition::Union(s::UnionType {
+                name: self.name(&t.name),
+                types: t.types.iter().map(|name| self.name(name)).collect(),
+                ..t.clone()
+            }),
+            s::TypeDefinition::Enum(t) => s::TypeDefinition::Enum(s::EnumType {
+                name: self.name(&t.name),
+                ..t.clone()
+            }),
+            s::TypeDefinition::InputObject(t) => {
+                s::TypeDefinition::InputObject(s::InputObjectType {
+                    name: self.name(&t.name),
+                    fields: t
+                        .fields
+                        .iter()
+                        .map(|field| self.input_value(field))
+                        .collect(),
+                    ..t.clone()
+                })
+            }
+            s::TypeDefinition::Scalar(t) => s::TypeDefinition::Scalar(t.clone()),
+        }
+    }
+
+    fn unprefix_selection_set(&self, selection_set: &mut q::SelectionSet) {
+        for selection in selection_set.items.iter_mut() {
+            match selection {
+                q::Selection::Field(field) => self.unprefix_selection_set(&mut field.selection_set),
+                q::Selection::FragmentSpread(_) => {}
+                q::Selection::InlineFragment(fragment) => {
+                    if let Some(q::TypeCondition::On(ref mut type_name)) = fragment.type_condition {
+                        *type_name = self.unprefix(type_name);
+                    }
+                    self.unprefix_selection_set(&mut fragment.selection_set);
+                }
+            }
+        }
+    }
+
+    fn prefix_typenames(&self, value: &mut q::Value) {
+        match value {
+            q::Value::Object(fields) => {
+                for (key, value) in fields.iter_mut() {
+                    if key != "__typename" {
+                        self.prefix_typenames(value);
+                    } else if let q::Value::String(type_name) = value {
+                        *type_name = self.name(type_name);
+                    }
+                }
+            }
+            q::Value::List(values) => {
+                for value in values.iter_mut() {
+                    self.prefix_typenames(value);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn unprefix_inline_fragments(document: &mut q::Document, renamer: &Renamer) {
+    for definition in document.definitions.iter_mut() {
+        match definition {
+            q::Definition::Operation(q::OperationDefinition::Query(query)) => {
+                renamer.unprefix_selection_set(&mut query.selection_set)
+            }
+            q::Definition::Fragment(fragment) => {
+                renamer.unprefix_selection_set(&mut fragment.selection_set)
+            }
+            _ => {}
+        }
+    }
+}
+
+fn type_name(type_definition: &s::TypeDefinition) -> &str {
+    match type_definition {
+        s::TypeDefinition::Scalar(t) => &t.name,
+        s::TypeDefinition::Object(t) => &t.name,
+        s::TypeDefinition::Interface(t) => &t.name,
+        s::TypeDefinition::Union(t) => &t.name,
+        s::TypeDefinition::Enum(t) => &t.name,
+        s::TypeDefinition::InputObject(t) => &t.name,
+    }
+}
+
+/// Namespaces are used as field names and type name prefixes, so they must
+/// be GraphQL names that are not reserved for introspection.
+fn is_valid_namespace(namespace: &str) -> bool {
+    let mut chars = namespace.chars();
+    match chars.next() {
+        Some(c) if c == '_' || c.is_ascii_alphabetic() => {}
+        _ => return false,
+    }
+    !namespace.starts_with("__") && chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+}
+
+fn collect_spreads(selection_set: &q::SelectionSet, spreads: &mut Vec<q::Name>) {
+    for selection in selection_set.items.iter() {
+        match selection {
+            q::Selection::Field(field) => collect_spreads(&field.selection_set, spreads),
+            q::Selection::FragmentSpread(spread) => spreads.push(spread.fragment_name.clone()),
+            q::Selection::InlineFragment(fragment) => {
+                collect_spreads(&fragment.selection_set, spreads)
+            }
+        }
+    }
+}
+
+fn collect_variables(selection_set: &q::SelectionSet, variables: &mut HashSet<q::Name>) {
+    for selection in selection_set.items.iter() {
+        match selection {
+            q::Selection::Field(field) => {
+                for (_, value) in field.arguments.iter() {
+                    collect_variables_in_value(value, variables);
+                }
+                collect_variables_in_directives(&field.directives, variables);
+                collect_variables(&field.selection_set, variables);
+            }
+            q::Selection::FragmentSpread(spread) => {
+                collect_variables_in_directives(&spread.directives, variables)
+            }
+            q::Selection::InlineFragment(fragment) => {
+                collect_variables_in_directives(&fragment.directives, variables);
+                collect_variables(&fragment.selection_set, variables);
+            }
+        }
+    }
+}
+
+fn collect_variables_in_directives(directives: &[q::Directive], variables: &mut HashSet<q::Name>) {
+    for directive in directives {
+        for (_, value) in directive.arguments.iter() {
+            collect_variables_in_value(value, variables);
+        }
+    }
+}
+
+fn collect_variables_in_value(value: &q::Value, variables: &mut HashSet<q::Name>) {
+    match value {
+        q::Value::Variable(name) => {
+            variables.insert(name.clone());
+        }
+        q::Value::List(values) => {
+            for value in values {
+                collect_variables_in_value(value, variables);
+            }
+        }
+        q::Value::Object(fields) => {
+            for value in fields.values() {
+                collect_variables_in_value(value, variables);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use graphql_parser::parse_query;
+
+    use super::*;
+    use crate::schema::api_schema;
+
+    fn schema(id: &str, raw: &str) -> Arc<Schema> {
+        let id = SubgraphDeploymentId::new(id).unwrap();
+        let mut schema = Schema::parse(raw, id.clone()).unwrap();
+        schema.document = api_schema(&schema.document).unwrap();
+        schema.add_subgraph_id_directives(id);
+        Arc::new(schema)
+    }
+
+    fn federation() -> Federation {
+        Federation::new(vec![
+            (
+                "music".to_owned(),
+                schema(
+                    "music",
+                    "type Musician @entity { id: ID!, name: String!, bands: [Band!]! }
+                     type Band @entity { id: ID!, name: String! }",
+                ),
+            ),
+            (
+                "books".to_owned(),
+                schema(
+                    "books",
+                    "type Book @entity { id: ID!, title: String!, pages: BigInt }",
+                ),
+            ),
+        ])
+        .unwrap()
+    }
+
+    fn query(federation: &Federation, raw: &str, variables: Option<QueryVariables>) -> Query {
+        Query {
+            schema: federation.schema.clone(),
+            document: parse_query(raw).unwrap(),
+            variables,
+        }
+    }
+
+    fn assert_query(query: &Query, expected: &str) {
+        assert_eq!(
+            query.document.to_string(),
+            parse_query(expected).unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn federated_schema_prefixes_types() {
+        let federation = federation();
+        let document = &federation.schema.document;
+
+        let root = crate::schema::ast::get_root_query_type(document).unwrap();
+        let root_fields = root
+            .fields
+            .iter()
+            .map(|field| (field.name.as_str(), format!("{}", field.field_type)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            root_fields,
+            vec![
+                ("music", "music_Query".to_owned()),
+                ("books", "books_Query".to_owned()),
+            ]
+        );
+
+        let musician = crate::schema::ast::get_named_type(document, &"music_Musician".to_owned())
+            .expect("Musician is prefixed");
+        match musician {
+            s::TypeDefinition::Object(t) => {
+                let bands = t.fields.iter().find(|field| field.name == "bands").unwrap();
+                assert_eq!(format!("{}", bands.field_type), "[music_Band!]!");
+            }
+            _ => panic!("Musician is not an object type"),
+        }
+
+        // Scalars are shared, subscriptions are not federated
+        assert!(crate::schema::ast::get_named_type(document, &"BigInt".to_owned()).is_some());
+        assert!(crate::schema::ast::get_named_type(document, &"books_BigInt".to_owned()).is_none());
+        assert!(
+            crate::schema::ast::get_named_type(document, &"music_Subscription".to_owned())
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn invalid_namespaces_are_rejected() {
+        let music = schema("music", "type Musician @entity { id: ID! }");
+        match Federation::new(vec![("a-b".to_owned(), music.clone())]) {
+            Err(FederationError::InvalidNamespace(_)) => {}
+            result => panic!("unexpected result {:?}", result.map(|_| ())),
+        }
+        match Federation::new(vec![
+            ("music".to_owned(), music.clone()),
+            ("music".to_owned(), music),
+        ]) {
+            Err(FederationError::DuplicateNamespace(_)) => {}
+            result => panic!("unexpected result {:?}", result.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn split_query_by_namespace() {
+        let federation = federation();
+        let mut variables = HashMap::new();
+        variables.insert("first".to_owned(), q::Value::Int(q::Number::from(5)));
+        let query = query(
+            &federation,
+            "query Both($first: Int, $title: String, $order: books_Book_orderBy) {
+               m: music { musicians(first: $first) { ...MusicianFields } }
+               books { books(where: { title: $title }, orderBy: $order) { __typename title } }
+               __typename
+             }
+             fragment MusicianFields on music_Musician { name ... on music_Musician { id } }",
+            Some(QueryVariables::new(variables)),
+        );
+
+        let queries = federation.split_query(&query).unwrap();
+        assert_eq!(queries.len(), 3);
+
+        assert_eq!(queries[0].namespace, None);
+        assert_query(&queries[0].query, "query Both { __typename }");
+
+        assert_eq!(queries[1].namespace, Some("music".to_owned()));
+        assert_eq!(queries[1].response_key, Some("m".to_owned()));
+        assert_eq!(queries[1].query.schema.id.to_string(), "music");
+        assert_query(
+            &queries[1].query,
+            "query Both($first: Int) { musicians(first: $first) { ...MusicianFields } }
+             fragment MusicianFields on Musician { name ... on Musician { id } }",
+        );
+        assert!(queries[1].query.variables.is_some());
+
+        assert_eq!(queries[2].namespace, Some("books".to_owned()));
+        assert_query(
+            &queries[2].query,
+            "query Both($title: String, $order: Book_orderBy) {
+               books(where: { title: $title }, orderBy: $order) { __typename title }
+             }",
+        );
+    }
+
+    #[test]
+    fn split_query_rejects_invalid_queries() {
+        let federation = federation();
+        let query = query(&federation, "{ music { books { id } } }", None);
+        assert!(federation.split_query(&query).is_err());
+    }
+
+    #[test]
+    fn merge_results_by_response_key() {
+        let federation = federation();
+        let query = query(
+            &federation,
+            "{ __typename music { __typename } books { books { id } } }",
+            None,
+        );
+        let queries = federation.split_query(&query).unwrap();
+
+        let mut root = BTreeMap::new();
+        root.insert(
+            "__typename".to_owned(),
+            q::Value::String("Query".to_owned()),
+        );
+        let mut music = BTreeMap::new();
+        music.insert(
+            "__typename".to_owned(),
+            q::Value::String("Query".to_owned()),
+        );
+        let mut music_result = QueryResult::new(Some(q::Value::Object(music)));
+        music_result.add_extension("complexity", serde_json::Value::from(1));
+        let books_result = QueryResult::from(QueryExecutionError::Timeout);
+
+        let result = federation.merge_results(
+            queries
+                .into_iter()
+                .zip(vec![
+                    QueryResult::new(Some(q::Value::Object(root))),
+                    music_result,
+                    books_result,
+                ])
+                .collect(),
+        );
+
+        let data = serde_json::to_value(&result).unwrap();
+        assert_eq!(
+            data,
+            serde_json::json!({
+                "data": {
+                    "__typename": "Query",
+                    "music": { "__typename": "music_Query" },
+                    "books": null,
+                },
+                "errors": [{ "message": "Query timed out", "path": ["books"] }],
+                "extensions": { "music": { "complexity": 1 } },
+            })
+        );
+    }
+
+    #[test]
+    fn merge_results_nests_errors_under_response_keys() {
+        let federation = federation();
+        let query = query(
+            &federation,
+            "{ __typename tunes: music { __typename } books { books { id } } }",
+            None,
+        );
+        let queries = federation.split_query(&query).unwrap();
+
+        let result = federation.merge_results(
+            queries
+                .into_iter()
+                .zip(vec![
+                    QueryResult::from(QueryExecutionError::Timeout),
+                    QueryResult::from(QueryExecutionError::TooManyEntities(8)),
+                    QueryResult::from(QueryExecutionError::Timeout),
+                ])
+                .collect(),
+        );
+
+        // Errors of the root fields have no path since they are not nested
+        // under a namespace
+        let errors = serde_json::to_value(&result).unwrap()["errors"].clone();
+        assert_eq!(
+            errors,
+            serde_json::json!([
+                { "message": "Query timed out" },
+                { "message": QueryExecutionError::TooManyEntities(8).to_string(), "path": ["tunes"] },
+                { "message": "Query timed out", "path": ["books"] },
+            ])
+        );
+    }
+}
@@ -83,7 +83,7 @@ fn type_definition_object(
                 object_type_object(schema, type_objects, object_type)
             }
             s::TypeDefinition::Scalar(scalar_type) => scalar_type_object(scalar_type),
-            s::TypeDefinition::Union(union_type) => union_type_object(schema, union_type),
+            s::TypeDefinition::Union(union_type) => union_type_object(union_type),
         };
 
         type_objects.insert(type_name.to_owned(), type_object.clone());
@@ -274,7 +274,7 @@ fn scalar_type_object(scalar_type: &s::ScalarType) -> q::Value {
     ])
 }
 
-fn union_type_object(schema: &Schema, union_type: &s::UnionType) -> q::Value {
+fn union_type_object(union_type: &s::UnionType) -> q::Value {
     object_value(vec![
         ("name", q::Value::String(union_type.name.to_owned())),
         ("kind", q::Value::Enum(String::from("UNION"))),
@@ -288,15 +288,10 @@ fn union_type_object(schema: &Schema, union_type: &s::UnionType) -> q::Value {
         (
             "possibleTypes",
             q::Value::List(
-                sast::get_object_type_definitions(&schema.document)
+                union_type
+                    .types
                     .iter()
-                    .filter(|object_type| {
-                        object_type
-                            .implements_interfaces
-                            .iter()
-                            .any(|implemented_name| implemented_name == &union_type.name)
-                    })
-                    .map(|object_type| q::Value::String(object_type.name.to_owned()))
+                    .map(|type_name| q::Value::String(type_name.to_owned()))
                     .collect(),
             ),
         ),
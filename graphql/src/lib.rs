@@ -1,3 +1,4 @@
+extern crate futures;
 pub extern crate graphql_parser;
 
 use graph::prelude::failure;
@@ -17,6 +18,9 @@ pub mod query;
 /// Utilities for executing GraphQL subscriptions.
 pub mod subscription;
 
+/// Utilities for serving several deployments through one endpoint.
+pub mod federation;
+
 /// Utilities for working with GraphQL values.
 mod values;
 
@@ -26,11 +30,14 @@ mod store;
 /// Prelude that exports the most important traits and types.
 pub mod prelude {
     pub use super::execution::{ExecutionContext, ObjectOrInterface, Resolver};
+    pub use super::federation::{FederatedQuery, Federation, FederationError};
     pub use super::introspection::{introspection_schema, IntrospectionResolver};
     pub use super::query::{execute_query, QueryExecutionOptions};
     pub use super::schema::{api_schema, APISchemaError};
     pub use super::store::{build_query, StoreResolver};
-    pub use super::subscription::{execute_subscription, SubscriptionExecutionOptions};
+    pub use super::subscription::{
+        execute_subscription, SharedSubscriptions, SubscriptionExecutionOptions,
+    };
     pub use super::values::{object_value, MaybeCoercible};
 
     pub use super::graphql_parser::{query::Name, schema::ObjectType};
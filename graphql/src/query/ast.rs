@@ -1,5 +1,5 @@
 use graphql_parser::query::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use graph::prelude::QueryExecutionError;
 
@@ -111,6 +111,47 @@ pub fn get_response_key(field: &Field) -> &Name {
     field.alias.as_ref().unwrap_or(&field.name)
 }
 
+/// Returns `true` if the only operation of the document selects nothing but
+/// introspection fields like `__schema` and `__type` at the top level, so
+/// that executing it does not read from the store.
+pub fn is_introspection_only(document: &Document) -> bool {
+    fn selects_only_introspection<'a>(
+        document: &'a Document,
+        selection_set: &'a SelectionSet,
+        visited_fragments: &mut HashSet<&'a Name>,
+    ) -> bool {
+        selection_set.items.iter().all(|selection| match selection {
+            Selection::Field(field) => field.name.starts_with("__"),
+            Selection::InlineFragment(fragment) => {
+                selects_only_introspection(document, &fragment.selection_set, visited_fragments)
+            }
+            Selection::FragmentSpread(spread) => {
+                // Fragment cycles are rejected by validation; here, it is
+                // enough to not follow them
+                if !visited_fragments.insert(&spread.fragment_name) {
+                    return true;
+                }
+                match get_fragment(document, &spread.fragment_name) {
+                    Some(fragment) => selects_only_introspection(
+                        document,
+                        &fragment.selection_set,
+                        visited_fragments,
+                    ),
+                    None => false,
+                }
+            }
+        })
+    }
+
+    match get_operation(document, None) {
+        Ok(OperationDefinition::Query(Query { selection_set, .. }))
+        | Ok(OperationDefinition::SelectionSet(selection_set)) => {
+            selects_only_introspection(document, selection_set, &mut HashSet::new())
+        }
+        _ => false,
+    }
+}
+
 /// Returns up the fragment with the given name, if it exists.
 pub fn get_fragment<'a>(document: &'a Document, name: &Name) -> Option<&'a FragmentDefinition> {
     document
@@ -134,3 +175,29 @@ pub fn get_variable_definitions(
         OperationDefinition::SelectionSet(_) => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use graphql_parser::parse_query;
+
+    use super::is_introspection_only;
+
+    #[test]
+    fn detects_introspection_only_queries() {
+        let check = |query: &str| is_introspection_only(&parse_query(query).unwrap());
+
+        assert!(check("{ __schema { types { name } } }"));
+        assert!(check(
+            "query { ...Types __type(name: \"Musician\") { name } } \
+             fragment Types on Query { __schema { types { name } } }"
+        ));
+        assert!(check("{ ... on Query { __typename } }"));
+
+        assert!(!check("{ __schema { types { name } } musicians { id } }"));
+        assert!(!check(
+            "{ ...Musicians } fragment Musicians on Query { musicians { id } }"
+        ));
+        assert!(!check("subscription { __typename }"));
+        assert!(!check("query A { __typename } query B { __typename }"));
+    }
+}
@@ -1,5 +1,7 @@
 use graph::prelude::*;
+use graph::serde_json;
 use graphql_parser::{query as q, Style};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Instant;
 use uuid::Uuid;
 
@@ -10,6 +12,11 @@ use crate::schema::ast as sast;
 /// Utilities for working with GraphQL query ASTs.
 pub mod ast;
 
+/// Validation of queries against a schema.
+mod validation;
+
+pub use self::validation::validate_query;
+
 /// Options available for query execution.
 pub struct QueryExecutionOptions<R>
 where
@@ -29,6 +36,14 @@ where
 
     /// Maximum depth for a query.
     pub max_depth: u8,
+
+    /// Maximum number of entities a query may return.
+    pub max_entities: Option<usize>,
+
+    /// Records where the time executing the query goes, if the query is
+    /// traced. The resolver needs to record its store queries in the same
+    /// trace.
+    pub trace: Option<Arc<Trace>>,
 }
 
 /// Executes a query and returns a result.
@@ -42,6 +57,16 @@ where
         "query_id" => query_id
     ));
 
+    // Reject invalid queries before executing any part of them
+    let validation_start = Instant::now();
+    let validation = validate_query(&query.schema.document, &query.document);
+    if let Some(ref trace) = options.trace {
+        trace.validation(validation_start);
+    }
+    if let Err(errors) = validation {
+        return QueryResult::from(errors);
+    }
+
     // Obtain the only operation of the query (fail if there is none or more than one)
     let operation = match qast::get_operation(&query.document, None) {
         Ok(op) => op,
@@ -64,8 +89,15 @@ where
         fields: vec![],
         variable_values: Arc::new(coerced_variable_values),
         deadline: options.deadline,
+        max_entities: options.max_entities,
+        entity_count: Arc::new(AtomicUsize::new(0)),
+        trace: options.trace.clone(),
+        path: vec![],
     };
 
+    // The complexity of the query, once it is known
+    let mut query_complexity = None;
+
     let result = match operation {
         // Execute top-level `query { ... }` and `{ ... }` expressions.
         q::OperationDefinition::Query(q::Query { selection_set, .. })
@@ -91,7 +123,10 @@ where
                         max_complexity,
                     )])
                 }
-                (Ok(_), _) => execute_root_selection_set(&ctx, selection_set, &None),
+                (Ok(complexity), _) => {
+                    query_complexity = Some(complexity);
+                    execute_root_selection_set(&ctx, selection_set, &None)
+                }
             }
         }
         // Everything else (e.g. mutations) is unsupported
@@ -100,8 +135,22 @@ where
         )]),
     };
 
-    match result {
+    let mut result = match result {
         Ok(value) => QueryResult::new(Some(value)),
         Err(e) => QueryResult::from(e),
+    };
+
+    if let Some(ref trace) = options.trace {
+        trace.finish();
+    }
+
+    // Report the cost of executed queries so that clients can tune them
+    if let Some(complexity) = query_complexity {
+        result.add_extension("complexity", serde_json::Value::from(complexity));
+        result.add_extension(
+            "entityCount",
+            serde_json::Value::from(ctx.entity_count.load(Ordering::SeqCst) as u64),
+        );
     }
+    result
 }
@@ -0,0 +1,1164 @@
+use graphql_parser::{query as q, schema as s, Pos};
+use indexmap::IndexMap;
+use lazy_static::lazy_static;
+use std::collections::{HashMap, HashSet};
+use std::ptr;
+
+use graph::prelude::QueryExecutionError;
+
+use crate::introspection::INTROSPECTION_DOCUMENT;
+use crate::query::ast as qast;
+use crate::schema::ast as sast;
+use crate::values::MaybeCoercible;
+
+lazy_static! {
+    /// Arguments of the built-in `@skip` and `@include` directives.
+    static ref CONDITIONAL_DIRECTIVE_ARGUMENTS: Vec<s::InputValue> = vec![s::InputValue {
+        position: Pos::default(),
+        description: None,
+        name: "if".to_owned(),
+        value_type: s::Type::NonNullType(Box::new(s::Type::NamedType("Boolean".to_owned()))),
+        default_value: None,
+        directives: vec![],
+    }];
+}
+
+/// Validates a query document against a schema, following the validation
+/// rules of the GraphQL spec (https://graphql.github.io/graphql-spec/June2018/#sec-Validation).
+///
+/// Validation does not stop at the first problem; all errors found in the
+/// document are returned together.
+pub fn validate_query(
+    schema: &s::Document,
+    document: &q::Document,
+) -> Result<(), Vec<QueryExecutionError>> {
+    let mut validator = Validator {
+        schema,
+        document,
+        fragments: HashMap::new(),
+        errors: vec![],
+    };
+    validator.validate();
+
+    if validator.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(validator.errors)
+    }
+}
+
+/// A variable used as (part of) an argument value.
+#[derive(Clone)]
+struct VariableUsage<'a> {
+    name: &'a q::Name,
+    position: Pos,
+    /// The type expected where the variable is used.
+    location_type: &'a s::Type,
+    /// Whether the argument or input field the variable is used for has a
+    /// default value.
+    location_has_default: bool,
+}
+
+/// Variable usages and fragment spreads found in an operation or fragment.
+#[derive(Default)]
+struct Scope<'a> {
+    usages: Vec<VariableUsage<'a>>,
+    spreads: Vec<&'a q::Name>,
+}
+
+/// A field in a selection set, together with the type it is selected on
+/// and, for fields nested in a selection that only applies to a specific
+/// object type, the name of that type.
+type SelectedField<'a> = (&'a s::TypeDefinition, &'a q::Field, Option<&'a str>);
+
+struct Validator<'a> {
+    schema: &'a s::Document,
+    document: &'a q::Document,
+    fragments: HashMap<&'a q::Name, &'a q::FragmentDefinition>,
+    errors: Vec<QueryExecutionError>,
+}
+
+impl<'a> Validator<'a> {
+    fn error(&mut self, position: Pos, message: String) {
+        self.errors
+            .push(QueryExecutionError::ValidationError(position, message));
+    }
+
+    fn validate(&mut self) {
+        let document = self.document;
+        let operations = qast::get_operations(document);
+        let fragments = document
+            .definitions
+            .iter()
+            .filter_map(|definition| match definition {
+                q::Definition::Fragment(fragment) => Some(fragment),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        // Operation names must be unique, and an anonymous operation must be
+        // the only operation in the document
+        let mut operation_names = HashSet::new();
+        for operation in operations.iter() {
+            match qast::get_operation_name(operation) {
+                Some(name) => {
+                    if !operation_names.insert(name) {
+                        self.error(
+                            operation_position(operation),
+                            format!("There can be only one operation named `{}`", name),
+                        );
+                    }
+                }
+                None if operations.len() > 1 => self.error(
+                    operation_position(operation),
+                    "An anonymous operation must be the only defined operation".to_owned(),
+                ),
+                None => (),
+            }
+        }
+
+        // Fragment names must be unique
+        for &fragment in fragments.iter() {
+            if self.fragments.insert(&fragment.name, fragment).is_some() {
+                self.error(
+                    fragment.position,
+                    format!("There can be only one fragment named `{}`", fragment.name),
+                );
+            }
+        }
+
+        // Validate fragment definitions on their own; their variable usages
+        // are attributed to the operations that use them below
+        let mut fragment_scopes = HashMap::new();
+        for &fragment in fragments.iter() {
+            let mut scope = Scope::default();
+            self.validate_directives(&fragment.directives, &mut scope);
+
+            let q::TypeCondition::On(type_name) = &fragment.type_condition;
+            if let Some(fragment_type) = self.composite_type(fragment.position, type_name) {
+                self.validate_selection_set(fragment_type, &fragment.selection_set, &mut scope);
+            }
+            fragment_scopes.insert(&fragment.name, scope);
+        }
+        self.validate_fragment_cycles(&fragments, &fragment_scopes);
+
+        let mut used_fragments = HashSet::new();
+        for operation in operations {
+            let (root_type, variable_definitions, directives, selection_set) = match operation {
+                q::OperationDefinition::SelectionSet(selection_set) => {
+                    (self.query_type(), &[][..], &[][..], selection_set)
+                }
+                q::OperationDefinition::Query(query) => (
+                    self.query_type(),
+                    &query.variable_definitions[..],
+                    &query.directives[..],
+                    &query.selection_set,
+                ),
+                q::OperationDefinition::Subscription(subscription) => (
+                    self.subscription_type(),
+                    &subscription.variable_definitions[..],
+                    &subscription.directives[..],
+                    &subscription.selection_set,
+                ),
+
+                // Mutations are rejected when executing the query
+                q::OperationDefinition::Mutation(_) => continue,
+            };
+
+            let root_type = match root_type {
+                Some(root_type) => root_type,
+                None => {
+                    self.errors.push(match operation {
+                        q::OperationDefinition::Subscription(_) => {
+                            QueryExecutionError::NoRootSubscriptionObjectType
+                        }
+                        _ => QueryExecutionError::NoRootQueryObjectType,
+                    });
+                    continue;
+                }
+            };
+
+            let mut scope = Scope::default();
+            self.validate_directives(directives, &mut scope);
+            self.validate_selection_set(root_type, selection_set, &mut scope);
+
+            let mut fields = vec![];
+            self.collect_fields(
+                root_type,
+                selection_set,
+                None,
+                &mut HashSet::new(),
+                &mut fields,
+            );
+            self.validate_fields_can_merge(fields);
+
+            // Add the variables used by all fragments the operation uses,
+            // directly or through other fragments
+            let Scope {
+                mut usages,
+                spreads,
+            } = scope;
+            let mut pending = spreads;
+            let mut visited = HashSet::new();
+            while let Some(name) = pending.pop() {
+                if !visited.insert(name) {
+                    continue;
+                }
+                used_fragments.insert(name);
+                if let Some(fragment_scope) = fragment_scopes.get(name) {
+                    usages.extend(fragment_scope.usages.iter().cloned());
+                    pending.extend(fragment_scope.spreads.iter().cloned());
+                }
+            }
+
+            self.validate_variables(variable_definitions, &usages);
+        }
+
+        for fragment in fragments {
+            if !used_fragments.contains(&fragment.name) {
+                self.error(
+                    fragment.position,
+                    format!("Fragment `{}` is never used", fragment.name),
+                );
+            }
+        }
+    }
+
+    /// Reports fragments that spread themselves, directly or through other
+    /// fragments.
+    fn validate_fragment_cycles(
+        &mut self,
+        fragments: &[&'a q::FragmentDefinition],
+        scopes: &HashMap<&'a q::Name, Scope<'a>>,
+    ) {
+        for &fragment in fragments {
+            let mut pending = scopes[&fragment.name].spreads.clone();
+            let mut visited = HashSet::new();
+            while let Some(name) = pending.pop() {
+                if name == &fragment.name {
+                    self.error(
+                        fragment.position,
+                        format!("Cannot spread fragment `{}` within itself", fragment.name),
+                    );
+                    break;
+                }
+                if visited.insert(name) {
+                    if let Some(scope) = scopes.get(name) {
+                        pending.extend(scope.spreads.iter().cloned());
+                    }
+                }
+            }
+        }
+    }
+
+    fn validate_selection_set(
+        &mut self,
+        parent_type: &'a s::TypeDefinition,
+        selection_set: &'a q::SelectionSet,
+        scope: &mut Scope<'a>,
+    ) {
+        for selection in selection_set.items.iter() {
+            match selection {
+                q::Selection::Field(field) => self.validate_field(parent_type, field, scope),
+
+                q::Selection::FragmentSpread(spread) => {
+                    self.validate_directives(&spread.directives, scope);
+                    scope.spreads.push(&spread.fragment_name);
+
+                    let fragment = match self.fragments.get(&spread.fragment_name) {
+                        Some(fragment) => *fragment,
+                        None => {
+                            self.error(
+                                spread.position,
+                                format!("Unknown fragment `{}`", spread.fragment_name),
+                            );
+                            continue;
+                        }
+                    };
+
+                    // Unknown type conditions are reported with the fragment definition
+                    let q::TypeCondition::On(type_name) = &fragment.type_condition;
+                    if let Some(fragment_type) = self.named_type(type_name) {
+                        self.validate_spread_is_possible(
+                            spread.position,
+                            parent_type,
+                            fragment_type,
+                        );
+                    }
+                }
+
+                q::Selection::InlineFragment(fragment) => {
+                    self.validate_directives(&fragment.directives, scope);
+
+                    let fragment_type = match &fragment.type_condition {
+                        Some(q::TypeCondition::On(type_name)) => {
+                            match self.composite_type(fragment.position, type_name) {
+                                Some(fragment_type) => {
+                                    self.validate_spread_is_possible(
+                                        fragment.position,
+                                        parent_type,
+                                        fragment_type,
+                                    );
+                                    fragment_type
+                                }
+                                None => continue,
+                            }
+                        }
+                        None => parent_type,
+                    };
+                    self.validate_selection_set(fragment_type, &fragment.selection_set, scope);
+                }
+            }
+        }
+    }
+
+    fn validate_field(
+        &mut self,
+        parent_type: &'a s::TypeDefinition,
+        field: &'a q::Field,
+        scope: &mut Scope<'a>,
+    ) {
+        self.validate_directives(&field.directives, scope);
+
+        let field_definition = match self.field_definition(parent_type, &field.name) {
+            Some(field_definition) => field_definition,
+            None => {
+                self.errors.push(QueryExecutionError::UnknownField(
+                    field.position,
+                    sast::get_type_name(parent_type).to_owned(),
+                    field.name.to_owned(),
+                ));
+                return;
+            }
+        };
+
+        self.validate_arguments(
+            field.position,
+            &field.name,
+            &field_definition.arguments,
+            &field.arguments,
+            scope,
+        );
+
+        let type_name = named_type_name(&field_definition.field_type);
+        match self.named_type(type_name) {
+            Some(field_type) if is_composite_type(field_type) => {
+                if field.selection_set.items.is_empty() {
+                    self.error(
+                        field.position,
+                        format!(
+                            "Field `{}` of type `{}` must have a selection of subfields",
+                            field.name, type_name
+                        ),
+                    );
+                } else {
+                    self.validate_selection_set(field_type, &field.selection_set, scope);
+                }
+            }
+            Some(_) => {
+                if !field.selection_set.items.is_empty() {
+                    self.error(
+                        field.position,
+                        format!(
+                            "Field `{}` must not have a selection since type `{}` has no subfields",
+                            field.name, type_name
+                        ),
+                    );
+                }
+            }
+
+            // The schema itself is broken; there is nothing to validate against
+            None => (),
+        }
+    }
+
+    fn validate_directives(&mut self, directives: &'a [q::Directive], scope: &mut Scope<'a>) {
+        let mut names = HashSet::new();
+        for directive in directives {
+            if !names.insert(&directive.name) {
+                self.error(
+                    directive.position,
+                    format!(
+                        "The directive `@{}` can only be used once at this location",
+                        directive.name
+                    ),
+                );
+            }
+
+            match directive.name.as_str() {
+                "skip" | "include" => self.validate_arguments(
+                    directive.position,
+                    &directive.name,
+                    &CONDITIONAL_DIRECTIVE_ARGUMENTS,
+                    &directive.arguments,
+                    scope,
+                ),
+                _ => self.error(
+                    directive.position,
+                    format!("Unknown directive `@{}`", directive.name),
+                ),
+            }
+        }
+    }
+
+    /// Validates the arguments passed to a field or directive.
+    fn validate_arguments(
+        &mut self,
+        position: Pos,
+        owner: &str,
+        definitions: &'a [s::InputValue],
+        arguments: &'a [(q::Name, q::Value)],
+        scope: &mut Scope<'a>,
+    ) {
+        let mut names = HashSet::new();
+        for (name, value) in arguments {
+            if !names.insert(name) {
+                self.error(
+                    position,
+                    format!("There can be only one argument named `{}`", name),
+                );
+                continue;
+            }
+
+            match definitions
+                .iter()
+                .find(|definition| &definition.name == name)
+            {
+                Some(definition) => {
+                    if !self.is_valid_value(
+                        value,
+                        &definition.value_type,
+                        definition.default_value.is_some(),
+                        position,
+                        &mut scope.usages,
+                    ) {
+                        self.errors.push(QueryExecutionError::InvalidArgumentError(
+                            position,
+                            name.to_owned(),
+                            value.clone(),
+                        ));
+                    }
+                }
+                None => self.error(
+                    position,
+                    format!("Unknown argument `{}` on `{}`", name, owner),
+                ),
+            }
+        }
+
+        for definition in definitions {
+            if sast::is_non_null_type(&definition.value_type)
+                && definition.default_value.is_none()
+                && !names.contains(&definition.name)
+            {
+                self.errors.push(QueryExecutionError::MissingArgumentError(
+                    position,
+                    definition.name.to_owned(),
+                ));
+            }
+        }
+    }
+
+    /// Checks whether a literal value is valid for the type `value_type`.
+    /// Variables are accepted anywhere; their usages are collected so they
+    /// can be checked against the variable definitions of the operation.
+    fn is_valid_value(
+        &self,
+        value: &'a q::Value,
+        value_type: &'a s::Type,
+        has_default: bool,
+        position: Pos,
+        usages: &mut Vec<VariableUsage<'a>>,
+    ) -> bool {
+        match (value_type, value) {
+            (_, q::Value::Variable(name)) => {
+                usages.push(VariableUsage {
+                    name,
+                    position,
+                    location_type: value_type,
+                    location_has_default: has_default,
+                });
+                true
+            }
+
+            (s::Type::NonNullType(_), q::Value::Null) => false,
+            (s::Type::NonNullType(inner), _) => {
+                self.is_valid_value(value, inner, false, position, usages)
+            }
+            (_, q::Value::Null) => true,
+
+            (s::Type::ListType(inner), q::Value::List(values)) => {
+                values.iter().fold(true, |valid, value| {
+                    self.is_valid_value(value, inner, false, position, usages) && valid
+                })
+            }
+            (s::Type::ListType(_), _) => false,
+
+            (s::Type::NamedType(name), _) => match self.named_type(name) {
+                Some(s::TypeDefinition::Scalar(t)) => value.coerce(t).is_some(),
+                Some(s::TypeDefinition::Enum(t)) => value.coerce(t).is_some(),
+                Some(s::TypeDefinition::InputObject(t)) => match value {
+                    q::Value::Object(object) => {
+                        let fields_valid =
+                            object.iter().fold(true, |valid, (name, value)| {
+                                match t.fields.iter().find(|field| &field.name == name) {
+                                    Some(field) => {
+                                        self.is_valid_value(
+                                            value,
+                                            &field.value_type,
+                                            field.default_value.is_some(),
+                                            position,
+                                            usages,
+                                        ) && valid
+                                    }
+                                    None => false,
+                                }
+                            });
+                        let required_present = t.fields.iter().all(|field| {
+                            !sast::is_non_null_type(&field.value_type)
+                                || field.default_value.is_some()
+                                || object.contains_key(&field.name)
+                        });
+                        fields_valid && required_present
+                    }
+                    _ => false,
+                },
+                _ => false,
+            },
+        }
+    }
+
+    /// Checks the variable definitions of an operation against the places
+    /// where the variables are used.
+    fn validate_variables(
+        &mut self,
+        definitions: &'a [q::VariableDefinition],
+        usages: &[VariableUsage<'a>],
+    ) {
+        let mut defined = HashMap::new();
+        for definition in definitions {
+            if defined.insert(&definition.name, definition).is_some() {
+                self.error(
+                    definition.position,
+                    format!(
+                        "There can be only one variable named `${}`",
+                        definition.name
+                    ),
+                );
+            }
+
+            if !sast::is_input_type(self.schema, &definition.var_type) {
+                self.errors
+                    .push(QueryExecutionError::InvalidVariableTypeError(
+                        definition.position,
+                        definition.name.to_owned(),
+                    ));
+                continue;
+            }
+
+            if let Some(default_value) = &definition.default_value {
+                // Default values must be constant
+                let mut nested_usages = vec![];
+                if !self.is_valid_value(
+                    default_value,
+                    &definition.var_type,
+                    false,
+                    definition.position,
+                    &mut nested_usages,
+                ) || !nested_usages.is_empty()
+                {
+                    self.errors.push(QueryExecutionError::InvalidArgumentError(
+                        definition.position,
+                        definition.name.to_owned(),
+                        default_value.clone(),
+                    ));
+                }
+            }
+        }
+
+        let mut reported = HashSet::new();
+        for usage in usages {
+            match defined.get(usage.name) {
+                Some(definition) => {
+                    if !is_variable_usage_allowed(definition, usage) {
+                        self.error(
+                            usage.position,
+                            format!(
+                                "Variable `${}` of type `{}` cannot be used where a value \
+                                 of type `{}` is expected",
+                                usage.name, definition.var_type, usage.location_type
+                            ),
+                        );
+                    }
+                }
+                None => {
+                    if reported.insert(usage.name) {
+                        self.error(
+                            usage.position,
+                            format!("Variable `${}` is not defined", usage.name),
+                        );
+                    }
+                }
+            }
+        }
+
+        let used = usages
+            .iter()
+            .map(|usage| usage.name)
+            .collect::<HashSet<_>>();
+        for definition in definitions {
+            if !used.contains(&definition.name) {
+                self.error(
+                    definition.position,
+                    format!("Variable `${}` is never used", definition.name),
+                );
+            }
+        }
+    }
+
+    /// A fragment may only be spread where the objects it applies to can
+    /// occur.
+    fn validate_spread_is_possible(
+        &mut self,
+        position: Pos,
+        parent_type: &'a s::TypeDefinition,
+        fragment_type: &'a s::TypeDefinition,
+    ) {
+        let parent_types = self.possible_types(parent_type);
+        let fragment_types = self.possible_types(fragment_type);
+        if parent_types.is_disjoint(&fragment_types) {
+            self.error(
+                position,
+                format!(
+                    "Fragment cannot be spread here as objects of type `{}` \
+                     can never be of type `{}`",
+                    sast::get_type_name(parent_type),
+                    sast::get_type_name(fragment_type)
+                ),
+            );
+        }
+    }
+
+    /// Collects the fields of a selection set, including those selected
+    /// through fragments, together with the type they are selected on.
+    fn collect_fields(
+        &self,
+        parent_type: &'a s::TypeDefinition,
+        selection_set: &'a q::SelectionSet,
+        exclusive_type: Option<&'a str>,
+        visited_fragments: &mut HashSet<&'a q::Name>,
+        fields: &mut Vec<SelectedField<'a>>,
+    ) {
+        for selection in selection_set.items.iter() {
+            match selection {
+                q::Selection::Field(field) => fields.push((parent_type, field, exclusive_type)),
+                q::Selection::InlineFragment(fragment) => {
+                    let fragment_type = match &fragment.type_condition {
+                        Some(q::TypeCondition::On(type_name)) => self.named_type(type_name),
+                        None => Some(parent_type),
+                    };
+                    if let Some(fragment_type) = fragment_type {
+                        self.collect_fields(
+                            fragment_type,
+                            &fragment.selection_set,
+                            exclusive_type,
+                            visited_fragments,
+                            fields,
+                        );
+                    }
+                }
+                q::Selection::FragmentSpread(spread) => {
+                    // Guard against fragment cycles, which are reported elsewhere
+                    if !visited_fragments.insert(&spread.fragment_name) {
+                        continue;
+                    }
+                    if let Some(&fragment) = self.fragments.get(&spread.fragment_name) {
+                        let q::TypeCondition::On(type_name) = &fragment.type_condition;
+                        if let Some(fragment_type) = self.named_type(type_name) {
+                            self.collect_fields(
+                                fragment_type,
+                                &fragment.selection_set,
+                                exclusive_type,
+                                visited_fragments,
+                                fields,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Fields with the same response key are merged into one field in the
+    /// response, which is only possible if they select the same field with
+    /// the same arguments, unless they can never apply to the same object.
+    fn validate_fields_can_merge(&mut self, fields: Vec<SelectedField<'a>>) {
+        let mut fields_by_key: IndexMap<&'a q::Name, Vec<SelectedField<'a>>> = IndexMap::new();
+        for field in fields {
+            fields_by_key
+                .entry(qast::get_response_key(field.1))
+                .or_insert_with(Vec::new)
+                .push(field);
+        }
+
+        for (response_key, fields) in fields_by_key {
+            // Fields that select the same field of the same type with the
+            // same arguments never conflict with each other, so only one of
+            // them needs to be compared with the other fields. Otherwise,
+            // repeating a field many times makes validation quadratic
+            let mut distinct_fields: Vec<&SelectedField<'a>> = vec![];
+            for field in fields.iter() {
+                if !distinct_fields
+                    .iter()
+                    .any(|other| same_selected_field(other, field))
+                {
+                    distinct_fields.push(field);
+                }
+            }
+
+            let conflict = distinct_fields.iter().enumerate().find_map(|(i, a)| {
+                distinct_fields[i + 1..]
+                    .iter()
+                    .find_map(|b| self.fields_conflict(a, b).map(|reason| (b.1, reason)))
+            });
+            if let Some((field, reason)) = conflict {
+                self.error(
+                    field.position,
+                    format!(
+                        "Fields `{}` conflict because {}. Use different aliases on the \
+                         fields to fetch both if this was intentional",
+                        response_key, reason
+                    ),
+                );
+                continue;
+            }
+
+            // The selections of the fields are merged, too
+            let mut sub_fields = vec![];
+            let mut visited_fragments = HashSet::new();
+            for (parent_type, field, exclusive_type) in fields {
+                if field.selection_set.items.is_empty() {
+                    continue;
+                }
+                let field_type =
+                    self.field_definition(parent_type, &field.name)
+                        .and_then(|definition| {
+                            self.named_type(named_type_name(&definition.field_type))
+                        });
+                if let Some(field_type) = field_type {
+                    let exclusive_type = exclusive_type.or_else(|| object_type_name(parent_type));
+                    self.collect_fields(
+                        field_type,
+                        &field.selection_set,
+                        exclusive_type,
+                        &mut visited_fragments,
+                        &mut sub_fields,
+                    );
+                }
+            }
+            self.validate_fields_can_merge(sub_fields);
+        }
+    }
+
+    /// Returns why two fields with the same response key cannot be merged,
+    /// if they can't.
+    fn fields_conflict(&self, a: &SelectedField<'a>, b: &SelectedField<'a>) -> Option<String> {
+        let &(parent_a, field_a, exclusive_a) = a;
+        let &(parent_b, field_b, exclusive_b) = b;
+
+        // Fields that apply to different object types never apply to the
+        // same object and only need to have the same type
+        let mutually_exclusive = match (
+            exclusive_a.or_else(|| object_type_name(parent_a)),
+            exclusive_b.or_else(|| object_type_name(parent_b)),
+        ) {
+            (Some(type_a), Some(type_b)) => type_a != type_b,
+            _ => false,
+        };
+
+        if !mutually_exclusive {
+            if field_a.name != field_b.name {
+                return Some(format!(
+                    "`{}` and `{}` are different fields",
+                    field_a.name, field_b.name
+                ));
+            }
+            if !same_arguments(&field_a.arguments, &field_b.arguments) {
+                return Some("they have differing arguments".to_owned());
+            }
+        }
+
+        match (
+            self.field_definition(parent_a, &field_a.name),
+            self.field_definition(parent_b, &field_b.name),
+        ) {
+            (Some(definition_a), Some(definition_b))
+                if definition_a.field_type != definition_b.field_type =>
+            {
+                Some(format!(
+                    "they return conflicting types `{}` and `{}`",
+                    definition_a.field_type, definition_b.field_type
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// Looks up a type in the schema, falling back to the introspection
+    /// schema for introspection types like `__Type`.
+    fn named_type(&self, name: &q::Name) -> Option<&'a s::TypeDefinition> {
+        sast::get_named_type(self.schema, name).or_else(|| {
+            if name.starts_with("__") {
+                sast::get_named_type(&INTROSPECTION_DOCUMENT, name)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Looks up the type of a fragment, which must be an object, interface
+    /// or union type.
+    fn composite_type(&mut self, position: Pos, name: &q::Name) -> Option<&'a s::TypeDefinition> {
+        match self.named_type(name) {
+            Some(named_type) if is_composite_type(named_type) => Some(named_type),
+            Some(_) => {
+                self.error(
+                    position,
+                    format!("Fragment cannot condition on non-composite type `{}`", name),
+                );
+                None
+            }
+            None => {
+                self.error(position, format!("Unknown type `{}`", name));
+                None
+            }
+        }
+    }
+
+    fn query_type(&self) -> Option<&'a s::TypeDefinition> {
+        sast::get_root_query_type_def(self.schema)
+    }
+
+    fn subscription_type(&self) -> Option<&'a s::TypeDefinition> {
+        sast::get_root_subscription_type(self.schema)
+            .and_then(|subscription_type| self.named_type(&subscription_type.name))
+    }
+
+    /// Looks up a field of an object, interface or union type. The root query
+    /// and subscription types also have the introspection fields `__schema`
+    /// and `__type`.
+    fn field_definition(
+        &self,
+        parent_type: &'a s::TypeDefinition,
+        name: &q::Name,
+    ) -> Option<&'a s::Field> {
+        match parent_type {
+            s::TypeDefinition::Object(object_type) => {
+                sast::get_field(object_type, name).or_else(|| {
+                    let is_root_type = |root_type: Option<&s::TypeDefinition>| {
+                        root_type.map_or(false, |root_type| ptr::eq(root_type, parent_type))
+                    };
+                    if is_root_type(self.query_type()) || is_root_type(self.subscription_type()) {
+                        sast::get_root_query_type(&INTROSPECTION_DOCUMENT).and_then(
+                            |introspection_type| sast::get_field(introspection_type, name),
+                        )
+                    } else {
+                        None
+                    }
+                })
+            }
+            s::TypeDefinition::Interface(interface_type) => sast::get_field(interface_type, name),
+
+            // Members of a union have no fields in common that could be selected
+            // on the union, except for `__typename`
+            s::TypeDefinition::Union(union_type) if name == "__typename" => {
+                sast::get_field(union_type, name)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the names of all object types an object of `t` could be.
+    fn possible_types(&self, t: &'a s::TypeDefinition) -> HashSet<&'a str> {
+        match t {
+            s::TypeDefinition::Object(object_type) => {
+                vec![object_type.name.as_str()].into_iter().collect()
+            }
+            s::TypeDefinition::Interface(interface_type) => {
+                sast::get_object_type_definitions(self.schema)
+                    .into_iter()
+                    .filter(|object_type| {
+                        object_type
+                            .implements_interfaces
+                            .contains(&interface_type.name)
+                    })
+                    .map(|object_type| object_type.name.as_str())
+                    .collect()
+            }
+            s::TypeDefinition::Union(union_type) => {
+                union_type.types.iter().map(|name| name.as_str()).collect()
+            }
+            _ => HashSet::new(),
+        }
+    }
+}
+
+fn operation_position(operation: &q::OperationDefinition) -> Pos {
+    match operation {
+        q::OperationDefinition::SelectionSet(selection_set) => selection_set.span.0,
+        q::OperationDefinition::Query(query) => query.position,
+        q::OperationDefinition::Mutation(mutation) => mutation.position,
+        q::OperationDefinition::Subscription(subscription) => subscription.position,
+    }
+}
+
+fn is_composite_type(t: &s::TypeDefinition) -> bool {
+    match t {
+        s::TypeDefinition::Object(_)
+        | s::TypeDefinition::Interface(_)
+        | s::TypeDefinition::Union(_) => true,
+        _ => false,
+    }
+}
+
+fn object_type_name(t: &s::TypeDefinition) -> Option<&str> {
+    match t {
+        s::TypeDefinition::Object(object_type) => Some(&object_type.name),
+        _ => None,
+    }
+}
+
+/// Returns the name of the named type inside of list and non-null types.
+fn named_type_name(t: &s::Type) -> &s::Name {
+    match t {
+        s::Type::NamedType(name) => name,
+        s::Type::ListType(inner) => named_type_name(inner),
+        s::Type::NonNullType(inner) => named_type_name(inner),
+    }
+}
+
+/// Whether two fields select the same field of the same type with the same
+/// arguments.
+fn same_selected_field(a: &SelectedField, b: &SelectedField) -> bool {
+    let &(parent_a, field_a, exclusive_a) = a;
+    let &(parent_b, field_b, exclusive_b) = b;
+    std::ptr::eq(parent_a, parent_b)
+        && exclusive_a == exclusive_b
+        && field_a.name == field_b.name
+        && same_arguments(&field_a.arguments, &field_b.arguments)
+}
+
+fn same_arguments(a: &[(q::Name, q::Value)], b: &[(q::Name, q::Value)]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .all(|(name, value)| qast::get_argument_value(b, name) == Some(value))
+}
+
+/// A variable may be used where a value of the variable's type, or a more
+/// general type, is expected. A nullable variable may also be used where a
+/// non-null value is expected, as long as there is a default value.
+fn is_variable_usage_allowed(definition: &q::VariableDefinition, usage: &VariableUsage) -> bool {
+    match (&definition.var_type, usage.location_type) {
+        (s::Type::NonNullType(_), _) => {
+            is_type_compatible(&definition.var_type, usage.location_type)
+        }
+        (variable_type, s::Type::NonNullType(location_type)) => {
+            let has_default = definition
+                .default_value
+                .as_ref()
+                .map_or(false, |value| value != &q::Value::Null)
+                || usage.location_has_default;
+            has_default && is_type_compatible(variable_type, location_type)
+        }
+        (variable_type, location_type) => is_type_compatible(variable_type, location_type),
+    }
+}
+
+fn is_type_compatible(variable_type: &s::Type, location_type: &s::Type) -> bool {
+    use graphql_parser::schema::Type::*;
+
+    match (variable_type, location_type) {
+        (NonNullType(variable_type), NonNullType(location_type)) => {
+            is_type_compatible(variable_type, location_type)
+        }
+        (NonNullType(variable_type), location_type) => {
+            is_type_compatible(variable_type, location_type)
+        }
+        (_, NonNullType(_)) => false,
+        (ListType(variable_type), ListType(location_type)) => {
+            is_type_compatible(variable_type, location_type)
+        }
+        (NamedType(variable_type), NamedType(location_type)) => variable_type == location_type,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use graphql_parser::{parse_query, parse_schema};
+
+    use super::*;
+
+    const SCHEMA: &str = "
+        scalar ID
+        scalar Int
+        scalar String
+        scalar Boolean
+
+        enum OrderDirection { asc, desc }
+
+        input Musician_filter { name: String, name_in: [String!] }
+
+        interface Node { id: ID! }
+
+        type Musician implements Node { id: ID!, name: String!, bands: [Band!]! }
+        type Band implements Node { id: ID!, name: String!, members: [Musician!]! }
+
+        union Act = Musician | Band
+
+        type Query {
+          musician(id: ID!): Musician
+          musicians(first: Int = 100, where: Musician_filter, orderDirection: OrderDirection): [Musician!]!
+          node(id: ID!): Node
+          acts: [Act!]!
+        }
+    ";
+
+    fn validate(query: &str) -> Vec<String> {
+        let schema = parse_schema(SCHEMA).unwrap();
+        let query = parse_query(query).unwrap();
+        match validate_query(&schema, &query) {
+            Ok(()) => vec![],
+            Err(errors) => errors.into_iter().map(|e| e.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn accepts_valid_queries() {
+        let errors = validate(
+            "
+            query Musicians($first: Int, $name: String, $skip: Boolean!) {
+              musicians(first: $first, where: { name: $name }, orderDirection: desc) {
+                ...MusicianFields
+                bands { id @skip(if: $skip) }
+              }
+              node(id: \"1\") { id ... on Band { name } }
+              acts { __typename ... on Musician { name } ... on Band { name } }
+              __schema { queryType { name } }
+            }
+
+            fragment MusicianFields on Musician { id name }
+            ",
+        );
+        assert_eq!(errors, Vec::<String>::new());
+    }
+
+    #[test]
+    fn reports_all_errors() {
+        let errors = validate(
+            "
+            query Musicians($unused: Int) {
+              musicians(first: \"ten\", limit: 5) { id age }
+              musician { name { first } }
+              acts { name }
+              node(id: $id) { ...Unknown }
+            }
+            ",
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "Invalid value provided for argument `first`: String(\"ten\")",
+                "Unknown argument `limit` on `musicians`",
+                "Type `Musician` has no field `age`",
+                "No value provided for required argument: `id`",
+                "Field `name` must not have a selection since type `String` has no subfields",
+                "Type `Act` has no field `name`",
+                "Unknown fragment `Unknown`",
+                "Variable `$id` is not defined",
+                "Variable `$unused` is never used",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_fragment_errors() {
+        let errors = validate(
+            "
+            query { musicians { ...A ... on Band { id } } }
+            fragment A on Musician { ...B }
+            fragment B on Musician { ...A }
+            fragment C on String { id }
+            ",
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "Fragment cannot condition on non-composite type `String`",
+                "Cannot spread fragment `A` within itself",
+                "Cannot spread fragment `B` within itself",
+                "Fragment cannot be spread here as objects of type `Musician` \
+                 can never be of type `Band`",
+                "Fragment `C` is never used",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_variable_type_mismatches() {
+        let errors = validate(
+            "
+            query($id: String!, $first: Int = 10, $name: [String]) {
+              musician(id: $id) { id }
+              musicians(first: $first, where: { name_in: $name }) { id }
+            }
+            ",
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "Variable `$id` of type `String!` cannot be used where a value of type `ID!` \
+                 is expected",
+                "Variable `$name` of type `[String]` cannot be used where a value of type \
+                 `[String!]` is expected",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_conflicting_fields() {
+        let errors = validate(
+            "
+            {
+              musicians { name: id name }
+              acts { ... on Musician { name } ... on Band { name } }
+              node(id: \"1\") { ... on Musician { x: name } ... on Band { x: id } }
+            }
+            ",
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "Fields `name` conflict because `id` and `name` are different fields. \
+                 Use different aliases on the fields to fetch both if this was intentional",
+                "Fields `x` conflict because they return conflicting types `String!` and `ID!`. \
+                 Use different aliases on the fields to fetch both if this was intentional",
+            ]
+        );
+    }
+
+    #[test]
+    fn repeated_fields_are_compared_once() {
+        let fields = "x: musicians(first: 1) { id } ".repeat(1000);
+        assert!(validate(&format!("{{ {} }}", fields)).is_empty());
+
+        let errors = validate(&format!("{{ {} x: musicians(first: 2) {{ id }} }}", fields));
+        assert_eq!(
+            errors,
+            vec![
+                "Fields `x` conflict because they have differing arguments. \
+                 Use different aliases on the fields to fetch both if this was intentional",
+            ]
+        );
+    }
+}
@@ -1,9 +1,20 @@
+use crate::execution::ObjectOrInterface;
 use crate::schema::ast;
 use graph::prelude::*;
 use graphql_parser::schema::{Value, *};
 use graphql_parser::Pos;
 use inflector::Inflector;
 
+/// The name of the root query field that reports which block and deployment
+/// the data of a query comes from.
+pub const META_FIELD_NAME: &str = "_meta";
+
+/// The type of the `_meta` field.
+pub const META_FIELD_TYPE: &str = "_Meta_";
+
+/// The type of the `block` field of `_Meta_`.
+pub const BLOCK_FIELD_TYPE: &str = "_Block_";
+
 #[derive(Fail, Debug)]
 pub enum APISchemaError {
     #[fail(display = "type {} already exists in the input schema", _0)]
@@ -22,6 +33,7 @@ pub fn api_schema(input_schema: &Document) -> Result<Document, APISchemaError> {
     // Refactor: Take `input_schema` by value.
     let object_types = ast::get_object_type_definitions(input_schema);
     let interface_types = ast::get_interface_type_definitions(input_schema);
+    let union_types = ast::get_union_type_definitions(input_schema);
 
     // Refactor: Don't clone the schema.
     let mut schema = input_schema.clone();
@@ -30,8 +42,10 @@ pub fn api_schema(input_schema: &Document) -> Result<Document, APISchemaError> {
     add_field_arguments(&mut schema, &input_schema)?;
     add_types_for_object_types(&mut schema, &object_types)?;
     add_types_for_interface_types(&mut schema, &interface_types)?;
-    add_query_type(&mut schema, &object_types, &interface_types)?;
-    add_subscription_type(&mut schema, &object_types, &interface_types)?;
+    add_types_for_union_types(&mut schema, &union_types)?;
+    add_meta_field_types(&mut schema)?;
+    add_query_type(&mut schema, &object_types, &interface_types, &union_types)?;
+    add_subscription_type(&mut schema, &object_types, &interface_types, &union_types)?;
     Ok(schema)
 }
 
@@ -45,6 +59,8 @@ fn add_builtin_scalar_types(schema: &mut Document) -> Result<(), APISchemaError>
         "String",
         "Bytes",
         "BigInt",
+        "Int8",
+        "Timestamp",
     ]
     .into_iter()
     {
@@ -86,6 +102,56 @@ fn add_order_direction_enum(schema: &mut Document) {
     schema.definitions.push(def);
 }
 
+/// Adds the `_Meta_` and `_Block_` types of the `_meta` field to the schema.
+fn add_meta_field_types(schema: &mut Document) -> Result<(), APISchemaError> {
+    let field = |name: &str, field_type: Type| Field {
+        position: Pos::default(),
+        description: None,
+        name: name.to_string(),
+        arguments: vec![],
+        field_type,
+        directives: vec![],
+    };
+    let non_null = |name: &str| Type::NonNullType(Box::new(Type::NamedType(name.to_string())));
+
+    let meta_types = vec![
+        (
+            BLOCK_FIELD_TYPE,
+            "The block the data of a query reflects",
+            vec![
+                field("number", non_null("Int")),
+                field("hash", Type::NamedType("Bytes".to_string())),
+            ],
+        ),
+        (
+            META_FIELD_TYPE,
+            "Indexing status of the deployment a query runs against",
+            vec![
+                field("block", non_null(BLOCK_FIELD_TYPE)),
+                field("deployment", non_null("String")),
+                field("hasIndexingErrors", non_null("Boolean")),
+            ],
+        ),
+    ];
+
+    for (name, description, fields) in meta_types {
+        if ast::get_named_type(schema, &name.to_string()).is_some() {
+            return Err(APISchemaError::TypeExists(name.to_string()));
+        }
+
+        let typedef = TypeDefinition::Object(ObjectType {
+            position: Pos::default(),
+            description: Some(description.to_string()),
+            name: name.to_string(),
+            implements_interfaces: vec![],
+            directives: vec![],
+            fields,
+        });
+        schema.definitions.push(Definition::TypeDefinition(typedef));
+    }
+    Ok(())
+}
+
 fn add_types_for_object_types(
     schema: &mut Document,
     object_types: &Vec<&ObjectType>,
@@ -109,6 +175,21 @@ fn add_types_for_interface_types(
     Ok(())
 }
 
+/// Adds `*_orderBy` and `*_filter` enum types for the given unions to the schema.
+/// Since union members have no fields in common except for `id`, these only
+/// allow ordering and filtering by `id`.
+fn add_types_for_union_types(
+    schema: &mut Document,
+    union_types: &[&UnionType],
+) -> Result<(), APISchemaError> {
+    for union_type in union_types {
+        let fields = ObjectOrInterface::from(*union_type).fields();
+        add_order_by_type(schema, &union_type.name, fields)?;
+        add_filter_type(schema, &union_type.name, fields)?;
+    }
+    Ok(())
+}
+
 /// Adds a `<type_name>_orderBy` enum type for the given fields to the schema.
 fn add_order_by_type(
     schema: &mut Document,
@@ -204,9 +285,11 @@ fn field_filter_input_values(
             let named_type = ast::get_named_type(schema, name)
                 .ok_or_else(|| APISchemaError::TypeNotFound(name.clone()))?;
             Ok(match named_type {
-                TypeDefinition::Object(_) | TypeDefinition::Interface(_) => {
-                    // Only add `where` filter fields for object and interface fields
-                    // if they are not @derivedFrom
+                TypeDefinition::Object(_)
+                | TypeDefinition::Interface(_)
+                | TypeDefinition::Union(_) => {
+                    // Only add `where` filter fields for object, interface and union
+                    // fields if they are not @derivedFrom
                     if ast::get_derived_from_directive(field).is_some() {
                         vec![]
                     } else {
@@ -246,6 +329,8 @@ fn field_scalar_filter_input_values(
         "BigDecimal" => vec!["", "not", "gt", "lt", "gte", "lte", "in", "not_in"],
         "ID" => vec!["", "not", "gt", "lt", "gte", "lte", "in", "not_in"],
         "Int" => vec!["", "not", "gt", "lt", "gte", "lte", "in", "not_in"],
+        "Int8" => vec!["", "not", "gt", "lt", "gte", "lte", "in", "not_in"],
+        "Timestamp" => vec!["", "not", "gt", "lt", "gte", "lte", "in", "not_in"],
         "List" => vec!["", "not", "in", "not_in", "contains", "not_contains"],
         "String" => vec![
             "",
@@ -262,6 +347,16 @@ fn field_scalar_filter_input_values(
             "not_starts_with",
             "ends_with",
             "not_ends_with",
+            "contains_nocase",
+            "not_contains_nocase",
+            "starts_with_nocase",
+            "not_starts_with_nocase",
+            "ends_with_nocase",
+            "not_ends_with_nocase",
+            "like",
+            "like_nocase",
+            "regex",
+            "regex_nocase",
         ],
         _ => vec!["", "not"],
     }
@@ -309,10 +404,10 @@ fn field_list_filter_input_values(
     // Only add a filter field if the type of the field exists in the schema
     ast::get_type_definition_from_type(schema, field_type).and_then(|typedef| {
         // Decide what type of values can be passed to the filter. In the case
-        // one-to-many or many-to-many object or interface fields that are not
-        // derived, we allow ID strings to be passed on.
+        // one-to-many or many-to-many object, interface or union fields that
+        // are not derived, we allow ID strings to be passed on.
         let input_field_type = match typedef {
-            TypeDefinition::Interface(_) | TypeDefinition::Object(_) => {
+            TypeDefinition::Interface(_) | TypeDefinition::Object(_) | TypeDefinition::Union(_) => {
                 if ast::get_derived_from_directive(field).is_some() {
                     return None;
                 } else {
@@ -321,22 +416,30 @@ fn field_list_filter_input_values(
             }
             TypeDefinition::Scalar(ref t) => Type::NamedType(t.name.to_owned()),
             TypeDefinition::Enum(ref t) => Type::NamedType(t.name.to_owned()),
-            TypeDefinition::InputObject(_) | TypeDefinition::Union(_) => return None,
+            TypeDefinition::InputObject(_) => return None,
         };
 
         Some(
-            vec!["", "not", "contains", "not_contains"]
-                .into_iter()
-                .map(|filter_type| {
-                    input_value(
-                        &field.name,
-                        filter_type,
-                        Type::ListType(Box::new(Type::NonNullType(Box::new(
-                            input_field_type.clone(),
-                        )))),
-                    )
-                })
-                .collect(),
+            vec![
+                "",
+                "not",
+                "contains",
+                "not_contains",
+                "contains_any",
+                "contains_all",
+                "is_empty",
+            ]
+            .into_iter()
+            .map(|filter_type| {
+                let value_type = match filter_type {
+                    "is_empty" => Type::NamedType("Boolean".into()),
+                    _ => Type::ListType(Box::new(Type::NonNullType(Box::new(
+                        input_field_type.clone(),
+                    )))),
+                };
+                input_value(&field.name, filter_type, value_type)
+            })
+            .collect(),
         )
     })
 }
@@ -362,6 +465,7 @@ fn add_query_type(
     schema: &mut Document,
     object_types: &[&ObjectType],
     interface_types: &[&InterfaceType],
+    union_types: &[&UnionType],
 ) -> Result<(), APISchemaError> {
     let type_name = String::from("Query");
 
@@ -379,7 +483,19 @@ fn add_query_type(
             .iter()
             .map(|t| &t.name)
             .chain(interface_types.iter().map(|t| &t.name))
+            .chain(union_types.iter().map(|t| &t.name))
             .flat_map(|name| query_fields_for_type(schema, name))
+            .chain(Some(Field {
+                position: Pos::default(),
+                description: Some(
+                    "Access to the block and deployment the data of the query comes from"
+                        .to_string(),
+                ),
+                name: META_FIELD_NAME.to_string(),
+                arguments: vec![],
+                field_type: Type::NamedType(META_FIELD_TYPE.to_string()),
+                directives: vec![],
+            }))
             .collect(),
     });
     let def = Definition::TypeDefinition(typedef);
@@ -392,6 +508,7 @@ fn add_subscription_type(
     schema: &mut Document,
     object_types: &[&ObjectType],
     interface_types: &[&InterfaceType],
+    union_types: &[&UnionType],
 ) -> Result<(), APISchemaError> {
     let type_name = String::from("Subscription");
 
@@ -409,6 +526,7 @@ fn add_subscription_type(
             .iter()
             .map(|t| &t.name)
             .chain(interface_types.iter().map(|t| &t.name))
+            .chain(union_types.iter().map(|t| &t.name))
             .flat_map(|name| query_fields_for_type(schema, name))
             .collect(),
     });
@@ -524,8 +642,12 @@ fn add_field_arguments(
                             field.arguments =
                                 collection_arguments_for_named_type(&input_objects, &it.name);
                         }
+                        TypeDefinition::Union(ut) => {
+                            field.arguments =
+                                collection_arguments_for_named_type(&input_objects, &ut.name);
+                        }
                         _ => unreachable!(
-                            "referenced entity types can only be object or interface types"
+                            "referenced entity types can only be object, interface or union types"
                         ),
                     }
                 }
@@ -558,8 +680,12 @@ fn add_field_arguments(
                             field.arguments =
                                 collection_arguments_for_named_type(&input_objects, &it.name);
                         }
+                        TypeDefinition::Union(ut) => {
+                            field.arguments =
+                                collection_arguments_for_named_type(&input_objects, &ut.name);
+                        }
                         _ => unreachable!(
-                            "referenced entity types can only be object or interface types"
+                            "referenced entity types can only be object, interface or union types"
                         ),
                     }
                 }
@@ -592,6 +718,10 @@ mod tests {
             .expect("BigDecimal type is missing in API schema");
         ast::get_named_type(&schema, &"String".to_string())
             .expect("String type is missing in API schema");
+        ast::get_named_type(&schema, &"Int8".to_string())
+            .expect("Int8 type is missing in API schema");
+        ast::get_named_type(&schema, &"Timestamp".to_string())
+            .expect("Timestamp type is missing in API schema");
     }
 
     #[test]
@@ -621,6 +751,37 @@ mod tests {
             .expect("Root Query type is missing in API schema");
     }
 
+    #[test]
+    fn api_schema_contains_meta_field() {
+        let input_schema =
+            parse_schema("type User { id: ID! }").expect("Failed to parse input schema");
+        let schema = api_schema(&input_schema).expect("Failed to derive API schema");
+
+        let meta_field = match ast::get_named_type(&schema, &"Query".to_string()) {
+            Some(TypeDefinition::Object(t)) => ast::get_field(t, &"_meta".to_string()),
+            _ => None,
+        }
+        .expect("\"_meta\" field is missing on Query type");
+        assert_eq!(meta_field.field_type, Type::NamedType("_Meta_".to_string()));
+
+        let meta_type = match ast::get_named_type(&schema, &"_Meta_".to_string()) {
+            Some(TypeDefinition::Object(t)) => t,
+            _ => panic!("_Meta_ type is missing in API schema"),
+        };
+        let fields: Vec<&Name> = meta_type.fields.iter().map(|field| &field.name).collect();
+        assert_eq!(
+            fields,
+            [
+                &"block".to_string(),
+                &"deployment".to_string(),
+                &"hasIndexingErrors".to_string()
+            ]
+        );
+
+        ast::get_named_type(&schema, &"_Block_".to_string())
+            .expect("_Block_ type is missing in API schema");
+    }
+
     #[test]
     fn api_schema_contains_field_order_by_enum() {
         let input_schema = parse_schema("type User { id: ID!, name: String! }")
@@ -703,14 +864,30 @@ mod tests {
                 "name_not_starts_with",
                 "name_ends_with",
                 "name_not_ends_with",
+                "name_contains_nocase",
+                "name_not_contains_nocase",
+                "name_starts_with_nocase",
+                "name_not_starts_with_nocase",
+                "name_ends_with_nocase",
+                "name_not_ends_with_nocase",
+                "name_like",
+                "name_like_nocase",
+                "name_regex",
+                "name_regex_nocase",
                 "favoritePetNames",
                 "favoritePetNames_not",
                 "favoritePetNames_contains",
                 "favoritePetNames_not_contains",
+                "favoritePetNames_contains_any",
+                "favoritePetNames_contains_all",
+                "favoritePetNames_is_empty",
                 "pets",
                 "pets_not",
                 "pets_contains",
                 "pets_not_contains",
+                "pets_contains_any",
+                "pets_contains_all",
+                "pets_is_empty",
                 "favoritePet",
                 "favoritePet_not",
                 "favoritePet_gt",
@@ -725,6 +902,16 @@ mod tests {
                 "favoritePet_not_starts_with",
                 "favoritePet_ends_with",
                 "favoritePet_not_ends_with",
+                "favoritePet_contains_nocase",
+                "favoritePet_not_contains_nocase",
+                "favoritePet_starts_with_nocase",
+                "favoritePet_not_starts_with_nocase",
+                "favoritePet_ends_with_nocase",
+                "favoritePet_not_ends_with_nocase",
+                "favoritePet_like",
+                "favoritePet_like_nocase",
+                "favoritePet_regex",
+                "favoritePet_regex_nocase",
             ]
             .iter()
             .map(|name| name.to_string())
@@ -872,4 +1059,55 @@ mod tests {
                 .collect::<Vec<String>>()
         );
     }
+
+    #[test]
+    fn api_schema_contains_union_fields_on_query_type() {
+        let input_schema = parse_schema(
+            "
+            type Cat @entity { id: ID!, name: String! }
+            type Dog @entity { id: ID!, barks: Boolean! }
+            union Pet = Cat | Dog
+            ",
+        )
+        .expect("Failed to parse input schema");
+        let schema = api_schema(&input_schema).expect("Failed to derived API schema");
+
+        let query_type = ast::get_named_type(&schema, &"Query".to_string())
+            .expect("Query type is missing in derived API schema");
+
+        let singular_field = match query_type {
+            TypeDefinition::Object(ref t) => ast::get_field(t, &"pet".to_string()),
+            _ => None,
+        }
+        .expect("\"pet\" field is missing on Query type");
+        assert_eq!(
+            singular_field.field_type,
+            Type::NamedType("Pet".to_string())
+        );
+
+        let plural_field = match query_type {
+            TypeDefinition::Object(ref t) => ast::get_field(t, &"pets".to_string()),
+            _ => None,
+        }
+        .expect("\"pets\" field is missing on Query type");
+        assert_eq!(
+            plural_field
+                .arguments
+                .iter()
+                .map(|input_value| input_value.name.to_owned())
+                .collect::<Vec<String>>(),
+            ["skip", "first", "orderBy", "orderDirection", "where",]
+                .into_iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<String>>()
+        );
+
+        let order_by = match ast::get_named_type(&schema, &"Pet_orderBy".to_string()) {
+            Some(TypeDefinition::Enum(t)) => Some(t),
+            _ => None,
+        }
+        .expect("Pet_orderBy type is missing in derived API schema");
+        let values: Vec<&Name> = order_by.values.iter().map(|value| &value.name).collect();
+        assert_eq!(values, [&"id".to_string()]);
+    }
 }
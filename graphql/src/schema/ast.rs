@@ -22,12 +22,43 @@ pub(crate) enum FilterOp {
     NotStartsWith,
     EndsWith,
     NotEndsWith,
+    ContainsNoCase,
+    NotContainsNoCase,
+    StartsWithNoCase,
+    NotStartsWithNoCase,
+    EndsWithNoCase,
+    NotEndsWithNoCase,
+    Like,
+    LikeNoCase,
+    Regex,
+    RegexNoCase,
+    ContainsAny,
+    ContainsAll,
+    IsEmpty,
     Equal,
 }
 
 /// Split a "name_eq" style name into an attribute ("name") and a filter op (`Equal`).
 pub(crate) fn parse_field_as_filter(key: &Name) -> (Name, FilterOp) {
     let (suffix, op) = match key {
+        k if k.ends_with("_not_contains_nocase") => {
+            ("_not_contains_nocase", FilterOp::NotContainsNoCase)
+        }
+        k if k.ends_with("_contains_nocase") => ("_contains_nocase", FilterOp::ContainsNoCase),
+        k if k.ends_with("_not_starts_with_nocase") => {
+            ("_not_starts_with_nocase", FilterOp::NotStartsWithNoCase)
+        }
+        k if k.ends_with("_starts_with_nocase") => {
+            ("_starts_with_nocase", FilterOp::StartsWithNoCase)
+        }
+        k if k.ends_with("_not_ends_with_nocase") => {
+            ("_not_ends_with_nocase", FilterOp::NotEndsWithNoCase)
+        }
+        k if k.ends_with("_ends_with_nocase") => ("_ends_with_nocase", FilterOp::EndsWithNoCase),
+        k if k.ends_with("_like_nocase") => ("_like_nocase", FilterOp::LikeNoCase),
+        k if k.ends_with("_like") => ("_like", FilterOp::Like),
+        k if k.ends_with("_regex_nocase") => ("_regex_nocase", FilterOp::RegexNoCase),
+        k if k.ends_with("_regex") => ("_regex", FilterOp::Regex),
         k if k.ends_with("_not") => ("_not", FilterOp::Not),
         k if k.ends_with("_gt") => ("_gt", FilterOp::GreaterThan),
         k if k.ends_with("_lt") => ("_lt", FilterOp::LessThan),
@@ -41,6 +72,9 @@ pub(crate) fn parse_field_as_filter(key: &Name) -> (Name, FilterOp) {
         k if k.ends_with("_not_ends_with") => ("_not_ends_with", FilterOp::NotEndsWith),
         k if k.ends_with("_starts_with") => ("_starts_with", FilterOp::StartsWith),
         k if k.ends_with("_ends_with") => ("_ends_with", FilterOp::EndsWith),
+        k if k.ends_with("_contains_any") => ("_contains_any", FilterOp::ContainsAny),
+        k if k.ends_with("_contains_all") => ("_contains_all", FilterOp::ContainsAll),
+        k if k.ends_with("_is_empty") => ("_is_empty", FilterOp::IsEmpty),
         _ => ("", FilterOp::Equal),
     };
 
@@ -135,6 +169,18 @@ pub fn get_object_type_mut<'a>(
     })
 }
 
+/// Returns all union definitions in the schema.
+pub fn get_union_type_definitions(schema: &Document) -> Vec<&UnionType> {
+    schema
+        .definitions
+        .iter()
+        .filter_map(|d| match d {
+            Definition::TypeDefinition(TypeDefinition::Union(t)) => Some(t),
+            _ => None,
+        })
+        .collect()
+}
+
 /// Returns all interface definitions in the schema.
 pub fn get_interface_type_definitions(schema: &Document) -> Vec<&InterfaceType> {
     schema
@@ -387,8 +433,10 @@ pub fn is_entity_type_definition(type_def: &TypeDefinition) -> bool {
         // thus, any interface type definition is automatically an entity type
         Interface(_) => true,
 
-        // Everything else (unions, scalars, enums) are not considered entity
-        // types for now
+        // Schema validation ensures that unions only contain entity types
+        Union(_) => true,
+
+        // Everything else (scalars, enums) are not considered entity types
         _ => false,
     }
 }
@@ -455,3 +503,19 @@ pub fn get_derived_from_field<'a>(
         })
         .and_then(|derived_from_field_name| get_field(object_type, derived_from_field_name))
 }
+
+/// If the field has a `@cost(value: n)` directive, obtain the cost `n` that
+/// querying the field adds to the complexity of a query.
+pub fn get_field_cost(field_definition: &Field) -> Option<u64> {
+    field_definition
+        .directives
+        .iter()
+        .find(|directive| directive.name == Name::from("cost"))
+        .and_then(|directive| qast::get_argument_value(&directive.arguments, "value"))
+        .and_then(|value| match value {
+            Value::Int(n) => n.as_i64(),
+            _ => None,
+        })
+        .filter(|n| *n >= 0)
+        .map(|n| n as u64)
+}
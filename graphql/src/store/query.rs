@@ -16,6 +16,13 @@ lazy_static! {
         .map(|s| u64::from_str(&s)
             .unwrap_or_else(|_| panic!("failed to parse env var GRAPHQL_MAX_FIRST")))
         .unwrap_or(1000);
+    static ref GRAPHQL_MAX_FILTER_PATTERN_LENGTH: usize =
+        env::var("GRAPH_GRAPHQL_MAX_FILTER_PATTERN_LENGTH")
+            .ok()
+            .map(|s| usize::from_str(&s).unwrap_or_else(|_| {
+                panic!("failed to parse env var GRAPH_GRAPHQL_MAX_FILTER_PATTERN_LENGTH")
+            }))
+            .unwrap_or(256);
 }
 
 /// Builds a EntityQuery from GraphQL arguments.
@@ -29,10 +36,10 @@ pub fn build_query<'a>(
     let entity = entity.into();
     let entity_types = match &entity {
         ObjectOrInterface::Object(object) => vec![object.name.clone()],
-        ObjectOrInterface::Interface(interface) => types_for_interface[&interface.name]
-            .iter()
-            .map(|o| o.name.clone())
-            .collect(),
+        ObjectOrInterface::Interface(_) | ObjectOrInterface::Union(_) => {
+            let object_types = &types_for_interface[entity.name()];
+            object_types.iter().map(|o| o.name.clone()).collect()
+        }
     };
     Ok(EntityQuery {
         subgraph_id: parse_subgraph_id(entity)?,
@@ -142,6 +149,35 @@ fn build_filter_from_object(
                     NotStartsWith => EntityFilter::NotStartsWith(field_name, store_value),
                     EndsWith => EntityFilter::EndsWith(field_name, store_value),
                     NotEndsWith => EntityFilter::NotEndsWith(field_name, store_value),
+                    ContainsNoCase => EntityFilter::ContainsNoCase(field_name, store_value),
+                    NotContainsNoCase => EntityFilter::NotContainsNoCase(field_name, store_value),
+                    StartsWithNoCase => EntityFilter::StartsWithNoCase(field_name, store_value),
+                    NotStartsWithNoCase => {
+                        EntityFilter::NotStartsWithNoCase(field_name, store_value)
+                    }
+                    EndsWithNoCase => EntityFilter::EndsWithNoCase(field_name, store_value),
+                    NotEndsWithNoCase => EntityFilter::NotEndsWithNoCase(field_name, store_value),
+                    Like => EntityFilter::Like(field_name, pattern(store_value, "_like")?),
+                    LikeNoCase => {
+                        EntityFilter::LikeNoCase(field_name, pattern(store_value, "_like_nocase")?)
+                    }
+                    Regex => EntityFilter::Regex(field_name, pattern(store_value, "_regex")?),
+                    RegexNoCase => EntityFilter::RegexNoCase(
+                        field_name,
+                        pattern(store_value, "_regex_nocase")?,
+                    ),
+                    ContainsAny => EntityFilter::ContainsAny(
+                        field_name,
+                        list_values(store_value, "_contains_any")?,
+                    ),
+                    ContainsAll => EntityFilter::ContainsAll(
+                        field_name,
+                        list_values(store_value, "_contains_all")?,
+                    ),
+                    IsEmpty => match store_value {
+                        Value::Bool(is_empty) => EntityFilter::IsEmpty(field_name, is_empty),
+                        _ => return Err(QueryExecutionError::InvalidFilterError),
+                    },
                     Equal => EntityFilter::Equal(field_name, store_value),
                 })
             })
@@ -177,6 +213,20 @@ fn list_values(value: Value, filter_type: &str) -> Result<Vec<Value>, QueryExecu
     }
 }
 
+/// Checks that a pattern passed to a `_like` or `_regex` filter is not too
+/// long, which keeps the cost of matching it in the database bounded.
+fn pattern(value: Value, filter_type: &str) -> Result<Value, QueryExecutionError> {
+    match value {
+        Value::String(ref s) if s.chars().count() > *GRAPHQL_MAX_FILTER_PATTERN_LENGTH => {
+            Err(QueryExecutionError::FilterPatternTooLong(
+                filter_type.to_string(),
+                *GRAPHQL_MAX_FILTER_PATTERN_LENGTH,
+            ))
+        }
+        value => Ok(value),
+    }
+}
+
 /// Parses GraphQL arguments into an field name to order by, if present.
 fn build_order_by(
     entity: ObjectOrInterface,
@@ -260,8 +310,30 @@ pub fn collect_entities_from_query_field(
             // this should always be the case)
             if let Some(type_definition) = sast::get_type_definition_from_field(schema, field_type)
             {
-                // If the field's type definition is an object type, extract that type
-                if let s::TypeDefinition::Object(object_type) = type_definition {
+                // If the field's type definition is an object type, extract that type;
+                // for interfaces and unions, extract all types that may be returned
+                let object_types: Vec<&s::ObjectType> = match type_definition {
+                    s::TypeDefinition::Object(object_type) => vec![object_type],
+                    s::TypeDefinition::Interface(interface_type) => {
+                        sast::get_object_type_definitions(schema)
+                            .into_iter()
+                            .filter(|object_type| {
+                                object_type
+                                    .implements_interfaces
+                                    .contains(&interface_type.name)
+                            })
+                            .collect()
+                    }
+                    s::TypeDefinition::Union(union_type) => {
+                        sast::get_object_type_definitions(schema)
+                            .into_iter()
+                            .filter(|object_type| union_type.types.contains(&object_type.name))
+                            .collect()
+                    }
+                    _ => vec![],
+                };
+
+                for object_type in object_types {
                     // Only collect whether the field's type has an @entity directive
                     if sast::get_object_type_directive(object_type, String::from("entity"))
                         .is_some()
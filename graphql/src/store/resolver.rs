@@ -1,5 +1,6 @@
 use graphql_parser::{query as q, schema as s};
 use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
 use std::result;
 use std::sync::Arc;
 
@@ -8,12 +9,15 @@ use graph::prelude::*;
 
 use crate::prelude::*;
 use crate::schema::ast as sast;
+use crate::schema::META_FIELD_NAME;
 use crate::store::query::{collect_entities_from_query_field, parse_subgraph_id};
 
 /// A resolver that fetches entities from a `Store`.
 pub struct StoreResolver<S> {
     logger: Logger,
     store: Arc<S>,
+    snapshot: Option<Arc<QueryStore>>,
+    trace: Option<Arc<Trace>>,
 }
 
 impl<S> Clone for StoreResolver<S>
@@ -24,6 +28,8 @@ where
         StoreResolver {
             logger: self.logger.clone(),
             store: self.store.clone(),
+            snapshot: self.snapshot.clone(),
+            trace: self.trace.clone(),
         }
     }
 }
@@ -36,9 +42,107 @@ where
         StoreResolver {
             logger: logger.new(o!("component" => "StoreResolver")),
             store,
+            snapshot: None,
+            trace: None,
         }
     }
 
+    /// Makes all reads of the resolver see the same state of the store, even
+    /// if changes are committed in the meantime. The resolver must only be
+    /// used for a single query, since it holds on to that state until it is
+    /// dropped.
+    pub fn with_snapshot(mut self) -> Result<Self, QueryExecutionError> {
+        self.snapshot = Some(self.store.clone().query_store()?);
+        Ok(self)
+    }
+
+    /// Records the SQL of all store queries in `trace`.
+    pub fn with_trace(mut self, trace: Arc<Trace>) -> Self {
+        self.trace = Some(trace);
+        self
+    }
+
+    /// Finds entities in the store, recording the SQL that finds them if the
+    /// query is traced.
+    fn find(&self, query: EntityQuery) -> Result<Vec<Entity>, QueryExecutionError> {
+        let (entities, sql) = match self.snapshot {
+            Some(ref snapshot) => snapshot.find_with_sql(query)?,
+            None => self.store.find_with_sql(query)?,
+        };
+        if let (Some(trace), Some(sql)) = (&self.trace, sql) {
+            trace.sql(sql);
+        }
+        Ok(entities)
+    }
+
+    /// Finds a single entity in the store; goes through `find` so that the
+    /// SQL is recorded like for any other query.
+    fn find_one(&self, mut query: EntityQuery) -> Result<Option<Entity>, QueryExecutionError> {
+        query.range = EntityRange::first(1);
+        Ok(self.find(query)?.pop())
+    }
+
+    /// Looks up an entity by its key, recording the SQL that finds it if the
+    /// query is traced.
+    fn get(&self, key: EntityKey) -> Result<Option<Entity>, QueryExecutionError> {
+        let (entity, sql) = match self.snapshot {
+            Some(ref snapshot) => snapshot.get_with_sql(key)?,
+            None => self.store.get_with_sql(key)?,
+        };
+        if let (Some(trace), Some(sql)) = (&self.trace, sql) {
+            trace.sql(sql);
+        }
+        Ok(entity)
+    }
+
+    /// Returns the block the deployment is at, as seen by the snapshot of
+    /// the resolver if it has one.
+    pub fn block_ptr(
+        &self,
+        subgraph_id: SubgraphDeploymentId,
+    ) -> Result<EthereumBlockPointer, Error> {
+        match self.snapshot {
+            Some(ref snapshot) => snapshot.block_ptr(subgraph_id),
+            None => self.store.block_ptr(subgraph_id),
+        }
+    }
+
+    /// Looks up the block the deployment is at and whether indexing it
+    /// failed, for the `_meta` field.
+    fn lookup_meta(
+        &self,
+        object_type: ObjectOrInterface<'_>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        let subgraph_id = parse_subgraph_id(object_type)?;
+        let block_ptr = self
+            .block_ptr(subgraph_id.clone())
+            .map_err(QueryExecutionError::StoreError)?;
+        let has_indexing_errors = self
+            .get(SubgraphDeploymentEntity::key(subgraph_id.clone()))?
+            .and_then(|entity| entity.get("failed").cloned())
+            .map_or(false, |failed| failed == Value::Bool(true));
+
+        // GraphQL `Int`s are 32 bit
+        let block_number = i32::try_from(block_ptr.number).map_err(|_| {
+            QueryExecutionError::ValueParseError(
+                "Int".to_owned(),
+                format!("block number {} is too large", block_ptr.number),
+            )
+        })?;
+
+        Ok(object_value(vec![
+            (
+                "block",
+                object_value(vec![
+                    ("number", q::Value::Int(q::Number::from(block_number))),
+                    ("hash", q::Value::String(format!("0x{:x}", block_ptr.hash))),
+                ]),
+            ),
+            ("deployment", q::Value::String(subgraph_id.to_string())),
+            ("hasIndexingErrors", q::Value::Boolean(has_indexing_errors)),
+        ]))
+    }
+
     /// Adds a filter for matching entities that correspond to a derived field.
     ///
     /// Returns true if the field is a derived field (i.e., if it is defined with
@@ -93,6 +197,35 @@ where
         };
     }
 
+    /// Looks up the entity with the given ID. For interfaces and unions,
+    /// the entity may be of any of the types implementing the interface or
+    /// belonging to the union.
+    fn get_entity(
+        &self,
+        subgraph_id: SubgraphDeploymentId,
+        object_type: ObjectOrInterface<'_>,
+        id: &str,
+        types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+    ) -> Result<Option<Entity>, QueryExecutionError> {
+        match object_type {
+            ObjectOrInterface::Object(_) => self.get(EntityKey {
+                subgraph_id,
+                entity_type: object_type.name().to_owned(),
+                entity_id: id.to_owned(),
+            }),
+            ObjectOrInterface::Interface(_) | ObjectOrInterface::Union(_) => {
+                let entity_types = types_for_interface[object_type.name()]
+                    .iter()
+                    .map(|o| o.name.clone())
+                    .collect();
+                let range = EntityRange::first(1);
+                let query = EntityQuery::new(subgraph_id, entity_types, range)
+                    .filter(EntityFilter::Equal(String::from("id"), Value::from(id)));
+                self.find_one(query)
+            }
+        }
+    }
+
     /// Adds a filter for matching entities that are referenced by the given field.
     fn add_filter_for_reference_field(
         query: &mut EntityQuery,
@@ -195,7 +328,7 @@ where
         }
 
         let mut entity_values = Vec::new();
-        for entity in self.store.find(query)? {
+        for entity in self.find(query)? {
             entity_values.push(entity.into())
         }
         Ok(q::Value::List(entity_values))
@@ -210,6 +343,10 @@ where
         arguments: &HashMap<&q::Name, q::Value>,
         types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
     ) -> Result<q::Value, QueryExecutionError> {
+        if parent.is_none() && field.name == META_FIELD_NAME {
+            return self.lookup_meta(object_type);
+        }
+
         let id = arguments.get(&"id".to_string()).and_then(|id| match id {
             q::Value::String(s) => Some(s),
             _ => None,
@@ -218,22 +355,7 @@ where
         // subgraph_id directive is injected in all types.
         let subgraph_id = parse_subgraph_id(object_type).unwrap();
         let entity = if let Some(id) = id {
-            match object_type {
-                ObjectOrInterface::Object(_) => self.store.get(EntityKey {
-                    subgraph_id,
-                    entity_type: object_type.name().to_owned(),
-                    entity_id: id.to_owned(),
-                })?,
-                ObjectOrInterface::Interface(interface) => {
-                    let entity_types = types_for_interface[&interface.name]
-                        .iter()
-                        .map(|o| o.name.clone())
-                        .collect();
-                    let range = EntityRange::first(1);
-                    let query = EntityQuery::new(subgraph_id, entity_types, range);
-                    self.store.find(query)?.into_iter().next()
-                }
-            }
+            self.get_entity(subgraph_id, object_type, id, types_for_interface)?
         } else {
             // Identify whether the field is derived with @derivedFrom
             let derived_from_field = sast::get_derived_from_field(object_type, field_definition);
@@ -254,7 +376,7 @@ where
                 Self::add_filter_for_derived_field(&mut query, parent, derived_from_field);
 
                 // Find the entity or entities that reference the parent entity
-                let entities = self.store.find(query)?;
+                let entities = self.find(query)?;
 
                 if entities.len() > 1 {
                     return Err(QueryExecutionError::AmbiguousDerivedFromResult(
@@ -269,11 +391,12 @@ where
             } else {
                 match parent {
                     Some(q::Value::Object(parent_object)) => match parent_object.get(&field.name) {
-                        Some(q::Value::String(id)) => self.store.get(EntityKey {
-                            subgraph_id,
-                            entity_type: object_type.name().to_owned(),
-                            entity_id: id.to_owned(),
-                        })?,
+                        Some(q::Value::String(id)) => {
+                            self.get_entity(subgraph_id, object_type, id, types_for_interface)?
+                        }
+                        // Objects that are not entities, like the `block` of
+                        // `_meta`, are part of their parent
+                        Some(value @ q::Value::Object(_)) => return Ok(value.clone()),
                         _ => None,
                     },
                     _ => panic!("top level queries must either take an `id` or return a list"),
@@ -287,6 +410,10 @@ where
         })
     }
 
+    fn for_execution(&self) -> result::Result<Self, QueryExecutionError> {
+        self.clone().with_snapshot()
+    }
+
     fn resolve_field_stream<'a, 'b>(
         &self,
         schema: &'a s::Document,
@@ -1,14 +1,22 @@
 use graphql_parser::{query as q, schema as s, Style};
 use std::collections::HashMap;
 use std::result::Result;
+use std::sync::atomic::AtomicUsize;
 use std::time::{Duration, Instant};
 
 use graph::prelude::*;
+use graph::serde_json;
 
 use crate::execution::*;
 use crate::query::ast as qast;
+use crate::query::validate_query;
 use crate::schema::ast as sast;
 
+/// Sharing of subscription results between identical subscriptions.
+mod shared;
+
+pub use self::shared::SharedSubscriptions;
+
 /// Options available for subscription execution.
 pub struct SubscriptionExecutionOptions<R>
 where
@@ -28,6 +36,9 @@ where
 
     /// Maximum depth for a subscription query.
     pub max_depth: u8,
+
+    /// Maximum number of entities each subscription result may contain.
+    pub max_entities: Option<usize>,
 }
 
 pub fn execute_subscription<R>(
@@ -37,6 +48,12 @@ pub fn execute_subscription<R>(
 where
     R: Resolver + 'static,
 {
+    // Reject invalid subscriptions before executing any part of them
+    validate_query(
+        &subscription.query.schema.document,
+        &subscription.query.document,
+    )?;
+
     // Obtain the only operation of the subscription (fail if there is none or more than one)
     let operation = qast::get_operation(&subscription.query.document, None)?;
 
@@ -59,6 +76,10 @@ where
         fields: vec![],
         variable_values: Arc::new(coerced_variable_values),
         deadline: None,
+        max_entities: options.max_entities,
+        entity_count: Arc::new(AtomicUsize::new(0)),
+        trace: None,
+        path: vec![],
     };
 
     match operation {
@@ -159,6 +180,7 @@ where
     let document = ctx.document.clone();
     let selection_set = selection_set.to_owned();
     let variable_values = ctx.variable_values.clone();
+    let max_entities = ctx.max_entities;
 
     // Create a stream with a single empty event. By chaining this in front
     // of the real events, we trick the subscription into executing its query
@@ -170,20 +192,36 @@ where
         changes: Default::default(),
     }]);
 
-    Ok(Box::new(trigger_stream.chain(source_stream).map(
-        move |event| {
-            execute_subscription_event(
-                logger.clone(),
-                resolver.clone(),
-                schema.clone(),
-                document.clone(),
-                &selection_set,
-                variable_values.clone(),
-                event,
-                timeout.clone(),
-            )
-        },
-    )))
+    // The serialized form of the last result delivered, used to avoid sending
+    // results that are identical to the previous one
+    let mut last_result: Option<String> = None;
+
+    Ok(Box::new(
+        trigger_stream
+            .chain(source_stream)
+            .map(move |event| {
+                execute_subscription_event(
+                    logger.clone(),
+                    resolver.clone(),
+                    schema.clone(),
+                    document.clone(),
+                    &selection_set,
+                    variable_values.clone(),
+                    event,
+                    timeout.clone(),
+                    max_entities,
+                )
+            })
+            .filter_map(move |result| {
+                let serialized = serde_json::to_string(&result).ok();
+                if serialized.is_some() && serialized == last_result {
+                    None
+                } else {
+                    last_result = serialized;
+                    Some(Arc::new(result))
+                }
+            }),
+    ))
 }
 
 fn execute_subscription_event<R1>(
@@ -195,12 +233,20 @@ fn execute_subscription_event<R1>(
     variable_values: Arc<HashMap<q::Name, q::Value>>,
     event: StoreEvent,
     timeout: Option<Duration>,
+    max_entities: Option<usize>,
 ) -> QueryResult
 where
     R1: Resolver + 'static,
 {
     debug!(logger, "Execute subscription event"; "event" => format!("{:?}", event));
 
+    // Every execution reads from its own snapshot of the store, so that the
+    // result does not mix data from different blocks
+    let resolver = match resolver.for_execution() {
+        Ok(resolver) => Arc::new(resolver),
+        Err(e) => return QueryResult::from(e),
+    };
+
     // Create a fresh execution context with deadline.
     let ctx = ExecutionContext {
         logger: logger,
@@ -210,6 +256,10 @@ where
         fields: vec![],
         variable_values,
         deadline: timeout.map(|t| Instant::now() + t),
+        max_entities,
+        entity_count: Arc::new(AtomicUsize::new(0)),
+        trace: None,
+        path: vec![],
     };
 
     // We have established that this exists earlier in the subscription execution
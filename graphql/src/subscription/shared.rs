@@ -0,0 +1,226 @@
+use futures::sync::mpsc;
+use graphql_parser::Style;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, Weak};
+
+use graph::prelude::*;
+
+/// Identifies subscriptions that produce identical results: the same query
+/// with the same variables against the same subgraph deployment.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct SubscriptionKey {
+    subgraph_id: SubgraphDeploymentId,
+    query: String,
+    variables: String,
+}
+
+impl SubscriptionKey {
+    fn new(subscription: &Subscription) -> Self {
+        let query = &subscription.query;
+
+        // Sort variables by name so that the key does not depend on the
+        // order in which the client listed them
+        let variables = query
+            .variables
+            .as_ref()
+            .map(|variables| variables.iter().collect::<BTreeMap<_, _>>())
+            .unwrap_or_default();
+
+        SubscriptionKey {
+            subgraph_id: query.schema.id.clone(),
+            query: query.document.format(&Style::default().indent(0)),
+            variables: format!("{:?}", variables),
+        }
+    }
+}
+
+/// A subscription that is executed once per store event, with its results
+/// fanned out to all subscribers.
+struct SharedSubscription {
+    /// Distinguishes this subscription from later ones with the same key.
+    id: usize,
+
+    subscribers: Vec<mpsc::UnboundedSender<Arc<QueryResult>>>,
+
+    /// The most recent result, handed to new subscribers so that they
+    /// receive data right away.
+    last_result: Option<Arc<QueryResult>>,
+
+    /// Stops the subscription once dropped. Only the result streams of the
+    /// subscribers hold on to the guard, so that the subscription ends when
+    /// the last of them goes away.
+    guard: Weak<CancelGuard>,
+}
+
+/// Deduplicates identical subscriptions across connections.
+///
+/// Each distinct (subgraph, query, variables) combination is executed only
+/// once for every relevant store event; the result is then delivered to all
+/// clients that subscribed to it. A shared subscription is torn down once
+/// its last subscriber has gone away.
+#[derive(Clone)]
+pub struct SharedSubscriptions {
+    logger: Logger,
+    subscriptions: Arc<Mutex<HashMap<SubscriptionKey, SharedSubscription>>>,
+    next_id: Arc<AtomicUsize>,
+}
+
+impl SharedSubscriptions {
+    pub fn new(logger: &Logger) -> Self {
+        SharedSubscriptions {
+            logger: logger.new(o!("component" => "SharedSubscriptions")),
+            subscriptions: Arc::new(Mutex::new(HashMap::new())),
+            next_id: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Subscribes to the results of `subscription`. If an identical
+    /// subscription is already running, its results are shared; otherwise,
+    /// `execute` is called to start the subscription, and the resulting
+    /// stream is driven in the background.
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn subscribe<F>(
+        &self,
+        subscription: &Subscription,
+        execute: F,
+    ) -> Result<QueryResultStream, SubscriptionError>
+    where
+        F: FnOnce() -> Result<QueryResultStream, SubscriptionError>,
+    {
+        let key = SubscriptionKey::new(subscription);
+
+        let shared = self.join(&mut self.subscriptions.lock().unwrap(), &key);
+        if let Some(receiver) = shared {
+            return Ok(receiver);
+        }
+
+        // Execute without holding the lock, so that starting a subscription
+        // does not wait for other subscriptions to be executed
+        let results = execute()?;
+
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+
+        // An identical subscription may have been started in the meantime;
+        // share that one and drop ours
+        if let Some(receiver) = self.join(&mut subscriptions, &key) {
+            return Ok(receiver);
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (sender, receiver) = mpsc::unbounded();
+        let guard = Arc::new(CancelGuard::new());
+
+        subscriptions.insert(
+            key.clone(),
+            SharedSubscription {
+                id,
+                subscribers: vec![sender],
+                last_result: None,
+                guard: Arc::downgrade(&guard),
+            },
+        );
+        drop(subscriptions);
+
+        let logger = self.logger.clone();
+        let cancel_logger = self.logger.clone();
+        let cancel_subgraph_id = key.subgraph_id.clone();
+        let shared_subscriptions = self.subscriptions.clone();
+        let cleanup_subscriptions = self.subscriptions.clone();
+        let cleanup_key = key.clone();
+        tokio::spawn(
+            results
+                .for_each(move |result| {
+                    let mut subscriptions = shared_subscriptions.lock().unwrap();
+
+                    let done = match subscriptions.get_mut(&key) {
+                        Some(shared) if shared.id == id => {
+                            shared.subscribers.retain(|subscriber| {
+                                subscriber.unbounded_send(result.clone()).is_ok()
+                            });
+                            shared.last_result = Some(result);
+                            shared.subscribers.is_empty()
+                        }
+                        _ => true,
+                    };
+
+                    if done {
+                        debug!(
+                            logger,
+                            "Stopping shared subscription, no subscribers left";
+                            "subgraph_id" => key.subgraph_id.to_string(),
+                        );
+
+                        // Returning an error terminates the stream
+                        Err(())
+                    } else {
+                        Ok(())
+                    }
+                })
+                .cancelable(&*guard, move || {
+                    debug!(
+                        cancel_logger,
+                        "Stopping shared subscription, all subscribers are gone";
+                        "subgraph_id" => cancel_subgraph_id.to_string(),
+                    );
+                })
+                .then(move |_| {
+                    // Forget the subscription, unless it has been replaced by a
+                    // new one in the meantime; dropping the senders ends the
+                    // result streams of any remaining subscribers
+                    let mut subscriptions = cleanup_subscriptions.lock().unwrap();
+                    if subscriptions
+                        .get(&cleanup_key)
+                        .map_or(false, |shared| shared.id == id)
+                    {
+                        subscriptions.remove(&cleanup_key);
+                    }
+                    Ok(())
+                }),
+        );
+
+        Ok(Self::receiver(receiver, guard))
+    }
+
+    /// Adds a subscriber to the running subscription for `key`, if there is
+    /// one.
+    fn join(
+        &self,
+        subscriptions: &mut HashMap<SubscriptionKey, SharedSubscription>,
+        key: &SubscriptionKey,
+    ) -> Option<QueryResultStream> {
+        let shared = subscriptions.get_mut(key)?;
+
+        // All subscribers may be gone without the subscription having been
+        // torn down yet
+        let guard = shared.guard.upgrade()?;
+
+        debug!(
+            self.logger,
+            "Sharing existing subscription";
+            "subgraph_id" => key.subgraph_id.to_string(),
+            "subscribers" => shared.subscribers.len() + 1,
+        );
+
+        let (sender, receiver) = mpsc::unbounded();
+        if let Some(ref result) = shared.last_result {
+            // The receiver is still alive, so this cannot fail
+            sender.unbounded_send(result.clone()).unwrap();
+        }
+        shared.subscribers.push(sender);
+        Some(Self::receiver(receiver, guard))
+    }
+
+    /// Wraps the result stream of a subscriber so that it keeps the shared
+    /// subscription running for as long as the subscriber is around.
+    fn receiver(
+        receiver: mpsc::UnboundedReceiver<Arc<QueryResult>>,
+        guard: Arc<CancelGuard>,
+    ) -> QueryResultStream {
+        Box::new(receiver.map(move |result| {
+            let _ = &guard;
+            result
+        }))
+    }
+}
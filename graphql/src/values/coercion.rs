@@ -45,6 +45,12 @@ impl MaybeCoercible<ScalarType> for Value {
             ("Bytes", v @ Value::String(_)) => Some(v.clone()),
             ("BigInt", v @ Value::String(_)) => Some(v.clone()),
             ("BigInt", Value::Int(num)) => Some(Value::String(num.as_i64()?.to_string())),
+            ("Int8", Value::String(s)) | ("Timestamp", Value::String(s)) => {
+                Some(Value::String(s.parse::<i64>().ok()?.to_string()))
+            }
+            ("Int8", Value::Int(num)) | ("Timestamp", Value::Int(num)) => {
+                Some(Value::String(num.as_i64()?.to_string()))
+            }
             _ => None,
         }
     }
@@ -577,6 +583,54 @@ mod tests {
         );
     }
 
+    #[test]
+    fn coerce_int8_and_timestamp_scalars() {
+        for name in vec!["Int8", "Timestamp"] {
+            let scalar_type = TypeDefinition::Scalar(ScalarType::new(name.to_string()));
+            let resolver = |_: &String| Some(&scalar_type);
+
+            // We can coerce from Value::String and Value::Int
+            assert_eq!(
+                coerce_to_definition(
+                    &Value::String("-9223372036854775808".to_string()),
+                    &String::new(),
+                    &resolver,
+                    &HashMap::new()
+                ),
+                Some(Value::String("-9223372036854775808".to_string()))
+            );
+            assert_eq!(
+                coerce_to_definition(
+                    &Value::Int(1234.into()),
+                    &String::new(),
+                    &resolver,
+                    &HashMap::new()
+                ),
+                Some(Value::String("1234".to_string()))
+            );
+
+            // But not from strings that are not 64-bit integers
+            assert_eq!(
+                coerce_to_definition(
+                    &Value::String("9223372036854775808".to_string()),
+                    &String::new(),
+                    &resolver,
+                    &HashMap::new()
+                ),
+                None
+            );
+            assert_eq!(
+                coerce_to_definition(
+                    &Value::String("1.5".to_string()),
+                    &String::new(),
+                    &resolver,
+                    &HashMap::new()
+                ),
+                None
+            );
+        }
+    }
+
     #[test]
     fn coerce_bytes_scalar() {
         let bytes_type = TypeDefinition::Scalar(ScalarType::new("Bytes".to_string()));
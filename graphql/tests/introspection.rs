@@ -551,6 +551,8 @@ fn introspection_query(schema: Schema, query: &str) -> QueryResult {
             deadline: None,
             max_complexity: None,
             max_depth: 100,
+            max_entities: None,
+            trace: None,
         },
     )
 }
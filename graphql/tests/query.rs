@@ -29,7 +29,7 @@ fn test_schema(id: SubgraphDeploymentId) -> Schema {
                 name: String!
                 mainBand: Band
                 bands: [Band!]!
-                writtenSongs: [Song]! @derivedFrom(field: \"writtenBy\")
+                writtenSongs: [Song]! @derivedFrom(field: \"writtenBy\") @cost(value: 50)
             }
 
             type Band @entity {
@@ -214,6 +214,8 @@ fn execute_query_document_with_variables(
         deadline: None,
         max_complexity: None,
         max_depth: 100,
+        max_entities: None,
+        trace: None,
     };
 
     execute_query(&query, options)
@@ -707,6 +709,8 @@ fn query_complexity() {
         deadline: None,
         max_complexity,
         max_depth: 100,
+        max_entities: None,
+        trace: None,
     };
 
     // This query is exactly at the maximum complexity.
@@ -743,6 +747,8 @@ fn query_complexity() {
         deadline: None,
         max_complexity,
         max_depth: 100,
+        max_entities: None,
+        trace: None,
     };
 
     // The extra introspection causes the complexity to go over.
@@ -783,6 +789,7 @@ fn query_complexity_subscriptions() {
         timeout: None,
         max_complexity,
         max_depth: 100,
+        max_entities: None,
     };
 
     // This query is exactly at the maximum complexity.
@@ -818,6 +825,7 @@ fn query_complexity_subscriptions() {
         timeout: None,
         max_complexity,
         max_depth: 100,
+        max_entities: None,
     };
 
     // The extra introspection causes the complexity to go over.
@@ -847,6 +855,8 @@ fn instant_timeout() {
         deadline: Some(Instant::now()),
         max_complexity: None,
         max_depth: 100,
+        max_entities: None,
+        trace: None,
     };
 
     match execute_query(&query, options).errors.unwrap()[0] {
@@ -855,6 +865,183 @@ fn instant_timeout() {
     };
 }
 
+#[test]
+fn query_reports_complexity_and_entity_count() {
+    let result = execute_query_document(
+        graphql_parser::parse_query(
+            "query {
+                musicians(first: 100, orderBy: id) {
+                    name
+                    bands(first: 10) {
+                        name
+                    }
+                }
+            }",
+        )
+        .unwrap(),
+    );
+    assert!(result.errors.is_none());
+
+    // Up to 100 musicians in up to 10 bands each, but only 4 musicians who
+    // play in 5 bands between them
+    let extensions = result.extensions.unwrap();
+    assert_eq!(extensions["complexity"].as_u64(), Some(1_100));
+    assert_eq!(extensions["entityCount"].as_u64(), Some(9));
+}
+
+#[test]
+fn query_complexity_includes_field_costs() {
+    let result = execute_query_document(
+        graphql_parser::parse_query(
+            "query {
+                musicians(first: 10, orderBy: id) {
+                    name
+                    writtenSongs(first: 5) {
+                        title
+                    }
+                }
+            }",
+        )
+        .unwrap(),
+    );
+    assert!(result.errors.is_none());
+
+    // `writtenSongs` costs 50 on top of the 5 songs it may return
+    let extensions = result.extensions.unwrap();
+    assert_eq!(extensions["complexity"].as_u64(), Some(10 + 10 * (5 + 50)));
+}
+
+#[test]
+fn max_entities() {
+    let logger = Logger::root(slog::Discard, o!());
+    let store_resolver = StoreResolver::new(&logger, STORE.clone());
+
+    let query = Query {
+        schema: Arc::new(api_test_schema()),
+        document: graphql_parser::parse_query(
+            "query {
+                _meta { block { number } }
+                musicians(first: 100, orderBy: id) {
+                    name
+                    bands(first: 10) {
+                        name
+                    }
+                }
+            }",
+        )
+        .unwrap(),
+        variables: None,
+    };
+
+    let options = |max_entities| QueryExecutionOptions {
+        logger: logger.clone(),
+        resolver: store_resolver.clone(),
+        deadline: None,
+        max_complexity: None,
+        max_depth: 100,
+        max_entities: Some(max_entities),
+        trace: None,
+    };
+
+    // The query returns 4 musicians and 5 bands; `_meta` does not count
+    let result = execute_query(&query, options(9));
+    assert!(result.errors.is_none());
+
+    let result = execute_query(&query, options(8));
+    assert!(result.data.is_none());
+    match result.errors.unwrap()[0] {
+        QueryError::ExecutionError(QueryExecutionError::TooManyEntities(8)) => (), // Expected
+        _ => panic!("did not limit the number of entities"),
+    };
+}
+
+#[test]
+fn traced_query_records_fields_and_sql() {
+    let logger = Logger::root(slog::Discard, o!());
+    let trace = Arc::new(Trace::new());
+    let store_resolver = StoreResolver::new(&logger, STORE.clone()).with_trace(trace.clone());
+
+    let query = Query {
+        schema: Arc::new(api_test_schema()),
+        document: graphql_parser::parse_query(
+            "query {
+                musicians(first: 2, orderBy: id) {
+                    singer: name
+                }
+            }",
+        )
+        .unwrap(),
+        variables: None,
+    };
+
+    let options = QueryExecutionOptions {
+        logger,
+        resolver: store_resolver,
+        deadline: None,
+        max_complexity: None,
+        max_depth: 100,
+        max_entities: None,
+        trace: Some(trace.clone()),
+    };
+    let result = execute_query(&query, options);
+    assert!(result.errors.is_none());
+
+    let tracing = trace.to_json(true);
+    assert_eq!(tracing["version"].as_u64(), Some(1));
+    assert!(tracing["validation"]["duration"].is_u64());
+
+    let resolvers = tracing["execution"]["resolvers"].as_array().unwrap();
+    let paths = resolvers
+        .iter()
+        .map(|resolver| resolver["path"].to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        paths,
+        vec![
+            r#"["musicians"]"#,
+            r#"["musicians",0,"singer"]"#,
+            r#"["musicians",1,"singer"]"#,
+        ]
+    );
+    assert_eq!(resolvers[0]["parentType"].as_str(), Some("Query"));
+    assert_eq!(resolvers[0]["returnType"].as_str(), Some("[Musician!]!"));
+    assert_eq!(resolvers[1]["fieldName"].as_str(), Some("name"));
+
+    // Only the collection query runs SQL
+    assert_eq!(resolvers[0]["sql"].as_array().map(|sql| sql.len()), Some(1));
+    assert!(resolvers[1].get("sql").is_none());
+}
+
+#[test]
+fn traced_single_entity_query_records_sql() {
+    let logger = Logger::root(slog::Discard, o!());
+    let trace = Arc::new(Trace::new());
+    let store_resolver = StoreResolver::new(&logger, STORE.clone()).with_trace(trace.clone());
+
+    let query = Query {
+        schema: Arc::new(api_test_schema()),
+        document: graphql_parser::parse_query("query { musician(id: \"m1\") { name } }").unwrap(),
+        variables: None,
+    };
+
+    let options = QueryExecutionOptions {
+        logger,
+        resolver: store_resolver,
+        deadline: None,
+        max_complexity: None,
+        max_depth: 100,
+        max_entities: None,
+        trace: Some(trace.clone()),
+    };
+    let result = execute_query(&query, options);
+    assert!(result.errors.is_none());
+
+    let tracing = trace.to_json(true);
+    let resolvers = tracing["execution"]["resolvers"].as_array().unwrap();
+    assert_eq!(resolvers[0]["path"].to_string(), r#"["musician"]"#);
+    assert_eq!(resolvers[0]["sql"].as_array().map(|sql| sql.len()), Some(1));
+}
+
 #[test]
 fn variable_defaults() {
     let query = graphql_parser::parse_query(
@@ -1095,6 +1282,158 @@ fn can_filter_by_relationship_fields() {
     );
 }
 
+#[test]
+fn can_filter_by_list_membership() {
+    let result = execute_query_document(
+        graphql_parser::parse_query(
+            "
+        query {
+            containsAny: musicians(orderBy: id, where: { bands_contains_any: [\"b2\", \"b3\"] }) {
+                id
+            }
+            containsAll: musicians(orderBy: id, where: { bands_contains_all: [\"b1\", \"b2\"] }) {
+                id
+            }
+            empty: musicians(orderBy: id, where: { bands_is_empty: true }) {
+                id
+            }
+            notEmpty: musicians(orderBy: id, where: { bands_is_empty: false }) {
+                id
+            }
+        }
+        ",
+        )
+        .expect("invalid test query"),
+    );
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+
+    let musicians = |ids: Vec<&str>| {
+        q::Value::List(
+            ids.into_iter()
+                .map(|id| object_value(vec![("id", q::Value::String(String::from(id)))]))
+                .collect(),
+        )
+    };
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![
+            ("containsAny", musicians(vec!["m1", "m3"])),
+            ("containsAll", musicians(vec!["m1", "m3"])),
+            ("empty", musicians(vec!["m4"])),
+            ("notEmpty", musicians(vec!["m1", "m2", "m3"])),
+        ]))
+    );
+}
+
+#[test]
+fn can_filter_by_case_insensitive_and_pattern_filters() {
+    let result = execute_query_document(
+        graphql_parser::parse_query(
+            "
+        query {
+            nocase: musicians(orderBy: id, where: { name_starts_with_nocase: \"jO\" }) {
+                id
+            }
+            regex: musicians(orderBy: id, where: { name_regex_nocase: \"^(lisa|tom)$\" }) {
+                id
+            }
+        }
+        ",
+        )
+        .expect("invalid test query"),
+    );
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![
+            (
+                "nocase",
+                q::Value::List(vec![object_value(vec![(
+                    "id",
+                    q::Value::String(String::from("m1"))
+                )])])
+            ),
+            (
+                "regex",
+                q::Value::List(vec![
+                    object_value(vec![("id", q::Value::String(String::from("m2")))]),
+                    object_value(vec![("id", q::Value::String(String::from("m3")))]),
+                ])
+            ),
+        ]))
+    );
+}
+
+#[test]
+fn cannot_filter_by_overly_long_patterns() {
+    let result = execute_query_document(
+        graphql_parser::parse_query(&format!(
+            "query {{ musicians(where: {{ name_like: \"{}\" }}) {{ id }} }}",
+            "%".repeat(257)
+        ))
+        .expect("invalid test query"),
+    );
+
+    match &result.errors.expect("expected an error")[0] {
+        QueryError::ExecutionError(QueryExecutionError::FilterPatternTooLong(filter, 256)) => {
+            assert_eq!(filter, "_like")
+        }
+        e => panic!("expected FilterPatternTooLong error, got {}", e),
+    }
+}
+
+#[test]
+fn can_query_meta() {
+    let result = execute_query_document(
+        graphql_parser::parse_query(
+            "query {
+                _meta { block { number hash } deployment hasIndexingErrors }
+                musicians(first: 1, orderBy: id) { id }
+            }",
+        )
+        .expect("invalid test query"),
+    );
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![
+            (
+                "_meta",
+                object_value(vec![
+                    (
+                        "block",
+                        object_value(vec![
+                            ("number", q::Value::Int(q::Number::from(0))),
+                            ("hash", q::Value::String(format!("0x{}", "0".repeat(64))),),
+                        ]),
+                    ),
+                    ("deployment", q::Value::String(TEST_SUBGRAPH_ID.to_string()),),
+                    ("hasIndexingErrors", q::Value::Boolean(false)),
+                ]),
+            ),
+            (
+                "musicians",
+                q::Value::List(vec![object_value(vec![(
+                    "id",
+                    q::Value::String(String::from("m1")),
+                )])]),
+            ),
+        ]))
+    );
+}
+
 #[test]
 fn cannot_filter_by_derved_relationship_fields() {
     let result = execute_query_document(
@@ -1151,6 +1490,7 @@ fn subscription_gets_result_even_without_events() {
         timeout: None,
         max_complexity: None,
         max_depth: 100,
+        max_entities: None,
     };
 
     // Execute the subscription and expect at least one result to be
@@ -1176,3 +1516,125 @@ fn subscription_gets_result_even_without_events() {
         )])),
     );
 }
+
+#[test]
+fn identical_subscriptions_are_shared() {
+    let logger = Logger::root(slog::Discard, o!());
+    let store_resolver = StoreResolver::new(&logger, STORE.clone());
+    let subscriptions = SharedSubscriptions::new(&logger);
+
+    let subscription = Subscription {
+        query: Query {
+            schema: Arc::new(api_test_schema()),
+            document: graphql_parser::parse_query(
+                "subscription {
+                  musicians(orderBy: id, first: 2) {
+                    name
+                  }
+                }",
+            )
+            .unwrap(),
+            variables: None,
+        },
+    };
+
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    let (first, second, executions) = runtime
+        .block_on(future::lazy(move || {
+            let mut executions = 0;
+
+            let mut subscribe = || {
+                subscriptions
+                    .subscribe(&subscription, || {
+                        executions += 1;
+                        execute_subscription(
+                            &subscription,
+                            SubscriptionExecutionOptions {
+                                logger: logger.clone(),
+                                resolver: store_resolver.clone(),
+                                timeout: None,
+                                max_complexity: None,
+                                max_depth: 100,
+                                max_entities: None,
+                            },
+                        )
+                    })
+                    .unwrap()
+            };
+
+            let first = subscribe();
+            let second = subscribe();
+            Ok::<_, ()>((first, second, executions))
+        }))
+        .unwrap();
+
+    // The subscription was only executed once
+    assert_eq!(executions, 1);
+
+    // Both subscribers receive the initial result
+    let first = runtime
+        .block_on(first.take(1).collect().timeout(Duration::from_secs(3)))
+        .unwrap();
+    let second = runtime
+        .block_on(second.take(1).collect().timeout(Duration::from_secs(3)))
+        .unwrap();
+    assert_eq!(first.len(), 1);
+    assert_eq!(second.len(), 1);
+    assert_eq!(first[0].data, second[0].data);
+    assert!(first[0].errors.is_none());
+}
+
+#[test]
+fn shared_subscriptions_stop_when_all_subscribers_are_gone() {
+    let logger = Logger::root(slog::Discard, o!());
+    let subscriptions = SharedSubscriptions::new(&logger);
+
+    let subscription = Subscription {
+        query: Query {
+            schema: Arc::new(api_test_schema()),
+            document: graphql_parser::parse_query("subscription { musicians { name } }").unwrap(),
+            variables: None,
+        },
+    };
+
+    // Results that never arrive, like those of an idle deployment
+    let (sender, results) = futures::sync::mpsc::unbounded::<Arc<QueryResult>>();
+
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    let (first, second) = runtime
+        .block_on(future::lazy(move || {
+            let mut results = Some(results);
+            let mut subscribe = || {
+                subscriptions
+                    .subscribe(&subscription, || {
+                        let results = results.take().expect("executed more than once");
+                        Ok(Box::new(results) as QueryResultStream)
+                    })
+                    .unwrap()
+            };
+
+            let first = subscribe();
+            let second = subscribe();
+            Ok::<_, ()>((first, second))
+        }))
+        .unwrap();
+
+    // The subscription keeps running while any subscriber is around
+    drop(first);
+    std::thread::sleep(Duration::from_millis(100));
+    assert!(sender
+        .unbounded_send(Arc::new(QueryResult::new(None)))
+        .is_ok());
+
+    // Once the last subscriber is gone, the subscription is stopped without
+    // waiting for another result
+    drop(second);
+    let deadline = Instant::now() + Duration::from_secs(3);
+    while !sender.is_closed() {
+        assert!(
+            Instant::now() < deadline,
+            "shared subscription was not stopped"
+        );
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
@@ -66,7 +66,7 @@ impl BlockStreamBuilder for MockBlockStreamBuilder {
         _call_filter: Option<EthereumCallFilter>,
         _block_filter: Option<EthereumBlockFilter>,
         _include_calls_in_blocks: bool,
-    ) -> Self::Stream {
-        MockBlockStream::new()
+    ) -> Result<Self::Stream, Error> {
+        Ok(MockBlockStream::new())
     }
 }
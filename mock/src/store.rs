@@ -143,8 +143,31 @@ impl Store for MockStore {
         Ok(None)
     }
 
-    fn block_ptr(&self, _: SubgraphDeploymentId) -> Result<EthereumBlockPointer, Error> {
-        unimplemented!();
+    fn block_ptr(&self, subgraph_id: SubgraphDeploymentId) -> Result<EthereumBlockPointer, Error> {
+        let subgraph_entity = self
+            .get(SubgraphDeploymentEntity::key(subgraph_id.clone()))
+            .map_err(|e| format_err!("error reading subgraph entity: {}", e))?
+            .ok_or_else(|| {
+                format_err!(
+                    "could not read block ptr for non-existent subgraph {}",
+                    subgraph_id
+                )
+            })?;
+
+        let hash = subgraph_entity
+            .get("latestEthereumBlockHash")
+            .and_then(|value| value.clone().as_string())
+            .ok_or_else(|| format_err!("SubgraphDeployment is missing latestEthereumBlockHash"))?
+            .parse::<H256>()
+            .map_err(|e| format_err!("latestEthereumBlockHash: {}", e))?;
+
+        let number = subgraph_entity
+            .get("latestEthereumBlockNumber")
+            .and_then(|value| value.clone().as_bigint())
+            .ok_or_else(|| format_err!("SubgraphDeployment is missing latestEthereumBlockNumber"))?
+            .to_u64();
+
+        Ok(EthereumBlockPointer { hash, number })
     }
 
     fn set_block_ptr_with_no_changes(
@@ -408,6 +431,14 @@ impl ChainStore for MockStore {
         unimplemented!();
     }
 
+    fn upsert_block_calls(&self, _: H256, _: &[EthereumCall]) -> Result<(), Error> {
+        unimplemented!();
+    }
+
+    fn block_calls(&self, _: H256) -> Result<Option<Vec<EthereumCall>>, Error> {
+        unimplemented!();
+    }
+
     fn ancestor_block(
         &self,
         _: EthereumBlockPointer,
@@ -549,6 +580,14 @@ impl ChainStore for FakeStore {
         unimplemented!();
     }
 
+    fn upsert_block_calls(&self, _: H256, _: &[EthereumCall]) -> Result<(), Error> {
+        unimplemented!();
+    }
+
+    fn block_calls(&self, _: H256) -> Result<Option<Vec<EthereumCall>>, Error> {
+        unimplemented!();
+    }
+
     fn ancestor_block(
         &self,
         _: EthereumBlockPointer,
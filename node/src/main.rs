@@ -15,32 +15,39 @@ extern crate graph_store_postgres;
 extern crate http;
 extern crate ipfs_api;
 extern crate lazy_static;
+extern crate tokio_signal;
 extern crate url;
 
 use clap::{App, Arg};
-use futures::sync::mpsc;
+use futures::sync::{mpsc, oneshot};
 use git_testament::{git_testament, render_testament};
 use ipfs_api::IpfsClient;
 use lazy_static::lazy_static;
 use std::collections::HashMap;
 use std::env;
+use std::net::{IpAddr, SocketAddr};
 use std::str::FromStr;
 use std::time::Duration;
+use tokio_signal::unix::{Signal, SIGTERM};
+use url::Url;
 
 use graph::components::forward;
 use graph::log::logger;
-use graph::prelude::{JsonRpcServer as _, *};
+use graph::prelude::{JsonRpcServer as _, LinkResolver as LinkResolverTrait, *};
 use graph::tokio_executor;
 use graph::tokio_timer;
 use graph::tokio_timer::timer::Timer;
 use graph::util::security::SafeDisplay;
 use graph_core::{
-    LinkResolver, SubgraphAssignmentProvider as IpfsSubgraphAssignmentProvider,
-    SubgraphInstanceManager, SubgraphRegistrar as IpfsSubgraphRegistrar,
+    ipfs_timeout, CompositeLinkResolver, FileLinkResolver, GatewayLinkResolver, LinkResolver,
+    SubgraphAssignmentProvider as IpfsSubgraphAssignmentProvider, SubgraphInstanceManager,
+    SubgraphRegistrar as IpfsSubgraphRegistrar,
+};
+use graph_datasource_ethereum::{
+    BlockStreamBuilder, EthereumAdapter, FailoverEthereumAdapter, Transport,
 };
-use graph_datasource_ethereum::{BlockStreamBuilder, Transport};
 use graph_runtime_wasm::RuntimeHostBuilder as WASMRuntimeHostBuilder;
-use graph_server_http::GraphQLServer as GraphQLQueryServer;
+use graph_server_http::{parse_federated_subgraphs, GraphQLServer as GraphQLQueryServer};
 use graph_server_json_rpc::JsonRpcServer;
 use graph_server_websocket::SubscriptionServer as GraphQLSubscriptionServer;
 use graph_store_postgres::{Store as DieselStore, StoreConfig};
@@ -66,6 +73,23 @@ lazy_static! {
         .map(|s| u64::from_str(&s)
              .unwrap_or_else(|_| panic!("failed to parse env var ETHEREUM_START_BLOCK")))
         .unwrap_or(0);
+
+    // How often to retry a request against one Ethereum provider before
+    // trying the next one, if a network has several providers.
+    static ref ETHEREUM_PROVIDER_RETRY_LIMIT: usize = env::var("ETHEREUM_PROVIDER_RETRY_LIMIT")
+        .ok()
+        .map(|s| usize::from_str(&s)
+             .unwrap_or_else(|_| panic!("failed to parse env var ETHEREUM_PROVIDER_RETRY_LIMIT")))
+        .unwrap_or(3);
+
+    // How long to wait for subgraphs, block ingestion and the store to stop
+    // after receiving SIGTERM or SIGINT before exiting anyway.
+    static ref SHUTDOWN_TIMEOUT: Duration = env::var("GRAPH_SHUTDOWN_TIMEOUT")
+        .ok()
+        .map(|s| u64::from_str(&s)
+             .unwrap_or_else(|_| panic!("failed to parse env var GRAPH_SHUTDOWN_TIMEOUT")))
+        .map(Duration::from_secs)
+        .unwrap_or(Duration::from_secs(60));
 }
 
 git_testament!(TESTAMENT);
@@ -156,12 +180,13 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
                 .multiple(true)
                 .min_values(0)
                 .required_unless_one(&["ethereum-ws", "ethereum-ipc"])
-                .conflicts_with_all(&["ethereum-ws", "ethereum-ipc"])
                 .long("ethereum-rpc")
-                .value_name("NETWORK_NAME:URL")
+                .value_name("NETWORK_NAME:[CAPABILITIES]:URL")
                 .help(
-                    "Ethereum network name (e.g. 'mainnet') and \
-                     Ethereum RPC URL, separated by a ':'",
+                    "Ethereum network name (e.g. 'mainnet'), optional node \
+                     capabilities (e.g. 'archive,traces', the default) and \
+                     Ethereum RPC URL, separated by a ':'. May be given \
+                     several times per network for failover",
                 ),
         )
         .arg(
@@ -170,12 +195,13 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
                 .multiple(true)
                 .min_values(0)
                 .required_unless_one(&["ethereum-rpc", "ethereum-ipc"])
-                .conflicts_with_all(&["ethereum-rpc", "ethereum-ipc"])
                 .long("ethereum-ws")
-                .value_name("NETWORK_NAME:URL")
+                .value_name("NETWORK_NAME:[CAPABILITIES]:URL")
                 .help(
-                    "Ethereum network name (e.g. 'mainnet') and \
-                     Ethereum WebSocket URL, separated by a ':'",
+                    "Ethereum network name (e.g. 'mainnet'), optional node \
+                     capabilities (e.g. 'archive,traces', the default) and \
+                     Ethereum WebSocket URL, separated by a ':'. May be given \
+                     several times per network for failover",
                 ),
         )
         .arg(
@@ -184,21 +210,38 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
                 .multiple(true)
                 .min_values(0)
                 .required_unless_one(&["ethereum-rpc", "ethereum-ws"])
-                .conflicts_with_all(&["ethereum-rpc", "ethereum-ws"])
                 .long("ethereum-ipc")
-                .value_name("NETWORK_NAME:FILE")
+                .value_name("NETWORK_NAME:[CAPABILITIES]:FILE")
                 .help(
-                    "Ethereum network name (e.g. 'mainnet') and \
-                     Ethereum IPC pipe, separated by a ':'",
+                    "Ethereum network name (e.g. 'mainnet'), optional node \
+                     capabilities (e.g. 'archive,traces', the default) and \
+                     Ethereum IPC pipe, separated by a ':'. May be given \
+                     several times per network for failover",
                 ),
         )
         .arg(
             Arg::with_name("ipfs")
                 .takes_value(true)
-                .required(true)
+                .multiple(true)
+                .required_unless("ipfs-gateway")
                 .long("ipfs")
                 .value_name("HOST:PORT")
-                .help("HTTP address of an IPFS node"),
+                .help(
+                    "HTTP address of an IPFS node, or a local directory to resolve \
+                     links from, given as 'file://DIR'. May be given several times \
+                     to fall back to other nodes",
+                ),
+        )
+        .arg(
+            Arg::with_name("ipfs-gateway")
+                .takes_value(true)
+                .multiple(true)
+                .long("ipfs-gateway")
+                .value_name("URL")
+                .help(
+                    "URL of a read-only HTTP IPFS gateway, tried after the IPFS \
+                     nodes. May be given several times",
+                ),
         )
         .arg(
             Arg::with_name("http-port")
@@ -214,6 +257,19 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
                 .value_name("PORT")
                 .help("Port for the GraphQL WebSocket server"),
         )
+        .arg(
+            Arg::with_name("federated-subgraph")
+                .takes_value(true)
+                .multiple(true)
+                .long("federated-subgraph")
+                .value_name("[NAMESPACE=]SUBGRAPH_NAME")
+                .env("GRAPH_GRAPHQL_FEDERATED_SUBGRAPHS")
+                .use_delimiter(true)
+                .help(
+                    "Subgraph to serve at /subgraphs/federated, optionally prefixed \
+                     with the namespace of its root fields. May be given several times",
+                ),
+        )
         .arg(
             Arg::with_name("admin-port")
                 .default_value("8020")
@@ -221,6 +277,28 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
                 .value_name("PORT")
                 .help("Port for the JSON-RPC admin server"),
         )
+        .arg(
+            Arg::with_name("admin-listen-address")
+                .default_value("0.0.0.0")
+                .long("admin-listen-address")
+                .value_name("IP_ADDRESS")
+                .env("ADMIN_LISTEN_ADDRESS")
+                .help("Interface the JSON-RPC admin server binds to"),
+        )
+        .arg(
+            Arg::with_name("admin-token")
+                .takes_value(true)
+                .multiple(true)
+                .long("admin-token")
+                .value_name("[PERMISSION:]TOKEN")
+                .env("ADMIN_TOKENS")
+                .hide_env_values(true)
+                .use_delimiter(true)
+                .help(
+                    "Access token for the JSON-RPC admin server, optionally prefixed \
+                     with 'deploy:' or 'admin:' (the default) to restrict what it may do",
+                ),
+        )
         .arg(
             Arg::with_name("node-id")
                 .default_value("default")
@@ -325,6 +403,20 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
         .parse()
         .expect("invalid GraphQL WebSocket server port");
 
+    // Obtain the subgraphs served together by the GraphQL HTTP server
+    let federated_subgraphs = parse_federated_subgraphs(
+        matches
+            .values_of("federated-subgraph")
+            .into_iter()
+            .flatten(),
+    )
+    .unwrap_or_else(|e| {
+        panic!(
+            "invalid --federated-subgraph/GRAPH_GRAPHQL_FEDERATED_SUBGRAPHS value: {}",
+            e
+        )
+    });
+
     // Obtain JSON-RPC server port
     let json_rpc_port = matches
         .value_of("admin-port")
@@ -332,6 +424,23 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
         .parse()
         .expect("invalid admin port");
 
+    // Obtain the interface the JSON-RPC server binds to
+    let json_rpc_listen_address: IpAddr = matches
+        .value_of("admin-listen-address")
+        .unwrap()
+        .parse()
+        .expect("invalid admin listen address");
+
+    // Obtain the access tokens for the JSON-RPC server
+    let mut admin_access_control = AdminAccessControl::disabled();
+    if let Some(tokens) = matches.values_of("admin-token") {
+        for token in tokens {
+            admin_access_control
+                .add_token_spec(token)
+                .unwrap_or_else(|e| panic!("invalid --admin-token/ADMIN_TOKENS value: {}", e));
+        }
+    }
+
     debug!(logger, "Setting up Sentry");
 
     // Obtain DISABLE_BLOCK_INGESTOR setting
@@ -366,19 +475,6 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
     sentry::integrations::panic::register_panic_handler();
     info!(logger, "Starting up");
 
-    // Parse the IPFS URL from the `--ipfs` command line argument
-    let ipfs_address = matches
-        .value_of("ipfs")
-        .map(|uri| {
-            if uri.starts_with("http://") || uri.starts_with("https://") {
-                String::from(uri)
-            } else {
-                format!("http://{}", uri)
-            }
-        })
-        .unwrap()
-        .to_owned();
-
     // Optionally, identify the Elasticsearch logging configuration
     let elastic_config =
         matches
@@ -392,65 +488,39 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
     // Create a component and subgraph logger factory
     let logger_factory = LoggerFactory::new(logger.clone(), elastic_config);
 
-    info!(
-        logger,
-        "Trying IPFS node at: {}",
-        SafeDisplay(&ipfs_address)
-    );
-
-    // Try to create an IPFS client for this URL
-    let ipfs_client = match IpfsClient::new_from_uri(ipfs_address.as_ref()) {
-        Ok(ipfs_client) => ipfs_client,
-        Err(e) => {
-            error!(
-                logger,
-                "Failed to create IPFS client for `{}`: {}",
-                SafeDisplay(&ipfs_address),
-                e
-            );
-            panic!("Could not connect to IPFS");
-        }
-    };
-
-    // Test the IPFS client by getting the version from the IPFS daemon
-    let ipfs_test = ipfs_client.version();
-    let ipfs_ok_logger = logger.clone();
-    let ipfs_err_logger = logger.clone();
-    let ipfs_address_for_ok = ipfs_address.clone();
-    let ipfs_address_for_err = ipfs_address.clone();
-    tokio::spawn(
-        ipfs_test
-            .map_err(move |e| {
-                error!(
-                    ipfs_err_logger,
-                    "Is there an IPFS node running at \"{}\"?",
-                    SafeDisplay(ipfs_address_for_err),
-                );
-                panic!("Failed to connect to IPFS: {}", e);
-            })
-            .map(move |_| {
-                info!(
-                    ipfs_ok_logger,
-                    "Successfully connected to IPFS node at: {}",
-                    SafeDisplay(ipfs_address_for_ok)
-                );
-            }),
-    );
-
-    // Convert the client into a link resolver
-    let link_resolver = Arc::new(LinkResolver::from(ipfs_client));
+    // Create the link resolver from the `--ipfs` and `--ipfs-gateway` arguments
+    let link_resolver = Arc::new(create_link_resolver(
+        &logger,
+        matches
+            .values_of("ipfs")
+            .map_or(vec![], |values| values.collect()),
+        matches
+            .values_of("ipfs-gateway")
+            .map_or(vec![], |values| values.collect()),
+    ));
 
-    let eth_adapters = [
+    // Collect all providers, grouped by network
+    let mut eth_providers: HashMap<String, Vec<(String, NodeCapabilities, Transport)>> =
+        HashMap::new();
+    for (connection_type, values) in vec![
         (ConnectionType::RPC, ethereum_rpc),
         (ConnectionType::IPC, ethereum_ipc),
         (ConnectionType::WS, ethereum_ws),
-    ]
-    .iter()
-    .cloned()
-    .filter(|(_, values)| values.is_some())
-    .fold(HashMap::new(), |adapters, (connection_type, values)| {
-        match parse_ethereum_networks_and_nodes(logger.clone(), values.unwrap(), connection_type) {
-            Ok(adapter) => adapters.into_iter().chain(adapter).collect(),
+    ] {
+        let values = match values {
+            Some(values) => values,
+            None => continue,
+        };
+        match parse_ethereum_networks_and_nodes(logger.clone(), values, connection_type) {
+            Ok(transports) => {
+                for (network_name, capabilities, location, transport) in transports {
+                    eth_providers.entry(network_name).or_default().push((
+                        location,
+                        capabilities,
+                        transport,
+                    ));
+                }
+            }
             Err(e) => {
                 panic!(
                     "Failed to parse Ethereum networks and create Ethereum adapters: {}",
@@ -458,7 +528,59 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
                 );
             }
         }
-    });
+    }
+
+    // Providers that support subscriptions let the block ingestor learn
+    // about new blocks as soon as they arrive
+    let new_heads_transports: HashMap<String, Transport> = eth_providers
+        .iter()
+        .filter_map(|(network_name, providers)| {
+            providers
+                .iter()
+                .find(|(_, _, transport)| transport.supports_subscriptions())
+                .map(|(_, _, transport)| (network_name.clone(), transport.clone()))
+        })
+        .collect();
+
+    // Create one adapter per network that fails over between its providers
+    let eth_adapters: HashMap<_, _> = eth_providers
+        .into_iter()
+        .map(|(network_name, transports)| {
+            // With a single provider, keep retrying; otherwise give up on a
+            // provider after a few attempts and try the next one
+            let retry_limit = match transports.len() {
+                1 => None,
+                _ => Some(*ETHEREUM_PROVIDER_RETRY_LIMIT),
+            };
+
+            let providers = transports
+                .into_iter()
+                .map(|(location, capabilities, transport)| {
+                    let adapter = EthereumAdapter::new(transport, *ETHEREUM_START_BLOCK);
+                    let adapter = match retry_limit {
+                        Some(limit) => adapter.with_retry_limit(limit),
+                        None => adapter,
+                    };
+                    (location, capabilities, Arc::new(adapter))
+                })
+                .collect();
+
+            let adapter = FailoverEthereumAdapter::new(&network_name, providers);
+            (network_name, Arc::new(adapter))
+        })
+        .collect();
+
+    // Subgraphs only use the providers that support what they need, e.g.
+    // call handlers are only served by providers with traces
+    let mut eth_networks = EthereumNetworks::new();
+    for (network_name, eth_adapter) in eth_adapters.iter() {
+        for capabilities in eth_adapter.capabilities() {
+            let adapter = eth_adapter
+                .with_capabilities(&capabilities)
+                .expect("providers support their own capabilities");
+            eth_networks.insert(network_name.clone(), capabilities, Arc::new(adapter));
+        }
+    }
 
     // Warn if the start block is != genesis
     if *ETHEREUM_START_BLOCK > 0 {
@@ -485,7 +607,7 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
                 logger, "Connecting to Ethereum...";
                 "network" => &network_name,
             );
-            match eth_adapter.net_identifiers(&logger).wait() {
+            match eth_adapter.check_net_identifiers(&logger).wait() {
                 Ok(network_identifier) => {
                     info!(
                     logger,
@@ -526,10 +648,16 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
         graphql_runner.clone(),
         generic_store.clone(),
         node_id.clone(),
+        federated_subgraphs,
     );
     let mut subscription_server =
         GraphQLSubscriptionServer::new(&logger, graphql_runner.clone(), generic_store.clone());
 
+    // Canceled on shutdown to stop block ingestion and to stop accepting
+    // GraphQL queries and subscriptions
+    let block_ingestor_guard = SharedCancelGuard::new();
+    let server_guard = SharedCancelGuard::new();
+
     if !disable_block_ingestor {
         // BlockIngestor must be configured to keep at least REORG_THRESHOLD ancestors,
         // otherwise BlockStream will not work properly.
@@ -551,26 +679,47 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
             )
             .expect("failed to create Ethereum block ingestor");
 
-            // Run the Ethereum block ingestor in the background
-            tokio::spawn(block_ingestor.into_polling_stream());
+            // Run the Ethereum block ingestor in the background, subscribing
+            // to new blocks if possible
+            match new_heads_transports.get(network_name).cloned() {
+                Some(transport) => {
+                    tokio::spawn(
+                        block_ingestor
+                            .into_subscription_stream(move || {
+                                // Only transports that support subscriptions
+                                // are in `new_heads_transports`
+                                transport.new_heads().unwrap()
+                            })
+                            .cancelable(&block_ingestor_guard, || ()),
+                    );
+                }
+                None => {
+                    tokio::spawn(
+                        block_ingestor
+                            .into_polling_stream()
+                            .cancelable(&block_ingestor_guard, || ()),
+                    );
+                }
+            }
         });
     }
 
     let block_stream_builder = BlockStreamBuilder::new(
         generic_store.clone(),
         stores.clone(),
-        eth_adapters.clone(),
+        eth_networks.clone(),
         node_id.clone(),
         *REORG_THRESHOLD,
     );
     let runtime_host_builder =
-        WASMRuntimeHostBuilder::new(eth_adapters.clone(), link_resolver.clone(), stores.clone());
+        WASMRuntimeHostBuilder::new(eth_networks.clone(), link_resolver.clone(), stores.clone());
 
     let subgraph_instance_manager = SubgraphInstanceManager::new(
         &logger_factory,
         stores.clone(),
         runtime_host_builder,
         block_stream_builder,
+        eth_networks.capabilities(),
     );
 
     // Create IPFS-based subgraph provider
@@ -598,7 +747,8 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
         link_resolver,
         Arc::new(subgraph_provider),
         generic_store.clone(),
-        stores,
+        stores.clone(),
+        eth_networks.capabilities(),
         node_id.clone(),
         version_switching_mode,
     ));
@@ -610,18 +760,16 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
 
     // Start admin JSON-RPC server.
     let json_rpc_server = JsonRpcServer::serve(
-        json_rpc_port,
+        SocketAddr::new(json_rpc_listen_address, json_rpc_port),
         http_port,
         ws_port,
         subgraph_registrar.clone(),
         node_id.clone(),
+        admin_access_control,
         logger.clone(),
     )
     .expect("failed to start JSON-RPC admin server");
 
-    // Let the server run forever.
-    std::mem::forget(json_rpc_server);
-
     // Add the CLI subgraph with a REST request to the admin server.
     if let Some(subgraph) = subgraph {
         let (name, hash) = if subgraph.contains(':') {
@@ -655,14 +803,16 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
     tokio::spawn(
         graphql_server
             .serve(http_port, ws_port)
-            .expect("Failed to start GraphQL query server"),
+            .expect("Failed to start GraphQL query server")
+            .cancelable(&server_guard, || ()),
     );
 
     // Serve GraphQL subscriptions over WebSockets
     tokio::spawn(
         subscription_server
             .serve(ws_port)
-            .expect("Failed to start GraphQL subscription server"),
+            .expect("Failed to start GraphQL subscription server")
+            .cancelable(&server_guard, || ()),
     );
 
     // Periodically check for contention in the tokio threadpool. First spawn a
@@ -694,21 +844,201 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
         }
     });
 
-    future::empty()
+    // Run until we are asked to terminate, then shut down in order: stop
+    // accepting requests, let subgraphs finish the block they are processing,
+    // stop block ingestion, flush logs and wait for the store to finish its
+    // work. Anything that takes longer than `GRAPH_SHUTDOWN_TIMEOUT` is cut
+    // short.
+    //
+    // The shutdown runs on the runtime, since the timers it relies on are
+    // only driven there; the main thread merely waits for it to finish.
+    let shutdown_logger = logger.clone();
+    let log_flush_handle = logger_factory.elastic_flush_handle();
+    let (shutdown_sender, shutdown_receiver) = oneshot::channel();
+    tokio::spawn(
+        shutdown_signal(logger.clone())
+            .and_then(move |()| {
+                let steps: Vec<Box<Future<Item = (), Error = ()> + Send>> = vec![
+                    Box::new(future::lazy(move || {
+                        server_guard.cancel();
+                        json_rpc_server.close();
+                        subgraph_instance_manager.shutdown()
+                    })),
+                    Box::new(future::lazy(move || {
+                        info!(shutdown_logger, "Stopping block ingestion");
+                        block_ingestor_guard.cancel();
+                        log_flush_handle.flush()
+                    })),
+                    Box::new(future::lazy(move || {
+                        future::join_all(
+                            stores
+                                .values()
+                                .map(|store| store.close())
+                                .collect::<Vec<_>>(),
+                        )
+                        .map(|_| ())
+                    })),
+                ];
+                shutdown(logger, steps, *SHUTDOWN_TIMEOUT)
+            })
+            .then(move |_| shutdown_sender.send(()).map_err(|_| ())),
+    );
+
+    shutdown_receiver.map_err(|_| ())
+}
+
+/// Runs the steps of shutting down one after the other, giving up on the
+/// remaining steps once `timeout` has passed.
+fn shutdown(
+    logger: Logger,
+    steps: Vec<Box<Future<Item = (), Error = ()> + Send>>,
+    timeout: Duration,
+) -> impl Future<Item = (), Error = ()> + Send {
+    let timeout_logger = logger.clone();
+    let steps = stream::iter_ok(steps)
+        .for_each(|step| step)
+        .map(move |()| info!(logger, "Shutdown complete"));
+
+    tokio::timer::Timeout::new(steps, timeout).or_else(move |_| {
+        warn!(
+            timeout_logger,
+            "Shutdown did not complete within {}s, exiting anyway",
+            timeout.as_secs()
+        );
+        Ok(())
+    })
+}
+
+/// Resolves when the process receives SIGTERM or SIGINT. If listening for
+/// signals fails, never resolves.
+fn shutdown_signal(logger: Logger) -> impl Future<Item = (), Error = ()> {
+    let sigint = tokio_signal::ctrl_c().flatten_stream().map(|()| "SIGINT");
+    let sigterm = Signal::new(SIGTERM).flatten_stream().map(|_| "SIGTERM");
+
+    sigint.select(sigterm).into_future().then(
+        move |result| -> Box<Future<Item = (), Error = ()> + Send> {
+            match result {
+                Ok((signal, _)) => {
+                    info!(
+                        logger,
+                        "Received {}, shutting down",
+                        signal.unwrap_or("signal")
+                    );
+                    Box::new(future::ok(()))
+                }
+                Err((e, _)) => {
+                    error!(logger, "Failed to listen for shutdown signals: {}", e);
+                    Box::new(future::empty())
+                }
+            }
+        },
+    )
 }
 
-/// Parses an Ethereum connection string and returns the network name and Ethereum adapter.
+/// Creates a link resolver for every IPFS node or local directory and every
+/// IPFS gateway. If there are several, they are tried in that order.
+fn create_link_resolver(
+    logger: &Logger,
+    ipfs: Vec<&str>,
+    gateways: Vec<&str>,
+) -> Box<dyn LinkResolverTrait> {
+    let has_fallbacks = ipfs.len() + gateways.len() > 1;
+    let mut resolvers: Vec<Box<dyn LinkResolverTrait>> = vec![];
+
+    for uri in ipfs {
+        if uri.starts_with("file://") {
+            let dir = uri.trim_start_matches("file://");
+            info!(logger, "Resolving links from local directory: {}", dir);
+            resolvers.push(Box::new(FileLinkResolver::new(dir)));
+            continue;
+        }
+
+        let ipfs_address = if uri.starts_with("http://") || uri.starts_with("https://") {
+            String::from(uri)
+        } else {
+            format!("http://{}", uri)
+        };
+
+        info!(
+            logger,
+            "Trying IPFS node at: {}",
+            SafeDisplay(&ipfs_address)
+        );
+
+        // Try to create an IPFS client for this URL
+        let ipfs_client = match IpfsClient::new_from_uri(ipfs_address.as_ref()) {
+            Ok(ipfs_client) => ipfs_client,
+            Err(e) => {
+                error!(
+                    logger,
+                    "Failed to create IPFS client for `{}`: {}",
+                    SafeDisplay(&ipfs_address),
+                    e
+                );
+                panic!("Could not connect to IPFS");
+            }
+        };
+
+        // Test the IPFS client by getting the version from the IPFS daemon.
+        // Only give up if there are no other nodes to fall back to
+        let ipfs_test = ipfs_client.version();
+        let ipfs_ok_logger = logger.clone();
+        let ipfs_err_logger = logger.clone();
+        let ipfs_address_for_ok = ipfs_address.clone();
+        let ipfs_address_for_err = ipfs_address.clone();
+        tokio::spawn(
+            ipfs_test
+                .map_err(move |e| {
+                    error!(
+                        ipfs_err_logger,
+                        "Is there an IPFS node running at \"{}\"?",
+                        SafeDisplay(ipfs_address_for_err),
+                    );
+                    if !has_fallbacks {
+                        panic!("Failed to connect to IPFS: {}", e);
+                    }
+                })
+                .map(move |_| {
+                    info!(
+                        ipfs_ok_logger,
+                        "Successfully connected to IPFS node at: {}",
+                        SafeDisplay(ipfs_address_for_ok)
+                    );
+                }),
+        );
+
+        resolvers.push(Box::new(LinkResolver::from(ipfs_client)));
+    }
+
+    for uri in gateways {
+        let url =
+            Url::parse(uri).unwrap_or_else(|e| panic!("Invalid IPFS gateway URL `{}`: {}", uri, e));
+        info!(
+            logger,
+            "Resolving links through IPFS gateway: {}",
+            SafeDisplay(&url)
+        );
+        resolvers.push(Box::new(GatewayLinkResolver::new(url)));
+    }
+
+    if resolvers.len() == 1 {
+        resolvers.pop().unwrap()
+    } else {
+        Box::new(CompositeLinkResolver::new(
+            logger,
+            resolvers,
+            ipfs_timeout(),
+        ))
+    }
+}
+
+/// Parses Ethereum connection strings and returns the network name, the
+/// node capabilities, the location and the transport for each of them.
 fn parse_ethereum_networks_and_nodes(
     logger: Logger,
     networks: clap::Values,
     connection_type: ConnectionType,
-) -> Result<
-    HashMap<
-        String,
-        Arc<graph_datasource_ethereum::EthereumAdapter<graph_datasource_ethereum::Transport>>,
-    >,
-    Error,
-> {
+) -> Result<Vec<(String, NodeCapabilities, String, Transport)>, Error> {
     networks
         .map(|network| {
             if network.starts_with("wss://")
@@ -720,7 +1050,7 @@ fn parse_ethereum_networks_and_nodes(
                      Try 'mainnet:' + the Ethereum node URL."
                 ));
             } else {
-                // Parse string (format is "NETWORK_NAME:URL")
+                // Parse string (format is "NETWORK_NAME:[CAPABILITIES:]URL")
                 let split_at = network.find(':').ok_or_else(|| {
                     return format_err!(
                         "A network name must be provided alongside the \
@@ -731,6 +1061,8 @@ fn parse_ethereum_networks_and_nodes(
                 let (name, loc_with_delim) = network.split_at(split_at);
                 let loc = &loc_with_delim[1..];
 
+                let (capabilities, loc) = parse_node_capabilities(loc)?;
+
                 if name.is_empty() {
                     return Err(format_err!(
                         "Ethereum network name cannot be an empty string"
@@ -745,6 +1077,7 @@ fn parse_ethereum_networks_and_nodes(
                     logger,
                     "Creating transport";
                     "network" => &name,
+                    "capabilities" => capabilities.to_string(),
                     "url" => &loc,
                 );
 
@@ -758,14 +1091,146 @@ fn parse_ethereum_networks_and_nodes(
                 // For now it's fine to just leak it.
                 std::mem::forget(transport_event_loop);
 
-                Ok((
-                    name.to_string(),
-                    Arc::new(graph_datasource_ethereum::EthereumAdapter::new(
-                        transport,
-                        *ETHEREUM_START_BLOCK,
-                    )),
-                ))
+                Ok((name.to_string(), capabilities, loc.to_string(), transport))
             }
         })
         .collect()
 }
+
+/// Splits the optional capabilities off an Ethereum node location of the
+/// form `[CAPABILITIES:]URL`. Providers without declared capabilities are
+/// assumed to support everything.
+fn parse_node_capabilities(loc: &str) -> Result<(NodeCapabilities, &str), Error> {
+    let split_at = match loc.find(':') {
+        Some(split_at) if split_at > 0 => split_at,
+        _ => return Ok((NodeCapabilities::full(), loc)),
+    };
+    let (prefix, rest) = (&loc[..split_at], &loc[split_at + 1..]);
+
+    // URL schemes (`http://…`) and host/port pairs (`127.0.0.1:8545`,
+    // `localhost:8545`) are part of the location
+    let is_location = rest.starts_with("//")
+        || rest.starts_with(|c: char| c.is_ascii_digit())
+        || !prefix
+            .chars()
+            .all(|c| c.is_ascii_alphabetic() || c == ',' || c == ' ');
+    if is_location {
+        return Ok((NodeCapabilities::full(), loc));
+    }
+
+    let capabilities = NodeCapabilities::from_str(prefix).map_err(|e| {
+        format_err!(
+            "Invalid capabilities `{}` for Ethereum node `{}`: {}. \
+             Supported capabilities are `archive` and `traces`.",
+            prefix,
+            rest,
+            e
+        )
+    })?;
+    Ok((capabilities, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::time::Instant;
+    use tokio::timer::Delay;
+
+    fn step(
+        steps: &Arc<Mutex<Vec<&'static str>>>,
+        name: &'static str,
+        delay: Duration,
+    ) -> Box<Future<Item = (), Error = ()> + Send> {
+        let steps = steps.clone();
+        Box::new(
+            Delay::new(Instant::now() + delay)
+                .map_err(|_| ())
+                .map(move |()| steps.lock().unwrap().push(name)),
+        )
+    }
+
+    #[test]
+    fn shutdown_runs_steps_in_order() {
+        let logger = Logger::root(slog::Discard, o!());
+        let steps = Arc::new(Mutex::new(vec![]));
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+
+        // Earlier steps are slower, so that they would finish last if the
+        // steps ran concurrently
+        runtime
+            .block_on(shutdown(
+                logger,
+                vec![
+                    step(&steps, "subgraphs", Duration::from_millis(200)),
+                    step(&steps, "logs", Duration::from_millis(100)),
+                    step(&steps, "store", Duration::from_millis(0)),
+                ],
+                Duration::from_secs(10),
+            ))
+            .unwrap();
+
+        assert_eq!(*steps.lock().unwrap(), vec!["subgraphs", "logs", "store"]);
+    }
+
+    #[test]
+    fn shutdown_gives_up_after_timeout() {
+        let logger = Logger::root(slog::Discard, o!());
+        let steps = Arc::new(Mutex::new(vec![]));
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+
+        runtime
+            .block_on(shutdown(
+                logger,
+                vec![
+                    step(&steps, "subgraphs", Duration::from_millis(0)),
+                    Box::new(future::empty()),
+                    step(&steps, "store", Duration::from_millis(0)),
+                ],
+                Duration::from_millis(100),
+            ))
+            .unwrap();
+
+        assert_eq!(*steps.lock().unwrap(), vec!["subgraphs"]);
+    }
+
+    #[test]
+    fn node_capabilities_are_optional() {
+        assert_eq!(
+            parse_node_capabilities("http://localhost:8545").unwrap(),
+            (NodeCapabilities::full(), "http://localhost:8545")
+        );
+        assert_eq!(
+            parse_node_capabilities("127.0.0.1:8545").unwrap(),
+            (NodeCapabilities::full(), "127.0.0.1:8545")
+        );
+        assert_eq!(
+            parse_node_capabilities("localhost:8545").unwrap(),
+            (NodeCapabilities::full(), "localhost:8545")
+        );
+        assert_eq!(
+            parse_node_capabilities("/var/run/geth.ipc").unwrap(),
+            (NodeCapabilities::full(), "/var/run/geth.ipc")
+        );
+        assert_eq!(
+            parse_node_capabilities("archive,traces:http://localhost:8545").unwrap(),
+            (NodeCapabilities::full(), "http://localhost:8545")
+        );
+        assert_eq!(
+            parse_node_capabilities("archive:http://localhost:8545").unwrap(),
+            (
+                NodeCapabilities {
+                    archive: true,
+                    traces: false,
+                },
+                "http://localhost:8545"
+            )
+        );
+    }
+
+    #[test]
+    fn unknown_node_capabilities_are_rejected() {
+        assert!(parse_node_capabilities("archve:http://localhost:8545").is_err());
+        assert!(parse_node_capabilities("archive,trace:http://localhost:8545").is_err());
+    }
+}
@@ -251,6 +251,12 @@ impl From<EnumPayload> for i32 {
     }
 }
 
+impl From<EnumPayload> for i64 {
+    fn from(payload: EnumPayload) -> i64 {
+        payload.0 as i64
+    }
+}
+
 impl From<EnumPayload> for f64 {
     fn from(payload: EnumPayload) -> f64 {
         f64::from_bits(payload.0)
@@ -351,6 +357,8 @@ pub enum StoreValueKind {
     Null,
     Bytes,
     BigInt,
+    Int8,
+    Timestamp,
 }
 
 impl StoreValueKind {
@@ -366,6 +374,8 @@ impl StoreValueKind {
             Value::Null => StoreValueKind::Null,
             Value::Bytes(_) => StoreValueKind::Bytes,
             Value::BigInt(_) => StoreValueKind::BigInt,
+            Value::Int8(_) => StoreValueKind::Int8,
+            Value::Timestamp(_) => StoreValueKind::Timestamp,
         }
     }
 }
@@ -479,6 +489,55 @@ pub(crate) struct AscEthereumCall_0_0_3 {
     pub outputs: AscPtr<AscLogParamArray>,
 }
 
+#[repr(C)]
+#[derive(AscType)]
+pub(crate) struct AscEthereumLog {
+    pub address: AscPtr<AscAddress>,
+    pub topics: AscPtr<Array<AscPtr<AscH256>>>,
+    pub data: AscPtr<Bytes>,
+    pub log_index: AscPtr<AscBigInt>,
+    pub transaction_log_index: AscPtr<AscBigInt>,
+    pub log_type: AscPtr<AscString>,
+}
+
+#[repr(C)]
+#[derive(AscType)]
+pub(crate) struct AscEthereumTransactionReceipt {
+    pub status: AscPtr<AscBigInt>,
+    pub gas_used: AscPtr<AscBigInt>,
+    pub cumulative_gas_used: AscPtr<AscBigInt>,
+    pub contract_address: AscPtr<AscAddress>,
+    pub logs: AscPtr<Array<AscPtr<AscEthereumLog>>>,
+}
+
+#[repr(C)]
+#[derive(AscType)]
+pub(crate) struct AscEthereumEvent_0_0_4<T>
+where
+    T: AscType,
+{
+    pub address: AscPtr<AscAddress>,
+    pub log_index: AscPtr<AscBigInt>,
+    pub transaction_log_index: AscPtr<AscBigInt>,
+    pub log_type: AscPtr<AscString>,
+    pub block: AscPtr<AscEthereumBlock>,
+    pub transaction: AscPtr<T>,
+    pub params: AscPtr<AscLogParamArray>,
+    pub receipt: AscPtr<AscEthereumTransactionReceipt>,
+}
+
+#[repr(C)]
+#[derive(AscType)]
+pub(crate) struct AscEthereumCall_0_0_4 {
+    pub to: AscPtr<AscAddress>,
+    pub from: AscPtr<AscAddress>,
+    pub block: AscPtr<AscEthereumBlock>,
+    pub transaction: AscPtr<AscEthereumTransaction>,
+    pub inputs: AscPtr<AscLogParamArray>,
+    pub outputs: AscPtr<AscLogParamArray>,
+    pub receipt: AscPtr<AscEthereumTransactionReceipt>,
+}
+
 #[repr(C)]
 #[derive(AscType)]
 pub(crate) struct AscTypedMapEntry<K, V> {
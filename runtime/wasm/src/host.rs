@@ -25,7 +25,7 @@ pub struct RuntimeHostConfig {
 }
 
 pub struct RuntimeHostBuilder<T, L, S> {
-    ethereum_adapters: HashMap<String, Arc<T>>,
+    ethereum_networks: EthereumNetworks<T>,
     link_resolver: Arc<L>,
     stores: HashMap<String, Arc<S>>,
 }
@@ -38,7 +38,7 @@ where
 {
     fn clone(&self) -> Self {
         RuntimeHostBuilder {
-            ethereum_adapters: self.ethereum_adapters.clone(),
+            ethereum_networks: self.ethereum_networks.clone(),
             link_resolver: self.link_resolver.clone(),
             stores: self.stores.clone(),
         }
@@ -52,12 +52,12 @@ where
     S: Store,
 {
     pub fn new(
-        ethereum_adapters: HashMap<String, Arc<T>>,
+        ethereum_networks: EthereumNetworks<T>,
         link_resolver: Arc<L>,
         stores: HashMap<String, Arc<S>>,
     ) -> Self {
         RuntimeHostBuilder {
-            ethereum_adapters,
+            ethereum_networks,
             link_resolver,
             stores,
         }
@@ -86,12 +86,18 @@ where
             )
         })?;
 
-        let ethereum_adapter = self.ethereum_adapters.get(&network_name).ok_or_else(|| {
-            format_err!(
-                "No Ethereum adapter found that matches subgraph network: \"{}\"",
-                &network_name
-            )
-        })?;
+        // Pick a provider that can serve everything the data source and its
+        // templates need, e.g. `ethereum.call` on historical blocks
+        let ethereum_adapter = self
+            .ethereum_networks
+            .adapter_with_capabilities(&network_name, &data_source.required_capabilities())
+            .map_err(|e| {
+                format_err!(
+                    "No Ethereum adapter found for data source `{}`: {}",
+                    data_source.name,
+                    e
+                )
+            })?;
 
         RuntimeHost::new(
             logger,
@@ -168,9 +174,9 @@ impl RuntimeHost {
         ));
 
         let api_version = Version::parse(&config.data_source.mapping.api_version)?;
-        if !VersionReq::parse("<= 0.0.3").unwrap().matches(&api_version) {
+        if !VersionReq::parse("<= 0.0.4").unwrap().matches(&api_version) {
             return Err(format_err!(
-                "This Graph Node only supports mapping API versions <= 0.0.3, but subgraph `{}` uses `{}`",
+                "This Graph Node only supports mapping API versions <= 0.0.4, but subgraph `{}` uses `{}`",
                 config.subgraph_id,
                 api_version
             ));
@@ -273,6 +279,7 @@ impl RuntimeHost {
                             transaction,
                             log,
                             params,
+                            handler.receipt,
                         ),
                         MappingTrigger::Call {
                             transaction,
@@ -286,6 +293,7 @@ impl RuntimeHost {
                             call,
                             inputs,
                             outputs,
+                            handler.receipt,
                         ),
                         MappingTrigger::Block { handler } => {
                             module.handle_ethereum_block(handler.handler.as_str())
@@ -356,7 +364,9 @@ impl RuntimeHost {
         self.data_source_event_handlers
             .as_ref()
             .map_or(false, |handlers| {
-                handlers.iter().any(|handler| *topic0 == handler.topic0())
+                handlers.iter().any(|handler| {
+                    *topic0 == handler.topic0() && handler.matches_indexed_topics(&log.topics)
+                })
             })
     }
 
@@ -390,7 +400,9 @@ impl RuntimeHost {
                 )
             })?
             .iter()
-            .filter(|handler| *topic0 == handler.topic0())
+            .filter(|handler| {
+                *topic0 == handler.topic0() && handler.matches_indexed_topics(&log.topics)
+            })
             .cloned()
             .collect::<Vec<_>>();
 
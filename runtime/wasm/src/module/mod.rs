@@ -238,40 +238,41 @@ where
         transaction: Arc<Transaction>,
         log: Arc<Log>,
         params: Vec<LogParam>,
+        include_receipt: bool,
     ) -> Result<BlockState, FailureError> {
         self.start_time = Instant::now();
 
         let block = self.ctx.block.block.clone();
+        let receipt = if include_receipt {
+            Some(self.transaction_receipt(&transaction)?)
+        } else {
+            None
+        };
 
         // Prepare an EthereumEvent for the WASM runtime
+        let event = EthereumEventData {
+            block: EthereumBlockData::from(&block),
+            transaction: EthereumTransactionData::from(transaction.deref()),
+            receipt,
+            address: log.address,
+            log_index: log.log_index.unwrap_or(U256::zero()),
+            transaction_log_index: log.transaction_log_index.unwrap_or(U256::zero()),
+            log_type: log.log_type.clone(),
+            params,
+        };
+
         // Decide on the destination type using the mapping
         // api version provided in the subgraph manifest
-        let event = if self.host_exports().api_version >= Version::new(0, 0, 2) {
+        let event = if self.host_exports().api_version >= Version::new(0, 0, 4) {
+            RuntimeValue::from(
+                self.asc_new::<AscEthereumEvent_0_0_4<AscEthereumTransaction_0_0_2>, _>(&event),
+            )
+        } else if self.host_exports().api_version >= Version::new(0, 0, 2) {
             RuntimeValue::from(
-                self.asc_new::<AscEthereumEvent<AscEthereumTransaction_0_0_2>, _>(
-                    &EthereumEventData {
-                        block: EthereumBlockData::from(&block),
-                        transaction: EthereumTransactionData::from(transaction.deref()),
-                        address: log.address,
-                        log_index: log.log_index.unwrap_or(U256::zero()),
-                        transaction_log_index: log.transaction_log_index.unwrap_or(U256::zero()),
-                        log_type: log.log_type.clone(),
-                        params,
-                    },
-                ),
+                self.asc_new::<AscEthereumEvent<AscEthereumTransaction_0_0_2>, _>(&event),
             )
         } else {
-            RuntimeValue::from(self.asc_new::<AscEthereumEvent<AscEthereumTransaction>, _>(
-                &EthereumEventData {
-                    block: EthereumBlockData::from(&block),
-                    transaction: EthereumTransactionData::from(transaction.deref()),
-                    address: log.address,
-                    log_index: log.log_index.unwrap_or(U256::zero()),
-                    transaction_log_index: log.transaction_log_index.unwrap_or(U256::zero()),
-                    log_type: log.log_type.clone(),
-                    params,
-                },
-            ))
+            RuntimeValue::from(self.asc_new::<AscEthereumEvent<AscEthereumTransaction>, _>(&event))
         };
 
         // Invoke the event handler
@@ -322,18 +323,28 @@ where
         call: Arc<EthereumCall>,
         inputs: Vec<LogParam>,
         outputs: Vec<LogParam>,
+        include_receipt: bool,
     ) -> Result<BlockState, FailureError> {
         self.start_time = Instant::now();
 
+        let receipt = if include_receipt {
+            Some(self.transaction_receipt(&transaction)?)
+        } else {
+            None
+        };
+
         let call = EthereumCallData {
             to: call.to,
             from: call.from,
             block: EthereumBlockData::from(&self.ctx.block.block),
             transaction: EthereumTransactionData::from(transaction.deref()),
+            receipt,
             inputs,
             outputs,
         };
-        let arg = if self.host_exports().api_version >= Version::new(0, 0, 3) {
+        let arg = if self.host_exports().api_version >= Version::new(0, 0, 4) {
+            RuntimeValue::from(self.asc_new::<AscEthereumCall_0_0_4, _>(&call))
+        } else if self.host_exports().api_version >= Version::new(0, 0, 3) {
             RuntimeValue::from(self.asc_new::<AscEthereumCall_0_0_3, _>(&call))
         } else {
             RuntimeValue::from(self.asc_new::<AscEthereumCall, _>(&call))
@@ -353,6 +364,24 @@ where
         })
     }
 
+    /// Looks up the receipt of `transaction` in the block being processed.
+    /// Full blocks always carry the receipts of all their transactions.
+    fn transaction_receipt(
+        &self,
+        transaction: &Transaction,
+    ) -> Result<EthereumTransactionReceiptData, FailureError> {
+        self.ctx
+            .block
+            .receipt_for_transaction(&transaction.hash)
+            .map(EthereumTransactionReceiptData::from)
+            .ok_or_else(|| {
+                format_err!(
+                    "Receipt for transaction {:x} not found in block",
+                    transaction.hash
+                )
+            })
+    }
+
     pub(crate) fn handle_ethereum_block(
         mut self,
         handler_name: &str,
@@ -14,7 +14,7 @@ use graph::components::store::*;
 use graph::data::store::scalar;
 use graph::data::subgraph::*;
 use graph::prelude::LinkResolver;
-use graph::web3::types::{Address, Block, Transaction, H160, H256};
+use graph::web3::types::{Address, Block, Log, Transaction, TransactionReceipt, H160, H256};
 use graph_core;
 
 use crate::failure::Error;
@@ -100,6 +100,7 @@ impl EthereumAdapter for MockEthereumAdapter {
         _: Option<EthereumLogFilter>,
         _: Option<EthereumCallFilter>,
         _: Option<EthereumBlockFilter>,
+        _: ScanRanges,
     ) -> Box<Future<Item = Vec<EthereumBlockPointer>, Error = Error> + Send> {
         unimplemented!();
     }
@@ -110,6 +111,7 @@ impl EthereumAdapter for MockEthereumAdapter {
         _: u64,
         _: u64,
         _: EthereumLogFilter,
+        _: AdaptiveRangeSize,
     ) -> Box<Future<Item = Vec<EthereumBlockPointer>, Error = Error> + Send> {
         unimplemented!();
     }
@@ -120,6 +122,7 @@ impl EthereumAdapter for MockEthereumAdapter {
         _: u64,
         _: u64,
         _: EthereumCallFilter,
+        _: AdaptiveRangeSize,
     ) -> Box<Future<Item = Vec<EthereumBlockPointer>, Error = Error> + Send> {
         unimplemented!();
     }
@@ -825,3 +828,148 @@ fn ens_name_by_hash() {
     let hash = module.asc_new("impossible keccak hash");
     assert!(dbg!(module.takes_ptr_returns_ptr::<_, AscString>("nameByHash", hash,)).is_null());
 }
+
+fn mock_log_and_receipt() -> (Transaction, Log, TransactionReceipt) {
+    let hash = "0x0000000000000000000000000000000000000000000000000000000000000001";
+    let address = "0x0123123123012312312301231231230123123123";
+    let log = format!(
+        r#"{{
+            "address": "{}",
+            "topics": [],
+            "data": "0x",
+            "transactionHash": "{}",
+            "logIndex": "0x0"
+        }}"#,
+        address, hash
+    );
+    let transaction = format!(
+        r#"{{
+            "hash": "{}",
+            "nonce": "0x0",
+            "from": "{}",
+            "to": "{}",
+            "value": "0x0",
+            "gasPrice": "0x1",
+            "gas": "0x5208",
+            "input": "0x"
+        }}"#,
+        hash, address, address
+    );
+    let receipt = format!(
+        r#"{{
+            "transactionHash": "{}",
+            "transactionIndex": "0x0",
+            "cumulativeGasUsed": "0xa410",
+            "gasUsed": "0x5208",
+            "logs": [{}],
+            "status": "0x1",
+            "logsBloom": "0x{}"
+        }}"#,
+        hash,
+        log,
+        "0".repeat(512)
+    );
+    (
+        graph::serde_json::from_str(&transaction).unwrap(),
+        graph::serde_json::from_str(&log).unwrap(),
+        graph::serde_json::from_str(&receipt).unwrap(),
+    )
+}
+
+fn mock_context_with_receipts(receipts: Vec<TransactionReceipt>) -> MappingContext {
+    let mut block = EthereumBlock::default();
+    block.transaction_receipts = receipts;
+    MappingContext {
+        block: Arc::new(block),
+        ..mock_context()
+    }
+}
+
+#[test]
+fn event_with_receipt() {
+    let mut data_source = mock_data_source("wasm_test/event_receipt.wasm");
+    data_source.mapping.api_version = String::from("0.0.4");
+    let valid_module = test_valid_module(data_source);
+    let (transaction, log, receipt) = mock_log_and_receipt();
+    let transaction = Arc::new(transaction);
+    let log = Arc::new(log);
+
+    // The handler traps unless the event it receives has a receipt
+    let module = WasmiModule::from_valid_module_with_ctx(
+        valid_module.clone(),
+        mock_context_with_receipts(vec![receipt.clone()]),
+    )
+    .unwrap();
+    module
+        .handle_ethereum_log(
+            "handleEvent",
+            transaction.clone(),
+            log.clone(),
+            vec![],
+            true,
+        )
+        .expect("handler did not receive the receipt");
+
+    let module = WasmiModule::from_valid_module_with_ctx(
+        valid_module.clone(),
+        mock_context_with_receipts(vec![receipt.clone()]),
+    )
+    .unwrap();
+    assert!(module
+        .handle_ethereum_log(
+            "handleEvent",
+            transaction.clone(),
+            log.clone(),
+            vec![],
+            false
+        )
+        .is_err());
+
+    // Asking for a receipt that is not in the block is an error
+    let module = WasmiModule::from_valid_module_with_ctx(
+        valid_module.clone(),
+        mock_context_with_receipts(vec![]),
+    )
+    .unwrap();
+    let err = module
+        .handle_ethereum_log(
+            "handleEvent",
+            transaction.clone(),
+            log.clone(),
+            vec![],
+            true,
+        )
+        .unwrap_err();
+    assert!(err.to_string().starts_with("Receipt for transaction"));
+
+    // The receipt fields end up where the mapping expects them
+    let mut module = WasmiModule::from_valid_module_with_ctx(
+        valid_module,
+        mock_context_with_receipts(vec![receipt.clone()]),
+    )
+    .unwrap();
+    let event = EthereumEventData {
+        address: log.address,
+        log_index: U256::zero(),
+        transaction_log_index: U256::zero(),
+        log_type: None,
+        block: EthereumBlockData::from(&EthereumBlock::default().block),
+        transaction: EthereumTransactionData::from(transaction.deref()),
+        receipt: Some(EthereumTransactionReceiptData::from(&receipt)),
+        params: vec![],
+    };
+    let event_ptr: AscPtr<AscEthereumEvent_0_0_4<AscEthereumTransaction_0_0_2>> =
+        module.asc_new(&event);
+    let gas_used: AscPtr<AscBigInt> = module.takes_ptr_returns_ptr("receiptGasUsed", event_ptr);
+    assert_eq!(module.asc_get::<BigInt, _>(gas_used), BigInt::from(21000));
+    let log_count = module
+        .module
+        .clone()
+        .invoke_export(
+            "receiptLogCount",
+            &[RuntimeValue::from(event_ptr)],
+            &mut module,
+        )
+        .expect("call failed");
+    assert_eq!(log_count, Some(RuntimeValue::I32(1)));
+}
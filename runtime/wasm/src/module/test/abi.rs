@@ -307,6 +307,16 @@ fn abi_store_value() {
         new_value,
         Value::BigInt(::graph::data::store::scalar::BigInt::from_unsigned_bytes_le(bytes))
     );
+
+    // Value::Int8 and Value::Timestamp are passed in the enum payload
+    for value in vec![
+        Value::Int8(i64::min_value()),
+        Value::Timestamp(1_564_000_000_000_000),
+    ] {
+        let value_ptr: AscPtr<AscEnum<StoreValueKind>> = module.asc_new(&value);
+        let new_value: Value = module.asc_get(value_ptr);
+        assert_eq!(new_value, value);
+    }
 }
 
 #[test]
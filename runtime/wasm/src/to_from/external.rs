@@ -3,6 +3,7 @@ use std::collections::HashMap;
 
 use graph::components::ethereum::{
     EthereumBlockData, EthereumCallData, EthereumEventData, EthereumTransactionData,
+    EthereumTransactionReceiptData,
 };
 use graph::data::store;
 use graph::prelude::{BigDecimal, BigInt};
@@ -191,6 +192,8 @@ impl FromAscObj<AscEnum<StoreValueKind>> for store::Value {
                 let array: Vec<u8> = heap.asc_get(ptr);
                 Value::BigInt(store::scalar::BigInt::from_signed_bytes_le(&array))
             }
+            StoreValueKind::Int8 => Value::Int8(i64::from(payload)),
+            StoreValueKind::Timestamp => Value::Timestamp(i64::from(payload)),
         }
     }
 }
@@ -214,6 +217,7 @@ impl ToAscObj<AscEnum<StoreValueKind>> for store::Value {
                 let bytes_obj: AscPtr<Uint8Array> = heap.asc_new(&*big_int.to_signed_bytes_le());
                 bytes_obj.into()
             }
+            Value::Int8(n) | Value::Timestamp(n) => EnumPayload::from(*n),
         };
 
         AscEnum {
@@ -359,6 +363,33 @@ where
     }
 }
 
+impl<T: AscType> ToAscObj<AscEthereumEvent_0_0_4<T>> for EthereumEventData
+where
+    EthereumTransactionData: ToAscObj<T>,
+{
+    fn to_asc_obj<H: AscHeap>(&self, heap: &mut H) -> AscEthereumEvent_0_0_4<T> {
+        AscEthereumEvent_0_0_4 {
+            address: heap.asc_new(&self.address),
+            log_index: heap.asc_new(&BigInt::from_unsigned_u256(&self.log_index)),
+            transaction_log_index: heap
+                .asc_new(&BigInt::from_unsigned_u256(&self.transaction_log_index)),
+            log_type: self
+                .log_type
+                .clone()
+                .map(|log_type| heap.asc_new(&log_type))
+                .unwrap_or_else(|| AscPtr::null()),
+            block: heap.asc_new(&self.block),
+            transaction: heap.asc_new::<T, EthereumTransactionData>(&self.transaction),
+            params: heap.asc_new(self.params.as_slice()),
+            receipt: self
+                .receipt
+                .as_ref()
+                .map(|receipt| heap.asc_new(receipt))
+                .unwrap_or_else(|| AscPtr::null()),
+        }
+    }
+}
+
 impl ToAscObj<AscEthereumCall> for EthereumCallData {
     fn to_asc_obj<H: AscHeap>(&self, heap: &mut H) -> AscEthereumCall {
         AscEthereumCall {
@@ -384,6 +415,69 @@ impl ToAscObj<AscEthereumCall_0_0_3> for EthereumCallData {
     }
 }
 
+impl ToAscObj<AscEthereumCall_0_0_4> for EthereumCallData {
+    fn to_asc_obj<H: AscHeap>(&self, heap: &mut H) -> AscEthereumCall_0_0_4 {
+        AscEthereumCall_0_0_4 {
+            to: heap.asc_new(&self.to),
+            from: heap.asc_new(&self.from),
+            block: heap.asc_new(&self.block),
+            transaction: heap.asc_new(&self.transaction),
+            inputs: heap.asc_new(self.inputs.as_slice()),
+            outputs: heap.asc_new(self.outputs.as_slice()),
+            receipt: self
+                .receipt
+                .as_ref()
+                .map(|receipt| heap.asc_new(receipt))
+                .unwrap_or_else(|| AscPtr::null()),
+        }
+    }
+}
+
+impl ToAscObj<AscEthereumLog> for web3::Log {
+    fn to_asc_obj<H: AscHeap>(&self, heap: &mut H) -> AscEthereumLog {
+        AscEthereumLog {
+            address: heap.asc_new(&self.address),
+            topics: heap.asc_new(self.topics.as_slice()),
+            data: heap.asc_new(&*self.data.0),
+            log_index: self
+                .log_index
+                .map(|log_index| heap.asc_new(&BigInt::from_unsigned_u256(&log_index)))
+                .unwrap_or_else(|| AscPtr::null()),
+            transaction_log_index: self
+                .transaction_log_index
+                .map(|index| heap.asc_new(&BigInt::from_unsigned_u256(&index)))
+                .unwrap_or_else(|| AscPtr::null()),
+            log_type: self
+                .log_type
+                .clone()
+                .map(|log_type| heap.asc_new(&log_type))
+                .unwrap_or_else(|| AscPtr::null()),
+        }
+    }
+}
+
+impl ToAscObj<AscEthereumTransactionReceipt> for EthereumTransactionReceiptData {
+    fn to_asc_obj<H: AscHeap>(&self, heap: &mut H) -> AscEthereumTransactionReceipt {
+        AscEthereumTransactionReceipt {
+            status: self
+                .status
+                .map(|status| heap.asc_new(&BigInt::from(status.as_u64())))
+                .unwrap_or_else(|| AscPtr::null()),
+            gas_used: self
+                .gas_used
+                .map(|gas_used| heap.asc_new(&BigInt::from_unsigned_u256(&gas_used)))
+                .unwrap_or_else(|| AscPtr::null()),
+            cumulative_gas_used: heap
+                .asc_new(&BigInt::from_unsigned_u256(&self.cumulative_gas_used)),
+            contract_address: self
+                .contract_address
+                .map(|address| heap.asc_new(&address))
+                .unwrap_or_else(|| AscPtr::null()),
+            logs: heap.asc_new(self.logs.as_slice()),
+        }
+    }
+}
+
 impl FromAscObj<AscUnresolvedContractCall> for UnresolvedContractCall {
     fn from_asc_obj<H: AscHeap>(asc_call: AscUnresolvedContractCall, heap: &H) -> Self {
         UnresolvedContractCall {
@@ -0,0 +1,29 @@
+;; Hand-written because the handlers only need to inspect the raw
+;; `AscEthereumEvent_0_0_4` layout; assemble with `wat2wasm`.
+(module
+  (memory (export "memory") 4)
+  (global $heap (mut i32) (i32.const 1024))
+
+  (func (export "memory.allocate") (param $size i32) (result i32)
+    (local $ptr i32)
+    (set_local $ptr (get_global $heap))
+    (set_global $heap
+      (i32.and
+        (i32.add (i32.add (get_local $ptr) (get_local $size)) (i32.const 7))
+        (i32.const -8)))
+    (get_local $ptr))
+
+  ;; `receipt` is the eighth field of `AscEthereumEvent_0_0_4`
+  (func $receipt (param $event i32) (result i32)
+    (i32.load offset=28 (get_local $event)))
+
+  (func (export "handleEvent") (param $event i32)
+    (if (i32.eqz (call $receipt (get_local $event)))
+      (then unreachable)))
+
+  (func (export "receiptGasUsed") (param $event i32) (result i32)
+    (i32.load offset=4 (call $receipt (get_local $event))))
+
+  (func (export "receiptLogCount") (param $event i32) (result i32)
+    (i32.load offset=4
+      (i32.load offset=16 (call $receipt (get_local $event))))))
@@ -7,16 +7,23 @@ extern crate graphql_parser;
 extern crate http;
 extern crate hyper;
 extern crate itertools;
+#[macro_use]
+extern crate lazy_static;
+extern crate lru_time_cache;
 extern crate serde;
+extern crate sha2;
+extern crate url;
 
+mod persisted;
 mod request;
 mod response;
 mod server;
 mod service;
 
+pub use self::persisted::PersistedQueries;
 pub use self::request::GraphQLRequest;
 pub use self::response::GraphQLResponse;
 pub use self::server::GraphQLServer;
-pub use self::service::{GraphQLService, GraphQLServiceResponse};
+pub use self::service::{parse_federated_subgraphs, GraphQLService, GraphQLServiceResponse};
 
 pub mod test_utils;
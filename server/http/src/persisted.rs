@@ -0,0 +1,89 @@
+use lru_time_cache::LruCache;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::sync::Mutex;
+
+use graph::components::server::query::GraphQLServerError;
+
+/// The only version of the automatic persisted query protocol we support.
+pub const PERSISTED_QUERY_VERSION: u64 = 1;
+
+/// Returns the hex-encoded SHA-256 hash of a query string.
+pub fn query_hash(query: &str) -> String {
+    format!("{:x}", Sha256::digest(query.as_bytes()))
+}
+
+/// A bounded store of queries registered through automatic persisted
+/// queries, keyed by the SHA-256 hash of the query text.
+///
+/// Clients first send only the hash of a query. If the query is not known,
+/// they retry with the full query text and the hash, which registers the
+/// query for subsequent requests.
+pub struct PersistedQueries {
+    queries: Mutex<LruCache<String, String>>,
+}
+
+impl PersistedQueries {
+    pub fn new(capacity: usize) -> Self {
+        PersistedQueries {
+            queries: Mutex::new(LruCache::with_capacity(capacity)),
+        }
+    }
+
+    /// Looks up the query text for a hash.
+    pub fn get(&self, hash: &str) -> Option<String> {
+        self.queries
+            .lock()
+            .unwrap()
+            .get(&hash.to_lowercase())
+            .cloned()
+    }
+
+    /// Registers a query under `hash`, after checking that the hash matches
+    /// the query text.
+    pub fn register(&self, hash: &str, query: &str) -> Result<(), GraphQLServerError> {
+        let hash = hash.to_lowercase();
+        if query_hash(query) != hash {
+            return Err(GraphQLServerError::ClientError(String::from(
+                "Provided sha256Hash does not match query",
+            )));
+        }
+        self.queries.lock().unwrap().insert(hash, query.to_owned());
+        Ok(())
+    }
+}
+
+impl fmt::Debug for PersistedQueries {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PersistedQueries")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{query_hash, PersistedQueries};
+
+    #[test]
+    fn registers_queries_with_matching_hash() {
+        let queries = PersistedQueries::new(10);
+        let query = "{ name }";
+        let hash = query_hash(query);
+
+        assert_eq!(queries.get(&hash), None);
+        queries
+            .register(&hash, query)
+            .expect("Should accept a matching hash");
+        assert_eq!(queries.get(&hash.to_uppercase()), Some(query.to_owned()));
+    }
+
+    #[test]
+    fn rejects_queries_with_mismatched_hash() {
+        let queries = PersistedQueries::new(10);
+        let hash = query_hash("{ name }");
+
+        queries
+            .register(&hash, "{ other }")
+            .expect_err("Should reject a hash that does not match the query");
+        assert_eq!(queries.get(&hash), None);
+    }
+}
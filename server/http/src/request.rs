@@ -1,23 +1,181 @@
 use graph::serde_json;
+use graph_graphql::query::ast as qast;
 use graphql_parser;
+use graphql_parser::query as q;
 use hyper::Chunk;
+use url::form_urlencoded;
 
 use graph::components::server::query::GraphQLServerError;
 use graph::prelude::*;
 
+use crate::persisted::{PersistedQueries, PERSISTED_QUERY_VERSION};
+
+/// Where the parameters of a GraphQL request come from.
+enum RequestSource {
+    /// A JSON object sent as the body of a POST request.
+    Body(Chunk),
+
+    /// The URL query string of a GET request. `variables` and `extensions`
+    /// are JSON-encoded, all other parameters are plain strings.
+    Params(String),
+}
+
 /// Future for a query parsed from an HTTP request.
 pub struct GraphQLRequest {
-    body: Chunk,
+    source: RequestSource,
     schema: Arc<Schema>,
+    persisted_queries: Option<Arc<PersistedQueries>>,
 }
 
 impl GraphQLRequest {
     /// Creates a new GraphQLRequest future based on an HTTP request and a result sender.
     pub fn new(body: Chunk, schema: Arc<Schema>) -> Self {
-        GraphQLRequest { body, schema }
+        GraphQLRequest {
+            source: RequestSource::Body(body),
+            schema,
+            persisted_queries: None,
+        }
+    }
+
+    /// Creates a new GraphQLRequest future based on the URL query string of
+    /// a GET request.
+    pub fn from_params(params: &str, schema: Arc<Schema>) -> Self {
+        GraphQLRequest {
+            source: RequestSource::Params(params.to_owned()),
+            schema,
+            persisted_queries: None,
+        }
+    }
+
+    /// Enables automatic persisted queries for this request.
+    pub fn with_persisted_queries(mut self, persisted_queries: Arc<PersistedQueries>) -> Self {
+        self.persisted_queries = Some(persisted_queries);
+        self
+    }
+
+    /// Turns the request parameters into the same JSON object a POST request
+    /// would carry in its body.
+    fn request_data(
+        &self,
+    ) -> Result<serde_json::Map<String, serde_json::Value>, GraphQLServerError> {
+        match self.source {
+            RequestSource::Body(ref body) => {
+                // Parse request body as JSON
+                let json: serde_json::Value = serde_json::from_slice(body)
+                    .map_err(|e| GraphQLServerError::ClientError(format!("{}", e)))?;
+
+                // Ensure the JSON data is an object
+                match json {
+                    serde_json::Value::Object(obj) => Ok(obj),
+                    _ => Err(GraphQLServerError::ClientError(String::from(
+                        "Request data is not an object",
+                    ))),
+                }
+            }
+            RequestSource::Params(ref params) => form_urlencoded::parse(params.as_bytes())
+                .map(|(name, value)| -> Result<_, GraphQLServerError> {
+                    let value = match name.as_ref() {
+                        "variables" | "extensions" => {
+                            serde_json::from_str(&value).map_err(|e| {
+                                GraphQLServerError::ClientError(format!(
+                                    "Invalid \"{}\" parameter: {}",
+                                    name, e
+                                ))
+                            })?
+                        }
+                        _ => serde_json::Value::String(value.into_owned()),
+                    };
+                    Ok((name.into_owned(), value))
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the query text of the request, resolving and registering
+    /// automatic persisted queries along the way.
+    fn query_string(
+        &self,
+        obj: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<String, GraphQLServerError> {
+        // Ensure the "query" field, if present, is a string
+        let query = match obj.get("query") {
+            None | Some(serde_json::Value::Null) => None,
+            Some(serde_json::Value::String(query)) => Some(query),
+            Some(_) => {
+                return Err(GraphQLServerError::ClientError(String::from(
+                    "The\"query\" field is not a string",
+                )));
+            }
+        };
+
+        let hash = match obj
+            .get("extensions")
+            .and_then(|extensions| extensions.get("persistedQuery"))
+        {
+            None | Some(serde_json::Value::Null) => None,
+            Some(persisted_query) => {
+                let version = persisted_query.get("version").and_then(|v| v.as_u64());
+                if version != Some(PERSISTED_QUERY_VERSION) {
+                    return Err(GraphQLServerError::ClientError(String::from(
+                        "Unsupported persisted query version",
+                    )));
+                }
+                Some(
+                    persisted_query
+                        .get("sha256Hash")
+                        .and_then(|hash| hash.as_str())
+                        .ok_or_else(|| {
+                            GraphQLServerError::ClientError(String::from(
+                                "The \"sha256Hash\" of the persisted query is missing",
+                            ))
+                        })?,
+                )
+            }
+        };
+
+        match (query, hash, &self.persisted_queries) {
+            (Some(query), Some(hash), Some(persisted_queries)) => {
+                persisted_queries.register(hash, query)?;
+                Ok(query.clone())
+            }
+            (Some(query), _, _) => Ok(query.clone()),
+            (None, Some(hash), Some(persisted_queries)) => persisted_queries
+                .get(hash)
+                .ok_or(GraphQLServerError::PersistedQueryNotFound),
+            (None, Some(_), None) => Err(GraphQLServerError::ClientError(String::from(
+                "Persisted queries are not supported",
+            ))),
+            (None, None, _) => Err(GraphQLServerError::ClientError(String::from(
+                "The \"query\" field missing in request data",
+            ))),
+        }
     }
 }
 
+/// Removes all operations except the one named `operation_name` from the
+/// document, so that documents with several operations can be executed.
+fn select_operation(
+    document: q::Document,
+    operation_name: &str,
+) -> Result<q::Document, QueryExecutionError> {
+    // Fail early if the operation does not exist
+    qast::get_operation(&document, Some(operation_name))?;
+
+    Ok(q::Document {
+        definitions: document
+            .definitions
+            .into_iter()
+            .filter(|definition| match definition {
+                q::Definition::Operation(operation) => {
+                    qast::get_operation_name(operation).map(|name| name.as_str())
+                        == Some(operation_name)
+                }
+                q::Definition::Fragment(_) => true,
+            })
+            .collect(),
+    })
+}
+
 impl Future for GraphQLRequest {
     type Item = Query;
     type Error = GraphQLServerError;
@@ -26,31 +184,27 @@ impl Future for GraphQLRequest {
         // Fail if no schema is available
         let schema = self.schema.clone();
 
-        // Parse request body as JSON
-        let json: serde_json::Value = serde_json::from_slice(&self.body)
-            .map_err(|e| GraphQLServerError::ClientError(format!("{}", e)))?;
-
-        // Ensure the JSON data is an object
-        let obj = json.as_object().ok_or_else(|| {
-            GraphQLServerError::ClientError(String::from("Request data is not an object"))
-        })?;
-
-        // Ensure the JSON data has a "query" field
-        let query_value = obj.get("query").ok_or_else(|| {
-            GraphQLServerError::ClientError(String::from(
-                "The \"query\" field missing in request data",
-            ))
-        })?;
-
-        // Ensure the "query" field is a string
-        let query_string = query_value.as_str().ok_or_else(|| {
-            GraphQLServerError::ClientError(String::from("The\"query\" field is not a string"))
-        })?;
+        let obj = self.request_data()?;
+        let query_string = self.query_string(&obj)?;
 
         // Parse the "query" field of the JSON body
-        let document = graphql_parser::parse_query(query_string)
+        let mut document = graphql_parser::parse_query(&query_string)
             .map_err(|e| GraphQLServerError::from(QueryError::from(e)))?;
 
+        // Only keep the operation selected by "operationName", if present
+        match obj.get("operationName") {
+            None | Some(serde_json::Value::Null) => (),
+            Some(serde_json::Value::String(operation_name)) => {
+                document = select_operation(document, operation_name)
+                    .map_err(|e| GraphQLServerError::from(QueryError::from(e)))?;
+            }
+            Some(_) => {
+                return Err(GraphQLServerError::ClientError(String::from(
+                    "The \"operationName\" field is not a string",
+                )));
+            }
+        }
+
         // Parse the "variables" field of the JSON body, if present
         let variables = match obj.get("variables") {
             None | Some(serde_json::Value::Null) => Ok(None),
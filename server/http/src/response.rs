@@ -21,7 +21,9 @@ impl GraphQLResponse {
 
     fn status_code_from_result(&self) -> StatusCode {
         match self.result {
-            Ok(_) => StatusCode::OK,
+            // Clients expect to receive this error like any other GraphQL
+            // error, and then retry with the full query
+            Ok(_) | Err(GraphQLServerError::PersistedQueryNotFound) => StatusCode::OK,
             Err(GraphQLServerError::ClientError(_)) | Err(GraphQLServerError::QueryError(_)) => {
                 StatusCode::BAD_REQUEST
             }
@@ -60,7 +62,10 @@ impl Future for GraphQLResponse {
         let response = Response::builder()
             .status(status_code)
             .header("Access-Control-Allow-Origin", "*")
-            .header("Access-Control-Allow-Headers", "Content-Type")
+            .header(
+                "Access-Control-Allow-Headers",
+                "Content-Type, X-Graph-Trace",
+            )
             .header("Access-Control-Allow-Methods", "GET, OPTIONS, POST")
             .header("Content-Type", "application/json")
             .body(Body::from(json))
@@ -1,13 +1,37 @@
+use std::env;
 use std::error::Error;
 use std::fmt;
 use std::net::{Ipv4Addr, SocketAddrV4};
+use std::str::FromStr;
 
 use hyper;
 use hyper::Server;
 
+use crate::persisted::PersistedQueries;
 use crate::service::GraphQLService;
 use graph::prelude::{GraphQLServer as GraphQLServerTrait, *};
 
+lazy_static! {
+    /// Maximum number of automatic persisted queries to remember; `0`
+    /// disables persisted queries.
+    static ref PERSISTED_QUERY_CACHE_SIZE: usize =
+        env::var("GRAPH_GRAPHQL_PERSISTED_QUERY_CACHE_SIZE")
+            .ok()
+            .map(|s| usize::from_str(&s).unwrap_or_else(|_| {
+                panic!("failed to parse env var GRAPH_GRAPHQL_PERSISTED_QUERY_CACHE_SIZE")
+            }))
+            .unwrap_or(10000);
+
+    /// Whether clients may ask for traces of their queries with the
+    /// `X-Graph-Trace` header.
+    static ref ALLOW_TRACE_HEADER: bool = env::var("GRAPH_GRAPHQL_ALLOW_TRACE_HEADER")
+        .ok()
+        .map(|s| bool::from_str(&s).unwrap_or_else(|_| {
+            panic!("failed to parse env var GRAPH_GRAPHQL_ALLOW_TRACE_HEADER")
+        }))
+        .unwrap_or(false);
+}
+
 /// Errors that may occur when starting the server.
 #[derive(Debug)]
 pub enum GraphQLServeError {
@@ -44,15 +68,19 @@ pub struct GraphQLServer<Q, S> {
     graphql_runner: Arc<Q>,
     store: Arc<S>,
     node_id: NodeId,
+    persisted_queries: Option<Arc<PersistedQueries>>,
+    federated_subgraphs: Vec<(String, SubgraphName)>,
 }
 
 impl<Q, S> GraphQLServer<Q, S> {
-    /// Creates a new GraphQL server.
+    /// Creates a new GraphQL server. The `federated_subgraphs` are served
+    /// together at `/subgraphs/federated`, each under its namespace.
     pub fn new(
         logger_factory: &LoggerFactory,
         graphql_runner: Arc<Q>,
         store: Arc<S>,
         node_id: NodeId,
+        federated_subgraphs: Vec<(String, SubgraphName)>,
     ) -> Self {
         let logger = logger_factory.component_logger(
             "GraphQLServer",
@@ -63,11 +91,19 @@ impl<Q, S> GraphQLServer<Q, S> {
             }),
         );
 
+        // Persisted queries are shared by all connections
+        let persisted_queries = match *PERSISTED_QUERY_CACHE_SIZE {
+            0 => None,
+            size => Some(Arc::new(PersistedQueries::new(size))),
+        };
+
         GraphQLServer {
             logger,
             graphql_runner,
             store,
             node_id,
+            persisted_queries,
+            federated_subgraphs,
         }
     }
 }
@@ -94,21 +130,19 @@ where
         let addr = SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), port);
 
         // On every incoming request, launch a new GraphQL service that writes
-        // incoming queries to the query sink.
-        let logger_for_service = self.logger.clone();
-        let graphql_runner = self.graphql_runner.clone();
-        let store = self.store.clone();
-        let node_id = self.node_id.clone();
-        let new_service = move || {
-            let service = GraphQLService::new(
-                logger_for_service.clone(),
-                graphql_runner.clone(),
-                store.clone(),
-                ws_port,
-                node_id.clone(),
-            );
-            future::ok::<GraphQLService<Q, S>, hyper::Error>(service)
-        };
+        // incoming queries to the query sink. The services are clones of one
+        // service, so that they share the cached federation
+        let service = GraphQLService::new(
+            self.logger.clone(),
+            self.graphql_runner.clone(),
+            self.store.clone(),
+            ws_port,
+            self.node_id.clone(),
+            self.persisted_queries.clone(),
+            *ALLOW_TRACE_HEADER,
+            self.federated_subgraphs.clone(),
+        );
+        let new_service = move || future::ok::<GraphQLService<Q, S>, hyper::Error>(service.clone());
 
         // Create a task to run the server and handle HTTP requests
         let task = Server::try_bind(&addr.into())?
@@ -1,21 +1,44 @@
+use std::env;
 use std::ops::Deref;
+use std::str::FromStr;
+use std::sync::Mutex;
 use std::time::Instant;
 
 use graph::components::server::query::GraphQLServerError;
 use graph::data::subgraph::schema::{SubgraphEntity, SUBGRAPHS_ID};
 use graph::prelude::*;
+use graph_graphql::prelude::Federation;
 use http::header;
 use hyper::service::Service;
 use hyper::{Body, Method, Request, Response, StatusCode};
 
+use crate::persisted::PersistedQueries;
 use crate::request::GraphQLRequest;
 use crate::response::GraphQLResponse;
 
+lazy_static! {
+    /// How long (in seconds) shared caches may serve the result of a GET
+    /// request without revalidating it.
+    static ref HTTP_CACHE_MAX_AGE: u64 = env::var("GRAPH_GRAPHQL_HTTP_CACHE_MAX_AGE")
+        .ok()
+        .map(|s| u64::from_str(&s).unwrap_or_else(|_| {
+            panic!("failed to parse env var GRAPH_GRAPHQL_HTTP_CACHE_MAX_AGE")
+        }))
+        .unwrap_or(5);
+}
+
+/// Request header that asks for the response to include a trace of the
+/// query execution in its `extensions`.
+const TRACE_HEADER: &str = "X-Graph-Trace";
+
+/// A future that resolves to the query contained in a request.
+type QueryFuture = Box<Future<Item = Query, Error = GraphQLServerError> + Send>;
+
 /// An asynchronous response to a GraphQL request.
 pub type GraphQLServiceResponse =
     Box<Future<Item = Response<Body>, Error = GraphQLServerError> + Send>;
 
-/// A Hyper Service that serves GraphQL over a POST / endpoint.
+/// A Hyper Service that serves GraphQL over POST and GET endpoints.
 #[derive(Debug)]
 pub struct GraphQLService<Q, S> {
     logger: Logger,
@@ -23,6 +46,14 @@ pub struct GraphQLService<Q, S> {
     store: Arc<S>,
     ws_port: u16,
     node_id: NodeId,
+    persisted_queries: Option<Arc<PersistedQueries>>,
+    allow_trace_header: bool,
+    /// The subgraphs served together at `/subgraphs/federated`, each under
+    /// its namespace.
+    federated_subgraphs: Arc<Vec<(String, SubgraphName)>>,
+    /// The federation of the federated subgraphs, together with the
+    /// deployments it was built from. Shared by all clones of the service.
+    federation: Arc<Mutex<Option<(Vec<SubgraphDeploymentId>, Arc<Federation>)>>>,
 }
 
 impl<Q, S> Clone for GraphQLService<Q, S> {
@@ -33,6 +64,10 @@ impl<Q, S> Clone for GraphQLService<Q, S> {
             store: self.store.clone(),
             ws_port: self.ws_port,
             node_id: self.node_id.clone(),
+            persisted_queries: self.persisted_queries.clone(),
+            allow_trace_header: self.allow_trace_header,
+            federated_subgraphs: self.federated_subgraphs.clone(),
+            federation: self.federation.clone(),
         }
     }
 }
@@ -42,13 +77,19 @@ where
     Q: GraphQlRunner,
     S: SubgraphDeploymentStore + Store,
 {
-    /// Creates a new GraphQL service.
+    /// Creates a new GraphQL service. Clients can only ask for traces of
+    /// their queries if `allow_trace_header` is set. The
+    /// `federated_subgraphs` are served at `/subgraphs/federated`; if there
+    /// are none, that endpoint does not exist.
     pub fn new(
         logger: Logger,
         graphql_runner: Arc<Q>,
         store: Arc<S>,
         ws_port: u16,
         node_id: NodeId,
+        persisted_queries: Option<Arc<PersistedQueries>>,
+        allow_trace_header: bool,
+        federated_subgraphs: Vec<(String, SubgraphName)>,
     ) -> Self {
         GraphQLService {
             logger,
@@ -56,9 +97,26 @@ where
             store,
             ws_port,
             node_id,
+            persisted_queries,
+            allow_trace_header,
+            federated_subgraphs: Arc::new(federated_subgraphs),
+            federation: Arc::new(Mutex::new(None)),
         }
     }
 
+    /// Returns `true` if the request asks for a trace of the query execution
+    /// with an `X-Graph-Trace: true` (or `1`) header and clients are allowed
+    /// to do that. Traces bypass the query cache and reveal how the query was
+    /// executed, so they are not available to every client by default.
+    fn is_traced(&self, request: &Request<Body>) -> bool {
+        self.allow_trace_header
+            && request
+                .headers()
+                .get(TRACE_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .map_or(false, |value| value == "true" || value == "1")
+    }
+
     fn graphiql_html(&self) -> String {
         include_str!("../assets/index.html")
             .replace("__WS_PORT__", format!("{}", self.ws_port).as_str())
@@ -162,9 +220,7 @@ where
                         "Subgraph name not found".to_owned(),
                     ))
                 })
-                .and_then(move |subgraph_id| {
-                    service.handle_graphql_query(&subgraph_id, request.into_body())
-                }),
+                .and_then(move |subgraph_id| service.handle_graphql_query(&subgraph_id, request)),
         )
     }
 
@@ -175,14 +231,14 @@ where
     ) -> GraphQLServiceResponse {
         match SubgraphDeploymentId::new(id) {
             Err(()) => self.handle_not_found(),
-            Ok(id) => self.handle_graphql_query(&id, request.into_body()),
+            Ok(id) => self.handle_graphql_query(&id, request),
         }
     }
 
     fn handle_graphql_query(
         &self,
         id: &SubgraphDeploymentId,
-        request_body: Body,
+        request: Request<Body>,
     ) -> GraphQLServiceResponse {
         let service = self.clone();
         let logger = self.logger.clone();
@@ -212,18 +268,76 @@ where
             }
         };
 
+        // Results of GET requests may be cached by intermediaries for as long
+        // as the deployment stays at the same block. Clients that already
+        // have the result for the block the deployment is at don't need the
+        // query to be run again; deployments without a block pointer (e.g.
+        // the subgraph of subgraphs) are not cacheable
+        let is_get = *request.method() == Method::GET;
+        let traced = self.is_traced(&request);
+        let current_etag = if is_get {
+            self.store
+                .block_ptr(id.clone())
+                .ok()
+                .map(|block_ptr| etag(id, &block_ptr))
+        } else {
+            None
+        };
+
+        if let Some(ref etag) = current_etag {
+            if if_none_match(&request, etag) {
+                return Box::new(future::ok(
+                    Response::builder()
+                        .status(StatusCode::NOT_MODIFIED)
+                        .header("Access-Control-Allow-Origin", "*")
+                        .header(header::CACHE_CONTROL, cache_control().as_str())
+                        .header(header::ETAG, etag.as_str())
+                        .body(Body::empty())
+                        .unwrap(),
+                ));
+            }
+        }
+
+        let persisted_queries = self.persisted_queries.clone();
+        let with_persisted_queries = move |request: GraphQLRequest| match persisted_queries {
+            Some(persisted_queries) => request.with_persisted_queries(persisted_queries),
+            None => request,
+        };
+
+        let query: QueryFuture = if is_get {
+            let params = request.uri().query().unwrap_or("");
+            Box::new(with_persisted_queries(GraphQLRequest::from_params(
+                params, schema,
+            )))
+        } else {
+            Box::new(
+                request
+                    .into_body()
+                    .concat2()
+                    .map_err(|_| GraphQLServerError::from("Failed to read request body"))
+                    .and_then(move |body| {
+                        with_persisted_queries(GraphQLRequest::new(body, schema))
+                    }),
+            )
+        };
+
         let start = Instant::now();
         Box::new(
-            request_body
-                .concat2()
-                .map_err(|_| GraphQLServerError::from("Failed to read request body"))
-                .and_then(move |body| GraphQLRequest::new(body, schema))
+            query
                 .and_then(move |query| {
-                    // Run the query using the query runner
-                    service
-                        .graphql_runner
-                        .run_query(query)
-                        .map_err(|e| GraphQLServerError::from(e))
+                    // Run the query using the query runner; traced results
+                    // are not cacheable, so their block does not matter
+                    let result: QueryResultAtBlockFuture = if traced {
+                        Box::new(
+                            service
+                                .graphql_runner
+                                .run_traced_query(query)
+                                .map(|result| (result, None)),
+                        )
+                    } else {
+                        service.graphql_runner.run_query_at_block(query)
+                    };
+                    result.map_err(|e| GraphQLServerError::from(e))
                 })
                 .then(move |result| {
                     let elapsed = start.elapsed().as_millis();
@@ -244,7 +358,198 @@ where
                             "code" => LogCode::GraphQlQueryFailure,
                         ),
                     }
-                    GraphQLResponse::new(result)
+
+                    // Only complete, error-free results may be cached, under
+                    // the block the result was computed at
+                    let etag = match result {
+                        Ok((ref result, Some(ref block_ptr)))
+                            if is_get && result.errors.is_none() =>
+                        {
+                            Some(etag(&sd_id, block_ptr))
+                        }
+                        _ => None,
+                    };
+                    let result = result.map(|(result, _)| result);
+
+                    GraphQLResponse::new(result).map(move |mut response| {
+                        if is_get {
+                            let headers = response.headers_mut();
+                            match etag {
+                                Some(etag) => {
+                                    headers.insert(
+                                        header::CACHE_CONTROL,
+                                        header::HeaderValue::from_str(&cache_control()).unwrap(),
+                                    );
+                                    headers.insert(
+                                        header::ETAG,
+                                        header::HeaderValue::from_str(&etag).unwrap(),
+                                    );
+                                }
+                                None => {
+                                    headers.insert(
+                                        header::CACHE_CONTROL,
+                                        header::HeaderValue::from_static("no-cache"),
+                                    );
+                                }
+                            }
+                        }
+                        response
+                    })
+                }),
+        )
+    }
+
+    /// Returns the federated schema for the current deployments of the
+    /// federated subgraphs. The schema is only rebuilt when one of the
+    /// subgraphs moves to a different deployment.
+    fn federation(&self) -> Result<Arc<Federation>, GraphQLServerError> {
+        let ids = self
+            .federated_subgraphs
+            .iter()
+            .map(|(_, subgraph_name)| {
+                self.store
+                    .resolve_subgraph_name_to_id(subgraph_name.clone())
+                    .map_err(|e| {
+                        GraphQLServerError::InternalError(format!(
+                            "Error resolving subgraph name: {}",
+                            e
+                        ))
+                    })?
+                    .ok_or_else(|| {
+                        GraphQLServerError::ClientError(format!(
+                            "Subgraph name {} not found",
+                            subgraph_name
+                        ))
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if let Some((ref cached_ids, ref federation)) = *self.federation.lock().unwrap() {
+            if *cached_ids == ids {
+                return Ok(federation.clone());
+            }
+        }
+
+        let deployments = self
+            .federated_subgraphs
+            .iter()
+            .zip(ids.iter())
+            .map(|((namespace, _), id)| {
+                self.store
+                    .subgraph_schema(id)
+                    .map(|schema| (namespace.clone(), schema))
+                    .map_err(|e| GraphQLServerError::InternalError(e.to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let federation = Arc::new(
+            Federation::new(deployments)
+                .map_err(|e| GraphQLServerError::InternalError(e.to_string()))?,
+        );
+
+        *self.federation.lock().unwrap() = Some((ids, federation.clone()));
+        Ok(federation)
+    }
+
+    /// Serves a query against the federated subgraphs. Each top-level field
+    /// of the query is executed against the deployment it belongs to, and the
+    /// results are combined into one response.
+    fn handle_federated_query(&self, request: Request<Body>) -> GraphQLServiceResponse {
+        if self.federated_subgraphs.is_empty() {
+            return self.handle_not_found();
+        }
+
+        let service = self.clone();
+        let logger = self.logger.clone();
+
+        let federation = match self.federation() {
+            Ok(federation) => federation,
+            Err(e) => return Box::new(future::err(e)),
+        };
+
+        let is_get = *request.method() == Method::GET;
+        let traced = self.is_traced(&request);
+
+        let persisted_queries = self.persisted_queries.clone();
+        let with_persisted_queries = move |request: GraphQLRequest| match persisted_queries {
+            Some(persisted_queries) => request.with_persisted_queries(persisted_queries),
+            None => request,
+        };
+
+        let schema = federation.schema.clone();
+        let query: QueryFuture = if is_get {
+            let params = request.uri().query().unwrap_or("");
+            Box::new(with_persisted_queries(GraphQLRequest::from_params(
+                params, schema,
+            )))
+        } else {
+            Box::new(
+                request
+                    .into_body()
+                    .concat2()
+                    .map_err(|_| GraphQLServerError::from("Failed to read request body"))
+                    .and_then(move |body| {
+                        with_persisted_queries(GraphQLRequest::new(body, schema))
+                    }),
+            )
+        };
+
+        let start = Instant::now();
+        Box::new(
+            query
+                .and_then(move |query| -> QueryResultFuture {
+                    let queries = match federation.split_query(&query) {
+                        Ok(queries) => queries,
+                        Err(errors) => return Box::new(future::ok(QueryResult::from(errors))),
+                    };
+
+                    // Run the queries against the individual deployments
+                    // concurrently
+                    let graphql_runner = service.graphql_runner.clone();
+                    Box::new(
+                        future::join_all(queries.into_iter().map(move |federated_query| {
+                            let query = federated_query.query.clone();
+                            if traced {
+                                graphql_runner.run_traced_query(query)
+                            } else {
+                                graphql_runner.run_query(query)
+                            }
+                            .map(move |result| (federated_query, result))
+                        }))
+                        .map(move |results| federation.merge_results(results)),
+                    )
+                })
+                .map_err(|e| GraphQLServerError::from(e))
+                .then(move |result| {
+                    let elapsed = start.elapsed().as_millis();
+                    match result {
+                        Ok(_) => info!(
+                            logger,
+                            "GraphQL query served";
+                            "subgraph_deployment" => "federated",
+                            "query_time_ms" => elapsed,
+                            "code" => LogCode::GraphQlQuerySuccess,
+                        ),
+                        Err(ref e) => error!(
+                            logger,
+                            "GraphQL query failed";
+                            "subgraph_deployment" => "federated",
+                            "error" => e.to_string(),
+                            "query_time_ms" => elapsed,
+                            "code" => LogCode::GraphQlQueryFailure,
+                        ),
+                    }
+
+                    GraphQLResponse::new(result).map(move |mut response| {
+                        // The deployments may be at different blocks, so
+                        // there is no entity tag to revalidate against
+                        if is_get {
+                            response.headers_mut().insert(
+                                header::CACHE_CONTROL,
+                                header::HeaderValue::from_static("no-cache"),
+                            );
+                        }
+                        response
+                    })
                 }),
         )
     }
@@ -255,7 +560,10 @@ where
             Response::builder()
                 .status(200)
                 .header("Access-Control-Allow-Origin", "*")
-                .header("Access-Control-Allow-Headers", "Content-Type")
+                .header(
+                    "Access-Control-Allow-Headers",
+                    "Content-Type, X-Graph-Trace",
+                )
                 .header("Access-Control-Allow-Methods", "GET, OPTIONS, POST")
                 .body(Body::from(""))
                 .unwrap(),
@@ -312,11 +620,34 @@ where
             (Method::GET, &["subgraphs", "id", _, "graphql"])
             | (Method::GET, &["subgraphs", "name", _, "graphql"])
             | (Method::GET, &["subgraphs", "name", _, _, "graphql"])
+            | (Method::GET, &["subgraphs", "federated", "graphql"])
             | (Method::GET, &["subgraphs", "graphql"]) => self.handle_graphiql(),
 
+            // GET requests with query parameters are GraphQL queries, all
+            // other GET requests are redirected to GraphiQL
+            (Method::GET, &["subgraphs", "id", subgraph_id]) if has_query_params(&req) => {
+                self.handle_graphql_query_by_id(subgraph_id.to_owned(), req)
+            }
+            (Method::GET, &["subgraphs", "name", subgraph_name]) if has_query_params(&req) => {
+                self.handle_graphql_query_by_name(subgraph_name.to_owned(), req)
+            }
+            (Method::GET, ["subgraphs", "name", subgraph_name_part1, subgraph_name_part2])
+                if has_query_params(&req) =>
+            {
+                let subgraph_name = format!("{}/{}", subgraph_name_part1, subgraph_name_part2);
+                self.handle_graphql_query_by_name(subgraph_name, req)
+            }
+            (Method::GET, &["subgraphs", "federated"]) if has_query_params(&req) => {
+                self.handle_federated_query(req)
+            }
+            (Method::GET, &["subgraphs"]) if has_query_params(&req) => {
+                self.handle_graphql_query_by_id(SUBGRAPHS_ID.to_string(), req)
+            }
+
             (Method::GET, path @ ["subgraphs", "id", _])
             | (Method::GET, path @ ["subgraphs", "name", _])
             | (Method::GET, path @ ["subgraphs", "name", _, _])
+            | (Method::GET, path @ ["subgraphs", "federated"])
             | (Method::GET, path @ ["subgraphs"]) => {
                 let dest = format!("/{}/graphql", path.join("/"));
                 self.handle_temp_redirect(&dest)
@@ -336,6 +667,9 @@ where
             (Method::OPTIONS, ["subgraphs", "name", _])
             | (Method::OPTIONS, ["subgraphs", "name", _, _]) => self.handle_graphql_options(req),
 
+            (Method::POST, &["subgraphs", "federated"]) => self.handle_federated_query(req),
+            (Method::OPTIONS, ["subgraphs", "federated"]) => self.handle_graphql_options(req),
+
             // `/subgraphs` acts as an alias to `/subgraphs/id/SUBGRAPHS_ID`
             (Method::POST, &["subgraphs"]) => {
                 self.handle_graphql_query_by_id(SUBGRAPHS_ID.to_string(), req)
@@ -347,6 +681,75 @@ where
     }
 }
 
+/// Returns `true` if the URL of a request carries GraphQL request
+/// parameters.
+fn has_query_params(request: &Request<Body>) -> bool {
+    request.uri().query().map_or(false, |query| {
+        query.split('&').any(|param| {
+            let name = param.split('=').next().unwrap_or("");
+            name == "query" || name == "extensions"
+        })
+    })
+}
+
+/// Parses the subgraphs to federate. Each entry is either
+/// `<namespace>=<subgraph name>` or a subgraph name, whose namespace is the
+/// name with all characters that are not allowed in GraphQL names replaced
+/// by `_`. Empty entries are ignored.
+pub fn parse_federated_subgraphs<'a>(
+    entries: impl IntoIterator<Item = &'a str>,
+) -> Result<Vec<(String, SubgraphName)>, String> {
+    entries
+        .into_iter()
+        .map(|entry| entry.trim())
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (namespace, name) = match entry.find('=') {
+                Some(pos) => (entry[..pos].trim().to_owned(), entry[pos + 1..].trim()),
+                None => (
+                    entry
+                        .chars()
+                        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                        .collect(),
+                    entry,
+                ),
+            };
+            SubgraphName::new(name)
+                .map(|name| (namespace, name))
+                .map_err(|()| format!("invalid subgraph name `{}`", name))
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .and_then(|subgraphs| {
+            Federation::check_namespaces(subgraphs.iter().map(|(namespace, _)| namespace.as_str()))
+                .map(|()| subgraphs)
+                .map_err(|e| e.to_string())
+        })
+}
+
+/// Returns the entity tag for results of queries against the deployment at
+/// `block_ptr`.
+fn etag(id: &SubgraphDeploymentId, block_ptr: &EthereumBlockPointer) -> String {
+    format!("\"{}-{}-{:x}\"", id, block_ptr.number, block_ptr.hash)
+}
+
+/// Returns `true` if the request's `If-None-Match` header matches `etag`.
+/// The `*` wildcard is not honored, since it would answer queries that were
+/// never validated with a 304.
+fn if_none_match(request: &Request<Body>, etag: &str) -> bool {
+    request
+        .headers()
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim())
+        .any(|tag| tag.trim_start_matches("W/") == etag)
+}
+
+fn cache_control() -> String {
+    format!("public, max-age={}", *HTTP_CACHE_MAX_AGE)
+}
+
 impl<Q, S> Service for GraphQLService<Q, S>
 where
     Q: GraphQlRunner,
@@ -373,7 +776,8 @@ where
                     .body(Body::from("Internal server error (operation canceled)"))
                     .unwrap())
             }
-            Err(err @ GraphQLServerError::ClientError(_)) => {
+            Err(err @ GraphQLServerError::ClientError(_))
+            | Err(err @ GraphQLServerError::PersistedQueryNotFound) => {
                 debug!(logger, "GraphQLService call failed: {}", err);
 
                 Ok(Response::builder()
@@ -416,8 +820,10 @@ mod tests {
 
     use graph::data::subgraph::schema::*;
     use graph::prelude::*;
+    use graph::serde_json;
 
-    use super::GraphQLService;
+    use super::{parse_federated_subgraphs, GraphQLService};
+    use crate::persisted::{query_hash, PersistedQueries};
     use crate::test_utils;
 
     /// A simple stupid query runner for testing.
@@ -432,6 +838,24 @@ mod tests {
             unimplemented!();
         }
 
+        fn run_traced_query(&self, query: Query) -> QueryResultFuture {
+            Box::new(self.run_query(query).map(|mut result| {
+                result.add_extension("tracing", serde_json::json!({ "version": 1 }));
+                result
+            }))
+        }
+
+        fn run_query_at_block(&self, query: Query) -> QueryResultAtBlockFuture {
+            let block_ptr = EthereumBlockPointer {
+                hash: H256::zero(),
+                number: 0,
+            };
+            Box::new(
+                self.run_query(query)
+                    .map(move |result| (result, Some(block_ptr))),
+            )
+        }
+
         fn run_query(&self, _query: Query) -> QueryResultFuture {
             Box::new(future::ok(QueryResult::new(Some(q::Value::Object(
                 BTreeMap::from_iter(
@@ -449,8 +873,15 @@ mod tests {
         }
     }
 
-    #[test]
-    fn posting_invalid_query_yields_error_response() {
+    /// Creates a service for a deployed subgraph whose schema has a single
+    /// `name` field, and returns it together with the subgraph's ID.
+    fn test_service(
+        persisted_queries: Option<Arc<PersistedQueries>>,
+        allow_trace_header: bool,
+    ) -> (
+        GraphQLService<TestGraphQlRunner, MockStore>,
+        SubgraphDeploymentId,
+    ) {
         let logger = Logger::root(slog::Discard, o!());
         let id = SubgraphDeploymentId::new("testschema").unwrap();
         let schema = Schema::parse(
@@ -491,7 +922,22 @@ mod tests {
             .unwrap();
 
         let node_id = NodeId::new("test").unwrap();
-        let mut service = GraphQLService::new(logger, graphql_runner, store, 8001, node_id);
+        let service = GraphQLService::new(
+            logger,
+            graphql_runner,
+            store,
+            8001,
+            node_id,
+            persisted_queries,
+            allow_trace_header,
+            vec![],
+        );
+        (service, id)
+    }
+
+    #[test]
+    fn posting_invalid_query_yields_error_response() {
+        let (mut service, id) = test_service(None, false);
 
         let request = Request::builder()
             .method(Method::POST)
@@ -521,52 +967,11 @@ mod tests {
 
     #[test]
     fn posting_valid_queries_yields_result_response() {
-        let logger = Logger::root(slog::Discard, o!());
-        let id = SubgraphDeploymentId::new("testschema").unwrap();
-        let schema = Schema::parse(
-            "\
-             scalar String \
-             type Query @entity { name: String } \
-             ",
-            id.clone(),
-        )
-        .unwrap();
-        let manifest = SubgraphManifest {
-            id: id.clone(),
-            location: "".to_owned(),
-            spec_version: "".to_owned(),
-            description: None,
-            repository: None,
-            schema: schema.clone(),
-            data_sources: vec![],
-        };
-        let graphql_runner = Arc::new(TestGraphQlRunner);
-        let store = Arc::new(MockStore::new(vec![(id.clone(), schema)]));
-
         let mut runtime = tokio::runtime::Runtime::new().unwrap();
         runtime
             .block_on(future::lazy(move || {
                 let res: Result<_, ()> = Ok({
-                    store
-                        .apply_entity_operations(
-                            SubgraphDeploymentEntity::new(
-                                &manifest,
-                                false,
-                                false,
-                                EthereumBlockPointer {
-                                    hash: H256::zero(),
-                                    number: 0,
-                                },
-                                0,
-                            )
-                            .create_operations(&id),
-                            None,
-                        )
-                        .unwrap();
-
-                    let node_id = NodeId::new("test").unwrap();
-                    let mut service =
-                        GraphQLService::new(logger, graphql_runner, store, 8001, node_id);
+                    let (mut service, id) = test_service(None, false);
 
                     let request = Request::builder()
                         .method(Method::POST)
@@ -593,4 +998,139 @@ mod tests {
             }))
             .unwrap()
     }
+
+    #[test]
+    fn trace_header_requests_traced_query() {
+        let (mut service, id) = test_service(None, true);
+
+        let post = |trace: Option<&str>| {
+            let mut request = Request::builder();
+            request
+                .method(Method::POST)
+                .uri(format!("http://localhost:8000/subgraphs/id/{}", id));
+            if let Some(trace) = trace {
+                request.header("X-Graph-Trace", trace);
+            }
+            request
+                .body(Body::from("{\"query\": \"{ name }\"}"))
+                .unwrap()
+        };
+        let extensions = |response: hyper::Response<Body>| {
+            let body = response.into_body().concat2().wait().unwrap();
+            let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            json.get("extensions").cloned()
+        };
+
+        let response = service.call(post(None)).wait().unwrap();
+        assert_eq!(extensions(response), None);
+
+        let response = service.call(post(Some("false"))).wait().unwrap();
+        assert_eq!(extensions(response), None);
+
+        let response = service.call(post(Some("true"))).wait().unwrap();
+        assert_eq!(
+            extensions(response).unwrap()["tracing"]["version"],
+            serde_json::json!(1)
+        );
+
+        // The header is ignored unless it is allowed
+        let (mut service, _) = test_service(None, false);
+        let response = service.call(post(Some("true"))).wait().unwrap();
+        assert_eq!(extensions(response), None);
+    }
+
+    #[test]
+    fn get_requests_use_persisted_queries_and_are_cacheable() {
+        let persisted_queries = Arc::new(PersistedQueries::new(10));
+        let (mut service, id) = test_service(Some(persisted_queries), false);
+
+        let hash = query_hash("{ name }");
+        let extensions = format!(
+            "%7B%22persistedQuery%22%3A%7B%22version%22%3A1%2C%22sha256Hash%22%3A%22{}%22%7D%7D",
+            hash
+        );
+        let get = |params: String, etag: Option<&str>| {
+            let mut request = Request::builder();
+            request.method(Method::GET).uri(format!(
+                "http://localhost:8000/subgraphs/id/{}?{}",
+                id, params
+            ));
+            if let Some(etag) = etag {
+                request.header("If-None-Match", etag);
+            }
+            request.body(Body::empty()).unwrap()
+        };
+
+        // The hash alone is not known yet
+        let response = service
+            .call(get(format!("extensions={}", extensions), None))
+            .wait()
+            .expect("Should return a response");
+        let errors = test_utils::assert_error_response(response, StatusCode::OK);
+        assert_eq!(errors[0]["message"], "PersistedQueryNotFound");
+
+        // Sending the query along with the hash registers it
+        let response = service
+            .call(get(
+                format!("query=%7B%20name%20%7D&extensions={}", extensions),
+                None,
+            ))
+            .wait()
+            .expect("Should return a response");
+        let etag = response
+            .headers()
+            .get("ETag")
+            .expect("Response has no ETag")
+            .to_str()
+            .unwrap()
+            .to_owned();
+        assert!(response.headers().contains_key("Cache-Control"));
+        test_utils::assert_successful_response(response);
+
+        // From now on, the hash is enough
+        let response = service
+            .call(get(format!("extensions={}", extensions), None))
+            .wait()
+            .expect("Should return a response");
+        let data = test_utils::assert_successful_response(response);
+        assert_eq!(data["name"], "Jordi");
+
+        // Clients that already have the result get a 304
+        let response = service
+            .call(get(format!("extensions={}", extensions), Some(&etag)))
+            .wait()
+            .expect("Should return a response");
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        // A wildcard does not skip running the query
+        let response = service
+            .call(get(format!("extensions={}", extensions), Some("*")))
+            .wait()
+            .expect("Should return a response");
+        test_utils::assert_successful_response(response);
+    }
+
+    #[test]
+    fn parses_federated_subgraphs() {
+        let subgraphs =
+            parse_federated_subgraphs(vec!["music=org/music", " org/books", ""]).unwrap();
+        assert_eq!(
+            subgraphs,
+            vec![
+                ("music".to_owned(), SubgraphName::new("org/music").unwrap()),
+                (
+                    "org_books".to_owned(),
+                    SubgraphName::new("org/books").unwrap()
+                ),
+            ]
+        );
+
+        assert!(parse_federated_subgraphs(vec!["music=org/mu sic"]).is_err());
+
+        // Namespaces must be usable as GraphQL names
+        assert!(parse_federated_subgraphs(vec!["1inch"]).is_err());
+        assert!(parse_federated_subgraphs(vec!["__music=org/music"]).is_err());
+        assert!(parse_federated_subgraphs(vec!["music=org/music", "music=org/books"]).is_err());
+        assert!(parse_federated_subgraphs(vec!["inch=1inch"]).is_ok());
+    }
 }
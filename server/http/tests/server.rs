@@ -32,6 +32,14 @@ impl GraphQlRunner for TestGraphQlRunner {
         unimplemented!();
     }
 
+    fn run_traced_query(&self, _query: Query) -> QueryResultFuture {
+        unimplemented!();
+    }
+
+    fn run_query_at_block(&self, query: Query) -> QueryResultAtBlockFuture {
+        Box::new(self.run_query(query).map(|result| (result, None)))
+    }
+
     fn run_query(&self, query: Query) -> QueryResultFuture {
         Box::new(future::ok(QueryResult::new(Some(q::Value::Object(
             if query.variables.is_some()
@@ -119,7 +127,7 @@ mod test {
                 let query_runner = Arc::new(TestGraphQlRunner);
                 let store = mock_store(id.clone());
                 let node_id = NodeId::new("test").unwrap();
-                let mut server = HyperGraphQLServer::new(&logger_factory, query_runner, store, node_id);
+                let mut server = HyperGraphQLServer::new(&logger_factory, query_runner, store, node_id, vec![]);
                 let http_server = server
                     .serve(8001, 8002)
                     .expect("Failed to start GraphQL server");
@@ -170,7 +178,7 @@ mod test {
                 let store = mock_store(id.clone());
                 let node_id = NodeId::new("test").unwrap();
                 let mut server =
-                    HyperGraphQLServer::new(&logger_factory, query_runner, store, node_id);
+                    HyperGraphQLServer::new(&logger_factory, query_runner, store, node_id, vec![]);
                 let http_server = server
                     .serve(8002, 8003)
                     .expect("Failed to start GraphQL server");
@@ -255,7 +263,7 @@ mod test {
                 let store = mock_store(id.clone());
                 let node_id = NodeId::new("test").unwrap();
                 let mut server =
-                    HyperGraphQLServer::new(&logger_factory, query_runner, store, node_id);
+                    HyperGraphQLServer::new(&logger_factory, query_runner, store, node_id, vec![]);
                 let http_server = server
                     .serve(8003, 8004)
                     .expect("Failed to start GraphQL server");
@@ -306,7 +314,7 @@ mod test {
                 let store = mock_store(id.clone());
                 let node_id = NodeId::new("test").unwrap();
                 let mut server =
-                    HyperGraphQLServer::new(&logger_factory, query_runner, store, node_id);
+                    HyperGraphQLServer::new(&logger_factory, query_runner, store, node_id, vec![]);
                 let http_server = server
                     .serve(8005, 8006)
                     .expect("Failed to start GraphQL server");
@@ -6,7 +6,8 @@ extern crate serde;
 use graph::prelude::{JsonRpcServer as JsonRpcServerTrait, *};
 use graph::serde_json;
 use jsonrpc_http_server::{
-    jsonrpc_core::{self, Compatibility, IoHandler, Params, Value},
+    hyper,
+    jsonrpc_core::{self, Compatibility, MetaIoHandler, Metadata, Params, Value},
     RestApi, Server, ServerBuilder,
 };
 use lazy_static::lazy_static;
@@ -14,7 +15,7 @@ use lazy_static::lazy_static;
 use std::collections::BTreeMap;
 use std::env;
 use std::io;
-use std::net::{Ipv4Addr, SocketAddrV4};
+use std::net::SocketAddr;
 
 lazy_static! {
     static ref EXTERNAL_HTTP_BASE_URL: Option<String> = env::var_os("EXTERNAL_HTTP_BASE_URL")
@@ -27,6 +28,15 @@ const JSON_RPC_DEPLOY_ERROR: i64 = 0;
 const JSON_RPC_REMOVE_ERROR: i64 = 1;
 const JSON_RPC_CREATE_ERROR: i64 = 2;
 const JSON_RPC_REASSIGN_ERROR: i64 = 3;
+const JSON_RPC_UNAUTHORIZED_ERROR: i64 = 4;
+
+/// Per-request metadata extracted from the HTTP request.
+#[derive(Clone, Debug, Default)]
+struct RequestMeta {
+    authorization: Option<String>,
+}
+
+impl Metadata for RequestMeta {}
 
 #[derive(Debug, Deserialize)]
 struct SubgraphCreateParams {
@@ -56,6 +66,7 @@ pub struct JsonRpcServer<R> {
     http_port: u16,
     ws_port: u16,
     node_id: NodeId,
+    access_control: AdminAccessControl,
     logger: Logger,
 }
 
@@ -63,6 +74,24 @@ impl<R> JsonRpcServer<R>
 where
     R: SubgraphRegistrar,
 {
+    /// Checks that the request is allowed to call `method`, which requires
+    /// the `required` permission.
+    fn authorize(
+        &self,
+        method: &str,
+        required: AdminPermission,
+        meta: &RequestMeta,
+    ) -> Result<(), jsonrpc_core::Error> {
+        self.access_control
+            .authorize(meta.authorization.as_ref().map(|s| s.as_str()), required)
+            .map_err(|e| {
+                warn!(self.logger, "Rejected unauthorized admin request";
+                      "method" => method,
+                      "error" => e.to_string());
+                json_rpc_error(JSON_RPC_UNAUTHORIZED_ERROR, e.to_string())
+            })
+    }
+
     /// Handler for the `subgraph_create` endpoint.
     fn create_handler(
         &self,
@@ -174,73 +203,98 @@ where
     type Server = Server;
 
     fn serve(
-        port: u16,
+        addr: SocketAddr,
         http_port: u16,
         ws_port: u16,
         registrar: Arc<R>,
         node_id: NodeId,
+        access_control: AdminAccessControl,
         logger: Logger,
     ) -> Result<Self::Server, io::Error> {
         let logger = logger.new(o!("component" => "JsonRpcServer"));
 
-        info!(
-            logger,
-            "Starting JSON-RPC admin server at: http://localhost:{}", port
-        );
+        info!(logger, "Starting JSON-RPC admin server at: http://{}", addr);
 
-        let addr = SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), port);
+        if !access_control.is_enabled() {
+            warn!(
+                logger,
+                "No admin access tokens configured, the JSON-RPC admin server \
+                 accepts requests from anyone who can reach it"
+            );
+        }
 
-        let mut handler = IoHandler::with_compatibility(Compatibility::Both);
+        let mut handler = MetaIoHandler::with_compatibility(Compatibility::Both);
 
         let arc_self = Arc::new(JsonRpcServer {
             registrar,
             http_port,
             ws_port,
             node_id,
+            access_control,
             logger,
         });
 
         let me = arc_self.clone();
-        handler.add_method("subgraph_create", move |params: Params| {
-            let me = me.clone();
-            params
-                .parse()
-                .into_future()
-                .and_then(move |params| me.create_handler(params))
-        });
+        handler.add_method_with_meta(
+            "subgraph_create",
+            move |params: Params, meta: RequestMeta| {
+                let me = me.clone();
+                me.authorize("subgraph_create", AdminPermission::Deploy, &meta)
+                    .and_then(|_| params.parse())
+                    .into_future()
+                    .and_then(move |params| me.create_handler(params))
+            },
+        );
 
         let me = arc_self.clone();
-        handler.add_method("subgraph_deploy", move |params: Params| {
-            let me = me.clone();
-            params
-                .parse()
-                .into_future()
-                .and_then(move |params| me.deploy_handler(params))
-        });
+        handler.add_method_with_meta(
+            "subgraph_deploy",
+            move |params: Params, meta: RequestMeta| {
+                let me = me.clone();
+                me.authorize("subgraph_deploy", AdminPermission::Deploy, &meta)
+                    .and_then(|_| params.parse())
+                    .into_future()
+                    .and_then(move |params| me.deploy_handler(params))
+            },
+        );
 
         let me = arc_self.clone();
-        handler.add_method("subgraph_remove", move |params: Params| {
-            let me = me.clone();
-            params
-                .parse()
-                .into_future()
-                .and_then(move |params| me.remove_handler(params))
-        });
+        handler.add_method_with_meta(
+            "subgraph_remove",
+            move |params: Params, meta: RequestMeta| {
+                let me = me.clone();
+                me.authorize("subgraph_remove", AdminPermission::Admin, &meta)
+                    .and_then(|_| params.parse())
+                    .into_future()
+                    .and_then(move |params| me.remove_handler(params))
+            },
+        );
 
         let me = arc_self.clone();
-        handler.add_method("subgraph_reassign", move |params: Params| {
-            let me = me.clone();
-            params
-                .parse()
-                .into_future()
-                .and_then(move |params| me.reassign_handler(params))
-        });
+        handler.add_method_with_meta(
+            "subgraph_reassign",
+            move |params: Params, meta: RequestMeta| {
+                let me = me.clone();
+                me.authorize("subgraph_reassign", AdminPermission::Admin, &meta)
+                    .and_then(|_| params.parse())
+                    .into_future()
+                    .and_then(move |params| me.reassign_handler(params))
+            },
+        );
 
-        ServerBuilder::new(handler)
-            // Enable REST API:
-            // POST /<method>/<param1>/<param2>
-            .rest_api(RestApi::Secure)
-            .start_http(&addr.into())
+        ServerBuilder::with_meta_extractor(handler, |req: &hyper::Request<hyper::Body>| {
+            RequestMeta {
+                authorization: req
+                    .headers()
+                    .get(hyper::header::AUTHORIZATION)
+                    .and_then(|value| value.to_str().ok())
+                    .map(|value| value.to_owned()),
+            }
+        })
+        // Enable REST API:
+        // POST /<method>/<param1>/<param2>
+        .rest_api(RestApi::Secure)
+        .start_http(&addr)
     }
 }
 
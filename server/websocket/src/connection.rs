@@ -2,13 +2,77 @@ use futures::future::IntoFuture;
 use futures::stream::SplitStream;
 use futures::sync::mpsc;
 use graphql_parser::parse_query;
+use lazy_static::lazy_static;
 use std::collections::HashMap;
+use std::env;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::timer::Interval;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
 use tokio_tungstenite::tungstenite::{Error as WsError, Message as WsMessage};
 use tokio_tungstenite::WebSocketStream;
 use uuid::Uuid;
 
 use graph::prelude::*;
-use graph::serde_json;
+use graph::serde_json::{self, json};
+
+lazy_static! {
+    /// How often keep-alive messages are sent to clients; `0` disables them.
+    static ref KEEP_ALIVE_INTERVAL: Option<Duration> = env::var("GRAPH_GRAPHQL_WS_KEEP_ALIVE_INTERVAL")
+        .ok()
+        .map(|s| u64::from_str(&s).unwrap_or_else(|_| panic!(
+            "failed to parse env var GRAPH_GRAPHQL_WS_KEEP_ALIVE_INTERVAL"
+        )))
+        .or(Some(30))
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs);
+
+    /// The maximum number of concurrent operations on a single connection.
+    static ref MAX_OPERATIONS_PER_CONNECTION: Option<usize> = env::var("GRAPH_GRAPHQL_WS_MAX_OPERATIONS")
+        .ok()
+        .map(|s| usize::from_str(&s).unwrap_or_else(|_| panic!(
+            "failed to parse env var GRAPH_GRAPHQL_WS_MAX_OPERATIONS"
+        )));
+}
+
+/// The GraphQL over WebSocket protocols supported by the server.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum GraphQlProtocol {
+    /// The legacy Apollo `subscriptions-transport-ws` protocol.
+    Legacy,
+    /// The `graphql-transport-ws` protocol from `graphql-ws`.
+    TransportWs,
+}
+
+impl GraphQlProtocol {
+    /// Picks the first protocol supported by the server from the list of
+    /// protocols in a `Sec-WebSocket-Protocol` header. Falls back to the
+    /// legacy protocol if the client did not request any protocol.
+    pub fn negotiate(header: Option<&str>) -> Option<Self> {
+        match header {
+            None => Some(GraphQlProtocol::Legacy),
+            Some(header) => header
+                .split(',')
+                .map(|protocol| protocol.trim())
+                .filter_map(|protocol| match protocol {
+                    "graphql-ws" => Some(GraphQlProtocol::Legacy),
+                    "graphql-transport-ws" => Some(GraphQlProtocol::TransportWs),
+                    _ => None,
+                })
+                .next(),
+        }
+    }
+
+    /// The value of the `Sec-WebSocket-Protocol` header to respond with.
+    pub fn name(&self) -> &'static str {
+        match self {
+            GraphQlProtocol::Legacy => "graphql-ws",
+            GraphQlProtocol::TransportWs => "graphql-transport-ws",
+        }
+    }
+}
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -18,20 +82,80 @@ struct StartPayload {
     operation_name: Option<String>,
 }
 
-/// GraphQL/WebSocket message received from a client.
+/// Message received from a client using the legacy protocol.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum LegacyIncomingMessage {
+    ConnectionInit { payload: Option<serde_json::Value> },
+    ConnectionTerminate,
+    Start { id: String, payload: StartPayload },
+    Stop { id: String },
+}
+
+/// Message received from a client using the `graphql-transport-ws` protocol.
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
+enum TransportWsIncomingMessage {
+    ConnectionInit { payload: Option<serde_json::Value> },
+    Ping { payload: Option<serde_json::Value> },
+    Pong { payload: Option<serde_json::Value> },
+    Subscribe { id: String, payload: StartPayload },
+    Complete { id: String },
+}
+
+/// GraphQL/WebSocket message received from a client, independent of the
+/// protocol used by the connection.
+#[derive(Debug)]
 enum IncomingMessage {
     ConnectionInit { payload: Option<serde_json::Value> },
     ConnectionTerminate,
     Start { id: String, payload: StartPayload },
     Stop { id: String },
+    Ping { payload: Option<serde_json::Value> },
+    Pong,
+}
+
+impl From<LegacyIncomingMessage> for IncomingMessage {
+    fn from(msg: LegacyIncomingMessage) -> Self {
+        match msg {
+            LegacyIncomingMessage::ConnectionInit { payload } => {
+                IncomingMessage::ConnectionInit { payload }
+            }
+            LegacyIncomingMessage::ConnectionTerminate => IncomingMessage::ConnectionTerminate,
+            LegacyIncomingMessage::Start { id, payload } => IncomingMessage::Start { id, payload },
+            LegacyIncomingMessage::Stop { id } => IncomingMessage::Stop { id },
+        }
+    }
+}
+
+impl From<TransportWsIncomingMessage> for IncomingMessage {
+    fn from(msg: TransportWsIncomingMessage) -> Self {
+        match msg {
+            TransportWsIncomingMessage::ConnectionInit { payload } => {
+                IncomingMessage::ConnectionInit { payload }
+            }
+            TransportWsIncomingMessage::Ping { payload } => IncomingMessage::Ping { payload },
+            TransportWsIncomingMessage::Pong { .. } => IncomingMessage::Pong,
+            TransportWsIncomingMessage::Subscribe { id, payload } => {
+                IncomingMessage::Start { id, payload }
+            }
+            TransportWsIncomingMessage::Complete { id } => IncomingMessage::Stop { id },
+        }
+    }
 }
 
 impl IncomingMessage {
-    pub fn from_ws_message(msg: WsMessage) -> Result<Self, WsError> {
+    pub fn from_ws_message(msg: WsMessage, protocol: GraphQlProtocol) -> Result<Self, WsError> {
         let text = msg.into_text()?;
-        serde_json::from_str(text.as_str()).map_err(|e| {
+        let result = match protocol {
+            GraphQlProtocol::Legacy => {
+                serde_json::from_str::<LegacyIncomingMessage>(text.as_str()).map(Self::from)
+            }
+            GraphQlProtocol::TransportWs => {
+                serde_json::from_str::<TransportWsIncomingMessage>(text.as_str()).map(Self::from)
+            }
+        };
+        result.map_err(|e| {
             WsError::Protocol(
                 format!("Invalid GraphQL over WebSocket message: {}: {}", text, e).into(),
             )
@@ -39,18 +163,37 @@ impl IncomingMessage {
     }
 }
 
-/// GraphQL/WebSocket message to be sent to the client.
-#[derive(Debug, Serialize)]
-#[serde(tag = "type", rename_all = "snake_case")]
+/// GraphQL/WebSocket message to be sent to the client, independent of the
+/// protocol used by the connection.
+#[derive(Debug)]
 enum OutgoingMessage {
     ConnectionAck,
-    Error { id: String, payload: String },
-    Data { id: String, payload: QueryResult },
-    Complete { id: String },
+    Error {
+        id: String,
+        payload: String,
+    },
+    Data {
+        id: String,
+        payload: Arc<QueryResult>,
+    },
+    Complete {
+        id: String,
+    },
+    /// The errors of an operation that failed to execute. The legacy
+    /// protocol reports them as data, `graphql-transport-ws` as an error
+    /// that also completes the operation.
+    ExecutionErrors {
+        id: String,
+        payload: Arc<QueryResult>,
+    },
+    KeepAlive,
+    Pong {
+        payload: Option<serde_json::Value>,
+    },
 }
 
 impl OutgoingMessage {
-    pub fn from_query_result(id: String, result: QueryResult) -> Self {
+    pub fn from_query_result(id: String, result: Arc<QueryResult>) -> Self {
         OutgoingMessage::Data {
             id: id,
             payload: result,
@@ -60,44 +203,116 @@ impl OutgoingMessage {
     pub fn from_error_string(id: String, s: String) -> Self {
         OutgoingMessage::Error { id, payload: s }
     }
-}
 
-impl From<OutgoingMessage> for WsMessage {
-    fn from(msg: OutgoingMessage) -> Self {
-        WsMessage::text(serde_json::to_string(&msg).expect("invalid GraphQL/WebSocket message"))
+    /// Encodes the message for the given protocol.
+    pub fn into_ws_message(self, protocol: GraphQlProtocol) -> WsMessage {
+        use self::GraphQlProtocol::*;
+        use self::OutgoingMessage::*;
+
+        let value = match (protocol, self) {
+            (_, ConnectionAck) => json!({ "type": "connection_ack" }),
+            (Legacy, Error { id, payload }) => json!({
+                "type": "error",
+                "id": id,
+                "payload": payload,
+            }),
+            (TransportWs, Error { id, payload }) => json!({
+                "type": "error",
+                "id": id,
+                "payload": [{ "message": payload }],
+            }),
+            (Legacy, Data { id, payload }) => json!({
+                "type": "data",
+                "id": id,
+                "payload": &*payload,
+            }),
+            (TransportWs, Data { id, payload }) => json!({
+                "type": "next",
+                "id": id,
+                "payload": &*payload,
+            }),
+            (_, Complete { id }) => json!({ "type": "complete", "id": id }),
+            (Legacy, ExecutionErrors { id, payload }) => json!({
+                "type": "data",
+                "id": id,
+                "payload": &*payload,
+            }),
+            (TransportWs, ExecutionErrors { id, payload }) => json!({
+                "type": "error",
+                "id": id,
+                "payload": &payload.errors,
+            }),
+            (Legacy, KeepAlive) => json!({ "type": "ka" }),
+            (TransportWs, KeepAlive) => json!({ "type": "ping" }),
+            (_, Pong { payload: None }) => json!({ "type": "pong" }),
+            (
+                _,
+                Pong {
+                    payload: Some(payload),
+                },
+            ) => json!({ "type": "pong", "payload": payload }),
+        };
+
+        WsMessage::text(serde_json::to_string(&value).expect("invalid GraphQL/WebSocket message"))
     }
 }
 
-/// Helper function to send outgoing messages.
-fn send_message(
-    sink: &mpsc::UnboundedSender<WsMessage>,
-    msg: OutgoingMessage,
-) -> Result<(), WsError> {
-    sink.unbounded_send(msg.into())
-        .map_err(|_| WsError::Http(500))
+/// Sink for outgoing messages that encodes them for the connection's protocol.
+#[derive(Clone)]
+struct MessageSink {
+    sink: mpsc::UnboundedSender<WsMessage>,
+    protocol: GraphQlProtocol,
 }
 
-/// Helper function to send error messages.
-fn send_error_string(
-    sink: &mpsc::UnboundedSender<WsMessage>,
-    operation_id: String,
-    error: String,
-) -> Result<(), WsError> {
-    sink.unbounded_send(OutgoingMessage::from_error_string(operation_id, error).into())
-        .map_err(|_| WsError::Http(500))
+impl MessageSink {
+    fn new(sink: mpsc::UnboundedSender<WsMessage>, protocol: GraphQlProtocol) -> Self {
+        MessageSink { sink, protocol }
+    }
+
+    /// Helper function to send outgoing messages.
+    fn send(&self, msg: OutgoingMessage) -> Result<(), WsError> {
+        self.sink
+            .unbounded_send(msg.into_ws_message(self.protocol))
+            .map_err(|_| WsError::Http(500))
+    }
+
+    /// Helper function to send error messages.
+    fn send_error_string(&self, operation_id: String, error: String) -> Result<(), WsError> {
+        self.send(OutgoingMessage::from_error_string(operation_id, error))
+    }
+
+    /// Asks the client to close the connection with the given close code, as
+    /// required by the `graphql-transport-ws` protocol for protocol violations.
+    fn close(&self, code: u16, reason: String) -> Result<(), WsError> {
+        self.sink
+            .unbounded_send(WsMessage::Close(Some(CloseFrame {
+                code: CloseCode::from(code),
+                reason: reason.into(),
+            })))
+            .map_err(|_| WsError::Http(500))
+    }
+
+    fn close_sink(&mut self) {
+        // Closing an already closed sink is not an error we care about
+        let _ = self.sink.close();
+    }
 }
 
 /// Responsible for recording operation ids and stopping them.
 /// On drop, cancels all operations.
 struct Operations {
-    operations: HashMap<String, CancelGuard>,
-    msg_sink: mpsc::UnboundedSender<WsMessage>,
+    /// The running operations by id, along with a serial number that tells
+    /// apart operations that reuse the id of an earlier one.
+    operations: HashMap<String, (usize, CancelGuard)>,
+    next_serial: usize,
+    msg_sink: MessageSink,
 }
 
 impl Operations {
-    fn new(msg_sink: mpsc::UnboundedSender<WsMessage>) -> Self {
+    fn new(msg_sink: MessageSink) -> Self {
         Self {
             operations: HashMap::new(),
+            next_serial: 0,
             msg_sink,
         }
     }
@@ -106,30 +321,52 @@ impl Operations {
         self.operations.contains_key(id)
     }
 
-    fn insert(&mut self, id: String, guard: CancelGuard) {
-        self.operations.insert(id, guard);
+    fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    /// Records a new operation and returns its serial number.
+    fn insert(&mut self, id: String, guard: CancelGuard) -> usize {
+        let serial = self.next_serial;
+        self.next_serial += 1;
+        self.operations.insert(id, (serial, guard));
+        serial
+    }
+
+    /// Forgets an operation that ended by itself, unless it was stopped or
+    /// replaced in the meantime. Returns whether the operation was removed,
+    /// in which case the client still needs to be told that it ended.
+    fn finish(&mut self, id: &str, serial: usize) -> bool {
+        match self.operations.get(id) {
+            Some((current, _)) if *current == serial => {
+                self.operations.remove(id);
+                true
+            }
+            _ => false,
+        }
     }
 
     fn stop(&mut self, operation_id: String) -> Result<(), WsError> {
         // Remove the operation with this ID from the known operations.
         match self.operations.remove(&operation_id) {
-            Some(stopper) => {
+            Some((_, stopper)) => {
                 // Cancel the subscription result stream.
                 stopper.cancel();
 
                 // Send a GQL_COMPLETE to indicate the operation is been completed.
-                send_message(
-                    &self.msg_sink,
-                    OutgoingMessage::Complete {
-                        id: operation_id.clone(),
-                    },
-                )
+                self.msg_sink.send(OutgoingMessage::Complete {
+                    id: operation_id.clone(),
+                })
             }
-            None => send_error_string(
-                &self.msg_sink,
-                operation_id.clone(),
-                format!("Unknown operation ID: {}", operation_id),
-            ),
+            None => match self.msg_sink.protocol {
+                GraphQlProtocol::Legacy => self.msg_sink.send_error_string(
+                    operation_id.clone(),
+                    format!("Unknown operation ID: {}", operation_id),
+                ),
+                // Completing an unknown or already finished operation is
+                // allowed by `graphql-transport-ws`
+                GraphQlProtocol::TransportWs => Ok(()),
+            },
         }
     }
 }
@@ -151,6 +388,7 @@ pub struct GraphQlConnection<Q, S> {
     graphql_runner: Arc<Q>,
     stream: WebSocketStream<S>,
     schema: Arc<Schema>,
+    protocol: GraphQlProtocol,
 }
 
 impl<Q, S> GraphQlConnection<Q, S>
@@ -163,6 +401,7 @@ where
         logger: &Logger,
         schema: Arc<Schema>,
         stream: WebSocketStream<S>,
+        protocol: GraphQlProtocol,
         graphql_runner: Arc<Q>,
     ) -> Self {
         GraphQlConnection {
@@ -171,68 +410,111 @@ where
             graphql_runner,
             stream,
             schema,
+            protocol,
         }
     }
 
     fn handle_incoming_messages(
         ws_stream: SplitStream<WebSocketStream<S>>,
-        mut msg_sink: mpsc::UnboundedSender<WsMessage>,
+        mut msg_sink: MessageSink,
         logger: Logger,
         connection_id: String,
         schema: Arc<Schema>,
         graphql_runner: Arc<Q>,
     ) -> impl Future<Item = (), Error = WsError> {
-        let mut operations = Operations::new(msg_sink.clone());
+        // Operations remove themselves when they end, but must not keep the
+        // map alive, as dropping it stops all operations of the connection
+        let operations = Arc::new(Mutex::new(Operations::new(msg_sink.clone())));
+        let mut initialized = false;
 
         // Process incoming messages as long as the WebSocket is open
         ws_stream.for_each(move |ws_msg| {
             use self::IncomingMessage::*;
-            use self::OutgoingMessage::*;
 
             debug!(logger, "Received message";
                    "connection" => &connection_id,
                    "msg" => format!("{}", ws_msg).as_str());
 
-            let msg = IncomingMessage::from_ws_message(ws_msg.clone())?;
+            // Control frames are handled by the WebSocket library
+            if ws_msg.is_ping() || ws_msg.is_pong() {
+                return Ok(());
+            }
+
+            let msg = IncomingMessage::from_ws_message(ws_msg.clone(), msg_sink.protocol)?;
 
             debug!(logger, "GraphQL/WebSocket message";
                    "connection" => &connection_id,
                    "msg" => format!("{:?}", msg).as_str());
 
+            let transport_ws = msg_sink.protocol == GraphQlProtocol::TransportWs;
+
             match msg {
-                // Always accept connection init requests
-                ConnectionInit { payload: _ } => send_message(&msg_sink, ConnectionAck),
+                ConnectionInit { payload: _ } => {
+                    // `graphql-transport-ws` allows only one init request
+                    if transport_ws && initialized {
+                        return msg_sink.close(4429, "Too many initialisation requests".to_owned());
+                    }
+                    initialized = true;
+
+                    // Always accept connection init requests
+                    msg_sink.send(OutgoingMessage::ConnectionAck)
+                }
 
                 // When receiving a connection termination request
                 ConnectionTerminate => {
                     // Close the message sink
-                    msg_sink.close().unwrap();
+                    msg_sink.close_sink();
 
                     // Return an error here to terminate the connection
                     Err(WsError::ConnectionClosed(None))
                 }
 
+                // Answer pings, ignore pongs sent in response to our keep-alives
+                Ping { payload } => msg_sink.send(OutgoingMessage::Pong { payload }),
+                Pong => Ok(()),
+
                 // When receiving a stop request
-                Stop { id } => operations.stop(id),
+                Stop { id } => operations.lock().unwrap().stop(id),
+
+                // `graphql-transport-ws` requires an acknowledged connection
+                // before any operation can be started
+                Start { .. } if transport_ws && !initialized => {
+                    msg_sink.close(4401, "Unauthorized".to_owned())
+                }
 
                 // When receiving a start request
                 Start { id, payload } => {
                     // Respond with a GQL_ERROR if we already have an operation with this ID
-                    if operations.contains(&id) {
-                        return send_error_string(
-                            &msg_sink,
+                    if operations.lock().unwrap().contains(&id) {
+                        if transport_ws {
+                            return msg_sink
+                                .close(4409, format!("Subscriber for {} already exists", id));
+                        }
+                        return msg_sink.send_error_string(
                             id.clone(),
                             format!("Operation with ID already started: {}", id),
                         );
                     }
 
+                    // Refuse to start more operations than allowed per connection
+                    if let Some(max_operations) = *MAX_OPERATIONS_PER_CONNECTION {
+                        if operations.lock().unwrap().len() >= max_operations {
+                            return msg_sink.send_error_string(
+                                id.clone(),
+                                format!(
+                                    "Too many operations on this connection (maximum: {})",
+                                    max_operations
+                                ),
+                            );
+                        }
+                    }
+
                     // Parse the GraphQL query document; respond with a GQL_ERROR if
                     // the query is invalid
                     let query = match parse_query(&payload.query) {
                         Ok(query) => query,
                         Err(e) => {
-                            return send_error_string(
-                                &msg_sink,
+                            return msg_sink.send_error_string(
                                 id.clone(),
                                 format!("Invalid query: {}: {}", payload.query, e),
                             );
@@ -246,8 +528,7 @@ where
                             match serde_json::from_value(variables.clone()) {
                                 Ok(variables) => Some(variables),
                                 Err(e) => {
-                                    return send_error_string(
-                                        &msg_sink,
+                                    return msg_sink.send_error_string(
                                         id.clone(),
                                         format!("Invalid variables provided: {}", e),
                                     );
@@ -255,8 +536,7 @@ where
                             }
                         }
                         _ => {
-                            return send_error_string(
-                                &msg_sink,
+                            return msg_sink.send_error_string(
                                 id.clone(),
                                 format!("Invalid variables provided (must be an object)"),
                             );
@@ -276,54 +556,87 @@ where
                            "connection" => &connection_id,
                            "id" => &id);
 
+                    // Setup cancelation.
+                    let guard = CancelGuard::new();
+                    let cancel_handle = guard.handle();
+                    let serial = operations.lock().unwrap().insert(id.clone(), guard);
+
                     // Execute the GraphQL subscription
                     let graphql_runner = graphql_runner.clone();
-                    let error_sink = msg_sink.clone();
                     let result_sink = msg_sink.clone();
                     let result_id = id.clone();
                     let err_id = id.clone();
                     let err_connection_id = connection_id.clone();
                     let err_logger = logger.clone();
-                    let run_subscription = graphql_runner
-                        .run_subscription(subscription)
-                        .map_err(move |e| {
-                            debug!(err_logger, "Subscription error";
-                                               "connection" => &err_connection_id,
-                                               "id" => &err_id,
-                                               "error" => format!("{:?}", e));
-
-                            // Send errors back to the client as GQL_DATA
-                            match e {
-                                SubscriptionError::GraphQLError(e) => {
-                                    let result = QueryResult::from(e);
-                                    let msg =
-                                        OutgoingMessage::from_query_result(err_id.clone(), result);
-                                    error_sink.unbounded_send(msg.into()).unwrap();
+                    let run_subscription =
+                        graphql_runner
+                            .run_subscription(subscription)
+                            .then(move |result| match result {
+                                Ok(result_stream) => {
+                                    // Send results back to the client as GQL_DATA
+                                    let protocol = result_sink.protocol;
+                                    future::Either::A(
+                                        result_stream
+                                            .map(move |result| {
+                                                OutgoingMessage::from_query_result(
+                                                    result_id.clone(),
+                                                    result,
+                                                )
+                                                .into_ws_message(protocol)
+                                            })
+                                            .forward(result_sink.sink.sink_map_err(|_| ()))
+                                            .then(|_| Ok::<_, ()>(None)),
+                                    )
+                                }
+                                Err(e) => {
+                                    debug!(err_logger, "Subscription error";
+                                                   "connection" => &err_connection_id,
+                                                   "id" => &err_id,
+                                                   "error" => format!("{:?}", e));
+
+                                    let result = match e {
+                                        SubscriptionError::GraphQLError(e) => QueryResult::from(e),
+                                    };
+                                    future::Either::B(future::ok(Some(Arc::new(result))))
                                 }
-                            };
-                        })
-                        .and_then(move |result_stream| {
-                            // Send results back to the client as GQL_DATA
-                            result_stream
-                                .map(move |result| {
-                                    OutgoingMessage::from_query_result(result_id.clone(), result)
-                                })
-                                .map(WsMessage::from)
-                                .forward(result_sink.sink_map_err(|_| ()))
-                                .map(|_| ())
+                            });
+
+                    // When the operation ends by itself, forget it and tell
+                    // the client, unless it was stopped in the meantime
+                    let finished_operations = Arc::downgrade(&operations);
+                    let finished_sink = msg_sink.clone();
+                    let finished_id = id.clone();
+                    let run_subscription = run_subscription.and_then(move |errors| {
+                        let finished = finished_operations.upgrade().map_or(false, |operations| {
+                            operations.lock().unwrap().finish(&finished_id, serial)
                         });
+                        if !finished {
+                            return Ok(());
+                        }
+
+                        // Discard send errors, the connection is being closed
+                        let failed = errors.is_some();
+                        if let Some(errors) = errors {
+                            let _ = finished_sink.send(OutgoingMessage::ExecutionErrors {
+                                id: finished_id.clone(),
+                                payload: errors,
+                            });
+                        }
+                        if !(failed && finished_sink.protocol == GraphQlProtocol::TransportWs) {
+                            let _ =
+                                finished_sink.send(OutgoingMessage::Complete { id: finished_id });
+                        }
+                        Ok(())
+                    });
 
-                    // Setup cancelation.
-                    let guard = CancelGuard::new();
                     let logger = logger.clone();
                     let cancel_id = id.clone();
                     let connection_id = connection_id.clone();
-                    let run_subscription = run_subscription.cancelable(&guard, move || {
+                    let run_subscription = run_subscription.cancelable(&cancel_handle, move || {
                         debug!(logger, "Stopped operation";
                                        "connection" => &connection_id,
                                        "id" => &cancel_id)
                     });
-                    operations.insert(id, guard);
 
                     tokio::spawn(run_subscription);
                     Ok(())
@@ -350,6 +663,21 @@ where
 
         // Allocate a channel for writing
         let (msg_sink, msg_stream) = mpsc::unbounded();
+        let msg_sink = MessageSink::new(msg_sink, self.protocol);
+
+        // Periodically send keep-alive messages so that idle connections are
+        // not dropped by proxies and load balancers
+        let keep_alive_sink = msg_sink.clone();
+        let keep_alive: Box<Future<Item = (), Error = ()> + Send> = match *KEEP_ALIVE_INTERVAL {
+            Some(interval) => Box::new(Interval::new_interval(interval).map_err(|_| ()).for_each(
+                move |_| {
+                    keep_alive_sink
+                        .send(OutgoingMessage::KeepAlive)
+                        .map_err(|_| ())
+                },
+            )),
+            None => Box::new(future::empty()),
+        };
 
         // Handle incoming messages asynchronously
         let ws_reader = Self::handle_incoming_messages(
@@ -368,7 +696,11 @@ where
         // we can do about these errors ourselves. Clients will be disconnected
         // as a result of this but most will try to reconnect (GraphiQL for sure,
         // Apollo maybe).
-        let ws_writer = ws_writer.map(|_| ());
+        let ws_writer = ws_writer
+            .map(|_| ())
+            .select(keep_alive)
+            .map(|_| ())
+            .map_err(|_| ());
         let ws_reader = ws_reader.map(|_| ()).map_err(|_| ());
 
         // Return a future that is fulfilled when either we or the client close
@@ -381,3 +713,191 @@ where
         }))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{GraphQlProtocol, MessageSink, Operations, OutgoingMessage};
+    use futures::sync::mpsc;
+    use graph::prelude::*;
+    use graph::serde_json::{self, json};
+    use graphql_parser::query as q;
+
+    fn encode(msg: OutgoingMessage, protocol: GraphQlProtocol) -> serde_json::Value {
+        let text = msg
+            .into_ws_message(protocol)
+            .into_text()
+            .expect("message is not a text message");
+        serde_json::from_str(&text).expect("message is not valid JSON")
+    }
+
+    #[test]
+    fn negotiates_protocols() {
+        assert_eq!(
+            GraphQlProtocol::negotiate(Some("graphql-ws")),
+            Some(GraphQlProtocol::Legacy)
+        );
+        assert_eq!(
+            GraphQlProtocol::negotiate(Some("graphql-transport-ws")),
+            Some(GraphQlProtocol::TransportWs)
+        );
+
+        // The first supported protocol in the header wins
+        assert_eq!(
+            GraphQlProtocol::negotiate(Some("foo, graphql-transport-ws, graphql-ws")),
+            Some(GraphQlProtocol::TransportWs)
+        );
+        assert_eq!(
+            GraphQlProtocol::negotiate(Some("graphql-ws,graphql-transport-ws")),
+            Some(GraphQlProtocol::Legacy)
+        );
+
+        // Unknown protocols are refused, a missing header means the legacy protocol
+        assert_eq!(GraphQlProtocol::negotiate(Some("foo, bar")), None);
+        assert_eq!(GraphQlProtocol::negotiate(Some("")), None);
+        assert_eq!(
+            GraphQlProtocol::negotiate(None),
+            Some(GraphQlProtocol::Legacy)
+        );
+    }
+
+    #[test]
+    fn protocol_names_round_trip() {
+        for protocol in &[GraphQlProtocol::Legacy, GraphQlProtocol::TransportWs] {
+            assert_eq!(
+                GraphQlProtocol::negotiate(Some(protocol.name())),
+                Some(*protocol)
+            );
+        }
+    }
+
+    #[test]
+    fn encodes_legacy_messages() {
+        let protocol = GraphQlProtocol::Legacy;
+
+        assert_eq!(
+            encode(OutgoingMessage::ConnectionAck, protocol),
+            json!({ "type": "connection_ack" })
+        );
+        assert_eq!(
+            encode(
+                OutgoingMessage::from_error_string("1".to_owned(), "oops".to_owned()),
+                protocol
+            ),
+            json!({ "type": "error", "id": "1", "payload": "oops" })
+        );
+        assert_eq!(
+            encode(
+                OutgoingMessage::from_query_result(
+                    "1".to_owned(),
+                    Arc::new(QueryResult::new(Some(q::Value::Null)))
+                ),
+                protocol
+            ),
+            json!({ "type": "data", "id": "1", "payload": { "data": null } })
+        );
+        assert_eq!(
+            encode(OutgoingMessage::Complete { id: "1".to_owned() }, protocol),
+            json!({ "type": "complete", "id": "1" })
+        );
+        assert_eq!(
+            encode(
+                OutgoingMessage::ExecutionErrors {
+                    id: "1".to_owned(),
+                    payload: Arc::new(QueryResult::from(QueryExecutionError::Timeout))
+                },
+                protocol
+            ),
+            json!({
+                "type": "data",
+                "id": "1",
+                "payload": { "errors": [{ "message": "Query timed out" }] }
+            })
+        );
+        assert_eq!(
+            encode(OutgoingMessage::KeepAlive, protocol),
+            json!({ "type": "ka" })
+        );
+    }
+
+    #[test]
+    fn encodes_transport_ws_messages() {
+        let protocol = GraphQlProtocol::TransportWs;
+
+        assert_eq!(
+            encode(OutgoingMessage::ConnectionAck, protocol),
+            json!({ "type": "connection_ack" })
+        );
+        assert_eq!(
+            encode(
+                OutgoingMessage::from_error_string("1".to_owned(), "oops".to_owned()),
+                protocol
+            ),
+            json!({ "type": "error", "id": "1", "payload": [{ "message": "oops" }] })
+        );
+        assert_eq!(
+            encode(
+                OutgoingMessage::from_query_result(
+                    "1".to_owned(),
+                    Arc::new(QueryResult::new(Some(q::Value::Null)))
+                ),
+                protocol
+            ),
+            json!({ "type": "next", "id": "1", "payload": { "data": null } })
+        );
+        assert_eq!(
+            encode(OutgoingMessage::Complete { id: "1".to_owned() }, protocol),
+            json!({ "type": "complete", "id": "1" })
+        );
+        assert_eq!(
+            encode(
+                OutgoingMessage::ExecutionErrors {
+                    id: "1".to_owned(),
+                    payload: Arc::new(QueryResult::from(QueryExecutionError::Timeout))
+                },
+                protocol
+            ),
+            json!({ "type": "error", "id": "1", "payload": [{ "message": "Query timed out" }] })
+        );
+        assert_eq!(
+            encode(OutgoingMessage::KeepAlive, protocol),
+            json!({ "type": "ping" })
+        );
+        assert_eq!(
+            encode(OutgoingMessage::Pong { payload: None }, protocol),
+            json!({ "type": "pong" })
+        );
+        assert_eq!(
+            encode(
+                OutgoingMessage::Pong {
+                    payload: Some(json!({ "foo": 1 }))
+                },
+                protocol
+            ),
+            json!({ "type": "pong", "payload": { "foo": 1 } })
+        );
+    }
+
+    #[test]
+    fn finished_operations_are_forgotten() {
+        let (sink, _stream) = mpsc::unbounded();
+        let mut operations = Operations::new(MessageSink::new(sink, GraphQlProtocol::Legacy));
+
+        let first = operations.insert("1".to_owned(), CancelGuard::new());
+        let second = operations.insert("2".to_owned(), CancelGuard::new());
+        assert_eq!(operations.len(), 2);
+
+        assert!(operations.finish("1", first));
+        assert!(!operations.contains("1"));
+        assert_eq!(operations.len(), 1);
+
+        // Finishing twice, or finishing an operation that was replaced by
+        // one with the same id, leaves the map alone
+        assert!(!operations.finish("1", first));
+        operations.stop("2".to_owned()).unwrap();
+        let third = operations.insert("2".to_owned(), CancelGuard::new());
+        assert!(!operations.finish("2", second));
+        assert!(operations.contains("2"));
+        assert!(operations.finish("2", third));
+        assert_eq!(operations.len(), 0);
+    }
+}
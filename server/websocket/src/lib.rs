@@ -3,6 +3,7 @@ extern crate graph;
 extern crate graph_graphql;
 extern crate graphql_parser;
 extern crate hyper;
+extern crate lazy_static;
 extern crate serde_derive;
 extern crate tokio_tungstenite;
 extern crate uuid;
@@ -8,7 +8,7 @@ use std::sync::Mutex;
 use tokio_tungstenite::accept_hdr_async;
 use tokio_tungstenite::tungstenite::{handshake::server::Request, Error as WsError};
 
-use crate::connection::GraphQlConnection;
+use crate::connection::{GraphQlConnection, GraphQlProtocol};
 
 /// A GraphQL subscription server based on Hyper / Websockets.
 pub struct SubscriptionServer<Q, S> {
@@ -103,6 +103,10 @@ where
                 let subgraph_id = Arc::new(Mutex::new(None));
                 let accept_subgraph_id = subgraph_id.clone();
 
+                // Protocol negotiated with the client
+                let protocol = Arc::new(Mutex::new(GraphQlProtocol::Legacy));
+                let accept_protocol = protocol.clone();
+
                 accept_hdr_async(stream, move |request: &Request| {
                     // Try to obtain the subgraph ID or name from the URL path.
                     // Return a 404 if the URL path contains no name/ID segment.
@@ -151,11 +155,30 @@ where
                         Ok(true) => (),
                     }
 
+                    // Pick a GraphQL over WebSocket protocol supported by both
+                    // the client and us
+                    let requested_protocols = request
+                        .headers
+                        .find_first("Sec-WebSocket-Protocol")
+                        .map(|value| String::from_utf8_lossy(value).into_owned());
+                    let negotiated_protocol = GraphQlProtocol::negotiate(
+                        requested_protocols.as_ref().map(|s| s.as_str()),
+                    )
+                    .ok_or_else(|| {
+                        debug!(
+                            logger,
+                            "Failed to establish WS connection, no supported protocol requested";
+                            "protocols" => requested_protocols.as_ref().map(|s| s.as_str()).unwrap_or(""),
+                        );
+                        WsError::Http(400)
+                    })?;
+
                     *accept_subgraph_id.lock().unwrap() = Some(subgraph_id);
+                    *accept_protocol.lock().unwrap() = negotiated_protocol;
 
                     Ok(Some(vec![(
                         String::from("Sec-WebSocket-Protocol"),
-                        String::from("graphql-ws"),
+                        String::from(negotiated_protocol.name()),
                     )]))
                 })
                 .then(move |result| {
@@ -177,10 +200,12 @@ where
                             };
 
                             // Spawn a GraphQL over WebSocket connection
+                            let protocol = *protocol.lock().unwrap();
                             let service = GraphQlConnection::new(
                                 &logger2,
                                 schema,
                                 ws_stream,
+                                protocol,
                                 graphql_runner.clone(),
                             );
                             tokio::spawn(service.into_future());
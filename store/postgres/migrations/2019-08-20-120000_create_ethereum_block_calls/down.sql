@@ -0,0 +1 @@
+DROP TABLE IF EXISTS ethereum_block_calls;
@@ -0,0 +1,8 @@
+-- Caches the calls (from traces) of Ethereum blocks that subgraphs needed,
+-- so that they don't have to be fetched from the Ethereum node again when
+-- another subgraph indexes the same blocks
+CREATE TABLE IF NOT EXISTS ethereum_block_calls (
+    hash VARCHAR PRIMARY KEY REFERENCES ethereum_blocks (hash) ON DELETE CASCADE,
+    network_name VARCHAR NOT NULL REFERENCES ethereum_networks (name),
+    calls JSONB NOT NULL
+);
@@ -18,6 +18,14 @@ table! {
     }
 }
 
+table! {
+    ethereum_block_calls (hash) {
+        hash -> Varchar, // REFERENCES ethereum_blocks (hash),
+        network_name -> Varchar, // REFERENCES ethereum_networks (name),
+        calls -> Jsonb,
+    }
+}
+
 table! {
     large_notifications(id) {
         id -> Integer,
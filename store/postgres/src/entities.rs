@@ -338,7 +338,7 @@ impl<'a> Connection<'a> {
         subgraph: &SubgraphDeploymentId,
         entity: &String,
         id: &String,
-    ) -> Result<Option<serde_json::Value>, StoreError> {
+    ) -> Result<(Option<serde_json::Value>, String), StoreError> {
         let table = self.table(subgraph)?;
         table.find(self.conn, entity, id)
     }
@@ -351,7 +351,7 @@ impl<'a> Connection<'a> {
         order: Option<(String, &str, &str)>,
         first: Option<u32>,
         skip: u32,
-    ) -> Result<Vec<(serde_json::Value, String)>, QueryExecutionError> {
+    ) -> Result<(Vec<(serde_json::Value, String)>, String), QueryExecutionError> {
         let table = self.table(subgraph)?;
         table.query(self.conn, entity_types, filter, order, first, skip)
     }
@@ -755,22 +755,30 @@ impl Table {
         }
     }
 
+    /// Returns the entity, if it exists, together with the SQL that was run
+    /// to find it.
     fn find(
         &self,
         conn: &PgConnection,
         entity: &str,
         id: &String,
-    ) -> Result<Option<serde_json::Value>, StoreError> {
+    ) -> Result<(Option<serde_json::Value>, String), StoreError> {
         let entities = self.clone();
-        Ok(entities
+        let query = entities
             .table
             .filter(entities.entity.eq(entity).and(entities.id.eq(id)))
             .select(entities.data)
-            .first::<serde_json::Value>(conn)
-            .optional()?)
+            .limit(1);
+        let query_debug_info = debug_query::<Pg, _>(&query).to_string();
+
+        Ok((
+            query.get_result::<serde_json::Value>(conn).optional()?,
+            query_debug_info,
+        ))
     }
 
-    /// order is a tuple (attribute, cast, direction)
+    /// order is a tuple (attribute, cast, direction). Returns the matching
+    /// rows together with the SQL that was run to find them.
     fn query(
         &self,
         conn: &PgConnection,
@@ -779,7 +787,7 @@ impl Table {
         order: Option<(String, &str, &str)>,
         first: Option<u32>,
         skip: u32,
-    ) -> Result<Vec<(serde_json::Value, String)>, QueryExecutionError> {
+    ) -> Result<(Vec<(serde_json::Value, String)>, String), QueryExecutionError> {
         let entities = self.clone();
         let mut query = entities
             .table
@@ -817,6 +825,7 @@ impl Table {
 
         query
             .load::<(serde_json::Value, String)>(conn)
+            .map(|rows| (rows, query_debug_info.clone()))
             .map_err(|e| {
                 QueryExecutionError::ResolveEntitiesError(format!(
                     "{}, query = {:?}",
@@ -1119,7 +1128,9 @@ impl Table {
             | ValueType::BigDecimal
             | ValueType::ID
             | ValueType::Int
-            | ValueType::String => (String::from("btree"), String::from(""), "->>"),
+            | ValueType::Int8
+            | ValueType::String
+            | ValueType::Timestamp => (String::from("btree"), String::from(""), "->>"),
             ValueType::List => (String::from("gin"), String::from("jsonb_path_ops"), "->"),
         };
         // Cast between the type we store in JSONB for the field and the type
@@ -1128,6 +1139,7 @@ impl Table {
         // a number
         let type_cast = match index.field_value_type {
             ValueType::BigInt | ValueType::BigDecimal => "::numeric",
+            ValueType::Int8 | ValueType::Timestamp => "::bigint",
             ValueType::Boolean => "::bool",
             _ => "",
         };
@@ -1175,6 +1187,30 @@ impl Table {
             entity_name = &index.entity_name
         );
         conn.batch_execute(&*query)?;
+
+        // String attributes also get a trigram index, which the btree index
+        // above can not replace for case-insensitive and pattern filters
+        // (`ILIKE`, `~`, `~*`) and for `LIKE` patterns that do not have a
+        // fixed prefix
+        if index.field_value_type == ValueType::String {
+            let query = format!(
+                "create index if not exists {name}
+                             on {subgraph}.entities
+                          using gin ((data->'{attribute_name}'->>'data') gin_trgm_ops)
+                          where entity='{entity_name}'",
+                name = format!(
+                    "trgm_{}_{}_{}_{}",
+                    index.entity_number,
+                    index.attribute_number,
+                    to_snake_case(&index.entity_name),
+                    to_snake_case(&index.attribute_name)
+                ),
+                subgraph = self.schema,
+                attribute_name = &index.attribute_name,
+                entity_name = &index.entity_name
+            );
+            conn.batch_execute(&*query)?;
+        }
         Ok(1)
     }
 
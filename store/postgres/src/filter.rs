@@ -3,7 +3,7 @@ use diesel::pg::Pg;
 use diesel::prelude::*;
 use diesel::query_builder::BoxedSelectStatement;
 use diesel::serialize::ToSql;
-use diesel::sql_types::{Array, Bool, Double, HasSqlType, Integer, Numeric, Text};
+use diesel::sql_types::{Array, Bool, Double, HasSqlType, Int8, Integer, Numeric, Text};
 use std::str::FromStr;
 
 use graph::components::store::EntityFilter;
@@ -62,6 +62,18 @@ impl<QS> IntoFilter<QS> for i32 {
     }
 }
 
+impl<QS> IntoFilter<QS> for i64 {
+    fn into_filter(self, attribute: String, op: &str) -> FilterExpression<QS> {
+        Box::new(
+            sql("(data -> ")
+                .bind::<Text, _>(attribute)
+                .sql("->> 'data')::bigint")
+                .sql(op)
+                .bind::<Int8, _>(self),
+        ) as FilterExpression<QS>
+    }
+}
+
 impl<QS> IntoFilter<QS> for bool {
     fn into_filter(self, attribute: String, op: &str) -> FilterExpression<QS> {
         Box::new(
@@ -175,14 +187,20 @@ where
                 .map(|filter_expr| Box::new(p.or(filter_expr)) as FilterExpression<QS>)
         }),
 
-        Contains(..) | NotContains(..) => {
-            let (attribute, contains, op, value) = match filter {
-                EntityFilter::Contains(attribute, value) => (attribute, true, " LIKE ", value),
-                EntityFilter::NotContains(attribute, value) => {
-                    (attribute, false, " NOT LIKE ", value)
+        Contains(..) | NotContains(..) | ContainsNoCase(..) | NotContainsNoCase(..) => {
+            let (attribute, filter_name, op, value) = match filter {
+                Contains(attribute, value) => (attribute, "contains", " LIKE ", value),
+                NotContains(attribute, value) => (attribute, "not_contains", " NOT LIKE ", value),
+                ContainsNoCase(attribute, value) => {
+                    (attribute, "contains_nocase", " ILIKE ", value)
+                }
+                NotContainsNoCase(attribute, value) => {
+                    (attribute, "not_contains_nocase", " NOT ILIKE ", value)
                 }
                 _ => unreachable!(),
             };
+            let contains = !op.contains("NOT");
+            let case_sensitive = !op.contains("ILIKE");
 
             match value {
                 Value::String(s) => {
@@ -193,7 +211,7 @@ where
                     }
                 }
                 Value::Bytes(b) => Ok(format!("%{}%", b.to_string()).into_filter(attribute, op)),
-                Value::List(lst) => {
+                Value::List(lst) if case_sensitive => {
                     let s = serde_json::to_string(&lst).expect("failed to serialize list value");
                     let predicate = sql("data -> ")
                         .bind::<Text, _>(attribute)
@@ -206,13 +224,16 @@ where
                         Ok(Box::new(dsl::not(predicate)) as FilterExpression<QS>)
                     }
                 }
-                Value::Null
+                Value::List(_)
+                | Value::Null
                 | Value::BigDecimal(_)
                 | Value::Int(_)
+                | Value::Int8(_)
+                | Value::Timestamp(_)
                 | Value::Bool(_)
                 | Value::BigInt(_) => {
                     return Err(UnsupportedFilter {
-                        filter: if contains { "contains" } else { "not_contains" }.to_owned(),
+                        filter: filter_name.to_owned(),
                         value,
                     });
                 }
@@ -232,6 +253,7 @@ where
                 Value::Bytes(b) => Ok(b.to_string().into_filter(attribute, op)),
                 Value::BigDecimal(n) => Ok(n.into_filter(attribute, op)),
                 Value::Int(n) => Ok(n.into_filter(attribute, op)),
+                Value::Int8(n) | Value::Timestamp(n) => Ok(n.into_filter(attribute, op)),
                 Value::List(lst) => {
                     // In order to compare lists, we have to coerce the database value to jsonb
                     let s = serde_json::to_string(&lst).expect("failed to serialize list value");
@@ -288,6 +310,7 @@ where
                 Value::BigInt(n) => Ok(n.into_filter(attribute, op)),
                 Value::BigDecimal(n) => Ok(n.into_filter(attribute, op)),
                 Value::Int(n) => Ok(n.into_filter(attribute, op)),
+                Value::Int8(n) | Value::Timestamp(n) => Ok(n.into_filter(attribute, op)),
                 Value::String(s) => Ok(s.into_filter(attribute, op)),
                 Value::Bool(_) | Value::Bytes(_) | Value::List(_) | Value::Null => {
                     return Err(UnsupportedFilter {
@@ -317,6 +340,8 @@ where
                 }
                 Value::Int(_) => Ok(SqlValue::new_array(values)
                     .into_array_filter::<Integer>(attribute, op, "::int")),
+                Value::Int8(_) | Value::Timestamp(_) => Ok(SqlValue::new_array(values)
+                    .into_array_filter::<Int8>(attribute, op, "::bigint")),
                 Value::String(_) => {
                     Ok(SqlValue::new_array(values).into_array_filter::<Text>(attribute, op, ""))
                 }
@@ -340,10 +365,43 @@ where
                 .collect()))
         }
 
-        StartsWith(..) | NotStartsWith(..) => {
-            let (attribute, op, value) = match filter {
-                StartsWith(attribute, value) => (attribute, " LIKE ", value),
-                NotStartsWith(attribute, value) => (attribute, " NOT LIKE ", value),
+        ContainsAll(attribute, values) => build_filter(Contains(attribute, Value::List(values))),
+
+        ContainsAny(attribute, values) => {
+            if values.is_empty() {
+                return Ok(false_expr);
+            }
+
+            // Check for each value separately with `@>` so that the GIN index
+            // on the attribute can be used
+            build_filter(Or(values
+                .into_iter()
+                .map(|value| Contains(attribute.clone(), Value::List(vec![value])))
+                .collect()))
+        }
+
+        IsEmpty(attribute, is_empty) => Ok(Box::new(
+            sql("(jsonb_typeof(data -> ")
+                .bind::<Text, _>(attribute.clone())
+                .sql("-> 'data') = 'array' and data -> ")
+                .bind::<Text, _>(attribute)
+                .sql("-> 'data'")
+                .sql(if is_empty { " = " } else { " != " })
+                .sql("'[]'::jsonb)"),
+        ) as FilterExpression<QS>),
+
+        StartsWith(..) | NotStartsWith(..) | StartsWithNoCase(..) | NotStartsWithNoCase(..) => {
+            let (attribute, filter_name, op, value) = match filter {
+                StartsWith(attribute, value) => (attribute, "starts_with", " LIKE ", value),
+                NotStartsWith(attribute, value) => {
+                    (attribute, "not_starts_with", " NOT LIKE ", value)
+                }
+                StartsWithNoCase(attribute, value) => {
+                    (attribute, "starts_with_nocase", " ILIKE ", value)
+                }
+                NotStartsWithNoCase(attribute, value) => {
+                    (attribute, "not_starts_with_nocase", " NOT ILIKE ", value)
+                }
                 _ => unreachable!(),
             };
 
@@ -354,25 +412,28 @@ where
                 | Value::Bytes(_)
                 | Value::BigDecimal(_)
                 | Value::Int(_)
+                | Value::Int8(_)
+                | Value::Timestamp(_)
                 | Value::List(_)
                 | Value::Null => {
                     return Err(UnsupportedFilter {
-                        filter: if op == " LIKE " {
-                            "starts_with"
-                        } else {
-                            "not_starts_with"
-                        }
-                        .to_owned(),
+                        filter: filter_name.to_owned(),
                         value,
                     });
                 }
             }
         }
 
-        EndsWith(..) | NotEndsWith(..) => {
-            let (attribute, op, value) = match filter {
-                EndsWith(attribute, value) => (attribute, " LIKE ", value),
-                NotEndsWith(attribute, value) => (attribute, " NOT LIKE ", value),
+        EndsWith(..) | NotEndsWith(..) | EndsWithNoCase(..) | NotEndsWithNoCase(..) => {
+            let (attribute, filter_name, op, value) = match filter {
+                EndsWith(attribute, value) => (attribute, "ends_with", " LIKE ", value),
+                NotEndsWith(attribute, value) => (attribute, "not_ends_with", " NOT LIKE ", value),
+                EndsWithNoCase(attribute, value) => {
+                    (attribute, "ends_with_nocase", " ILIKE ", value)
+                }
+                NotEndsWithNoCase(attribute, value) => {
+                    (attribute, "not_ends_with_nocase", " NOT ILIKE ", value)
+                }
                 _ => unreachable!(),
             };
 
@@ -383,15 +444,42 @@ where
                 | Value::Bytes(_)
                 | Value::BigDecimal(_)
                 | Value::Int(_)
+                | Value::Int8(_)
+                | Value::Timestamp(_)
+                | Value::List(_)
+                | Value::Null => {
+                    return Err(UnsupportedFilter {
+                        filter: filter_name.to_owned(),
+                        value,
+                    });
+                }
+            }
+        }
+
+        Like(..) | LikeNoCase(..) | Regex(..) | RegexNoCase(..) => {
+            let (attribute, filter_name, op, value) = match filter {
+                Like(attribute, value) => (attribute, "like", " LIKE ", value),
+                LikeNoCase(attribute, value) => (attribute, "like_nocase", " ILIKE ", value),
+                Regex(attribute, value) => (attribute, "regex", " ~ ", value),
+                RegexNoCase(attribute, value) => (attribute, "regex_nocase", " ~* ", value),
+                _ => unreachable!(),
+            };
+
+            // The pattern is used as is; its length is limited when the
+            // filter is built from a GraphQL query
+            match value {
+                Value::String(s) => Ok(s.into_filter(attribute, op)),
+                Value::Bool(_)
+                | Value::BigInt(_)
+                | Value::Bytes(_)
+                | Value::BigDecimal(_)
+                | Value::Int(_)
+                | Value::Int8(_)
+                | Value::Timestamp(_)
                 | Value::List(_)
                 | Value::Null => {
                     return Err(UnsupportedFilter {
-                        filter: if op == " LIKE " {
-                            "ends_with"
-                        } else {
-                            "not_ends_with"
-                        }
-                        .to_owned(),
+                        filter: filter_name.to_owned(),
                         value,
                     });
                 }
@@ -1,6 +1,6 @@
 use diesel::pg::Pg;
 use diesel::serialize::{self, Output, ToSql};
-use diesel::sql_types::{Bool, Integer, Numeric, Text};
+use diesel::sql_types::{BigInt, Bool, Integer, Numeric, Text};
 use std::io::Write;
 
 use graph::data::store::Value;
@@ -32,6 +32,17 @@ impl ToSql<Integer, Pg> for SqlValue {
     }
 }
 
+impl ToSql<BigInt, Pg> for SqlValue {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        match self.0 {
+            Value::Int8(ref i) | Value::Timestamp(ref i) => {
+                <i64 as ToSql<BigInt, Pg>>::to_sql(&i, out)
+            }
+            _ => panic!("Failed to convert non-int8 attribute value to int8 in SQL"),
+        }
+    }
+}
+
 impl ToSql<Numeric, Pg> for SqlValue {
     fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
         match &self.0 {
@@ -1,4 +1,4 @@
-use diesel::connection::SimpleConnection;
+use diesel::connection::{SimpleConnection, TransactionManager};
 use diesel::pg::PgConnection;
 use diesel::prelude::*;
 use diesel::r2d2::{self, ConnectionManager, Pool, PooledConnection};
@@ -6,6 +6,7 @@ use diesel::{insert_into, select, update};
 use futures::sync::mpsc::{channel, Sender};
 use lru_time_cache::LruCache;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Mutex, RwLock};
 use std::time::{Duration, Instant};
 use uuid::Uuid;
@@ -105,6 +106,71 @@ pub struct StoreConfig {
     pub conn_pool_size: u32,
 }
 
+/// The connection pool of a store. Once the pool is closed, it stops
+/// handing out connections and is dropped as soon as all connections that
+/// are in use have been returned.
+#[derive(Clone)]
+struct ConnectionPool {
+    logger: Logger,
+    pool: Arc<RwLock<Option<Pool<ConnectionManager<PgConnection>>>>>,
+    closed: Arc<AtomicBool>,
+}
+
+impl ConnectionPool {
+    fn new(logger: Logger, pool: Pool<ConnectionManager<PgConnection>>) -> Self {
+        ConnectionPool {
+            logger,
+            pool: Arc::new(RwLock::new(Some(pool))),
+            closed: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn get(&self) -> Result<PooledConnection<ConnectionManager<PgConnection>>, Error> {
+        // Connections are checked out while holding the read lock so that
+        // `close` can't drop the pool in the middle of a checkout
+        let pool = self.pool.read().unwrap();
+        let pool = match pool.as_ref() {
+            Some(pool) if !self.closed.load(Ordering::SeqCst) => pool,
+            _ => return Err(format_err!("store is closed")),
+        };
+
+        let start_time = Instant::now();
+        let conn = pool.get();
+        let wait = start_time.elapsed();
+        if wait > Duration::from_millis(10) {
+            warn!(self.logger, "Possible contention in DB connection pool";
+                               "wait_ms" => wait.as_millis())
+        }
+        conn.map_err(Error::from)
+    }
+
+    fn close(&self) -> impl Future<Item = (), Error = ()> {
+        self.closed.store(true, Ordering::SeqCst);
+
+        let pool = self.pool.clone();
+        Interval::new(Instant::now(), Duration::from_millis(100))
+            .map_err(|_| ())
+            .take_while(move |_| {
+                // Only take the pool if no checkout is in progress; any
+                // checkout that starts later sees that the pool is closed
+                let mut pool = match pool.try_write() {
+                    Ok(pool) => pool,
+                    Err(_) => return Ok(true),
+                };
+                let idle = pool.as_ref().map_or(true, |pool| {
+                    let state = pool.state();
+                    state.idle_connections == state.connections
+                });
+                if idle {
+                    // Dropping the pool closes its connections
+                    pool.take();
+                }
+                Ok(!idle)
+            })
+            .for_each(|_| Ok(()))
+    }
+}
+
 /// A Store based on Diesel and Postgres.
 pub struct Store {
     logger: Logger,
@@ -114,7 +180,7 @@ pub struct Store {
     chain_head_update_listener: ChainHeadUpdateListener,
     network_name: String,
     genesis_block_ptr: EthereumBlockPointer,
-    conn: Pool<ConnectionManager<PgConnection>>,
+    conn: ConnectionPool,
     schema_cache: Mutex<LruCache<SubgraphDeploymentId, Arc<Schema>>>,
 }
 
@@ -177,7 +243,7 @@ impl Store {
             ),
             network_name: config.network_name.clone(),
             genesis_block_ptr: (net_identifiers.genesis_block_hash, config.start_block).into(),
-            conn: pool,
+            conn: ConnectionPool::new(logger.clone(), pool),
             schema_cache: Mutex::new(LruCache::with_capacity(100)),
         };
 
@@ -336,14 +402,28 @@ impl Store {
         op_entity: &String,
         op_id: &String,
     ) -> Result<Option<Entity>, QueryExecutionError> {
-        match conn.find(op_subgraph, op_entity, op_id).map_err(|e| {
+        self.get_entity_with_sql(conn, op_subgraph, op_entity, op_id)
+            .map(|(entity, _)| entity)
+    }
+
+    /// Gets an entity from Postgres together with the SQL that was run to
+    /// find it.
+    fn get_entity_with_sql(
+        &self,
+        conn: &e::Connection,
+        op_subgraph: &SubgraphDeploymentId,
+        op_entity: &String,
+        op_id: &String,
+    ) -> Result<(Option<Entity>, String), QueryExecutionError> {
+        let (json, sql) = conn.find(op_subgraph, op_entity, op_id).map_err(|e| {
             QueryExecutionError::ResolveEntityError(
                 op_subgraph.clone(),
                 op_entity.clone(),
                 op_id.clone(),
                 format!("{}", e),
             )
-        })? {
+        })?;
+        match json {
             Some(json) => {
                 let mut value = serde_json::from_value::<Entity>(json).map_err(|e| {
                     QueryExecutionError::ResolveEntityError(
@@ -354,17 +434,19 @@ impl Store {
                     )
                 })?;
                 value.set("__typename", op_entity);
-                Ok(Some(value))
+                Ok((Some(value), sql))
             }
-            None => Ok(None),
+            None => Ok((None, sql)),
         }
     }
 
+    /// Runs the query and returns the matching entities together with the
+    /// SQL that was run to find them.
     fn execute_query(
         &self,
         conn: &e::Connection,
         query: EntityQuery,
-    ) -> Result<Vec<Entity>, QueryExecutionError> {
+    ) -> Result<(Vec<Entity>, String), QueryExecutionError> {
         // Add order by filters to query
         let order = match query.order_by {
             Some((attribute, value_type)) => {
@@ -380,7 +462,7 @@ impl Store {
                     ValueType::Boolean => "::boolean",
                     ValueType::Bytes => "",
                     ValueType::ID => "",
-                    ValueType::Int => "::bigint",
+                    ValueType::Int | ValueType::Int8 | ValueType::Timestamp => "::bigint",
                     ValueType::String => "",
                     ValueType::List => {
                         return Err(QueryExecutionError::OrderByNotSupportedForType(
@@ -402,8 +484,8 @@ impl Store {
             query.range.first,
             query.range.skip,
         )
-        .map(|values| {
-            values
+        .map(|(values, sql)| {
+            let entities = values
                 .into_iter()
                 .map(|(value, entity_type)| {
                     let parse_error_msg = format!("Error parsing entity JSON: {:?}", value);
@@ -412,7 +494,8 @@ impl Store {
                     value.set("__typename", entity_type);
                     value
                 })
-                .collect()
+                .collect();
+            (entities, sql)
         })
     }
 
@@ -457,6 +540,30 @@ impl Store {
                 ));
             }
         }
+
+        // The same applies to types that are members of the same union.
+        let types_with_shared_union = Vec::from_iter(
+            schema
+                .unions_for_type(&key.entity_type)
+                .into_iter()
+                .flatten()
+                .map(|union_type| &types_for_interface[&union_type.name])
+                .flatten()
+                .map(|object_type| &object_type.name)
+                .filter(|type_name| **type_name != key.entity_type),
+        );
+
+        if !types_with_shared_union.is_empty() {
+            if let Some(conflicting_entity) =
+                conn.conflicting_entity(&key.subgraph_id, &key.entity_id, types_with_shared_union)?
+            {
+                return Err(StoreError::ConflictingUnionId(
+                    key.entity_type.clone(),
+                    key.entity_id.clone(),
+                    conflicting_entity,
+                ));
+            }
+        }
         Ok(())
     }
 
@@ -591,7 +698,7 @@ impl Store {
         _history_event: Option<&HistoryEvent>,
     ) -> Result<i32, StoreError> {
         // Execute query
-        let actual_entities = self.execute_query(conn, query.clone()).map_err(|e| {
+        let (actual_entities, _) = self.execute_query(conn, query.clone()).map_err(|e| {
             format_err!(
                 "AbortUnless ({}): query execution error: {:?}, {}",
                 description,
@@ -753,36 +860,28 @@ impl Store {
         Ok(())
     }
 
-    fn get_conn(&self) -> Result<PooledConnection<ConnectionManager<PgConnection>>, Error> {
-        let start_time = Instant::now();
-        let conn = self.conn.get();
-        let wait = start_time.elapsed();
-        if wait > Duration::from_millis(10) {
-            warn!(self.logger, "Possible contention in DB connection pool";
-                               "wait_ms" => wait.as_millis())
-        }
-        conn.map_err(Error::from)
+    /// Stops handing out database connections. The returned future
+    /// resolves once all connections in use have been returned and the
+    /// connection pool has been dropped. After this, any store operation
+    /// fails.
+    pub fn close(&self) -> impl Future<Item = (), Error = ()> {
+        info!(self.logger, "Closing store");
+        self.conn.close()
     }
 
-    /// Creates a history event to use when applying entity operations.
-    pub fn create_history_event(
-        &self,
-        subgraph: SubgraphDeploymentId,
-        event_source: EventSource,
-    ) -> Result<HistoryEvent, Error> {
-        let conn = self
-            .conn
-            .get()
-            .map_err(|e| QueryExecutionError::StoreError(e.into()))?;
-        let conn = e::Connection::new(&conn);
-        conn.create_history_event(subgraph, event_source)
+    fn get_conn(&self) -> Result<PooledConnection<ConnectionManager<PgConnection>>, Error> {
+        self.conn.get()
     }
-}
 
-impl StoreTrait for Store {
-    fn block_ptr(&self, subgraph_id: SubgraphDeploymentId) -> Result<EthereumBlockPointer, Error> {
+    /// Looks up the block pointer of the subgraph through `conn`.
+    fn block_ptr_with_conn(
+        &self,
+        conn: &e::Connection,
+        subgraph_id: SubgraphDeploymentId,
+    ) -> Result<EthereumBlockPointer, Error> {
+        let key = SubgraphDeploymentEntity::key(subgraph_id.clone());
         let subgraph_entity = self
-            .get(SubgraphDeploymentEntity::key(subgraph_id.clone()))
+            .get_entity(conn, &key.subgraph_id, &key.entity_type, &key.entity_id)
             .map_err(|e| format_err!("error reading subgraph entity: {}", e))?
             .ok_or_else(|| {
                 format_err!(
@@ -815,6 +914,25 @@ impl StoreTrait for Store {
         Ok(EthereumBlockPointer { hash, number })
     }
 
+    /// Creates a history event to use when applying entity operations.
+    pub fn create_history_event(
+        &self,
+        subgraph: SubgraphDeploymentId,
+        event_source: EventSource,
+    ) -> Result<HistoryEvent, Error> {
+        let conn = self.get_conn()?;
+        let conn = e::Connection::new(&conn);
+        conn.create_history_event(subgraph, event_source)
+    }
+}
+
+impl StoreTrait for Store {
+    fn block_ptr(&self, subgraph_id: SubgraphDeploymentId) -> Result<EthereumBlockPointer, Error> {
+        let conn = self.get_conn()?;
+        let conn = e::Connection::new(&conn);
+        self.block_ptr_with_conn(&conn, subgraph_id)
+    }
+
     fn get(&self, key: EntityKey) -> Result<Option<Entity>, QueryExecutionError> {
         let conn = self
             .get_conn()
@@ -823,12 +941,32 @@ impl StoreTrait for Store {
         self.get_entity(&conn, &key.subgraph_id, &key.entity_type, &key.entity_id)
     }
 
+    fn get_with_sql(
+        &self,
+        key: EntityKey,
+    ) -> Result<(Option<Entity>, Option<String>), QueryExecutionError> {
+        let conn = self
+            .get_conn()
+            .map_err(|e| QueryExecutionError::StoreError(e.into()))?;
+        let conn = e::Connection::new(&conn);
+        self.get_entity_with_sql(&conn, &key.subgraph_id, &key.entity_type, &key.entity_id)
+            .map(|(entity, sql)| (entity, Some(sql)))
+    }
+
     fn find(&self, query: EntityQuery) -> Result<Vec<Entity>, QueryExecutionError> {
+        self.find_with_sql(query).map(|(entities, _)| entities)
+    }
+
+    fn find_with_sql(
+        &self,
+        query: EntityQuery,
+    ) -> Result<(Vec<Entity>, Option<String>), QueryExecutionError> {
         let conn = self
             .get_conn()
             .map_err(|e| QueryExecutionError::StoreError(e.into()))?;
         let conn = e::Connection::new(&conn);
         self.execute_query(&conn, query)
+            .map(|(entities, sql)| (entities, Some(sql)))
     }
 
     fn find_one(&self, mut query: EntityQuery) -> Result<Option<Entity>, QueryExecutionError> {
@@ -839,13 +977,17 @@ impl StoreTrait for Store {
             .map_err(|e| QueryExecutionError::StoreError(e.into()))?;
         let conn = e::Connection::new(&conn);
 
-        let mut results = self.execute_query(&conn, query)?;
+        let (mut results, _) = self.execute_query(&conn, query)?;
         match results.len() {
             0 | 1 => Ok(results.pop()),
             n => panic!("find_one query found {} results", n),
         }
     }
 
+    fn query_store(self: Arc<Self>) -> Result<Arc<QueryStore>, QueryExecutionError> {
+        Ok(Arc::new(SnapshotQueryStore::new(self)))
+    }
+
     fn find_ens_name(&self, hash: &str) -> Result<Option<String>, QueryExecutionError> {
         use crate::db_schema::ens_names as dsl;
 
@@ -909,7 +1051,7 @@ impl StoreTrait for Store {
             );
         }
 
-        let conn = self.conn.get().map_err(Error::from)?;
+        let conn = self.get_conn()?;
         let econn = e::Connection::new(&conn);
 
         conn.transaction(|| {
@@ -1123,6 +1265,119 @@ impl StoreTrait for Store {
     }
 }
 
+/// Reads the entities of one GraphQL query through a single connection in a
+/// `REPEATABLE READ` read-only transaction, so that all reads see the same
+/// snapshot of the database, no matter what is committed in the meantime.
+/// The transaction is begun with the first read, so that queries that never
+/// read from the store don't tie up a connection, and rolled back when the
+/// handle is dropped.
+struct SnapshotQueryStore {
+    store: Arc<Store>,
+    conn: Mutex<Option<PooledConnection<ConnectionManager<PgConnection>>>>,
+}
+
+impl SnapshotQueryStore {
+    fn new(store: Arc<Store>) -> Self {
+        SnapshotQueryStore {
+            store,
+            conn: Mutex::new(None),
+        }
+    }
+
+    /// Runs `f` with the connection of the snapshot, beginning the
+    /// transaction if this is the first read.
+    fn with_conn<T, E>(&self, f: impl FnOnce(&e::Connection) -> Result<T, E>) -> Result<T, E>
+    where
+        E: From<StoreError>,
+    {
+        let mut conn = self.conn.lock().unwrap();
+        if conn.is_none() {
+            *conn = Some(self.begin().map_err(E::from)?);
+        }
+        f(&e::Connection::new(conn.as_ref().unwrap()))
+    }
+
+    fn begin(&self) -> Result<PooledConnection<ConnectionManager<PgConnection>>, StoreError> {
+        let conn = self.store.get_conn()?;
+        conn.transaction_manager().begin_transaction(&*conn)?;
+        if let Err(e) =
+            conn.batch_execute("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ READ ONLY")
+        {
+            // Don't hand the connection back to the pool in the middle of
+            // a transaction
+            let _ = conn.transaction_manager().rollback_transaction(&*conn);
+            return Err(e.into());
+        }
+        Ok(conn)
+    }
+}
+
+impl Drop for SnapshotQueryStore {
+    fn drop(&mut self) {
+        let conn = match self.conn.get_mut() {
+            Ok(conn) => conn,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let conn = match conn {
+            Some(conn) => conn,
+            // The query never read from the store
+            None => return,
+        };
+        if let Err(e) = conn.transaction_manager().rollback_transaction(&**conn) {
+            error!(
+                self.store.logger,
+                "Failed to end the transaction of a query";
+                "error" => e.to_string(),
+            );
+        }
+    }
+}
+
+impl QueryStore for SnapshotQueryStore {
+    fn block_ptr(&self, subgraph_id: SubgraphDeploymentId) -> Result<EthereumBlockPointer, Error> {
+        self.with_conn(|conn| self.store.block_ptr_with_conn(conn, subgraph_id))
+    }
+
+    fn get(&self, key: EntityKey) -> Result<Option<Entity>, QueryExecutionError> {
+        self.with_conn(|conn| {
+            self.store
+                .get_entity(conn, &key.subgraph_id, &key.entity_type, &key.entity_id)
+        })
+    }
+
+    fn get_with_sql(
+        &self,
+        key: EntityKey,
+    ) -> Result<(Option<Entity>, Option<String>), QueryExecutionError> {
+        self.with_conn(|conn| {
+            self.store
+                .get_entity_with_sql(conn, &key.subgraph_id, &key.entity_type, &key.entity_id)
+                .map(|(entity, sql)| (entity, Some(sql)))
+        })
+    }
+
+    fn find_with_sql(
+        &self,
+        query: EntityQuery,
+    ) -> Result<(Vec<Entity>, Option<String>), QueryExecutionError> {
+        self.with_conn(|conn| {
+            self.store
+                .execute_query(conn, query)
+                .map(|(entities, sql)| (entities, Some(sql)))
+        })
+    }
+
+    fn find_one(&self, mut query: EntityQuery) -> Result<Option<Entity>, QueryExecutionError> {
+        query.range = EntityRange::first(1);
+
+        let (mut results, _) = self.with_conn(|conn| self.store.execute_query(conn, query))?;
+        match results.len() {
+            0 | 1 => Ok(results.pop()),
+            n => panic!("find_one query found {} results", n),
+        }
+    }
+}
+
 impl SubgraphDeploymentStore for Store {
     fn subgraph_schema(&self, subgraph_id: &SubgraphDeploymentId) -> Result<Arc<Schema>, Error> {
         if let Some(schema) = self.schema_cache.lock().unwrap().get(&subgraph_id) {
@@ -1210,7 +1465,7 @@ impl ChainStore for Store {
                 .on_conflict(hash)
                 .do_update()
                 .set(values)
-                .execute(&*conn.get().map_err(Error::from)?)
+                .execute(&*conn.get()?)
                 .map_err(Error::from)
                 .map_err(E::from)
                 .map(|_| ())
@@ -1280,6 +1535,49 @@ impl ChainStore for Store {
             .and_then(|x| x)
     }
 
+    fn upsert_block_calls(
+        &self,
+        block_hash: H256,
+        block_calls: &[EthereumCall],
+    ) -> Result<(), Error> {
+        use crate::db_schema::ethereum_block_calls::dsl::*;
+
+        let json_blob = serde_json::to_value(block_calls).expect("Failed to serialize calls");
+        let values = (
+            hash.eq(format!("{:x}", block_hash)),
+            network_name.eq(&self.network_name),
+            calls.eq(json_blob),
+        );
+
+        // Like blocks, the calls of a block never change, so overwriting
+        // existing calls is a no-op
+        insert_into(ethereum_block_calls)
+            .values(values.clone())
+            .on_conflict(hash)
+            .do_update()
+            .set(values)
+            .execute(&*self.get_conn()?)
+            .map_err(Error::from)
+            .map(|_| ())
+    }
+
+    fn block_calls(&self, block_hash: H256) -> Result<Option<Vec<EthereumCall>>, Error> {
+        use crate::db_schema::ethereum_block_calls::dsl::*;
+
+        ethereum_block_calls
+            .select(calls)
+            .filter(network_name.eq(&self.network_name))
+            .filter(hash.eq(format!("{:x}", block_hash)))
+            .first::<serde_json::Value>(&*self.get_conn()?)
+            .optional()
+            .map_err(Error::from)
+            .and_then(|json_calls| {
+                json_calls
+                    .map(|json_calls| serde_json::from_value(json_calls).map_err(Error::from))
+                    .transpose()
+            })
+    }
+
     fn ancestor_block(
         &self,
         block_ptr: EthereumBlockPointer,
@@ -9,7 +9,9 @@ use std::fmt::Debug;
 use std::sync::Arc;
 
 use graph::components::store::ChainStore;
+use graph::prelude::EthereumCall;
 use graph::serde_json;
+use graph::web3::types::{Address, Bytes, H256, U256};
 use graph_store_postgres::db_schema_for_tests as db_schema;
 use graph_store_postgres::Store as DieselStore;
 
@@ -103,6 +105,9 @@ fn remove_test_data() {
     let url = postgres_test_url();
     let conn = PgConnection::establish(url.as_str()).expect("Failed to connect to Postgres");
 
+    diesel::delete(db_schema::ethereum_block_calls::table)
+        .execute(&conn)
+        .expect("Failed to delete ethereum_block_calls");
     diesel::delete(db_schema::ethereum_blocks::table)
         .execute(&conn)
         .expect("Failed to delete ethereum_blocks");
@@ -263,3 +268,40 @@ fn long_chain_with_uncles() {
     ];
     check_chain_head_update(chain, Some(&*BLOCK_FOUR), None);
 }
+
+#[test]
+fn block_calls_are_cached() {
+    run_test(vec![&*BLOCK_ONE, &*BLOCK_TWO], |store| -> Result<(), ()> {
+        let block_one_hash = BLOCK_ONE.hash.parse().unwrap();
+        let block_two_hash = BLOCK_TWO.hash.parse().unwrap();
+        let calls = vec![EthereumCall {
+            from: Address::from(1),
+            to: Address::from(2),
+            value: U256::from(3),
+            gas_used: U256::from(21000),
+            input: Bytes(vec![0xca, 0xfe]),
+            output: Bytes(vec![]),
+            block_number: BLOCK_ONE.number,
+            block_hash: block_one_hash,
+            transaction_hash: Some(H256::from(4)),
+        }];
+
+        store
+            .upsert_block_calls(block_one_hash, &calls)
+            .expect("upsert_block_calls failed");
+
+        assert_eq!(
+            store
+                .block_calls(block_one_hash)
+                .expect("block_calls failed"),
+            Some(calls)
+        );
+        assert_eq!(
+            store
+                .block_calls(block_two_hash)
+                .expect("block_calls failed"),
+            None
+        );
+        Ok(())
+    })
+}
@@ -14,7 +14,7 @@ use graph::data::subgraph::schema::*;
 use graph::data::subgraph::*;
 use graph::prelude::*;
 use graph::web3::types::{Address, H256};
-use graph_store_postgres::Store as DieselStore;
+use graph_store_postgres::{Store as DieselStore, StoreConfig};
 
 lazy_static! {
     static ref TEST_SUBGRAPH_ID_STRING: String = String::from("testsubgraph");
@@ -221,6 +221,15 @@ fn create_test_entity(
         "seconds_age".to_owned(),
         Value::BigInt(BigInt::from(age) * 31557600.into()),
     );
+    let seconds_age = age as i64 * 31557600;
+    test_entity.insert(
+        "nanos_age".to_owned(),
+        Value::Int8(seconds_age * 1_000_000_000),
+    );
+    test_entity.insert(
+        "born_at".to_owned(),
+        Value::Timestamp(1_500_000_000_000_000 - seconds_age * 1_000_000),
+    );
     test_entity.insert("weight".to_owned(), Value::BigDecimal(weight.into()));
     test_entity.insert("coffee".to_owned(), Value::Bool(coffee));
     test_entity.insert(
@@ -324,6 +333,11 @@ fn get_entity_1() {
             "seconds_age".to_owned(),
             Value::BigInt(BigInt::from(2114359200)),
         );
+        expected_entity.insert(
+            "nanos_age".to_owned(),
+            Value::Int8(2_114_359_200_000_000_000),
+        );
+        expected_entity.insert("born_at".to_owned(), Value::Timestamp(-614_359_200_000_000));
         expected_entity.insert("weight".to_owned(), Value::BigDecimal(184.4.into()));
         expected_entity.insert("coffee".to_owned(), Value::Bool(false));
         // "favorite_color" was set to `Null` earlier and should be absent
@@ -361,6 +375,8 @@ fn get_entity_3() {
             "seconds_age".to_owned(),
             Value::BigInt(BigInt::from(883612800)),
         );
+        expected_entity.insert("nanos_age".to_owned(), Value::Int8(883_612_800_000_000_000));
+        expected_entity.insert("born_at".to_owned(), Value::Timestamp(616_387_200_000_000));
         expected_entity.insert("weight".to_owned(), Value::BigDecimal(111.7.into()));
         expected_entity.insert("coffee".to_owned(), Value::Bool(false));
         // "favorite_color" was set to `Null` earlier and should be absent
@@ -464,6 +480,67 @@ fn update_existing() {
     })
 }
 
+#[test]
+fn query_store_reads_from_snapshot() {
+    run_test(|store| -> Result<(), ()> {
+        let entity_key = EntityKey {
+            subgraph_id: TEST_SUBGRAPH_ID.clone(),
+            entity_type: "user".to_owned(),
+            entity_id: "1".to_owned(),
+        };
+        let name = |entity: Option<Entity>| entity.unwrap().get("name").cloned().unwrap();
+
+        // The snapshot is taken with the first read
+        let snapshot = store.clone().query_store().unwrap();
+        assert_eq!(
+            name(snapshot.get(entity_key.clone()).unwrap()),
+            Value::from("Johnton")
+        );
+        let unread_snapshot = store.clone().query_store().unwrap();
+
+        let op = create_test_entity(
+            "1",
+            "user",
+            "Wanjon",
+            "wanawana@email.com",
+            76 as i32,
+            111.7,
+            true,
+            Some("green"),
+        );
+        store
+            .transact_block_operations(
+                TEST_SUBGRAPH_ID.clone(),
+                *TEST_BLOCK_3_PTR,
+                *TEST_BLOCK_4_PTR,
+                vec![op],
+            )
+            .unwrap();
+
+        // Changes committed after the snapshot was taken are only visible
+        // outside of it
+        assert_eq!(
+            name(snapshot.get(entity_key.clone()).unwrap()),
+            Value::from("Johnton")
+        );
+        assert_eq!(
+            snapshot.block_ptr(TEST_SUBGRAPH_ID.clone()).unwrap(),
+            *TEST_BLOCK_3_PTR
+        );
+        assert_eq!(
+            name(unread_snapshot.get(entity_key.clone()).unwrap()),
+            Value::from("Wanjon")
+        );
+        assert_eq!(name(store.get(entity_key).unwrap()), Value::from("Wanjon"));
+        assert_eq!(
+            store.block_ptr(TEST_SUBGRAPH_ID.clone()).unwrap(),
+            *TEST_BLOCK_4_PTR
+        );
+
+        Ok(())
+    })
+}
+
 #[test]
 fn partially_update_existing() {
     run_test(|store| -> Result<(), ()> {
@@ -557,6 +634,114 @@ fn find_string_contains() {
     )
 }
 
+#[test]
+fn find_string_contains_nocase() {
+    test_find(
+        vec!["1"],
+        EntityQuery {
+            subgraph_id: TEST_SUBGRAPH_ID.clone(),
+            entity_types: vec!["user".to_owned()],
+            filter: Some(EntityFilter::And(vec![EntityFilter::ContainsNoCase(
+                "name".into(),
+                "JOHN".into(),
+            )])),
+            order_by: None,
+            order_direction: None,
+            range: EntityRange::first(100),
+        },
+    )
+}
+
+#[test]
+fn find_string_not_starts_with_nocase() {
+    test_find(
+        vec!["1", "3"],
+        EntityQuery {
+            subgraph_id: TEST_SUBGRAPH_ID.clone(),
+            entity_types: vec!["user".to_owned()],
+            filter: Some(EntityFilter::And(vec![EntityFilter::NotStartsWithNoCase(
+                "name".into(),
+                "cIN".into(),
+            )])),
+            order_by: Some(("name".to_owned(), ValueType::String)),
+            order_direction: Some(EntityOrder::Ascending),
+            range: EntityRange::first(100),
+        },
+    )
+}
+
+#[test]
+fn find_string_ends_with_nocase() {
+    test_find(
+        vec!["3"],
+        EntityQuery {
+            subgraph_id: TEST_SUBGRAPH_ID.clone(),
+            entity_types: vec!["user".to_owned()],
+            filter: Some(EntityFilter::And(vec![EntityFilter::EndsWithNoCase(
+                "name".into(),
+                "EENA".into(),
+            )])),
+            order_by: None,
+            order_direction: None,
+            range: EntityRange::first(100),
+        },
+    )
+}
+
+#[test]
+fn find_string_like() {
+    test_find(
+        vec!["2"],
+        EntityQuery {
+            subgraph_id: TEST_SUBGRAPH_ID.clone(),
+            entity_types: vec!["user".to_owned()],
+            filter: Some(EntityFilter::And(vec![EntityFilter::Like(
+                "name".into(),
+                "%i_i%".into(),
+            )])),
+            order_by: None,
+            order_direction: None,
+            range: EntityRange::first(100),
+        },
+    )
+}
+
+#[test]
+fn find_string_regex() {
+    test_find(
+        vec![],
+        EntityQuery {
+            subgraph_id: TEST_SUBGRAPH_ID.clone(),
+            entity_types: vec!["user".to_owned()],
+            filter: Some(EntityFilter::And(vec![EntityFilter::Regex(
+                "name".into(),
+                "^(john|shaq)".into(),
+            )])),
+            order_by: None,
+            order_direction: None,
+            range: EntityRange::first(100),
+        },
+    )
+}
+
+#[test]
+fn find_string_regex_nocase() {
+    test_find(
+        vec!["1", "3"],
+        EntityQuery {
+            subgraph_id: TEST_SUBGRAPH_ID.clone(),
+            entity_types: vec!["user".to_owned()],
+            filter: Some(EntityFilter::And(vec![EntityFilter::RegexNoCase(
+                "name".into(),
+                "^(john|shaq)".into(),
+            )])),
+            order_by: Some(("name".to_owned(), ValueType::String)),
+            order_direction: Some(EntityOrder::Ascending),
+            range: EntityRange::first(100),
+        },
+    )
+}
+
 #[test]
 fn find_string_equal() {
     test_find(
@@ -1313,6 +1498,104 @@ fn find_order_by_int() {
     );
 }
 
+#[test]
+fn find_order_by_int8_and_timestamp() {
+    test_find(
+        vec!["3", "2", "1"],
+        EntityQuery {
+            subgraph_id: TEST_SUBGRAPH_ID.clone(),
+            entity_types: vec!["user".to_owned()],
+            filter: None,
+            order_by: Some(("nanos_age".to_owned(), ValueType::Int8)),
+            order_direction: Some(EntityOrder::Ascending),
+            range: EntityRange::first(100),
+        },
+    );
+    test_find(
+        vec!["1", "2", "3"],
+        EntityQuery {
+            subgraph_id: TEST_SUBGRAPH_ID.clone(),
+            entity_types: vec!["user".to_owned()],
+            filter: None,
+            order_by: Some(("born_at".to_owned(), ValueType::Timestamp)),
+            order_direction: Some(EntityOrder::Ascending),
+            range: EntityRange::first(100),
+        },
+    );
+}
+
+#[test]
+fn find_int8_greater_than() {
+    // The two values differ by less than what a float can represent
+    test_find(
+        vec!["1", "2"],
+        EntityQuery {
+            subgraph_id: TEST_SUBGRAPH_ID.clone(),
+            entity_types: vec!["user".to_owned()],
+            filter: Some(EntityFilter::And(vec![EntityFilter::GreaterThan(
+                "nanos_age".to_owned(),
+                Value::Int8(1_356_976_799_999_999_999),
+            )])),
+            order_by: Some(("nanos_age".to_owned(), ValueType::Int8)),
+            order_direction: Some(EntityOrder::Descending),
+            range: EntityRange::first(100),
+        },
+    );
+    test_find(
+        vec!["1"],
+        EntityQuery {
+            subgraph_id: TEST_SUBGRAPH_ID.clone(),
+            entity_types: vec!["user".to_owned()],
+            filter: Some(EntityFilter::And(vec![EntityFilter::GreaterThan(
+                "nanos_age".to_owned(),
+                Value::Int8(1_356_976_800_000_000_000),
+            )])),
+            order_by: None,
+            order_direction: None,
+            range: EntityRange::first(100),
+        },
+    );
+}
+
+#[test]
+fn find_int8_in() {
+    test_find(
+        vec!["1", "3"],
+        EntityQuery {
+            subgraph_id: TEST_SUBGRAPH_ID.clone(),
+            entity_types: vec!["user".to_owned()],
+            filter: Some(EntityFilter::And(vec![EntityFilter::In(
+                "nanos_age".to_owned(),
+                vec![
+                    Value::Int8(883_612_800_000_000_000),
+                    Value::Int8(2_114_359_200_000_000_000),
+                ],
+            )])),
+            order_by: Some(("name".to_owned(), ValueType::String)),
+            order_direction: Some(EntityOrder::Ascending),
+            range: EntityRange::first(100),
+        },
+    )
+}
+
+#[test]
+fn find_timestamp_less_than() {
+    test_find(
+        vec!["2", "1"],
+        EntityQuery {
+            subgraph_id: TEST_SUBGRAPH_ID.clone(),
+            entity_types: vec!["user".to_owned()],
+            filter: Some(EntityFilter::And(vec![EntityFilter::LessThan(
+                "born_at".to_owned(),
+                Value::Timestamp(616_387_200_000_000),
+            )])),
+            order_by: Some(("name".to_owned(), ValueType::String)),
+            order_direction: Some(EntityOrder::Ascending),
+            range: EntityRange::first(100),
+        },
+    )
+}
+
 #[test]
 fn find_order_by_string() {
     test_find(
@@ -2206,3 +2489,33 @@ fn create_subgraph_deployment_tolerates_locks() {
         Ok(())
     })
 }
+
+#[test]
+fn closed_stores_refuse_to_work() {
+    let mut runtime = STORE_RUNTIME.lock().unwrap();
+
+    // Use a store of its own, since closing it can't be undone
+    let store = runtime
+        .block_on(future::lazy(|| -> Result<_, ()> {
+            Ok(DieselStore::new(
+                StoreConfig {
+                    postgres_url: postgres_test_url(),
+                    network_name: NETWORK_NAME.to_owned(),
+                    start_block: 0u64,
+                    conn_pool_size: 2,
+                },
+                &*LOGGER,
+                EthereumNetworkIdentifier {
+                    net_version: NETWORK_VERSION.to_owned(),
+                    genesis_block_hash: GENESIS_PTR.hash,
+                },
+            ))
+        }))
+        .unwrap();
+    store.chain_head_ptr().expect("Failed to read chain head");
+
+    runtime.block_on(store.close()).unwrap();
+
+    let err = store.chain_head_ptr().unwrap_err();
+    assert_eq!(err.to_string(), "store is closed");
+}